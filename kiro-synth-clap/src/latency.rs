@@ -0,0 +1,11 @@
+use clap_sys::ext::latency::clap_plugin_latency;
+use clap_sys::plugin::clap_plugin;
+
+use crate::plugin::KiroPlugin;
+
+/// The delay added by the insert effects of the synth, so the host can compensate it
+pub(crate) static LATENCY: clap_plugin_latency = clap_plugin_latency { get: Some(get) };
+
+unsafe extern "C" fn get(plugin: *const clap_plugin) -> u32 {
+  KiroPlugin::from_raw(plugin).latency_samples()
+}
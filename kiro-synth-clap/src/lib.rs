@@ -0,0 +1,111 @@
+//! CLAP plugin wrapper for the Kiro synth
+//!
+//! The plugin exposes the params of the Kiro program, plays the notes and MIDI events from the host
+//! at the sample they are scheduled, and renders the synth into a stereo output.
+//! The host saves the patch with the project as a Kiro preset.
+
+mod latency;
+mod params;
+mod plugin;
+mod ports;
+mod state;
+
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::ptr;
+
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::host::clap_host;
+use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
+use clap_sys::plugin_features::{
+  CLAP_PLUGIN_FEATURE_INSTRUMENT, CLAP_PLUGIN_FEATURE_STEREO, CLAP_PLUGIN_FEATURE_SYNTHESIZER,
+};
+use clap_sys::version::CLAP_VERSION;
+
+use crate::plugin::KiroPlugin;
+
+struct Features([*const c_char; 4]);
+
+// the pointers are to static strings
+unsafe impl Sync for Features {}
+
+static FEATURES: Features = Features([
+  CLAP_PLUGIN_FEATURE_INSTRUMENT.as_ptr(),
+  CLAP_PLUGIN_FEATURE_SYNTHESIZER.as_ptr(),
+  CLAP_PLUGIN_FEATURE_STEREO.as_ptr(),
+  ptr::null(),
+]);
+
+pub(crate) struct Descriptor(clap_plugin_descriptor);
+
+// the pointers are to static strings
+unsafe impl Sync for Descriptor {}
+
+pub(crate) static DESCRIPTOR: Descriptor = Descriptor(clap_plugin_descriptor {
+  clap_version: CLAP_VERSION,
+  id: b"kiro-synth\0".as_ptr() as *const c_char,
+  name: b"Kiro Synth\0".as_ptr() as *const c_char,
+  vendor: b"Christian Perez Llamas\0".as_ptr() as *const c_char,
+  url: b"\0".as_ptr() as *const c_char,
+  manual_url: b"\0".as_ptr() as *const c_char,
+  support_url: b"\0".as_ptr() as *const c_char,
+  version: b"0.1.0\0".as_ptr() as *const c_char,
+  description: b"Virtual analog synthesiser\0".as_ptr() as *const c_char,
+  features: FEATURES.0.as_ptr(),
+});
+
+unsafe extern "C" fn get_plugin_count(_factory: *const clap_plugin_factory) -> u32 {
+  1
+}
+
+unsafe extern "C" fn get_plugin_descriptor(
+  _factory: *const clap_plugin_factory,
+  index: u32,
+) -> *const clap_plugin_descriptor {
+  if index == 0 {
+    &DESCRIPTOR.0
+  } else {
+    ptr::null()
+  }
+}
+
+unsafe extern "C" fn create_plugin(
+  _factory: *const clap_plugin_factory,
+  host: *const clap_host,
+  plugin_id: *const c_char,
+) -> *const clap_plugin {
+  if plugin_id.is_null() || CStr::from_ptr(plugin_id) != CStr::from_ptr(DESCRIPTOR.0.id) {
+    return ptr::null();
+  }
+  KiroPlugin::create(host)
+}
+
+static FACTORY: clap_plugin_factory = clap_plugin_factory {
+  get_plugin_count: Some(get_plugin_count),
+  get_plugin_descriptor: Some(get_plugin_descriptor),
+  create_plugin: Some(create_plugin),
+};
+
+unsafe extern "C" fn entry_init(_plugin_path: *const c_char) -> bool {
+  true
+}
+
+unsafe extern "C" fn entry_deinit() {}
+
+unsafe extern "C" fn entry_get_factory(factory_id: *const c_char) -> *const c_void {
+  if !factory_id.is_null() && CStr::from_ptr(factory_id) == CLAP_PLUGIN_FACTORY_ID {
+    &FACTORY as *const clap_plugin_factory as *const c_void
+  } else {
+    ptr::null()
+  }
+}
+
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static clap_entry: clap_plugin_entry = clap_plugin_entry {
+  clap_version: CLAP_VERSION,
+  init: Some(entry_init),
+  deinit: Some(entry_deinit),
+  get_factory: Some(entry_get_factory),
+};
@@ -0,0 +1,136 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
+use clap_sys::events::{clap_input_events, clap_output_events};
+use clap_sys::ext::params::{
+  clap_param_info, clap_plugin_params, CLAP_PARAM_IS_AUTOMATABLE, CLAP_PARAM_IS_STEPPED,
+};
+use clap_sys::id::clap_id;
+use clap_sys::plugin::clap_plugin;
+
+use crate::plugin::KiroPlugin;
+
+/// The params of the host are the params of the program, with the index of the param as the id
+pub(crate) static PARAMS: clap_plugin_params = clap_plugin_params {
+  count: Some(count),
+  get_info: Some(get_info),
+  get_value: Some(get_value),
+  value_to_text: Some(value_to_text),
+  text_to_value: Some(text_to_value),
+  flush: Some(flush),
+};
+
+unsafe extern "C" fn count(plugin: *const clap_plugin) -> u32 {
+  KiroPlugin::from_raw(plugin).program.get_params().len() as u32
+}
+
+unsafe extern "C" fn get_info(
+  plugin: *const clap_plugin,
+  param_index: u32,
+  param_info: *mut clap_param_info,
+) -> bool {
+  let program = &KiroPlugin::from_raw(plugin).program;
+  let param = match program.get_params().get(param_index as usize) {
+    Some(param) => param,
+    None => return false,
+  };
+
+  let info = &mut *param_info;
+  info.id = param_index;
+  info.flags = CLAP_PARAM_IS_AUTOMATABLE;
+  if param.values.is_discrete() {
+    info.flags |= CLAP_PARAM_IS_STEPPED;
+  }
+  info.cookie = ptr::null_mut();
+  // the ids are like `osc1-amplitude`, with the name of the module before the first dash
+  let module = param.id.split('-').next().unwrap_or("");
+  write_str(&mut info.name, param.id);
+  write_str(&mut info.module, module);
+  info.min_value = f64::from(param.values.min);
+  info.max_value = f64::from(param.values.max);
+  info.default_value = f64::from(param.values.initial_value);
+  true
+}
+
+unsafe extern "C" fn get_value(
+  plugin: *const clap_plugin,
+  param_id: clap_id,
+  out: *mut f64,
+) -> bool {
+  match KiroPlugin::from_raw(plugin).get_value(param_id) {
+    Some(value) => {
+      *out = value;
+      true
+    }
+    None => false,
+  }
+}
+
+unsafe extern "C" fn value_to_text(
+  plugin: *const clap_plugin,
+  param_id: clap_id,
+  value: f64,
+  display: *mut c_char,
+  size: u32,
+) -> bool {
+  let program = &KiroPlugin::from_raw(plugin).program;
+  let param = match program.get_params().get(param_id as usize) {
+    Some(param) => param,
+    None => return false,
+  };
+  if display.is_null() || size == 0 {
+    return false;
+  }
+
+  let text = if param.values.is_discrete() {
+    format!("{:.0}", value)
+  } else {
+    format!("{:.3}", value)
+  };
+  let display = std::slice::from_raw_parts_mut(display, size as usize);
+  write_str(display, text.as_str());
+  true
+}
+
+unsafe extern "C" fn text_to_value(
+  plugin: *const clap_plugin,
+  param_id: clap_id,
+  display: *const c_char,
+  out: *mut f64,
+) -> bool {
+  let program = &KiroPlugin::from_raw(plugin).program;
+  if display.is_null() || program.get_params().get(param_id as usize).is_none() {
+    return false;
+  }
+  match CStr::from_ptr(display)
+    .to_str()
+    .map(|text| text.trim().parse::<f64>())
+  {
+    Ok(Ok(value)) => {
+      *out = value;
+      true
+    }
+    _ => false,
+  }
+}
+
+unsafe extern "C" fn flush(
+  plugin: *const clap_plugin,
+  in_events: *const clap_input_events,
+  _out_events: *const clap_output_events,
+) {
+  KiroPlugin::from_raw(plugin).flush(in_events);
+}
+
+/// Write a string into a fixed size buffer of C chars, truncated to fit and terminated with a null
+pub(crate) fn write_str(buffer: &mut [c_char], text: &str) {
+  if buffer.is_empty() {
+    return;
+  }
+  let len = text.len().min(buffer.len() - 1);
+  for (dst, src) in buffer.iter_mut().zip(text.as_bytes()[..len].iter()) {
+    *dst = *src as c_char;
+  }
+  buffer[len] = 0;
+}
@@ -0,0 +1,434 @@
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use clap_sys::events::{
+  clap_event_header, clap_event_midi, clap_event_note, clap_event_param_value, clap_input_events,
+  CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_MIDI, CLAP_EVENT_NOTE_CHOKE, CLAP_EVENT_NOTE_OFF,
+  CLAP_EVENT_NOTE_ON, CLAP_EVENT_PARAM_VALUE, CLAP_TRANSPORT_HAS_TEMPO,
+};
+use clap_sys::ext::audio_ports::CLAP_EXT_AUDIO_PORTS;
+use clap_sys::ext::latency::CLAP_EXT_LATENCY;
+use clap_sys::ext::note_ports::CLAP_EXT_NOTE_PORTS;
+use clap_sys::ext::params::CLAP_EXT_PARAMS;
+use clap_sys::ext::state::CLAP_EXT_STATE;
+use clap_sys::host::clap_host;
+use clap_sys::id::clap_id;
+use clap_sys::plugin::clap_plugin;
+use clap_sys::process::{
+  clap_process, clap_process_status, CLAP_PROCESS_CONTINUE, CLAP_PROCESS_ERROR,
+};
+use ringbuf::RingBuffer;
+
+use kiro_synth_engine::event::Message;
+use kiro_synth_engine::globals::SynthGlobals;
+use kiro_synth_engine::program::{ParamRef, Program};
+use kiro_synth_engine::synth::{Synth, Synth32};
+use kiro_synth_program::kiro::KiroModule;
+use kiro_synth_program::preset::Preset;
+
+use crate::{latency, params, ports, state, DESCRIPTOR};
+
+/// The state that only exists while the plugin is active, and is used from the audio thread
+struct Audio {
+  synth: Synth32<'static>,
+}
+
+pub(crate) struct KiroPlugin {
+  raw: clap_plugin,
+  _host: *const clap_host,
+  globals: SynthGlobals<f32>,
+  /// The program with the description of the params, which the synth gets a copy of when activated
+  pub(crate) program: Program<'static, f32>,
+  /// The values of the params shared between the main and the audio threads, as the bits of an f64
+  values: Vec<AtomicU64>,
+  pitch_bend: Option<ParamRef>,
+  audio: Mutex<Option<Audio>>,
+}
+
+impl KiroPlugin {
+  pub(crate) fn create(host: *const clap_host) -> *const clap_plugin {
+    let globals = SynthGlobals::new();
+    let (program, _) =
+      KiroModule::new_program(globals.lfo_waveforms.len(), globals.osc_waveforms.len());
+    let values = program
+      .get_params()
+      .iter()
+      .map(|param| AtomicU64::new(f64::from(param.value.get()).to_bits()))
+      .collect();
+    let pitch_bend = program.get_param_ref("pitch-bend");
+
+    let plugin = Box::new(KiroPlugin {
+      raw: clap_plugin {
+        desc: &DESCRIPTOR.0,
+        plugin_data: ptr::null_mut(),
+        init: Some(init),
+        destroy: Some(destroy),
+        activate: Some(activate),
+        deactivate: Some(deactivate),
+        start_processing: Some(start_processing),
+        stop_processing: Some(stop_processing),
+        reset: Some(reset),
+        process: Some(process),
+        get_extension: Some(get_extension),
+        on_main_thread: Some(on_main_thread),
+      },
+      _host: host,
+      globals,
+      program,
+      values,
+      pitch_bend,
+      audio: Mutex::new(None),
+    });
+
+    let plugin = Box::into_raw(plugin);
+    unsafe {
+      (*plugin).raw.plugin_data = plugin as *mut c_void;
+      &(*plugin).raw
+    }
+  }
+
+  /// Get the plugin behind the pointer given by the host
+  pub(crate) unsafe fn from_raw<'a>(plugin: *const clap_plugin) -> &'a KiroPlugin {
+    &*((*plugin).plugin_data as *const KiroPlugin)
+  }
+
+  pub(crate) fn get_value(&self, param_id: clap_id) -> Option<f64> {
+    self
+      .values
+      .get(param_id as usize)
+      .map(|value| f64::from_bits(value.load(Ordering::Relaxed)))
+  }
+
+  /// Apply the param events when the plugin is not processing
+  pub(crate) unsafe fn flush(&self, in_events: *const clap_input_events) {
+    // while active the flush happens in the audio thread, and the synth is not being processed
+    let mut audio = self.audio.lock().unwrap();
+    for header in InputEvents::new(in_events) {
+      if (*header).space_id == CLAP_CORE_EVENT_SPACE_ID && (*header).type_ == CLAP_EVENT_PARAM_VALUE
+      {
+        let event = &*(header as *const clap_event_param_value);
+        self.set_value(audio.as_mut(), event.param_id, event.value);
+      }
+    }
+  }
+
+  /// The values of the params as a preset, to be saved by the host with the project
+  pub(crate) fn preset(&self) -> Preset {
+    let mut preset = Preset::from_program("", &self.program);
+    for (index, param) in self.program.get_params().iter().enumerate() {
+      if let Some(value) = self.get_value(index as clap_id) {
+        preset.params.insert(param.id.to_string(), value);
+      }
+    }
+    preset
+  }
+
+  /// Set the values of the params from a preset, the missing ones go back to their initial values
+  pub(crate) fn load_preset(&self, preset: &Preset) {
+    let mut audio = self.audio.lock().unwrap();
+    for (index, param) in self.program.get_params().iter().enumerate() {
+      let min = f64::from(param.values.min);
+      let max = f64::from(param.values.max);
+      let value = match preset.params.get(param.id) {
+        Some(value) => value.max(min).min(max),
+        None => f64::from(param.values.initial_value),
+      };
+      self.set_value(audio.as_mut(), index as clap_id, value);
+    }
+  }
+
+  /// The delay in samples added by the synth while active
+  pub(crate) fn latency_samples(&self) -> u32 {
+    let audio = self.audio.lock().unwrap();
+    audio
+      .as_ref()
+      .map_or(0, |audio| audio.synth.latency_samples() as u32)
+  }
+
+  fn activate(&self, sample_rate: f64) {
+    // the events from the host are applied straight away, so the synth does not use its queue
+    let (_, consumer) = RingBuffer::new(1).split();
+    let mut synth = Synth::new(
+      sample_rate as f32,
+      consumer,
+      self.program.clone(),
+      self.globals.clone(),
+    );
+    for index in 0..self.values.len() {
+      if let Some(value) = self.get_value(index as clap_id) {
+        synth.set_param_f64(ParamRef::new(index), value);
+      }
+    }
+    *self.audio.lock().unwrap() = Some(Audio { synth });
+  }
+
+  fn deactivate(&self) {
+    *self.audio.lock().unwrap() = None;
+  }
+
+  fn reset(&self) {
+    if let Some(audio) = self.audio.lock().unwrap().as_mut() {
+      audio.synth.all_sound_off();
+    }
+  }
+
+  unsafe fn process(&self, process: &clap_process) -> clap_process_status {
+    let mut audio = match self.audio.try_lock() {
+      Ok(audio) => audio,
+      Err(_) => return CLAP_PROCESS_ERROR,
+    };
+    let audio = match audio.as_mut() {
+      Some(audio) => audio,
+      None => return CLAP_PROCESS_ERROR,
+    };
+
+    if !process.transport.is_null() {
+      let transport = &*process.transport;
+      if transport.flags & CLAP_TRANSPORT_HAS_TEMPO != 0 {
+        audio.synth.set_tempo(transport.tempo as f32);
+      }
+    }
+
+    let (left_out, right_out) = if process.audio_outputs_count > 0 {
+      let output = &*process.audio_outputs;
+      let left = *output.data32;
+      let right = if output.channel_count > 1 {
+        *output.data32.add(1)
+      } else {
+        ptr::null_mut()
+      };
+      (left, right)
+    } else {
+      (ptr::null_mut(), ptr::null_mut())
+    };
+
+    // the events are sorted by time, and applied right before the frame they are scheduled at
+    let mut events = InputEvents::new(process.in_events).peekable();
+    for frame in 0..process.frames_count {
+      while let Some(header) = events.peek().cloned() {
+        if (*header).time > frame {
+          break;
+        }
+        self.handle_event(audio, header);
+        events.next();
+      }
+
+      let (left, right) = audio.synth.process();
+      if !right_out.is_null() {
+        *left_out.add(frame as usize) = left;
+        *right_out.add(frame as usize) = right;
+      } else if !left_out.is_null() {
+        *left_out.add(frame as usize) = (left + right) * 0.5;
+      }
+    }
+
+    // events past the end of the block are applied for the next one
+    for header in events {
+      self.handle_event(audio, header);
+    }
+
+    CLAP_PROCESS_CONTINUE
+  }
+
+  unsafe fn handle_event(&self, audio: &mut Audio, header: *const clap_event_header) {
+    if (*header).space_id != CLAP_CORE_EVENT_SPACE_ID {
+      return;
+    }
+    match (*header).type_ {
+      CLAP_EVENT_NOTE_ON => {
+        let event = &*(header as *const clap_event_note);
+        if let Some(key) = note_key(event.key) {
+          let velocity = event.velocity as f32;
+          send(audio, Message::NoteOn { key, velocity });
+        }
+      }
+      CLAP_EVENT_NOTE_OFF | CLAP_EVENT_NOTE_CHOKE => {
+        let event = &*(header as *const clap_event_note);
+        // a negative key is a wildcard for all the keys
+        match note_key(event.key) {
+          Some(key) => {
+            let velocity = event.velocity as f32;
+            send(audio, Message::NoteOff { key, velocity });
+          }
+          None if event.key < 0 => send(audio, Message::AllNotesOff),
+          None => {}
+        }
+      }
+      CLAP_EVENT_PARAM_VALUE => {
+        let event = &*(header as *const clap_event_param_value);
+        self.set_value(Some(audio), event.param_id, event.value);
+      }
+      CLAP_EVENT_MIDI => {
+        let event = &*(header as *const clap_event_midi);
+        self.handle_midi(audio, event.data);
+      }
+      _ => {}
+    }
+  }
+
+  fn handle_midi(&self, audio: &mut Audio, data: [u8; 3]) {
+    let (key, value) = (data[1] & 0x7f, data[2] & 0x7f);
+    let velocity = f32::from(value) / 127.0;
+    match data[0] & 0xf0 {
+      0x80 => send(audio, Message::NoteOff { key, velocity }),
+      0x90 if value == 0 => send(audio, Message::NoteOff { key, velocity }),
+      0x90 => send(audio, Message::NoteOn { key, velocity }),
+      0xa0 => {
+        let pressure = f32::from(value) / 127.0;
+        send(audio, Message::PolyPressure { key, pressure });
+      }
+      0xb0 if key == 120 => send(audio, Message::AllSoundOff),
+      0xb0 if key == 123 => send(audio, Message::AllNotesOff),
+      0xd0 => {
+        let pressure = f32::from(key) / 127.0;
+        send(audio, Message::ChannelPressure { pressure });
+      }
+      0xe0 => {
+        if let Some(param_ref) = self.pitch_bend {
+          let bend = (u16::from(value) << 7) | u16::from(key);
+          let t = f32::from(bend) / 16383.0;
+          audio.synth.set_param_normalized(param_ref, t);
+          if let Some(value) = audio.synth.get_param_f64(param_ref) {
+            self.store_value(param_ref.into(), value);
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+
+  fn set_value(&self, audio: Option<&mut Audio>, param_id: clap_id, value: f64) {
+    let index = param_id as usize;
+    if index < self.values.len() {
+      self.store_value(index, value);
+      if let Some(audio) = audio {
+        audio.synth.set_param_f64(ParamRef::new(index), value);
+      }
+    }
+  }
+
+  fn store_value(&self, index: usize, value: f64) {
+    self.values[index].store(value.to_bits(), Ordering::Relaxed);
+  }
+}
+
+fn send(audio: &mut Audio, message: Message<f32>) {
+  // the events are applied straight away, so none is lost however many the host sends in a block
+  audio.synth.handle_message(message);
+}
+
+fn note_key(key: i16) -> Option<u8> {
+  if (0..128).contains(&key) {
+    Some(key as u8)
+  } else {
+    None
+  }
+}
+
+/// Iterator over the events given by the host
+pub(crate) struct InputEvents {
+  events: *const clap_input_events,
+  index: u32,
+  size: u32,
+}
+
+impl InputEvents {
+  pub(crate) unsafe fn new(events: *const clap_input_events) -> Self {
+    let size = match events.as_ref().and_then(|events| events.size) {
+      Some(size) => size(events),
+      None => 0,
+    };
+    InputEvents {
+      events,
+      index: 0,
+      size,
+    }
+  }
+}
+
+impl Iterator for InputEvents {
+  type Item = *const clap_event_header;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while self.index < self.size {
+      let index = self.index;
+      self.index += 1;
+      let header = unsafe { (*self.events).get.map(|get| get(self.events, index)) };
+      match header {
+        Some(header) if !header.is_null() => return Some(header),
+        _ => {}
+      }
+    }
+    None
+  }
+}
+
+unsafe extern "C" fn init(_plugin: *const clap_plugin) -> bool {
+  true
+}
+
+unsafe extern "C" fn destroy(plugin: *const clap_plugin) {
+  drop(Box::from_raw((*plugin).plugin_data as *mut KiroPlugin));
+}
+
+unsafe extern "C" fn activate(
+  plugin: *const clap_plugin,
+  sample_rate: f64,
+  _min_frames_count: u32,
+  _max_frames_count: u32,
+) -> bool {
+  KiroPlugin::from_raw(plugin).activate(sample_rate);
+  true
+}
+
+unsafe extern "C" fn deactivate(plugin: *const clap_plugin) {
+  KiroPlugin::from_raw(plugin).deactivate();
+}
+
+unsafe extern "C" fn start_processing(_plugin: *const clap_plugin) -> bool {
+  true
+}
+
+unsafe extern "C" fn stop_processing(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn reset(plugin: *const clap_plugin) {
+  KiroPlugin::from_raw(plugin).reset();
+}
+
+unsafe extern "C" fn process(
+  plugin: *const clap_plugin,
+  process: *const clap_process,
+) -> clap_process_status {
+  if process.is_null() {
+    return CLAP_PROCESS_ERROR;
+  }
+  KiroPlugin::from_raw(plugin).process(&*process)
+}
+
+unsafe extern "C" fn get_extension(
+  _plugin: *const clap_plugin,
+  id: *const c_char,
+) -> *const c_void {
+  if id.is_null() {
+    return ptr::null();
+  }
+  let id = CStr::from_ptr(id);
+  if id == CLAP_EXT_PARAMS {
+    &params::PARAMS as *const _ as *const c_void
+  } else if id == CLAP_EXT_AUDIO_PORTS {
+    &ports::AUDIO_PORTS as *const _ as *const c_void
+  } else if id == CLAP_EXT_NOTE_PORTS {
+    &ports::NOTE_PORTS as *const _ as *const c_void
+  } else if id == CLAP_EXT_STATE {
+    &state::STATE as *const _ as *const c_void
+  } else if id == CLAP_EXT_LATENCY {
+    &latency::LATENCY as *const _ as *const c_void
+  } else {
+    ptr::null()
+  }
+}
+
+unsafe extern "C" fn on_main_thread(_plugin: *const clap_plugin) {}
@@ -0,0 +1,66 @@
+use std::ffi::c_void;
+
+use clap_sys::ext::state::clap_plugin_state;
+use clap_sys::plugin::clap_plugin;
+use clap_sys::stream::{clap_istream, clap_ostream};
+
+use kiro_synth_program::preset::Preset;
+
+use crate::plugin::KiroPlugin;
+
+/// The state is the patch as a preset in JSON, so it is migrated as the presets when the program changes
+pub(crate) static STATE: clap_plugin_state = clap_plugin_state {
+  save: Some(save),
+  load: Some(load),
+};
+
+unsafe extern "C" fn save(plugin: *const clap_plugin, stream: *const clap_ostream) -> bool {
+  let write = match stream.as_ref().and_then(|stream| stream.write) {
+    Some(write) => write,
+    None => return false,
+  };
+  let json = match KiroPlugin::from_raw(plugin).preset().to_json() {
+    Ok(json) => json,
+    Err(_) => return false,
+  };
+
+  // the stream can take less bytes than given at a time
+  let mut bytes = json.as_bytes();
+  while !bytes.is_empty() {
+    let written = write(stream, bytes.as_ptr() as *const c_void, bytes.len() as u64);
+    if written <= 0 {
+      return false;
+    }
+    bytes = &bytes[written as usize..];
+  }
+  true
+}
+
+unsafe extern "C" fn load(plugin: *const clap_plugin, stream: *const clap_istream) -> bool {
+  let read = match stream.as_ref().and_then(|stream| stream.read) {
+    Some(read) => read,
+    None => return false,
+  };
+
+  let mut json = Vec::new();
+  let mut buffer = [0u8; 4096];
+  let size = buffer.len() as u64;
+  loop {
+    match read(stream, buffer.as_mut_ptr() as *mut c_void, size) {
+      0 => break,
+      count if count < 0 => return false,
+      count => json.extend_from_slice(&buffer[..count as usize]),
+    }
+  }
+
+  let preset = std::str::from_utf8(&json)
+    .ok()
+    .and_then(|json| Preset::from_json(json).ok());
+  match preset {
+    Some(preset) => {
+      KiroPlugin::from_raw(plugin).load_preset(&preset);
+      true
+    }
+    None => false,
+  }
+}
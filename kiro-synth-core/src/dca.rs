@@ -1,41 +1,79 @@
+use crate::filters::one_pole::{self, OnePole};
 use crate::float::Float;
 use crate::funcs::decibels::Decibels;
+use crate::funcs::sine_table::{cos_lut, sin_lut};
 
-#[derive(Debug, Default)]
+/// Time to reach the new pan gains, long enough to avoid clicks on the channel that drops to zero
+const PAN_SMOOTHING_SECONDS: f64 = 0.005;
+
+/// The sample rate of the default DCA, so it still smooths the pan changes
+const DEFAULT_SAMPLE_RATE: f64 = 44100.0;
+
+#[derive(Debug)]
 pub struct DCA<F: Float> {
   amplitude: F,
   velocity: F,
+  velocity_floor: F,
   amp_mod: F,
   eg_mod: F,
 
   gain: F,
   gain_invalidated: bool,
 
+  saturation: F,
+
   pan: F,
   pan_mod: F,
 
   pan_left: F,
   pan_right: F,
+  pan_left_target: F,
+  pan_right_target: F,
+  pan_left_smoother: OnePole<F>,
+  pan_right_smoother: OnePole<F>,
+  pan_smoothed: bool,
   pan_invalidated: bool,
 }
 
+impl<F: Float> Default for DCA<F> {
+  fn default() -> Self {
+    DCA::new(F::val(DEFAULT_SAMPLE_RATE))
+  }
+}
+
 impl<F: Float> DCA<F> {
-  pub fn new() -> Self {
+  pub fn new(sample_rate: F) -> Self {
+    let mut pan_smoother = OnePole::new(sample_rate, one_pole::Mode::LowPass);
+    pan_smoother.set_time_constant(F::val(PAN_SMOOTHING_SECONDS));
+
     DCA {
       amplitude: F::one(),
       velocity: F::one(),
+      velocity_floor: F::zero(),
       amp_mod: F::zero(),
       eg_mod: F::zero(),
       gain: F::zero(),
       gain_invalidated: true,
+      saturation: F::zero(),
       pan: F::zero(),
       pan_mod: F::zero(),
       pan_left: F::val(0.776),
       pan_right: F::val(0.776),
+      pan_left_target: F::val(0.776),
+      pan_right_target: F::val(0.776),
+      pan_left_smoother: pan_smoother,
+      pan_right_smoother: pan_smoother,
+      pan_smoothed: false,
       pan_invalidated: true,
     }
   }
 
+  /// Apply the next pan straight away, as it was when created, ie. for a new note
+  pub fn reset(&mut self) {
+    self.pan_smoothed = false;
+    self.pan_invalidated = true;
+  }
+
   /// value in decibels
   pub fn set_amplitude_db(&mut self, decibels: F) {
     self.amplitude = Decibels::new(decibels).to_amplitude();
@@ -48,6 +86,13 @@ impl<F: Float> DCA<F> {
     self.gain_invalidated = true;
   }
 
+  /// the gain for a velocity of 0.0, from 0.0 (silent) to 1.0 (velocity ignored),
+  /// so the soft notes are quiet but still audible
+  pub fn set_velocity_floor(&mut self, value: F) {
+    self.velocity_floor = value.max(F::zero()).min(F::one());
+    self.gain_invalidated = true;
+  }
+
   /// amount in decibels
   pub fn set_amp_mod_db(&mut self, decibels: F) {
     // I don't think this makes any sense here: let value = bipolar_to_unipolar(decibels);
@@ -61,6 +106,11 @@ impl<F: Float> DCA<F> {
     self.gain_invalidated = true;
   }
 
+  /// amount of soft saturation from 0.0 (transparent) to 1.0 (soft limited to unity)
+  pub fn set_saturation(&mut self, amount: F) {
+    self.saturation = amount.max(F::zero()).min(F::one());
+  }
+
   /// value expected to be between -1.0 and 1.0
   pub fn set_pan(&mut self, value: F) {
     self.pan = value;
@@ -76,12 +126,23 @@ impl<F: Float> DCA<F> {
   pub fn process(&mut self, left: F, right: F) -> (F, F) {
     self.update_gain();
     self.update_pan();
+    self.smooth_pan();
 
-    let left_out = left * self.gain * self.pan_left;
-    let right_out = right * self.gain * self.pan_right;
+    let left_out = self.saturate(left * self.gain) * self.pan_left;
+    let right_out = self.saturate(right * self.gain) * self.pan_right;
     (left_out, right_out)
   }
 
+  /// Keeps the slope at the origin so low levels are not changed, while the peaks are
+  /// softly limited to `1.0 / saturation`
+  fn saturate(&self, input: F) -> F {
+    if self.saturation > F::zero() {
+      (input * self.saturation).tanh() / self.saturation
+    } else {
+      input
+    }
+  }
+
   fn update_gain(&mut self) {
     if self.gain_invalidated {
       self.gain_invalidated = false;
@@ -91,7 +152,8 @@ impl<F: Float> DCA<F> {
         self.eg_mod + F::one()
       };
 
-      self.gain = self.velocity * self.amplitude * self.amp_mod * eg_mod;
+      let velocity = self.velocity_floor + (F::one() - self.velocity_floor) * self.velocity;
+      self.gain = velocity * self.amplitude * self.amp_mod * eg_mod;
       // println!("gain = {:?}, {:?}, {:?}, {:?}, {:?}", self.gain, self.velocity, self.amplitude, self.amp_mod, eg_mod);
     }
   }
@@ -101,13 +163,143 @@ impl<F: Float> DCA<F> {
       self.pan_invalidated = false;
       let pan_total = (self.pan + self.pan_mod).max(F::one().neg()).min(F::one());
 
-      let pi_over_four = F::PI / F::val(4.0);
-      let pan_left = (pi_over_four * (pan_total + F::one())).cos();
-      let pan_right = (pi_over_four * (pan_total + F::one())).sin();
+      // constant power pan law, with a phase from 0 to a quarter of a cycle
+      let phase = (pan_total + F::one()) / F::val(8.0);
+      let pan_left = cos_lut(phase);
+      let pan_right = sin_lut(phase);
+
+      self.pan_left_target = pan_left.max(F::zero()).min(F::one());
+      self.pan_right_target = pan_right.max(F::zero()).min(F::one());
+      // println!("pan = {:?}, {:?}", self.pan_left_target, self.pan_right_target);
+
+      // the initial pan is applied straight away, only the changes are smoothed
+      if !self.pan_smoothed {
+        self.pan_smoothed = true;
+        self.pan_left = self.pan_left_target;
+        self.pan_right = self.pan_right_target;
+        self.pan_left_smoother.reset_to(self.pan_left);
+        self.pan_right_smoother.reset_to(self.pan_right);
+      }
+    }
+  }
+
+  /// Move the pan gains one sample towards their targets
+  fn smooth_pan(&mut self) {
+    self.pan_left = self.pan_left_smoother.process(self.pan_left_target);
+    self.pan_right = self.pan_right_smoother.process(self.pan_right_target);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  /// Amplitude of the harmonic of a sine with a period of `period` samples
+  fn harmonic(signal: &[f64], period: usize, harmonic: usize) -> f64 {
+    let (mut re, mut im) = (0.0, 0.0);
+    for (index, sample) in signal.iter().enumerate() {
+      let phase = 2.0 * std::f64::consts::PI * (harmonic * index) as f64 / period as f64;
+      re += sample * phase.cos();
+      im += sample * phase.sin();
+    }
+    2.0 * (re * re + im * im).sqrt() / signal.len() as f64
+  }
+
+  fn render(saturation: f64) -> Vec<f64> {
+    let mut dca = DCA::new(44100.0);
+    dca.set_amplitude_db(6.0);
+    dca.set_amp_mod_db(0.0);
+    dca.set_eg_mod(1.0);
+    dca.set_saturation(saturation);
+    (0..1000)
+      .map(|index| {
+        let input = (2.0 * std::f64::consts::PI * index as f64 / 100.0).sin();
+        let (left, right) = dca.process(input, input);
+        left.max(right) / std::f64::consts::FRAC_1_SQRT_2
+      })
+      .collect()
+  }
+
+  #[test]
+  fn transparent_without_saturation() {
+    let output = render(0.0);
+    let peak = output.iter().cloned().fold(0.0, f64::max);
+    assert!(peak > 1.99 && peak < 2.0);
+    assert!(harmonic(&output, 100, 3) < 1e-6);
+  }
+
+  #[test]
+  fn soft_saturation() {
+    let output = render(1.0);
+    let peak = output.iter().cloned().fold(0.0, f64::max);
+    assert!(peak < 1.0 && peak > 0.9);
+    // not hard clipped: only a few samples around the peaks are close to it
+    let near_peak = output.iter().filter(|sample| peak - **sample < 1e-3);
+    assert!(near_peak.count() < output.len() / 10);
+    // and harmonically enriched
+    assert!(harmonic(&output, 100, 3) > 0.05);
+  }
+
+  /// The gain of the DCA for a velocity, without the pan
+  fn velocity_gain(velocity: f64, floor: f64) -> f64 {
+    let mut dca = DCA::new(44100.0);
+    dca.set_amp_mod_db(0.0);
+    dca.set_eg_mod(1.0);
+    dca.set_velocity_floor(floor);
+    dca.set_velocity(velocity);
+    dca.process(1.0, 1.0).0 / std::f64::consts::FRAC_1_SQRT_2
+  }
+
+  #[test]
+  fn velocity_floor() {
+    assert!(velocity_gain(0.0, 0.0).abs() < 1e-3);
+    assert!((velocity_gain(0.0, 0.2) - 0.2).abs() < 1e-3);
+    assert!((velocity_gain(1.0, 0.2) - 1.0).abs() < 1e-3);
+    // the floor is raised without changing the curve
+    assert!((velocity_gain(0.5, 0.2) - (0.2 + 0.8 * 0.25)).abs() < 1e-3);
+  }
 
-      self.pan_left = pan_left.max(F::zero()).min(F::one());
-      self.pan_right = pan_right.max(F::zero()).min(F::one());
-      // println!("pan = {:?}, {:?}", self.pan_left, self.pan_right);
+  #[test]
+  fn smoothed_pan() {
+    let sample_rate = 44100.0;
+    let mut dca = DCA::new(sample_rate);
+    dca.set_amp_mod_db(0.0);
+    dca.set_eg_mod(1.0);
+    for _ in 0..1000 {
+      dca.process(1.0, 1.0);
     }
+    let (left, right) = dca.process(1.0, 1.0);
+    assert!((left - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+    assert!((right - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+
+    dca.set_pan(-1.0);
+    let output: Vec<(f64, f64)> = (0..2000).map(|_| dca.process(1.0, 1.0)).collect();
+    // every channel ramps towards its new gain without any big jump
+    let mut previous = (left, right);
+    for (left, right) in output.iter().cloned() {
+      assert!(left >= previous.0 && right <= previous.1);
+      assert!((left - previous.0).abs() < 0.01 && (right - previous.1).abs() < 0.01);
+      previous = (left, right);
+    }
+    let samples_to_settle = output.iter().position(|(_, right)| *right < 1e-3).unwrap();
+    assert!(samples_to_settle > (0.001 * sample_rate) as usize);
+    assert!((previous.0 - 1.0).abs() < 1e-3 && previous.1 < 1e-3);
+
+    // after a reset the new pan is not smoothed
+    dca.reset();
+    dca.set_pan(1.0);
+    let (left, right) = dca.process(1.0, 1.0);
+    assert!(left < 1e-3 && (right - 1.0).abs() < 1e-3);
+  }
+
+  #[test]
+  fn default_smoothed_pan() {
+    let mut dca: DCA<f64> = DCA::default();
+    dca.set_amp_mod_db(0.0);
+    dca.set_eg_mod(1.0);
+    dca.process(1.0, 1.0);
+    dca.set_pan(-1.0);
+    let (_, right) = dca.process(1.0, 1.0);
+    assert!((right - std::f64::consts::FRAC_1_SQRT_2).abs() < 0.01);
   }
 }
@@ -0,0 +1,265 @@
+use crate::effects::delay::{DelayLine, DelayTap, InterpMode};
+use crate::effects::Processor;
+use crate::float::Float;
+use crate::oscillators::lfo::Lfo;
+
+/// Number of delay taps used in the ensemble mode
+const ENSEMBLE_TAPS: usize = 3;
+
+/// Ratio between the rates of the fast and the slow LFOs in the ensemble mode
+const ENSEMBLE_FAST_RATIO: f64 = 7.3;
+
+/// Depth of the fast LFO relative to the slow one in the ensemble mode
+const ENSEMBLE_FAST_DEPTH: f64 = 0.2;
+
+/// Gains for the left and right channels of every tap in the ensemble mode, panning them from left to right
+const ENSEMBLE_PAN: [(f64, f64); ENSEMBLE_TAPS] = [(1.0, 0.0), (0.5, 0.5), (0.0, 1.0)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChorusMode {
+  /// A single delay tap modulated by one LFO
+  Single,
+  /// Several delay taps panned across the stereo field and modulated by LFOs with different phases,
+  /// like the chorus of the string ensembles
+  Ensemble,
+}
+
+impl ChorusMode {
+  pub fn count() -> usize {
+    2
+  }
+
+  pub fn from<F: Float>(value: F) -> Self {
+    match value.round().to_usize().unwrap_or(0) {
+      1 => ChorusMode::Ensemble,
+      _ => ChorusMode::Single,
+    }
+  }
+}
+
+/// Chorus effect with a single voice or in ensemble mode.
+pub struct Chorus<'a, F: Float> {
+  mode: ChorusMode,
+  /// The rate of the modulation in Hz
+  rate: F,
+  /// The amount of modulation of the delay in seconds
+  depth_seconds: F,
+  /// The delay in seconds around which it is modulated
+  delay_seconds: F,
+  /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
+  mix: F,
+  bypass: bool,
+  sample_rate: F,
+  interp_mode: InterpMode,
+  delayline: DelayLine<'a, F>,
+  taps: [DelayTap<F>; ENSEMBLE_TAPS],
+  slow_lfos: [Lfo<F>; ENSEMBLE_TAPS],
+  fast_lfos: [Lfo<F>; ENSEMBLE_TAPS],
+}
+
+impl<'a, F: Float> Chorus<'a, F> {
+  pub fn new(sample_rate: F, buffer: &'a mut [F]) -> Self {
+    let mut chorus = Chorus {
+      mode: ChorusMode::Single,
+      rate: F::val(0.8),
+      depth_seconds: F::val(0.002),
+      delay_seconds: F::val(0.007),
+      mix: F::val(0.5),
+      bypass: false,
+      sample_rate,
+      interp_mode: InterpMode::Linear,
+      delayline: DelayLine::new(buffer),
+      taps: [DelayTap::default(); ENSEMBLE_TAPS],
+      slow_lfos: [
+        Lfo::new(sample_rate),
+        Lfo::new(sample_rate),
+        Lfo::new(sample_rate),
+      ],
+      fast_lfos: [
+        Lfo::new(sample_rate),
+        Lfo::new(sample_rate),
+        Lfo::new(sample_rate),
+      ],
+    };
+    chorus.update_rates();
+    chorus.reset_lfos();
+    chorus
+  }
+
+  pub fn set_mode(&mut self, mode: ChorusMode) {
+    self.mode = mode;
+  }
+
+  pub fn get_mode(&self) -> ChorusMode {
+    self.mode
+  }
+
+  /// The LFOs keep their phases, so the modulation does not jump while the rate is changed
+  pub fn set_rate(&mut self, rate: F) {
+    self.rate = rate;
+    self.update_rates();
+  }
+
+  pub fn get_rate(&self) -> F {
+    self.rate
+  }
+
+  pub fn set_depth_seconds(&mut self, depth_seconds: F) {
+    self.depth_seconds = depth_seconds.max(F::zero());
+  }
+
+  pub fn get_depth_seconds(&self) -> F {
+    self.depth_seconds
+  }
+
+  pub fn set_delay_seconds(&mut self, delay_seconds: F) {
+    self.delay_seconds = delay_seconds.max(F::zero());
+  }
+
+  pub fn get_delay_seconds(&self) -> F {
+    self.delay_seconds
+  }
+
+  /// Choose how the delay is read between its samples, linear by default
+  pub fn set_interp_mode(&mut self, mode: InterpMode) {
+    self.interp_mode = mode;
+  }
+
+  pub fn get_interp_mode(&self) -> InterpMode {
+    self.interp_mode
+  }
+
+  fn update_rates(&mut self) {
+    let lfos = self.slow_lfos.iter_mut().zip(self.fast_lfos.iter_mut());
+    for (slow_lfo, fast_lfo) in lfos {
+      slow_lfo.set_rate(self.rate);
+      fast_lfo.set_rate(self.rate * F::val(ENSEMBLE_FAST_RATIO));
+    }
+  }
+
+  /// Spread the phases of the LFOs of the taps evenly across the cycle
+  fn reset_lfos(&mut self) {
+    let taps = F::val(ENSEMBLE_TAPS as f64);
+    let lfos = self.slow_lfos.iter_mut().zip(self.fast_lfos.iter_mut());
+    for (index, (slow_lfo, fast_lfo)) in lfos.enumerate() {
+      let phase = F::val(index as f64) / taps;
+      slow_lfo.set_phase(phase);
+      slow_lfo.reset();
+      fast_lfo.set_phase(phase);
+      fast_lfo.reset();
+    }
+  }
+
+  fn tap(&mut self, index: usize, modulation: F) -> F {
+    let max_delay = F::val(self.delayline.len().saturating_sub(2) as f64).max(F::one());
+    let delay_samples = (self.delay_seconds + self.depth_seconds * modulation) * self.sample_rate;
+    let delay_samples = delay_samples.max(F::one()).min(max_delay);
+    let tap = &mut self.taps[index];
+    self.delayline.read(delay_samples, self.interp_mode, tap)
+  }
+
+  fn process_single(&mut self) -> (F, F) {
+    let modulation = self.slow_lfos[0].generate();
+    let sample = self.tap(0, modulation);
+    (sample, sample)
+  }
+
+  fn process_ensemble(&mut self) -> (F, F) {
+    let (mut left, mut right) = (F::zero(), F::zero());
+    for (index, (left_gain, right_gain)) in ENSEMBLE_PAN.iter().enumerate() {
+      let slow = self.slow_lfos[index].generate();
+      let fast = self.fast_lfos[index].generate();
+      let sample = self.tap(index, slow + fast * F::val(ENSEMBLE_FAST_DEPTH));
+      left = left + sample * F::val(*left_gain);
+      right = right + sample * F::val(*right_gain);
+    }
+    (left, right)
+  }
+}
+
+impl<'a, F: Float> Processor<F> for Chorus<'a, F> {
+  fn get_mix(&self) -> F {
+    self.mix
+  }
+
+  fn set_mix(&mut self, mix: F) {
+    self.mix = mix;
+  }
+
+  fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  fn process_wet(&mut self, left: F, right: F) -> (F, F) {
+    self.delayline.update((left + right) * F::val(0.5));
+    match self.mode {
+      ChorusMode::Single => self.process_single(),
+      ChorusMode::Ensemble => self.process_ensemble(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn correlation(mode: ChorusMode) -> f64 {
+    let mut buffer = [0.0f64; 2048];
+    let mut chorus = Chorus::new(44100.0, &mut buffer);
+    chorus.set_mode(mode);
+    chorus.set_mix(1.0);
+
+    // white noise from a linear congruential generator
+    let mut seed = 12345u32;
+    let frames: Vec<(f64, f64)> = (0..8192)
+      .map(|_| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let input = f64::from(seed >> 16) / 32768.0 - 1.0;
+        chorus.process_frame(input, input)
+      })
+      .skip(1024)
+      .collect();
+
+    let (mut lr, mut ll, mut rr) = (0.0, 0.0, 0.0);
+    for (left, right) in frames.iter() {
+      lr += left * right;
+      ll += left * left;
+      rr += right * right;
+    }
+    lr / (ll * rr).sqrt()
+  }
+
+  #[test]
+  fn mode_from() {
+    assert_eq!(ChorusMode::from(0.0), ChorusMode::Single);
+    assert_eq!(ChorusMode::from(1.0), ChorusMode::Ensemble);
+  }
+
+  #[test]
+  fn single_mode_is_mono() {
+    assert!(correlation(ChorusMode::Single) > 0.99);
+  }
+
+  #[test]
+  fn ensemble_mode_decorrelates_the_channels() {
+    assert!(correlation(ChorusMode::Ensemble) < 0.5);
+  }
+
+  #[test]
+  fn rate_changes_keep_the_phases() {
+    let mut buffer = [0.0f64; 2048];
+    let mut chorus = Chorus::new(1000.0, &mut buffer);
+    for _ in 0..100 {
+      chorus.process_frame(0.0, 0.0);
+    }
+    let modulos: Vec<f64> = chorus.slow_lfos.iter().map(Lfo::get_modulo).collect();
+    chorus.set_rate(2.0);
+    let after: Vec<f64> = chorus.slow_lfos.iter().map(Lfo::get_modulo).collect();
+    assert_eq!(after, modulos);
+    assert!(modulos[0] > 0.0);
+  }
+}
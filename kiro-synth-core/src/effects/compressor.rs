@@ -0,0 +1,217 @@
+use crate::effects::Processor;
+use crate::filters::one_pole::OnePole;
+use crate::float::Float;
+use crate::funcs::decibels::Decibels;
+
+/// The lowest level considered by the detector, to avoid the logarithm of zero
+const MIN_LEVEL_DB: f64 = -120.0;
+
+/// Dynamic range compressor that reduces the level of the signal above a threshold by a ratio.
+///
+/// With a soft knee the gain reduction engages gradually within the knee width around the threshold.
+pub struct Compressor<F: Float> {
+  sample_rate: F,
+  threshold_db: F,
+  /// How much the level above the threshold is reduced, from 1.0 (no compression)
+  ratio: F,
+  /// Width in dB of the transition around the threshold, 0.0 for a hard knee
+  knee_db: F,
+  /// Gain in dB applied after the compression
+  makeup_db: F,
+  makeup: F,
+  attack_seconds: F,
+  release_seconds: F,
+  attack_coef: F,
+  release_coef: F,
+  /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
+  mix: F,
+  bypass: bool,
+  /// The smoothed gain reduction in dB, zero or negative
+  reduction_db: F,
+}
+
+impl<F: Float> Compressor<F> {
+  pub fn new(sample_rate: F) -> Self {
+    Compressor {
+      sample_rate,
+      threshold_db: F::zero(),
+      ratio: F::one(),
+      knee_db: F::zero(),
+      makeup_db: F::zero(),
+      makeup: F::one(),
+      attack_seconds: F::zero(),
+      release_seconds: F::zero(),
+      attack_coef: F::one(),
+      release_coef: F::one(),
+      mix: F::one(),
+      bypass: false,
+      reduction_db: F::zero(),
+    }
+  }
+
+  pub fn set_threshold_db(&mut self, threshold_db: F) {
+    self.threshold_db = threshold_db;
+  }
+
+  pub fn get_threshold_db(&self) -> F {
+    self.threshold_db
+  }
+
+  pub fn set_ratio(&mut self, ratio: F) {
+    self.ratio = ratio.max(F::one());
+  }
+
+  pub fn get_ratio(&self) -> F {
+    self.ratio
+  }
+
+  /// Set the width in dB of the soft knee around the threshold, 0.0 for a hard knee
+  pub fn set_knee(&mut self, width_db: F) {
+    self.knee_db = width_db.max(F::zero());
+  }
+
+  pub fn get_knee(&self) -> F {
+    self.knee_db
+  }
+
+  pub fn set_makeup_db(&mut self, makeup_db: F) {
+    self.makeup_db = makeup_db;
+    self.makeup = Decibels::new(makeup_db).to_amplitude();
+  }
+
+  pub fn get_makeup_db(&self) -> F {
+    self.makeup_db
+  }
+
+  pub fn set_attack_seconds(&mut self, seconds: F) {
+    self.attack_seconds = seconds.max(F::zero());
+    self.attack_coef = OnePole::time_constant_coef(self.attack_seconds, self.sample_rate);
+  }
+
+  pub fn get_attack_seconds(&self) -> F {
+    self.attack_seconds
+  }
+
+  pub fn set_release_seconds(&mut self, seconds: F) {
+    self.release_seconds = seconds.max(F::zero());
+    self.release_coef = OnePole::time_constant_coef(self.release_seconds, self.sample_rate);
+  }
+
+  pub fn get_release_seconds(&self) -> F {
+    self.release_seconds
+  }
+
+  /// The current gain reduction in dB, zero or negative
+  pub fn get_reduction_db(&self) -> F {
+    self.reduction_db
+  }
+
+  /// The static gain change in dB for an input level in dB, without the attack and release
+  pub fn gain_db(&self, level_db: F) -> F {
+    let two = F::val(2.0);
+    let over = level_db - self.threshold_db;
+    let slope = self.ratio.recip() - F::one();
+    if two * over <= self.knee_db.neg() {
+      F::zero()
+    } else if two * over.abs() <= self.knee_db {
+      let knee_over = over + self.knee_db / two;
+      slope * knee_over * knee_over / (two * self.knee_db)
+    } else {
+      slope * over
+    }
+  }
+}
+
+impl<F: Float> Processor<F> for Compressor<F> {
+  fn get_mix(&self) -> F {
+    self.mix
+  }
+
+  fn set_mix(&mut self, mix: F) {
+    self.mix = mix;
+  }
+
+  fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  fn process_wet(&mut self, left: F, right: F) -> (F, F) {
+    let peak = left.abs().max(right.abs());
+    let level_db = Decibels::from_amplitude(peak)
+      .value()
+      .max(F::val(MIN_LEVEL_DB));
+    let target_db = self.gain_db(level_db);
+    // more reduction is the attack, less reduction is the release
+    let coef = if target_db < self.reduction_db {
+      self.attack_coef
+    } else {
+      self.release_coef
+    };
+    self.reduction_db = self.reduction_db + (target_db - self.reduction_db) * coef;
+
+    let gain = Decibels::new(self.reduction_db).to_amplitude() * self.makeup;
+    (left * gain, right * gain)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn compressor(knee_db: f64) -> Compressor<f64> {
+    let mut compressor = Compressor::new(1000.0);
+    compressor.set_threshold_db(-20.0);
+    compressor.set_ratio(4.0);
+    compressor.set_knee(knee_db);
+    compressor
+  }
+
+  #[test]
+  fn hard_knee() {
+    let compressor = compressor(0.0);
+    assert_eq!(compressor.gain_db(-30.0), 0.0);
+    assert_eq!(compressor.gain_db(-20.0), 0.0);
+    assert_approx_eq!(compressor.gain_db(-19.0), -0.75);
+    assert_approx_eq!(compressor.gain_db(0.0), -15.0);
+  }
+
+  #[test]
+  fn soft_knee() {
+    let soft = compressor(10.0);
+    let hard = compressor(0.0);
+
+    // the same as the hard knee out of the knee
+    assert_eq!(soft.gain_db(-25.0), 0.0);
+    assert_approx_eq!(soft.gain_db(-15.0), hard.gain_db(-15.0));
+    assert_approx_eq!(soft.gain_db(0.0), hard.gain_db(0.0));
+
+    // already reducing at the threshold, where the hard knee does nothing yet
+    assert!(soft.gain_db(-20.0) < 0.0);
+
+    // within the knee the slope changes gradually, without a corner
+    let levels: Vec<f64> = (0..=100).map(|index| -25.0 + index as f64 * 0.1).collect();
+    let slopes: Vec<f64> = levels
+      .windows(2)
+      .map(|pair| (soft.gain_db(pair[1]) - soft.gain_db(pair[0])) / 0.1)
+      .collect();
+    let changes: Vec<f64> = slopes.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let gradual = |change: &f64| *change <= 1e-9 && change.abs() < 0.01;
+    assert!(changes.iter().all(gradual));
+    assert!(hard.gain_db(-19.9) - hard.gain_db(-20.0) < -0.07);
+  }
+
+  #[test]
+  fn compresses_the_signal() {
+    let mut compressor = compressor(0.0);
+    compressor.set_makeup_db(6.0);
+    let (left, right) = compressor.process_frame(1.0, -0.5);
+    let gain = Decibels::new(-15.0 + 6.0).to_amplitude();
+    assert_approx_eq!(left, gain);
+    assert_approx_eq!(right, -0.5 * gain);
+  }
+}
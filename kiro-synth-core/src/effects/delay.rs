@@ -1,6 +1,41 @@
+use crate::effects::Processor;
 use crate::float::Float;
+use crate::funcs::interpolation::cubic_interpolation;
 
-struct DelayLine<'a, F: Float> {
+/// How the modulated delays read their delay line between its samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterpMode {
+  /// Between the two closest samples, the cheapest but it attenuates the high frequencies
+  /// the more the closer the delay is to half a sample
+  Linear,
+  /// A first order allpass, with the same magnitude for all the frequencies,
+  /// but it rings briefly after the delay jumps
+  Allpass,
+  /// Cubic Hermite interpolation using four samples
+  Cubic,
+}
+
+impl InterpMode {
+  pub fn count() -> usize {
+    3
+  }
+
+  pub fn from<F: Float>(value: F) -> Self {
+    match value.round().to_usize().unwrap_or(0) {
+      1 => InterpMode::Allpass,
+      2 => InterpMode::Cubic,
+      _ => InterpMode::Linear,
+    }
+  }
+}
+
+/// The state of a position reading from a delay line, needed by the allpass interpolation
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DelayTap<F: Float> {
+  last_output: F,
+}
+
+pub(crate) struct DelayLine<'a, F: Float> {
   head: usize,
   buffer: &'a mut [F],
 }
@@ -25,6 +60,49 @@ impl<'a, F: Float> DelayLine<'a, F> {
     };
     self.buffer[index]
   }
+
+  /// Get a sample for a fractional delay interpolating linearly between the closest samples
+  pub fn get_interpolated(&self, delay_samples: F) -> F {
+    let whole = delay_samples.floor();
+    let index = whole.to_usize().unwrap_or(0);
+    let current = self.get(index);
+    let next = self.get(index + 1);
+    current + (next - current) * (delay_samples - whole)
+  }
+
+  /// Get a sample for a fractional delay interpolating with some mode.
+  /// Every tap has to read once per sample for the allpass interpolation.
+  pub fn read(&self, delay_samples: F, mode: InterpMode, tap: &mut DelayTap<F>) -> F {
+    let whole = delay_samples.floor();
+    let index = whole.to_usize().unwrap_or(0);
+    let fraction = delay_samples - whole;
+    let output = match mode {
+      InterpMode::Linear => self.get_interpolated(delay_samples),
+      InterpMode::Allpass => {
+        // with a fraction from 0.5 to 1.5 the coefficient stays away from the pole at -1
+        let (index, fraction) = if fraction < F::val(0.5) && index > 1 {
+          (index - 1, fraction + F::one())
+        } else {
+          (index, fraction)
+        };
+        let coefficient = (F::one() - fraction) / (F::one() + fraction);
+        coefficient * (self.get(index) - tap.last_output) + self.get(index + 1)
+      }
+      InterpMode::Cubic => cubic_interpolation(
+        self.get(index.saturating_sub(1).max(1)),
+        self.get(index),
+        self.get(index + 1),
+        self.get(index + 2),
+        fraction,
+      ),
+    };
+    tap.last_output = output;
+    output
+  }
+
+  pub fn len(&self) -> usize {
+    self.buffer.len()
+  }
 }
 
 /// Simple delay effect with 3 parameters: the delay amount, the amount of feedback and dry/wet mix.
@@ -35,6 +113,9 @@ pub struct Delay<'a, F: Float> {
   feedback: F,
   /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
   mix: F,
+  bypass: bool,
+  /// Whether the feedback is soft limited, so it can sustain without building up
+  saturated_feedback: bool,
   sample_rate: F,
   delayline: DelayLine<'a, F>,
   delay_samples: usize,
@@ -47,6 +128,8 @@ impl<'a, F: Float> Delay<'a, F> {
       delay_seconds: sample_rate.recip(),
       feedback: F::zero(),
       mix: F::zero(),
+      bypass: false,
+      saturated_feedback: false,
       delayline: DelayLine::<F>::new(buffer),
       sample_rate,
     }
@@ -77,11 +160,65 @@ impl<'a, F: Float> Delay<'a, F> {
     self.mix
   }
 
+  pub fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  pub fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  /// Pass the signal written back into the delay line through a tanh, so feedback near or above 1.0
+  /// keeps repeating within -1.0 and 1.0 instead of growing without limit
+  pub fn set_saturated_feedback(&mut self, saturated: bool) {
+    self.saturated_feedback = saturated;
+  }
+
+  pub fn is_feedback_saturated(&self) -> bool {
+    self.saturated_feedback
+  }
+
   pub fn process(&mut self, input: F) -> F {
+    if self.bypass {
+      input
+    } else {
+      let sample = self.process_delay(input);
+      sample * self.mix + input * (F::one() - self.mix)
+    }
+  }
+
+  fn process_delay(&mut self, input: F) -> F {
     let sample = self.delayline.get(self.delay_samples);
-    self.delayline.update(input + sample * self.feedback);
+    let feedback = input + sample * self.feedback;
+    if self.saturated_feedback {
+      self.delayline.update(feedback.tanh());
+    } else {
+      self.delayline.update(feedback);
+    }
+    sample
+  }
+}
 
-    sample * self.mix + input * (F::one() - self.mix)
+impl<'a, F: Float> Processor<F> for Delay<'a, F> {
+  fn get_mix(&self) -> F {
+    self.mix
+  }
+
+  fn set_mix(&mut self, mix: F) {
+    self.mix = mix;
+  }
+
+  fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  fn process_wet(&mut self, left: F, right: F) -> (F, F) {
+    let sample = self.process_delay((left + right) * F::val(0.5));
+    (sample, sample)
   }
 }
 
@@ -127,4 +264,114 @@ mod test {
         assert_approx_eq!(a, b);
       });
   }
+
+  /// The amplitude of a sine at a fifth of the sample rate delayed by a fractional number of samples
+  fn fractional_delay_amplitude(mode: InterpMode) -> f64 {
+    let mut buffer = [0.0f64; 32];
+    let mut delayline = DelayLine::new(&mut buffer);
+    let mut tap = DelayTap::default();
+    let output: Vec<f64> = (0..1000)
+      .map(|index| {
+        delayline.update((2.0 * std::f64::consts::PI * 0.2 * index as f64).sin());
+        delayline.read(10.5, mode, &mut tap)
+      })
+      .skip(500)
+      .collect();
+    let mean_square =
+      output.iter().map(|sample| sample * sample).sum::<f64>() / output.len() as f64;
+    (2.0 * mean_square).sqrt()
+  }
+
+  #[test]
+  fn delayline_interp_modes() {
+    assert_approx_eq!(fractional_delay_amplitude(InterpMode::Allpass), 1.0, 1e-6);
+    let linear = fractional_delay_amplitude(InterpMode::Linear);
+    assert_approx_eq!(linear, (0.2 * std::f64::consts::PI).cos(), 1e-6);
+    let cubic = fractional_delay_amplitude(InterpMode::Cubic);
+    assert!(linear < cubic && cubic < 1.0);
+  }
+
+  #[test]
+  fn interp_mode_from() {
+    assert_eq!(InterpMode::from(0.0), InterpMode::Linear);
+    assert_eq!(InterpMode::from(1.0), InterpMode::Allpass);
+    assert_eq!(InterpMode::from(2.0), InterpMode::Cubic);
+  }
+
+  #[test]
+  fn delay_bypass() {
+    let mut buffer = [0.0f64; 8];
+    let mut delay = Delay::new(4.0, &mut buffer);
+    delay.set_delay_seconds(0.5);
+    delay.set_feedback(0.5);
+    delay.set_mix(0.5);
+    delay.set_bypass(true);
+
+    for input in [1.0, -0.3, 0.123_456_789, 0.0].iter() {
+      assert_eq!(delay.process(*input).to_bits(), input.to_bits());
+      let (left, right) = delay.process_frame(*input, -*input);
+      assert_eq!(left.to_bits(), input.to_bits());
+      assert_eq!(right.to_bits(), (-*input).to_bits());
+    }
+
+    assert_eq!(delay.delayline.head, 0);
+    assert!(delay.delayline.buffer.iter().all(|sample| *sample == 0.0));
+  }
+
+  #[test]
+  fn delay_process_frame() {
+    let mut buffer = [0.0f64; 8];
+    let mut delay = Delay::new(4.0, &mut buffer);
+    delay.set_delay_seconds(0.25);
+    Processor::set_mix(&mut delay, 1.0);
+
+    let (left, right) = delay.process_frame(1.0, 0.0);
+    assert_approx_eq!(left, 0.0);
+    assert_approx_eq!(right, 0.0);
+
+    let (left, right) = delay.process_frame(0.0, 0.0);
+    assert_approx_eq!(left, 0.5);
+    assert_approx_eq!(right, 0.5);
+  }
+
+  fn render<F: Float>(buffer: &mut [F]) -> Vec<f64> {
+    let mut delay = Delay::new(F::val(100.0), buffer);
+    delay.set_delay_seconds(F::val(0.07));
+    delay.set_feedback(F::val(0.6));
+    delay.set_mix(F::val(0.4));
+    (0..256)
+      .map(|i| {
+        let input = F::val((i as f64 * 0.3).sin());
+        delay.process(input).to_f64().unwrap()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn delay_f32_matches_f64() {
+    let output32 = render(&mut [0.0f32; 16]);
+    let output64 = render(&mut [0.0f64; 16]);
+    for (a, b) in output32.iter().zip(output64.iter()) {
+      assert_approx_eq!(a, b, 1e-5);
+    }
+  }
+
+  /// The peak level of the delay line with a sustained input and full feedback
+  fn feedback_peak(saturated: bool) -> f64 {
+    let mut buffer = [0.0f64; 8];
+    let mut delay = Delay::new(4.0, &mut buffer);
+    delay.set_delay_seconds(1.0);
+    delay.set_feedback(1.0);
+    delay.set_mix(1.0);
+    delay.set_saturated_feedback(saturated);
+    (0..4000)
+      .map(|_| delay.process(0.5).abs())
+      .fold(0.0, f64::max)
+  }
+
+  #[test]
+  fn saturated_feedback_stays_bounded() {
+    assert!(feedback_peak(true) <= 1.0);
+    assert!(feedback_peak(false) > 100.0);
+  }
 }
@@ -0,0 +1,189 @@
+use crate::effects::Processor;
+use crate::filters::one_pole::OnePole;
+use crate::float::Float;
+use crate::sequencer::step_sequencer::MAX_STEPS;
+use crate::sequencer::{Step, StepSequencer};
+
+/// Rhythmic volume effect (trance gate) that opens and closes the signal following a pattern of steps
+/// synced to the position of the transport.
+pub struct Gate<F: Float> {
+  sequencer: StepSequencer<F>,
+  /// The position of the transport in beats, for the next frame
+  beats: F,
+  sample_rate: F,
+  /// Time in seconds for the gate to open
+  attack_seconds: F,
+  /// Time in seconds for the gate to close
+  release_seconds: F,
+  attack_coef: F,
+  release_coef: F,
+  /// How much the volume is reduced when the gate is closed. Values from 0.0 (no effect) to 1.0 (silence)
+  depth: F,
+  /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
+  mix: F,
+  bypass: bool,
+  level: F,
+}
+
+impl<F: Float> Gate<F> {
+  pub fn new(sample_rate: F) -> Self {
+    let mut sequencer = StepSequencer::new();
+    sequencer.set_steps(&[F::one()]);
+    Gate {
+      sequencer,
+      beats: F::zero(),
+      sample_rate,
+      attack_seconds: F::zero(),
+      release_seconds: F::zero(),
+      attack_coef: F::one(),
+      release_coef: F::one(),
+      depth: F::one(),
+      mix: F::one(),
+      bypass: false,
+      level: F::one(),
+    }
+  }
+
+  /// Set which steps open the gate. The pattern is repeated and can have up to [`MAX_STEPS`] steps.
+  pub fn set_pattern(&mut self, pattern: &[bool]) {
+    let num_steps = pattern.len().min(MAX_STEPS);
+    for (index, open) in pattern.iter().take(num_steps).enumerate() {
+      self
+        .sequencer
+        .set_step(index, Step::new(F::one()).with_gate(*open));
+    }
+    self.sequencer.set_num_steps(num_steps);
+  }
+
+  /// Set the number of steps per beat (4 for sixteenth notes)
+  pub fn set_division(&mut self, division: F) {
+    self.sequencer.set_division(division);
+  }
+
+  pub fn get_division(&self) -> F {
+    self.sequencer.get_division()
+  }
+
+  pub fn set_attack_seconds(&mut self, seconds: F) {
+    self.attack_seconds = seconds.max(F::zero());
+    self.attack_coef = OnePole::time_constant_coef(self.attack_seconds, self.sample_rate);
+  }
+
+  pub fn get_attack_seconds(&self) -> F {
+    self.attack_seconds
+  }
+
+  pub fn set_release_seconds(&mut self, seconds: F) {
+    self.release_seconds = seconds.max(F::zero());
+    self.release_coef = OnePole::time_constant_coef(self.release_seconds, self.sample_rate);
+  }
+
+  pub fn get_release_seconds(&self) -> F {
+    self.release_seconds
+  }
+
+  pub fn set_depth(&mut self, depth: F) {
+    self.depth = depth.max(F::zero()).min(F::one());
+  }
+
+  pub fn get_depth(&self) -> F {
+    self.depth
+  }
+
+  /// The current gain applied to the signal
+  pub fn get_gain(&self) -> F {
+    F::one() - self.depth * (F::one() - self.level)
+  }
+}
+
+impl<F: Float> Processor<F> for Gate<F> {
+  fn get_mix(&self) -> F {
+    self.mix
+  }
+
+  fn set_mix(&mut self, mix: F) {
+    self.mix = mix;
+  }
+
+  fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  fn set_beats(&mut self, beats: F) {
+    self.beats = beats;
+  }
+
+  fn process_wet(&mut self, left: F, right: F) -> (F, F) {
+    let target = self.sequencer.process(self.beats);
+    let coef = if target > self.level {
+      self.attack_coef
+    } else {
+      self.release_coef
+    };
+    self.level = self.level + (target - self.level) * coef;
+
+    let gain = self.get_gain();
+    (left * gain, right * gain)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn render(gate: &mut Gate<f64>, beats: &[f64]) -> Vec<f64> {
+    beats
+      .iter()
+      .map(|beats| {
+        gate.set_beats(*beats);
+        gate.process_frame(1.0, 1.0).0
+      })
+      .collect()
+  }
+
+  #[test]
+  fn follows_the_pattern() {
+    let mut gate = Gate::new(100.0);
+    gate.set_pattern(&[true, false, true, true]);
+    gate.set_division(2.0);
+
+    let beats: Vec<f64> = (0..8).map(|i| i as f64 * 0.5).collect();
+    let output = render(&mut gate, &beats);
+    assert_eq!(output, vec![1.0, 0.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0]);
+
+    gate.set_depth(0.25);
+    assert_approx_eq!(render(&mut gate, &[0.5])[0], 0.75);
+  }
+
+  #[test]
+  fn smoothed_transitions() {
+    let mut gate = Gate::new(100.0);
+    gate.set_pattern(&[true, false]);
+    gate.set_division(1.0);
+    gate.set_release_seconds(0.1);
+
+    let output = render(&mut gate, &[0.0, 1.0, 1.01, 1.02]);
+    assert_approx_eq!(output[0], 1.0);
+    assert!(output[1] < 1.0 && output[1] > output[2] && output[2] > output[3]);
+    assert!(output[3] > 0.0);
+  }
+
+  #[test]
+  fn zero_depth_is_passthrough() {
+    let mut gate = Gate::new(100.0);
+    gate.set_pattern(&[false]);
+    gate.set_depth(0.0);
+
+    for (index, input) in [1.0, -0.3, 0.123_456_789].iter().enumerate() {
+      gate.set_beats(index as f64 * 0.25);
+      let (left, right) = gate.process_frame(*input, -*input);
+      assert_eq!(left.to_bits(), input.to_bits());
+      assert_eq!(right.to_bits(), (-*input).to_bits());
+    }
+  }
+}
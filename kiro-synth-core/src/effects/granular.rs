@@ -0,0 +1,249 @@
+use crate::effects::delay::{DelayLine, DelayTap, InterpMode};
+use crate::effects::Processor;
+use crate::float::Float;
+use crate::funcs::random::Random;
+
+/// Maximum number of grains playing at the same time
+const MAX_GRAINS: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+struct Grain<F: Float> {
+  active: bool,
+  /// Samples since the grain started
+  age: F,
+  /// The delay at which the grain started reading
+  start_delay: F,
+  /// The playback speed of the grain, one for no pitch shift
+  ratio: F,
+  tap: DelayTap<F>,
+}
+
+impl<F: Float> Default for Grain<F> {
+  fn default() -> Self {
+    Grain {
+      active: false,
+      age: F::zero(),
+      start_delay: F::one(),
+      ratio: F::one(),
+      tap: DelayTap::default(),
+    }
+  }
+}
+
+/// Granular delay that replays short windowed grains of the input with some pitch shift and
+/// random position, from textures to stutters.
+///
+/// The grains are read from a delay line, so the longest grain and spray are limited by its buffer.
+pub struct Granular<'a, F: Float> {
+  /// The length of every grain in seconds
+  grain_seconds: F,
+  /// How many grains overlap, from 1.0 (one after the other) to the maximum number of grains
+  density: F,
+  /// The pitch shift of the grains in semitones
+  pitch: F,
+  /// How much the start of the grains is randomly delayed. Values from 0.0 to 1.0
+  spray: F,
+  /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
+  mix: F,
+  bypass: bool,
+  sample_rate: F,
+  interp_mode: InterpMode,
+  delayline: DelayLine<'a, F>,
+  grains: [Grain<F>; MAX_GRAINS],
+  /// Samples until the next grain starts
+  next_grain: F,
+  random: Random,
+}
+
+impl<'a, F: Float> Granular<'a, F> {
+  pub fn new(sample_rate: F, buffer: &'a mut [F]) -> Self {
+    Granular {
+      grain_seconds: F::val(0.05),
+      density: F::val(2.0),
+      pitch: F::zero(),
+      spray: F::zero(),
+      mix: F::val(0.5),
+      bypass: false,
+      sample_rate,
+      interp_mode: InterpMode::Linear,
+      delayline: DelayLine::new(buffer),
+      grains: [Grain::default(); MAX_GRAINS],
+      next_grain: F::zero(),
+      random: Random::default(),
+    }
+  }
+
+  pub fn set_grain_seconds(&mut self, grain_seconds: F) {
+    self.grain_seconds = grain_seconds.max(self.sample_rate.recip());
+  }
+
+  pub fn get_grain_seconds(&self) -> F {
+    self.grain_seconds
+  }
+
+  pub fn set_density(&mut self, density: F) {
+    self.density = density.max(F::one()).min(F::val(MAX_GRAINS as f64));
+  }
+
+  pub fn get_density(&self) -> F {
+    self.density
+  }
+
+  pub fn set_pitch(&mut self, semitones: F) {
+    self.pitch = semitones;
+  }
+
+  pub fn get_pitch(&self) -> F {
+    self.pitch
+  }
+
+  pub fn set_spray(&mut self, spray: F) {
+    self.spray = spray.max(F::zero()).min(F::one());
+  }
+
+  pub fn get_spray(&self) -> F {
+    self.spray
+  }
+
+  /// Choose how the grains read the delay between its samples, linear by default
+  pub fn set_interp_mode(&mut self, mode: InterpMode) {
+    self.interp_mode = mode;
+  }
+
+  pub fn get_interp_mode(&self) -> InterpMode {
+    self.interp_mode
+  }
+
+  fn grain_samples(&self) -> F {
+    let max_samples = F::val(self.delayline.len().saturating_sub(2) as f64).max(F::one());
+    (self.grain_seconds * self.sample_rate)
+      .round()
+      .max(F::one())
+      .min(max_samples)
+  }
+
+  fn start_grain(&mut self, grain_samples: F) {
+    let ratio = F::val(2.0).powf(self.pitch / F::val(12.0));
+    // the grains faster than the input need to start late enough to not overtake it
+    let drift = grain_samples * (F::one() - ratio);
+    let min_delay = F::one() + (-drift).max(F::zero());
+    let max_delay = F::val(self.delayline.len().saturating_sub(2) as f64) - drift.max(F::zero());
+    let spray = self.spray * self.random.unipolar::<F>() * (max_delay - min_delay).max(F::zero());
+
+    if let Some(grain) = self.grains.iter_mut().find(|grain| !grain.active) {
+      *grain = Grain {
+        active: true,
+        age: F::zero(),
+        start_delay: min_delay + spray,
+        ratio,
+        tap: DelayTap::default(),
+      };
+    }
+  }
+
+  fn process_grains(&mut self) -> F {
+    let grain_samples = self.grain_samples();
+    if self.next_grain <= F::zero() {
+      self.start_grain(grain_samples);
+      self.next_grain = self.next_grain + grain_samples / self.density;
+    }
+    self.next_grain = self.next_grain - F::one();
+
+    let max_delay = F::val(self.delayline.len().saturating_sub(2) as f64).max(F::one());
+    let mut output = F::zero();
+    for grain in self.grains.iter_mut().filter(|grain| grain.active) {
+      let t = grain.age / grain_samples;
+      let window = F::val(0.5) - F::val(0.5) * (F::val(2.0) * F::PI * t).cos();
+      let delay = grain.start_delay + grain.age * (F::one() - grain.ratio);
+      let delay = delay.max(F::one()).min(max_delay);
+      let sample = self.delayline.read(delay, self.interp_mode, &mut grain.tap);
+      output = output + sample * window;
+
+      grain.age = grain.age + F::one();
+      grain.active = grain.age < grain_samples;
+    }
+
+    // the overlapping windows add up to half the density
+    let gain = (F::val(2.0) / self.density).min(F::one());
+    output * gain
+  }
+}
+
+impl<'a, F: Float> Processor<F> for Granular<'a, F> {
+  fn get_mix(&self) -> F {
+    self.mix
+  }
+
+  fn set_mix(&mut self, mix: F) {
+    self.mix = mix;
+  }
+
+  fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  fn process_wet(&mut self, left: F, right: F) -> (F, F) {
+    self.delayline.update((left + right) * F::val(0.5));
+    let sample = self.process_grains();
+    (sample, sample)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  const SAMPLE_RATE: f64 = 1000.0;
+
+  fn sine(index: usize) -> f64 {
+    (2.0 * std::f64::consts::PI * 20.0 * index as f64 / SAMPLE_RATE).sin()
+  }
+
+  fn render(pitch: f64, density: f64) -> Vec<f64> {
+    let mut buffer = [0.0f64; 512];
+    let mut granular = Granular::new(SAMPLE_RATE, &mut buffer);
+    granular.set_grain_seconds(0.1);
+    granular.set_density(density);
+    granular.set_pitch(pitch);
+    granular.set_mix(1.0);
+    (0..2000)
+      .map(|index| granular.process_frame(sine(index), sine(index)).0)
+      .collect()
+  }
+
+  /// The power of the signal at some frequency
+  fn power(signal: &[f64], freq: f64) -> f64 {
+    let (mut re, mut im) = (0.0, 0.0);
+    for (index, sample) in signal.iter().enumerate() {
+      let phase = 2.0 * std::f64::consts::PI * freq * index as f64 / SAMPLE_RATE;
+      re += sample * phase.cos();
+      im += sample * phase.sin();
+    }
+    re * re + im * im
+  }
+
+  #[test]
+  fn overlapping_grains_reconstruct_the_input() {
+    for density in [2.0, 4.0].iter() {
+      let output = render(0.0, *density);
+      for (index, sample) in output.iter().enumerate().skip(200) {
+        assert_approx_eq!(sample, sine(index), 1e-6);
+      }
+    }
+  }
+
+  #[test]
+  fn pitch_shift_changes_the_frequency() {
+    let output = render(0.0, 2.0);
+    assert!(power(&output[200..], 20.0) > 100.0 * power(&output[200..], 40.0));
+
+    // an octave up moves the energy to the double of the frequency
+    let output = render(12.0, 2.0);
+    assert!(power(&output[200..], 40.0) > 10.0 * power(&output[200..], 20.0));
+  }
+}
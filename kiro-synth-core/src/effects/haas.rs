@@ -0,0 +1,130 @@
+use crate::effects::delay::DelayLine;
+use crate::effects::Processor;
+use crate::float::Float;
+
+/// The delay of the right channel for the full amount, short enough not to be perceived as an echo
+pub const MAX_HAAS_SECONDS: f64 = 0.02;
+
+/// The maximum proportion of the delayed signal in the right channel, which limits the comb
+/// filtering to about -6 dB when both channels are summed to mono
+const MAX_DELAYED: f64 = 0.5;
+
+/// Stereo widener based on the Haas effect, delaying the right channel a few milliseconds
+/// so the image gets wider without changing the pan.
+pub struct Haas<'a, F: Float> {
+  /// The width from 0.0 (no effect) to 1.0. It changes both the delay and the proportion of delayed signal.
+  amount: F,
+  /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
+  mix: F,
+  bypass: bool,
+  sample_rate: F,
+  delayline: DelayLine<'a, F>,
+  delay_samples: usize,
+  delayed: F,
+}
+
+impl<'a, F: Float> Haas<'a, F> {
+  /// The buffer should fit [`MAX_HAAS_SECONDS`], shorter buffers limit the delay
+  pub fn new(sample_rate: F, buffer: &'a mut [F]) -> Self {
+    Haas {
+      amount: F::zero(),
+      mix: F::one(),
+      bypass: false,
+      sample_rate,
+      delayline: DelayLine::new(buffer),
+      delay_samples: 1,
+      delayed: F::zero(),
+    }
+  }
+
+  pub fn set_amount(&mut self, amount: F) {
+    self.amount = amount.max(F::zero()).min(F::one());
+    let seconds = self.amount * F::val(MAX_HAAS_SECONDS);
+    let samples = (seconds * self.sample_rate).round().to_usize().unwrap_or(0);
+    self.delay_samples = samples.max(1).min(self.delayline.len());
+    self.delayed = self.amount * F::val(MAX_DELAYED);
+  }
+
+  pub fn get_amount(&self) -> F {
+    self.amount
+  }
+}
+
+impl<'a, F: Float> Processor<F> for Haas<'a, F> {
+  fn get_mix(&self) -> F {
+    self.mix
+  }
+
+  fn set_mix(&mut self, mix: F) {
+    self.mix = mix;
+  }
+
+  fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  fn process_wet(&mut self, left: F, right: F) -> (F, F) {
+    self.delayline.update(right);
+    let delayed = self.delayline.get(self.delay_samples);
+    let right = right * (F::one() - self.delayed) + delayed * self.delayed;
+    (left, right)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::funcs::random::Random;
+
+  const SAMPLE_RATE: f64 = 48000.0;
+
+  fn render(amount: f64, input: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    let mut buffer = [0.0; 1024];
+    let mut haas = Haas::new(SAMPLE_RATE, &mut buffer);
+    haas.set_amount(amount);
+    input
+      .iter()
+      .map(|sample| haas.process_frame(*sample, *sample))
+      .unzip()
+  }
+
+  fn correlation(left: &[f64], right: &[f64]) -> f64 {
+    let product: f64 = left.iter().zip(right.iter()).map(|(l, r)| l * r).sum();
+    let left_energy: f64 = left.iter().map(|l| l * l).sum();
+    let right_energy: f64 = right.iter().map(|r| r * r).sum();
+    product / (left_energy * right_energy).sqrt()
+  }
+
+  #[test]
+  fn decorrelates_the_channels() {
+    let mut random = Random::new(1);
+    let noise: Vec<f64> = (0..4800).map(|_| random.bipolar()).collect();
+
+    let (left, right) = render(0.0, &noise);
+    assert!(correlation(&left, &right) > 0.99);
+
+    let (left, right) = render(1.0, &noise);
+    assert!(correlation(&left, &right) < 0.95);
+  }
+
+  #[test]
+  fn mono_compatible() {
+    // the sum of both channels never drops more than 6 dB at any frequency
+    for freq in (1..200).map(|index| index as f64 * 100.0) {
+      let input: Vec<f64> = (0..4800)
+        .map(|index| (2.0 * std::f64::consts::PI * freq * index as f64 / SAMPLE_RATE).sin())
+        .collect();
+      let (left, right) = render(1.0, &input);
+      let peak = left[2400..]
+        .iter()
+        .zip(right[2400..].iter())
+        .map(|(l, r)| ((l + r) / 2.0).abs())
+        .fold(0.0, f64::max);
+      assert!(peak > 0.499, "{} Hz: {}", freq, peak);
+    }
+  }
+}
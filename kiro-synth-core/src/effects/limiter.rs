@@ -0,0 +1,229 @@
+use crate::effects::delay::DelayLine;
+use crate::effects::Processor;
+use crate::filters::one_pole::OnePole;
+use crate::float::Float;
+use crate::funcs::decibels::Decibels;
+
+/// Brickwall limiter that looks ahead the peaks through a short delay of the signal,
+/// so the gain is already reduced when they reach the output and it never overshoots the threshold.
+///
+/// The lookahead delays the output, see [`Limiter::get_latency_samples`].
+pub struct Limiter<'a, F: Float> {
+  /// The ceiling of the output in dB
+  threshold_db: F,
+  threshold: F,
+  /// Time in seconds for the gain to recover after a peak
+  release_seconds: F,
+  release_coef: F,
+  /// Time in seconds to anticipate the peaks. Clamped to the length of the buffers.
+  lookahead_seconds: F,
+  lookahead_samples: usize,
+  /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
+  mix: F,
+  bypass: bool,
+  sample_rate: F,
+  left_delayline: DelayLine<'a, F>,
+  right_delayline: DelayLine<'a, F>,
+  gain: F,
+}
+
+impl<'a, F: Float> Limiter<'a, F> {
+  pub fn new(sample_rate: F, left_buffer: &'a mut [F], right_buffer: &'a mut [F]) -> Self {
+    Limiter {
+      threshold_db: F::zero(),
+      threshold: F::one(),
+      release_seconds: F::zero(),
+      release_coef: F::one(),
+      lookahead_seconds: F::zero(),
+      lookahead_samples: 0,
+      mix: F::one(),
+      bypass: false,
+      sample_rate,
+      left_delayline: DelayLine::new(left_buffer),
+      right_delayline: DelayLine::new(right_buffer),
+      gain: F::one(),
+    }
+  }
+
+  pub fn set_threshold_db(&mut self, threshold_db: F) {
+    self.threshold_db = threshold_db;
+    self.threshold = Decibels::new(threshold_db).to_amplitude();
+  }
+
+  pub fn get_threshold_db(&self) -> F {
+    self.threshold_db
+  }
+
+  pub fn set_release_seconds(&mut self, seconds: F) {
+    self.release_seconds = seconds.max(F::zero());
+    self.release_coef = OnePole::time_constant_coef(self.release_seconds, self.sample_rate);
+  }
+
+  pub fn get_release_seconds(&self) -> F {
+    self.release_seconds
+  }
+
+  pub fn set_lookahead_seconds(&mut self, seconds: F) {
+    self.lookahead_seconds = seconds.max(F::zero());
+    let samples = (self.lookahead_seconds * self.sample_rate).round();
+    let max_samples = self
+      .left_delayline
+      .len()
+      .min(self.right_delayline.len())
+      .saturating_sub(1);
+    self.lookahead_samples = samples.to_usize().unwrap_or(0).min(max_samples);
+  }
+
+  pub fn get_lookahead_seconds(&self) -> F {
+    self.lookahead_seconds
+  }
+
+  /// The current gain reduction applied to the output
+  pub fn get_gain(&self) -> F {
+    self.gain
+  }
+
+  /// The gain needed for a frame not to exceed the threshold
+  fn required_gain(&self, delay_samples: usize) -> F {
+    let left = self.left_delayline.get(delay_samples).abs();
+    let right = self.right_delayline.get(delay_samples).abs();
+    let peak = left.max(right);
+    if peak > self.threshold {
+      self.threshold / peak
+    } else {
+      F::one()
+    }
+  }
+}
+
+impl<'a, F: Float> Processor<F> for Limiter<'a, F> {
+  fn get_mix(&self) -> F {
+    self.mix
+  }
+
+  fn set_mix(&mut self, mix: F) {
+    self.mix = mix;
+  }
+
+  fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  /// The delay of the output introduced by the lookahead, zero when bypassed
+  fn get_latency_samples(&self) -> usize {
+    if self.bypass {
+      0
+    } else {
+      self.lookahead_samples
+    }
+  }
+
+  fn process_wet(&mut self, left: F, right: F) -> (F, F) {
+    self.left_delayline.update(left);
+    self.right_delayline.update(right);
+
+    // recover towards unity, but ramp down linearly to reach the gain that every frame
+    // in the lookahead requires by the time it is output
+    let lookahead = self.lookahead_samples;
+    let mut gain = self.gain + (F::one() - self.gain) * self.release_coef;
+    for samples_left in 0..=lookahead {
+      let required = self.required_gain(lookahead + 1 - samples_left);
+      if required < self.gain {
+        let steps = F::val(samples_left + 1);
+        gain = gain.min(self.gain - (self.gain - required) / steps);
+      } else {
+        gain = gain.min(required);
+      }
+    }
+    self.gain = gain;
+
+    let (left, right) = self.delayed_dry(left, right);
+    (left * gain, right * gain)
+  }
+
+  /// The dry signal is delayed by the lookahead too, so it is aligned with the wet one in the mix
+  fn delayed_dry(&self, _left: F, _right: F) -> (F, F) {
+    let delay_samples = self.lookahead_samples + 1;
+    let left = self.left_delayline.get(delay_samples);
+    let right = self.right_delayline.get(delay_samples);
+    (left, right)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn render(limiter: &mut Limiter<f64>, input: &[f64]) -> Vec<f64> {
+    input
+      .iter()
+      .map(|sample| limiter.process_frame(*sample, -*sample).0)
+      .collect()
+  }
+
+  #[test]
+  fn transient_never_exceeds_the_ceiling() {
+    let (mut left_buffer, mut right_buffer) = ([0.0; 64], [0.0; 64]);
+    let mut limiter = Limiter::new(1000.0, &mut left_buffer, &mut right_buffer);
+    limiter.set_threshold_db(-6.0);
+    limiter.set_release_seconds(0.01);
+    limiter.set_lookahead_seconds(0.005);
+    assert_eq!(limiter.get_latency_samples(), 5);
+
+    let ceiling = Decibels::new(-6.0).to_amplitude();
+    let mut input = vec![0.1; 200];
+    input[50] = 2.0;
+    input[51] = -1.5;
+    input[120] = 0.9;
+    let output = render(&mut limiter, &input);
+
+    assert!(output.iter().all(|sample| sample.abs() <= ceiling + 1e-12));
+    // the peak is limited right at the ceiling after the latency
+    assert!((output[55].abs() - ceiling).abs() < 1e-12);
+    // the gain was already reduced before the peak
+    assert!(output[54] < 0.1);
+    // and recovers after it
+    assert!((output[110] - 0.1).abs() < 1e-3);
+  }
+
+  #[test]
+  fn below_threshold_is_only_delayed() {
+    let (mut left_buffer, mut right_buffer) = ([0.0; 16], [0.0; 16]);
+    let mut limiter = Limiter::new(1000.0, &mut left_buffer, &mut right_buffer);
+    limiter.set_lookahead_seconds(0.003);
+
+    let input: Vec<f64> = (0..10).map(|index| index as f64 * 0.1).collect();
+    let output = render(&mut limiter, &input);
+    assert_eq!(&output[..3], &[0.0, 0.0, 0.0]);
+    assert_eq!(&output[3..], &input[..7]);
+  }
+
+  #[test]
+  fn dry_signal_is_delayed_by_the_lookahead() {
+    let (mut left_buffer, mut right_buffer) = ([0.0; 16], [0.0; 16]);
+    let mut limiter = Limiter::new(1000.0, &mut left_buffer, &mut right_buffer);
+    limiter.set_threshold_db(-6.0);
+    limiter.set_lookahead_seconds(0.003);
+    limiter.set_mix(0.5);
+
+    let ceiling = Decibels::new(-6.0).to_amplitude();
+    let mut input = vec![0.0; 10];
+    input[2] = 1.0;
+    let output = render(&mut limiter, &input);
+    assert!(output[..5].iter().all(|sample| *sample == 0.0));
+    assert!((output[5] - (ceiling * 0.5 + 0.5)).abs() < 1e-12);
+    assert!(output[6..].iter().all(|sample| *sample == 0.0));
+  }
+
+  #[test]
+  fn lookahead_with_empty_buffers() {
+    let (mut left_buffer, mut right_buffer) = ([0.0; 0], [0.0; 0]);
+    let mut limiter: Limiter<f64> = Limiter::new(1000.0, &mut left_buffer, &mut right_buffer);
+    limiter.set_lookahead_seconds(0.003);
+    assert_eq!(limiter.get_latency_samples(), 0);
+  }
+}
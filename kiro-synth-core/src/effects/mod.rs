@@ -1 +1,166 @@
+pub mod chorus;
+pub mod compressor;
 pub mod delay;
+pub mod gate;
+pub mod granular;
+pub mod haas;
+pub mod limiter;
+pub mod reverb;
+pub mod stereo_delay;
+
+use crate::float::Float;
+use crate::meters::PeakMeter;
+
+/// Read-only level of the wet signal of an effect, to see how much it contributes to the output.
+pub struct WetMeter<F: Float> {
+  meter: PeakMeter<F>,
+}
+
+impl<F: Float> WetMeter<F> {
+  pub fn new(sample_rate: F) -> Self {
+    WetMeter {
+      meter: PeakMeter::new(sample_rate, F::val(0.7), F::val(24.0)),
+    }
+  }
+
+  /// The level in dB of the louder channel of the wet signal, minus infinity when silent or bypassed
+  pub fn get_level_db(&self) -> F {
+    self.meter.get_level()
+  }
+
+  pub fn get_peak_db(&self) -> F {
+    self.meter.get_peak()
+  }
+
+  fn process(&mut self, left: F, right: F) {
+    self.meter.process(left.abs().max(right.abs()));
+  }
+
+  pub fn reset(&mut self) {
+    self.meter.reset_all();
+  }
+}
+
+/// Common interface for the effects.
+///
+/// Implementors only need to provide the wet signal, the dry/wet mix and the bypass
+/// are handled by [`Processor::process_frame`].
+pub trait Processor<F: Float> {
+  /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
+  fn get_mix(&self) -> F;
+
+  fn set_mix(&mut self, mix: F);
+
+  fn is_bypassed(&self) -> bool;
+
+  /// When bypassed the input is returned unchanged and the internal state is not updated.
+  fn set_bypass(&mut self, bypass: bool);
+
+  /// Sync the effect with the position of the transport in beats, before processing a frame.
+  /// Only the tempo synced effects need to implement it.
+  fn set_beats(&mut self, _beats: F) {}
+
+  /// The delay in samples that the effect adds to the signal, such as a lookahead.
+  /// Only the effects that delay the output need to implement it.
+  fn get_latency_samples(&self) -> usize {
+    0
+  }
+
+  /// Process an stereo frame and return only the wet signal.
+  fn process_wet(&mut self, left: F, right: F) -> (F, F);
+
+  /// The dry signal to mix with the wet one after processing a frame, delayed as much as the output.
+  /// Only the effects that delay the output need to implement it.
+  fn delayed_dry(&self, left: F, right: F) -> (F, F) {
+    (left, right)
+  }
+
+  /// Process an stereo frame applying the bypass and the dry/wet mix.
+  fn process_frame(&mut self, left: F, right: F) -> (F, F) {
+    if self.is_bypassed() {
+      (left, right)
+    } else {
+      let mix = self.get_mix();
+      let dry = F::one() - mix;
+      let (wet_left, wet_right) = self.process_wet(left, right);
+      let (left, right) = self.delayed_dry(left, right);
+      (wet_left * mix + left * dry, wet_right * mix + right * dry)
+    }
+  }
+
+  /// Process an stereo frame like [`Processor::process_wet`], measuring the level of the wet signal.
+  fn process_wet_metered(&mut self, left: F, right: F, meter: &mut WetMeter<F>) -> (F, F) {
+    let (wet_left, wet_right) = self.process_wet(left, right);
+    meter.process(wet_left, wet_right);
+    (wet_left, wet_right)
+  }
+
+  /// Process an stereo frame like [`Processor::process_frame`], measuring the level of the wet signal
+  /// before the dry/wet mix. Use [`Processor::process_frame`] to skip the cost of metering.
+  fn process_frame_metered(&mut self, left: F, right: F, meter: &mut WetMeter<F>) -> (F, F) {
+    if self.is_bypassed() {
+      meter.reset();
+      (left, right)
+    } else {
+      let mix = self.get_mix();
+      let dry = F::one() - mix;
+      let (wet_left, wet_right) = self.process_wet_metered(left, right, meter);
+      let (left, right) = self.delayed_dry(left, right);
+      (wet_left * mix + left * dry, wet_right * mix + right * dry)
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::funcs::decibels::Decibels;
+  use assert_approx_eq::assert_approx_eq;
+
+  struct Gain {
+    gain: f64,
+    bypass: bool,
+  }
+
+  impl Processor<f64> for Gain {
+    fn get_mix(&self) -> f64 {
+      0.5
+    }
+
+    fn set_mix(&mut self, _mix: f64) {}
+
+    fn is_bypassed(&self) -> bool {
+      self.bypass
+    }
+
+    fn set_bypass(&mut self, bypass: bool) {
+      self.bypass = bypass;
+    }
+
+    fn process_wet(&mut self, left: f64, right: f64) -> (f64, f64) {
+      (left * self.gain, right * self.gain)
+    }
+  }
+
+  #[test]
+  fn wet_meter() {
+    let mut effect = Gain {
+      gain: 0.25,
+      bypass: false,
+    };
+    let mut meter = WetMeter::new(100.0);
+    assert_eq!(meter.get_level_db(), f64::NEG_INFINITY);
+
+    for _ in 0..10 {
+      let (left, right) = effect.process_frame_metered(1.0, -0.5, &mut meter);
+      assert_approx_eq!(left, 0.5 + 0.125);
+      assert_approx_eq!(right, -0.25 - 0.0625);
+    }
+    assert_approx_eq!(meter.get_level_db(), Decibels::from_amplitude(0.25).value());
+
+    effect.set_bypass(true);
+    let output = effect.process_frame_metered(1.0, -0.5, &mut meter);
+    assert_eq!(output, (1.0, -0.5));
+    assert_eq!(meter.get_level_db(), f64::NEG_INFINITY);
+  }
+}
@@ -0,0 +1,333 @@
+use crate::effects::delay::DelayLine;
+use crate::effects::Processor;
+use crate::filters::one_pole::{Mode, OnePole};
+use crate::float::Float;
+
+/// The longest pre-delay, which decides the size of the pre-delay lines
+pub const MAX_PREDELAY_SECONDS: f64 = 0.1;
+
+/// The sample rate for which the lengths of the delay lines are defined
+const REFERENCE_SAMPLE_RATE: f64 = 44100.0;
+
+/// Lengths of the delay lines of the feedback network, mutually prime so their echoes don't pile up
+const TANK_LENGTHS: [usize; 4] = [1557, 1617, 1491, 1422];
+
+/// Lengths of the allpass diffusers for the left and right channels, slightly different to decorrelate them
+const DIFFUSER_LENGTHS: [usize; 4] = [225, 341, 248, 364];
+
+/// The gain of the allpass diffusers
+const DIFFUSION: f64 = 0.6;
+
+/// The crossover frequency below which the low damping applies
+const LOW_DAMP_FREQ: f64 = 200.0;
+
+/// The maximum amount of the damping, to always keep some of the frequencies
+const MAX_DAMP: f64 = 0.95;
+
+struct Diffuser<'a, F: Float> {
+  delayline: DelayLine<'a, F>,
+}
+
+impl<'a, F: Float> Diffuser<'a, F> {
+  fn process(&mut self, input: F) -> F {
+    let gain = F::val(DIFFUSION);
+    let delayed = self.delayline.get(self.delayline.len());
+    let feedback = input + delayed * gain;
+    self.delayline.update(feedback);
+    delayed - feedback * gain
+  }
+}
+
+/// Stereo reverb based on a feedback delay network, with a pre-delay before the tail and
+/// separate damping of the high and low frequencies.
+pub struct Reverb<'a, F: Float> {
+  sample_rate: F,
+  /// The time in seconds for the tail to decay 60 dB
+  decay_seconds: F,
+  predelay_seconds: F,
+  predelay_samples: usize,
+  /// How much the high frequencies decay faster than the rest, from 0.0 to 1.0
+  high_damp: F,
+  /// How much the low frequencies decay faster than the rest, from 0.0 to 1.0
+  low_damp: F,
+  /// The stereo width of the tail, from 0.0 (mono) to 1.0
+  width: F,
+  /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
+  mix: F,
+  bypass: bool,
+  predelay: [DelayLine<'a, F>; 2],
+  diffusers: [Diffuser<'a, F>; 4],
+  tank: [DelayLine<'a, F>; 4],
+  gains: [F; 4],
+  high_state: [F; 4],
+  lows: [OnePole<F>; 4],
+}
+
+impl<'a, F: Float> Reverb<'a, F> {
+  /// The length of the buffer needed for a sample rate
+  pub fn buffer_len(sample_rate: F) -> usize {
+    let predelay = 2 * Self::predelay_len(sample_rate);
+    let lines = TANK_LENGTHS.iter().chain(DIFFUSER_LENGTHS.iter());
+    let lines_len: usize = lines.map(|len| Self::scaled_len(*len, sample_rate)).sum();
+    predelay + lines_len
+  }
+
+  /// The buffer is shared by all the delay lines, and needs at least [`buffer_len`](Self::buffer_len)
+  pub fn new(sample_rate: F, buffer: &'a mut [F]) -> Self {
+    assert!(buffer.len() >= Self::buffer_len(sample_rate));
+
+    let predelay_len = Self::predelay_len(sample_rate);
+    let (left_predelay, buffer) = buffer.split_at_mut(predelay_len);
+    let (right_predelay, buffer) = buffer.split_at_mut(predelay_len);
+
+    let len = |index: usize| Self::scaled_len(DIFFUSER_LENGTHS[index], sample_rate);
+    let (diffuser0, buffer) = buffer.split_at_mut(len(0));
+    let (diffuser1, buffer) = buffer.split_at_mut(len(1));
+    let (diffuser2, buffer) = buffer.split_at_mut(len(2));
+    let (diffuser3, buffer) = buffer.split_at_mut(len(3));
+
+    let len = |index: usize| Self::scaled_len(TANK_LENGTHS[index], sample_rate);
+    let (tank0, buffer) = buffer.split_at_mut(len(0));
+    let (tank1, buffer) = buffer.split_at_mut(len(1));
+    let (tank2, buffer) = buffer.split_at_mut(len(2));
+    let (tank3, _) = buffer.split_at_mut(len(3));
+
+    let diffuser = |buffer| Diffuser {
+      delayline: DelayLine::new(buffer),
+    };
+
+    let mut low = OnePole::new(sample_rate, Mode::LowPass);
+    low.set_cutoff(F::val(LOW_DAMP_FREQ));
+
+    let mut reverb = Reverb {
+      sample_rate,
+      decay_seconds: F::one(),
+      predelay_seconds: F::zero(),
+      predelay_samples: 0,
+      high_damp: F::zero(),
+      low_damp: F::zero(),
+      width: F::one(),
+      mix: F::one(),
+      bypass: false,
+      predelay: [
+        DelayLine::new(left_predelay),
+        DelayLine::new(right_predelay),
+      ],
+      diffusers: [
+        diffuser(diffuser0),
+        diffuser(diffuser1),
+        diffuser(diffuser2),
+        diffuser(diffuser3),
+      ],
+      tank: [
+        DelayLine::new(tank0),
+        DelayLine::new(tank1),
+        DelayLine::new(tank2),
+        DelayLine::new(tank3),
+      ],
+      gains: [F::zero(); 4],
+      high_state: [F::zero(); 4],
+      lows: [low; 4],
+    };
+    reverb.update_gains();
+    reverb
+  }
+
+  /// Set the time in seconds for the tail to decay 60 dB
+  pub fn set_decay(&mut self, seconds: F) {
+    self.decay_seconds = seconds.max(F::val(0.01));
+    self.update_gains();
+  }
+
+  pub fn get_decay(&self) -> F {
+    self.decay_seconds
+  }
+
+  /// Set the silence in seconds before the tail starts, up to [`MAX_PREDELAY_SECONDS`]
+  pub fn set_predelay(&mut self, seconds: F) {
+    let seconds = seconds.max(F::zero()).min(F::val(MAX_PREDELAY_SECONDS));
+    self.predelay_seconds = seconds;
+    let samples = (seconds * self.sample_rate).round().to_usize().unwrap_or(0);
+    self.predelay_samples = samples.min(self.predelay[0].len() - 1);
+  }
+
+  pub fn get_predelay(&self) -> F {
+    self.predelay_seconds
+  }
+
+  /// Set how much the low frequencies decay faster than the rest, from 0.0 to 1.0
+  pub fn set_low_damp(&mut self, amount: F) {
+    self.low_damp = amount.max(F::zero()).min(F::one());
+  }
+
+  pub fn get_low_damp(&self) -> F {
+    self.low_damp
+  }
+
+  /// Set how much the high frequencies decay faster than the rest, from 0.0 to 1.0
+  pub fn set_high_damp(&mut self, amount: F) {
+    self.high_damp = amount.max(F::zero()).min(F::one());
+  }
+
+  pub fn get_high_damp(&self) -> F {
+    self.high_damp
+  }
+
+  /// Set the stereo width of the tail, from 0.0 (mono) to 1.0
+  pub fn set_width(&mut self, width: F) {
+    self.width = width.max(F::zero()).min(F::one());
+  }
+
+  pub fn get_width(&self) -> F {
+    self.width
+  }
+
+  fn update_gains(&mut self) {
+    for (gain, line) in self.gains.iter_mut().zip(self.tank.iter()) {
+      let decay_samples = self.decay_seconds * self.sample_rate;
+      let exponent = F::val(-3.0) * F::val(line.len()) / decay_samples;
+      *gain = F::val(10.0).powf(exponent);
+    }
+  }
+
+  fn predelay_len(sample_rate: F) -> usize {
+    let samples = F::val(MAX_PREDELAY_SECONDS) * sample_rate;
+    samples.ceil().to_usize().unwrap_or(0) + 1
+  }
+
+  fn scaled_len(len: usize, sample_rate: F) -> usize {
+    let scale = sample_rate / F::val(REFERENCE_SAMPLE_RATE);
+    (F::val(len) * scale).round().to_usize().unwrap_or(1).max(1)
+  }
+}
+
+impl<'a, F: Float> Processor<F> for Reverb<'a, F> {
+  fn get_mix(&self) -> F {
+    self.mix
+  }
+
+  fn set_mix(&mut self, mix: F) {
+    self.mix = mix;
+  }
+
+  fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  fn process_wet(&mut self, left: F, right: F) -> (F, F) {
+    self.predelay[0].update(left);
+    self.predelay[1].update(right);
+    let left = self.predelay[0].get(self.predelay_samples + 1);
+    let right = self.predelay[1].get(self.predelay_samples + 1);
+
+    let left = self.diffusers[0].process(left);
+    let left = self.diffusers[1].process(left);
+    let right = self.diffusers[2].process(right);
+    let right = self.diffusers[3].process(right);
+
+    // the output of the delay lines after the damping
+    let high_damp = self.high_damp * F::val(MAX_DAMP);
+    let low_damp = self.low_damp * F::val(MAX_DAMP);
+    let mut damped = [F::zero(); 4];
+    for (index, line) in self.tank.iter().enumerate() {
+      let output = line.get(line.len());
+      let high = &mut self.high_state[index];
+      *high = output + (*high - output) * high_damp;
+      let low = self.lows[index].process(*high);
+      damped[index] = *high - low * low_damp;
+    }
+
+    // orthogonal mixing of the delay lines so the echoes spread among them
+    let half = F::val(0.5);
+    let (a, b) = (damped[0] + damped[1], damped[0] - damped[1]);
+    let (c, d) = (damped[2] + damped[3], damped[2] - damped[3]);
+    let feedback = [
+      (a + c) * half,
+      (b + d) * half,
+      (a - c) * half,
+      (b - d) * half,
+    ];
+    let inputs = [left, left, right, right];
+    for (index, line) in self.tank.iter_mut().enumerate() {
+      line.update(inputs[index] + feedback[index] * self.gains[index]);
+    }
+
+    let wet_left = (damped[0] + damped[1]) * half;
+    let wet_right = (damped[2] + damped[3]) * half;
+    let mid = (wet_left + wet_right) * half;
+    let side = (wet_left - wet_right) * half * self.width;
+    (mid + side, mid - side)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const SAMPLE_RATE: f64 = 8000.0;
+
+  fn render_impulse<S: Fn(&mut Reverb<f64>)>(len: usize, setup: S) -> Vec<f64> {
+    let mut buffer = vec![0.0; Reverb::buffer_len(SAMPLE_RATE)];
+    let mut reverb = Reverb::new(SAMPLE_RATE, &mut buffer);
+    setup(&mut reverb);
+    (0..len)
+      .map(|index| {
+        let input = if index == 0 { 1.0 } else { 0.0 };
+        let (left, right) = reverb.process_frame(input, input);
+        left + right
+      })
+      .collect()
+  }
+
+  fn onset(output: &[f64]) -> usize {
+    output
+      .iter()
+      .position(|sample| sample.abs() > 1e-9)
+      .unwrap()
+  }
+
+  #[test]
+  fn predelay_delays_the_tail() {
+    let output = render_impulse(2000, |_| {});
+    let delayed = render_impulse(2000, |reverb| reverb.set_predelay(0.05));
+    assert_eq!(onset(&delayed), onset(&output) + 400);
+    assert!(delayed[..onset(&output) + 400]
+      .iter()
+      .all(|sample| *sample == 0.0));
+  }
+
+  #[test]
+  fn high_damping_darkens_the_tail() {
+    // the proportion of the energy of the tail in the high frequencies, from the differences between samples
+    let brightness = |high_damp: f64| {
+      let output = render_impulse(8000, |reverb| {
+        reverb.set_decay(2.0);
+        reverb.set_high_damp(high_damp);
+      });
+      let tail = &output[4000..];
+      let total: f64 = tail.iter().map(|sample| sample * sample).sum();
+      let high: f64 = tail
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).powi(2))
+        .sum();
+      high / total
+    };
+    assert!(brightness(0.7) < 0.5 * brightness(0.0));
+  }
+
+  #[test]
+  fn decays() {
+    let output = render_impulse(16000, |reverb| reverb.set_decay(0.5));
+    let peak = |samples: &[f64]| {
+      samples
+        .iter()
+        .fold(0.0_f64, |peak, sample| peak.max(sample.abs()))
+    };
+    assert!(peak(&output[..4000]) > 0.01);
+    assert!(peak(&output[12000..]) < 1e-4 * peak(&output[..4000]));
+  }
+}
@@ -0,0 +1,169 @@
+use crate::effects::delay::DelayLine;
+use crate::effects::Processor;
+use crate::float::Float;
+
+/// Delay with independent times for the left and right channels, for rhythmic stereo patterns.
+///
+/// Every channel is fed back into its own delay line.
+pub struct StereoDelay<'a, F: Float> {
+  /// The delay of the left channel. Clamped to the length of its buffer.
+  left_seconds: F,
+  /// The delay of the right channel. Clamped to the length of its buffer.
+  right_seconds: F,
+  /// Whether the right channel uses the delay of the left one
+  link: bool,
+  /// The amount of feedback for every channel into its delay line. Values from 0.0 to 1.0
+  feedback: F,
+  /// The dry/wet proportion. Values from 0.0 (dry) to 1.0 (wet)
+  mix: F,
+  bypass: bool,
+  sample_rate: F,
+  left_delayline: DelayLine<'a, F>,
+  right_delayline: DelayLine<'a, F>,
+  left_samples: usize,
+  right_samples: usize,
+}
+
+impl<'a, F: Float> StereoDelay<'a, F> {
+  pub fn new(sample_rate: F, left_buffer: &'a mut [F], right_buffer: &'a mut [F]) -> Self {
+    Self {
+      left_seconds: sample_rate.recip(),
+      right_seconds: sample_rate.recip(),
+      link: false,
+      feedback: F::zero(),
+      mix: F::zero(),
+      bypass: false,
+      sample_rate,
+      left_delayline: DelayLine::new(left_buffer),
+      right_delayline: DelayLine::new(right_buffer),
+      left_samples: 1,
+      right_samples: 1,
+    }
+  }
+
+  pub fn set_delay_seconds_lr(&mut self, left_seconds: F, right_seconds: F) {
+    self.left_seconds = left_seconds;
+    self.right_seconds = right_seconds;
+    self.update_delay_samples();
+  }
+
+  pub fn get_delay_seconds_lr(&self) -> (F, F) {
+    (self.left_seconds, self.right_seconds)
+  }
+
+  /// When linked the right channel follows the delay of the left one
+  pub fn set_link(&mut self, link: bool) {
+    self.link = link;
+    self.update_delay_samples();
+  }
+
+  pub fn is_linked(&self) -> bool {
+    self.link
+  }
+
+  pub fn set_feedback(&mut self, feedback: F) {
+    self.feedback = feedback;
+  }
+
+  pub fn get_feedback(&self) -> F {
+    self.feedback
+  }
+
+  fn update_delay_samples(&mut self) {
+    let right_seconds = if self.link {
+      self.left_seconds
+    } else {
+      self.right_seconds
+    };
+    self.left_samples = self.delay_samples(self.left_seconds, self.left_delayline.len());
+    self.right_samples = self.delay_samples(right_seconds, self.right_delayline.len());
+  }
+
+  fn delay_samples(&self, seconds: F, len: usize) -> usize {
+    let samples = (seconds * self.sample_rate).round().to_usize().unwrap_or(0);
+    samples.max(1).min(len)
+  }
+}
+
+impl<'a, F: Float> Processor<F> for StereoDelay<'a, F> {
+  fn get_mix(&self) -> F {
+    self.mix
+  }
+
+  fn set_mix(&mut self, mix: F) {
+    self.mix = mix;
+  }
+
+  fn is_bypassed(&self) -> bool {
+    self.bypass
+  }
+
+  fn set_bypass(&mut self, bypass: bool) {
+    self.bypass = bypass;
+  }
+
+  fn process_wet(&mut self, left: F, right: F) -> (F, F) {
+    let feedback = self.feedback;
+    let left_sample = self.left_delayline.get(self.left_samples);
+    let right_sample = self.right_delayline.get(self.right_samples);
+    self.left_delayline.update(left + left_sample * feedback);
+    self.right_delayline.update(right + right_sample * feedback);
+    (left_sample, right_sample)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  /// The indices of the non zero samples of every channel for a mono impulse
+  fn echoes(delay: &mut StereoDelay<f64>, frames: usize) -> (Vec<usize>, Vec<usize>) {
+    let output: Vec<(f64, f64)> = (0..frames)
+      .map(|index| {
+        let input = if index == 0 { 1.0 } else { 0.0 };
+        delay.process_frame(input, input)
+      })
+      .collect();
+    let left = (0..frames).filter(|index| output[*index].0 != 0.0);
+    let right = (0..frames).filter(|index| output[*index].1 != 0.0);
+    (left.collect(), right.collect())
+  }
+
+  #[test]
+  fn independent_times() {
+    let (mut left_buffer, mut right_buffer) = ([0.0; 16], [0.0; 16]);
+    let mut delay = StereoDelay::new(10.0, &mut left_buffer, &mut right_buffer);
+    delay.set_delay_seconds_lr(0.3, 0.5);
+    delay.set_feedback(0.5);
+    delay.set_mix(1.0);
+
+    let (left, right) = echoes(&mut delay, 16);
+    assert_eq!(left, vec![3, 6, 9, 12, 15]);
+    assert_eq!(right, vec![5, 10, 15]);
+  }
+
+  #[test]
+  fn linked_times() {
+    let (mut left_buffer, mut right_buffer) = ([0.0; 16], [0.0; 16]);
+    let mut delay = StereoDelay::new(10.0, &mut left_buffer, &mut right_buffer);
+    delay.set_delay_seconds_lr(0.3, 0.5);
+    delay.set_link(true);
+    delay.set_mix(1.0);
+
+    let (left, right) = echoes(&mut delay, 16);
+    assert_eq!(left, vec![3]);
+    assert_eq!(right, vec![3]);
+  }
+
+  #[test]
+  fn times_clamped_to_the_buffers() {
+    let (mut left_buffer, mut right_buffer) = ([0.0; 8], [0.0; 4]);
+    let mut delay = StereoDelay::new(10.0, &mut left_buffer, &mut right_buffer);
+    delay.set_delay_seconds_lr(2.0, 2.0);
+    delay.set_mix(1.0);
+
+    let (left, right) = echoes(&mut delay, 16);
+    assert_eq!(left, vec![8]);
+    assert_eq!(right, vec![4]);
+  }
+}
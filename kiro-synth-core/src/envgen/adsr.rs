@@ -128,6 +128,13 @@ impl<F: Float> EnvGen<F> {
     }
   }
 
+  /// Change the sample rate keeping the times in seconds, so the contour is the same at any rate
+  pub fn set_sample_rate(&mut self, sample_rate: F) {
+    self.shutdown_dec = self.shutdown_dec * self.sample_rate / sample_rate;
+    self.sample_rate = sample_rate;
+    self.set_mode(self.mode);
+  }
+
   pub fn set_mode(&mut self, mode: Mode) {
     self.mode = mode;
     self.attack = ADR::attack(self.sample_rate, mode, self.attack.time_sec);
@@ -244,7 +251,7 @@ impl<F: Float> EnvGen<F> {
       }
       State::Release => {
         self.output = self.release.offset + self.output * self.release.coefficient;
-        if self.output <= F::zero() || self.release.time_sec <= F::zero() {
+        if self.output <= Self::silence_level() || self.release.time_sec <= F::zero() {
           self.output = F::zero();
           self.state = State::Off;
         }
@@ -282,4 +289,45 @@ impl<F: Float> EnvGen<F> {
   fn shutdown_time_sec() -> F {
     F::val(0.01)
   }
+
+  /// Level (-100 dB) under which the release is considered finished
+  #[inline]
+  fn silence_level() -> F {
+    F::val(1e-5)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  /// The level of an envelope with an instant attack and a decay of one second at some times
+  fn decay_levels(sample_rate: f64, times: &[f64]) -> Vec<f64> {
+    let mut envgen = EnvGen::new(44100.0);
+    envgen.set_sample_rate(sample_rate);
+    envgen.set_attack_time_sec(0.0);
+    envgen.set_decay_time_sec(1.0);
+    envgen.set_sustain_level(0.0);
+    envgen.start();
+
+    let output: Vec<f64> = (0..=sample_rate as usize)
+      .map(|_| envgen.generate())
+      .collect();
+    times
+      .iter()
+      .map(|time| output[(time * sample_rate).round() as usize])
+      .collect()
+  }
+
+  #[test]
+  fn times_independent_of_the_sample_rate() {
+    let times = [0.01, 0.1, 0.25, 0.5, 0.75, 0.99];
+    let levels_44k = decay_levels(44100.0, &times);
+    let levels_96k = decay_levels(96000.0, &times);
+    for (level_44k, level_96k) in levels_44k.iter().zip(levels_96k.iter()) {
+      assert_approx_eq!(level_44k, level_96k, 1e-3);
+    }
+    assert!(levels_44k[0] > 0.9 && levels_44k[5] < 0.01);
+  }
 }
@@ -0,0 +1,151 @@
+use crate::float::Float;
+
+/// Second order IIR filter in transposed direct form II
+#[derive(Debug, Clone)]
+pub struct Biquad<F: Float> {
+  b0: F,
+  b1: F,
+  b2: F,
+  a1: F,
+  a2: F,
+  z1: F,
+  z2: F,
+}
+
+impl<F: Float> Default for Biquad<F> {
+  fn default() -> Self {
+    Biquad {
+      b0: F::one(),
+      b1: F::zero(),
+      b2: F::zero(),
+      a1: F::zero(),
+      a2: F::zero(),
+      z1: F::zero(),
+      z2: F::zero(),
+    }
+  }
+}
+
+impl<F: Float> Biquad<F> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Set the coefficients already normalized by `a0`
+  pub fn set_coefficients(&mut self, b0: F, b1: F, b2: F, a1: F, a2: F) {
+    self.b0 = b0;
+    self.b1 = b1;
+    self.b2 = b2;
+    self.a1 = a1;
+    self.a2 = a2;
+  }
+
+  /// Band pass with a peak gain of 0 dB at the center frequency
+  pub fn set_band_pass(&mut self, sample_rate: F, freq: F, q: F) {
+    let two = F::val(2.0);
+    let w0 = two * F::PI * freq / sample_rate;
+    let alpha = w0.sin() / (two * q);
+    let a0 = F::one() + alpha;
+    self.set_coefficients(
+      alpha / a0,
+      F::zero(),
+      alpha.neg() / a0,
+      two.neg() * w0.cos() / a0,
+      (F::one() - alpha) / a0,
+    );
+  }
+
+  /// Low pass of 12 dB/oct, without resonance for a Q of `FRAC_1_SQRT_2`
+  pub fn set_low_pass(&mut self, sample_rate: F, freq: F, q: F) {
+    let two = F::val(2.0);
+    let w0 = two * F::PI * freq / sample_rate;
+    let alpha = w0.sin() / (two * q);
+    let cos_w0 = w0.cos();
+    let a0 = F::one() + alpha;
+    let b1 = (F::one() - cos_w0) / a0;
+    self.set_coefficients(
+      b1 / two,
+      b1,
+      b1 / two,
+      two.neg() * cos_w0 / a0,
+      (F::one() - alpha) / a0,
+    );
+  }
+
+  /// High pass of 12 dB/oct, without resonance for a Q of `FRAC_1_SQRT_2`
+  pub fn set_high_pass(&mut self, sample_rate: F, freq: F, q: F) {
+    let two = F::val(2.0);
+    let w0 = two * F::PI * freq / sample_rate;
+    let alpha = w0.sin() / (two * q);
+    let cos_w0 = w0.cos();
+    let a0 = F::one() + alpha;
+    let b1 = (F::one() + cos_w0) / a0;
+    self.set_coefficients(
+      b1 / two,
+      b1.neg(),
+      b1 / two,
+      two.neg() * cos_w0 / a0,
+      (F::one() - alpha) / a0,
+    );
+  }
+
+  pub fn reset(&mut self) {
+    self.z1 = F::zero();
+    self.z2 = F::zero();
+  }
+
+  pub fn process(&mut self, input: F) -> F {
+    let output = self.b0 * input + self.z1;
+    self.z1 = self.b1 * input - self.a1 * output + self.z2;
+    self.z2 = self.b2 * input - self.a2 * output;
+    output
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn band_pass_gain(freq: f64) -> f64 {
+    let mut filter = Biquad::new();
+    filter.set_band_pass(44100.0, 1000.0, 4.0);
+    gain(filter, freq)
+  }
+
+  fn gain(mut filter: Biquad<f64>, freq: f64) -> f64 {
+    let sample_rate = 44100.0;
+    let mut peak = 0.0f64;
+    for index in 0..8820 {
+      let input = (2.0 * std::f64::consts::PI * freq * index as f64 / sample_rate).sin();
+      let output = filter.process(input);
+      if index > 4410 {
+        peak = peak.max(output.abs());
+      }
+    }
+    peak
+  }
+
+  #[test]
+  fn band_pass() {
+    assert!((band_pass_gain(1000.0) - 1.0).abs() < 0.01);
+    assert!(band_pass_gain(250.0) < 0.1);
+    assert!(band_pass_gain(4000.0) < 0.1);
+  }
+
+  #[test]
+  fn low_pass_and_high_pass() {
+    let q = std::f64::consts::FRAC_1_SQRT_2;
+    let mut low_pass = Biquad::new();
+    low_pass.set_low_pass(44100.0, 1000.0, q);
+    let mut high_pass = Biquad::new();
+    high_pass.set_high_pass(44100.0, 1000.0, q);
+
+    // -3 dB at the cutoff and 12 dB/oct away from it
+    assert!((gain(low_pass.clone(), 1000.0) - q).abs() < 0.01);
+    assert!((gain(high_pass.clone(), 1000.0) - q).abs() < 0.01);
+    assert!((gain(low_pass.clone(), 100.0) - 1.0).abs() < 0.01);
+    assert!(gain(low_pass, 4000.0) < 0.07);
+    assert!((gain(high_pass.clone(), 10000.0) - 1.0).abs() < 0.01);
+    assert!(gain(high_pass, 250.0) < 0.07);
+  }
+}
@@ -0,0 +1,179 @@
+use crate::filters::biquad::Biquad;
+use crate::float::Float;
+use crate::funcs::decibels::Decibels;
+
+const NUM_FORMANTS: usize = 3;
+
+/// Frequency, gain in dB and bandwidth of the first formants of every vowel
+#[rustfmt::skip]
+const FORMANTS: [[(f64, f64, f64); NUM_FORMANTS]; 5] = [
+  [(600.0, 0.0, 60.0), (1040.0, -7.0, 70.0), (2250.0, -9.0, 110.0)],
+  [(400.0, 0.0, 40.0), (1620.0, -12.0, 80.0), (2400.0, -9.0, 100.0)],
+  [(250.0, 0.0, 60.0), (1750.0, -30.0, 90.0), (2600.0, -16.0, 100.0)],
+  [(400.0, 0.0, 40.0), (750.0, -11.0, 80.0), (2400.0, -21.0, 100.0)],
+  [(350.0, 0.0, 40.0), (600.0, -20.0, 80.0), (2400.0, -32.0, 100.0)],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vowel {
+  A,
+  E,
+  I,
+  O,
+  U,
+}
+
+impl Vowel {
+  pub fn count() -> usize {
+    5
+  }
+
+  pub fn from<F: Float>(value: F) -> Self {
+    match value.round().to_usize().unwrap_or(0) {
+      1 => Vowel::E,
+      2 => Vowel::I,
+      3 => Vowel::O,
+      4 => Vowel::U,
+      _ => Vowel::A,
+    }
+  }
+
+  /// The vowel reached by the morph
+  pub fn next(self) -> Self {
+    match self {
+      Vowel::A => Vowel::E,
+      Vowel::E => Vowel::I,
+      Vowel::I => Vowel::O,
+      Vowel::O => Vowel::U,
+      Vowel::U => Vowel::A,
+    }
+  }
+}
+
+/// Parallel band pass filters tuned to the formants of a vowel, for a talking character
+#[derive(Debug)]
+pub struct FormantFilter<F: Float> {
+  sample_rate: F,
+  vowel: Vowel,
+  morph: F,
+  invalidated: bool,
+  bands: [Biquad<F>; NUM_FORMANTS],
+  gains: [F; NUM_FORMANTS],
+}
+
+impl<F: Float> FormantFilter<F> {
+  pub fn new(sample_rate: F) -> Self {
+    FormantFilter {
+      sample_rate,
+      vowel: Vowel::A,
+      morph: F::zero(),
+      invalidated: true,
+      bands: [Biquad::new(), Biquad::new(), Biquad::new()],
+      gains: [F::one(); NUM_FORMANTS],
+    }
+  }
+
+  pub fn set_vowel(&mut self, vowel: Vowel) {
+    self.vowel = vowel;
+    self.invalidated = true;
+  }
+
+  /// Morph from the selected vowel (0.0) to the next one (1.0)
+  pub fn set_morph(&mut self, morph: F) {
+    self.morph = morph.max(F::zero()).min(F::one());
+    self.invalidated = true;
+  }
+
+  pub fn reset(&mut self) {
+    for band in self.bands.iter_mut() {
+      band.reset();
+    }
+  }
+
+  pub fn update(&mut self) {
+    if self.invalidated {
+      self.invalidated = false;
+      let from = &FORMANTS[self.vowel as usize];
+      let to = &FORMANTS[self.vowel.next() as usize];
+      for (index, band) in self.bands.iter_mut().enumerate() {
+        let (freq, gain, bandwidth) = Self::interpolate(from[index], to[index], self.morph);
+        band.set_band_pass(self.sample_rate, freq, freq / bandwidth);
+        self.gains[index] = Decibels::new(gain).to_amplitude();
+      }
+    }
+  }
+
+  pub fn process(&mut self, input: F) -> F {
+    self.update();
+
+    let mut output = F::zero();
+    for (band, gain) in self.bands.iter_mut().zip(self.gains.iter()) {
+      output = output + band.process(input) * *gain;
+    }
+    output
+  }
+
+  fn interpolate(from: (f64, f64, f64), to: (f64, f64, f64), t: F) -> (F, F, F) {
+    let lerp = |a: f64, b: f64| F::val(a) + (F::val(b) - F::val(a)) * t;
+    (lerp(from.0, to.0), lerp(from.1, to.1), lerp(from.2, to.2))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn gain(vowel: Vowel, morph: f64, freq: f64) -> f64 {
+    let sample_rate = 44100.0;
+    let mut filter = FormantFilter::new(sample_rate);
+    filter.set_vowel(vowel);
+    filter.set_morph(morph);
+    let mut peak = 0.0f64;
+    for index in 0..8820 {
+      let input = (2.0 * std::f64::consts::PI * freq * index as f64 / sample_rate).sin();
+      let output = filter.process(input);
+      if index > 4410 {
+        peak = peak.max(output.abs());
+      }
+    }
+    peak
+  }
+
+  /// The frequencies with a bigger gain than their neighbours
+  fn peaks(vowel: Vowel, morph: f64) -> Vec<f64> {
+    let freqs: Vec<f64> = (10..150).map(|index| index as f64 * 20.0).collect();
+    let gains: Vec<f64> = freqs.iter().map(|freq| gain(vowel, morph, *freq)).collect();
+    (1..freqs.len() - 1)
+      .filter(|index| gains[*index] > gains[index - 1] && gains[*index] > gains[index + 1])
+      .map(|index| freqs[index])
+      .collect()
+  }
+
+  fn has_peak_near(peaks: &[f64], freq: f64) -> bool {
+    peaks.iter().any(|peak| (peak - freq).abs() < freq * 0.05)
+  }
+
+  #[test]
+  fn vowel_from() {
+    assert_eq!(Vowel::from(0.0), Vowel::A);
+    assert_eq!(Vowel::from(2.2), Vowel::I);
+    assert_eq!(Vowel::from(4.0), Vowel::U);
+  }
+
+  #[test]
+  fn vowel_a_formants() {
+    let peaks = peaks(Vowel::A, 0.0);
+    assert_eq!(peaks.len(), 3);
+    assert!(has_peak_near(&peaks, 600.0));
+    assert!(has_peak_near(&peaks, 1040.0));
+    assert!(has_peak_near(&peaks, 2250.0));
+  }
+
+  #[test]
+  fn morph_between_vowels() {
+    // half way from A (1040 Hz) to E (1620 Hz) for the second formant
+    let peaks = peaks(Vowel::A, 0.5);
+    assert!(has_peak_near(&peaks, 1330.0));
+    assert!(!has_peak_near(&peaks, 1040.0));
+  }
+}
@@ -1,5 +1,8 @@
+pub mod biquad;
+pub mod formant;
 pub mod freq_control;
 pub mod oberheim_sem;
+pub mod one_pole;
 pub mod q_control;
 pub mod saturation;
 pub mod va_one_pole;
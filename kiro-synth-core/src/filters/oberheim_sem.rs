@@ -3,6 +3,9 @@ use crate::filters::q_control::QControl;
 use crate::filters::saturation::Saturation;
 use crate::float::Float;
 
+const MIN_Q: f64 = 0.5;
+const MAX_Q: f64 = 25.0;
+
 #[derive(Debug, Clone, Copy)]
 pub enum Mode {
   LowPass,
@@ -19,6 +22,11 @@ pub struct OberheimSEM<F: Float> {
   freq: FreqControl<F>,
   q: QControl<F>,
   saturation: Saturation<F>,
+  self_oscillation: bool,
+  /// The frequency of the self oscillation, which can go beyond the range of the cutoff
+  self_oscillation_freq: F,
+  invalidated: bool,
+  resonance_compensation: F,
   alpha: F,
   alpha0: F,
   rho: F,
@@ -34,8 +42,12 @@ impl<F: Float> OberheimSEM<F> {
       inv_sample_rate: F::one() / sample_rate,
       mode: Mode::LowPass,
       freq: FreqControl::new(fc),
-      q: QControl::new(F::val(0.5), F::val(25), q),
+      q: QControl::new(F::val(MIN_Q), F::val(MAX_Q), q),
       saturation: Saturation::new(false),
+      self_oscillation: false,
+      self_oscillation_freq: fc,
+      invalidated: true,
+      resonance_compensation: F::one(),
       alpha: F::one(),
       alpha0: F::one(),
       rho: F::one(),
@@ -61,21 +73,70 @@ impl<F: Float> OberheimSEM<F> {
     self.q.set_value(q);
   }
 
+  /// Remove the damping so the filter oscillates at the frequency given with
+  /// [`set_self_oscillation_frequency`](Self::set_self_oscillation_frequency), ignoring the cutoff and the Q
+  pub fn set_self_oscillation(&mut self, enabled: bool) {
+    self.self_oscillation = enabled;
+    self.invalidated = true;
+  }
+
+  /// Set the frequency of the self oscillation, without the limits nor the modulation of the cutoff
+  /// so it can follow the pitch of any note below the Nyquist frequency
+  pub fn set_self_oscillation_frequency(&mut self, freq: F) {
+    self.self_oscillation_freq = freq;
+    self.invalidated = true;
+  }
+
+  pub fn is_self_oscillating(&self) -> bool {
+    self.self_oscillation
+  }
+
+  /// Start the self oscillation from the beginning of a sine cycle with unity amplitude
+  pub fn start_self_oscillation(&mut self) {
+    self.z11 = F::one();
+    self.z12 = F::zero();
+  }
+
+  /// Gain that keeps the broadband level of the output about constant as the resonance rises,
+  /// to apply to the output. The resonant peak grows with the Q and makes the filter louder.
+  pub fn get_resonance_compensation(&mut self) -> F {
+    self.update();
+    self.resonance_compensation
+  }
+
   pub fn reset(&mut self) {
     self.z11 = F::zero();
     self.z12 = F::zero();
   }
 
   pub fn update(&mut self) {
-    if self.freq.is_invalidated() || self.q.is_invalidated() {
+    if self.invalidated || self.freq.is_invalidated() || self.q.is_invalidated() {
+      self.invalidated = false;
       let two = F::val(2.0);
       let fc = self.freq.get_modulated_freq();
+      let fc = if self.self_oscillation {
+        let max_freq = self.sample_rate * F::val(0.475);
+        self.self_oscillation_freq.max(F::zero()).min(max_freq)
+      } else {
+        fc
+      };
       let wd = two * F::PI * fc;
       let t = self.inv_sample_rate;
       let wa = (two / t) * (wd * t / two).tan();
       let g = wa * t / two;
 
-      let r = F::one() / (two * self.q.get_scaled_value());
+      let q = self.q.get_scaled_value();
+      let r = if self.self_oscillation {
+        F::zero()
+      } else {
+        F::one() / (two * q)
+      };
+
+      self.resonance_compensation = if self.self_oscillation {
+        F::one()
+      } else {
+        (F::val(MIN_Q) / q).sqrt()
+      };
 
       self.alpha0 = F::one() / (F::one() + two * r * g + g * g);
       self.alpha = g;
@@ -102,3 +163,56 @@ impl<F: Float> OberheimSEM<F> {
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn render<F: Float>(mode: Mode) -> Vec<f64> {
+    let mut filter = OberheimSEM::new(F::val(44100.0), F::val(1000.0), F::val(4.0));
+    filter.set_mode(mode);
+    (0..512)
+      .map(|i| {
+        let input = F::val((i as f64 * 0.07).sin() + 0.5 * (i as f64 * 0.61).sin());
+        filter.process(input).to_f64().unwrap()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn f32_matches_f64() {
+    for mode in [Mode::LowPass, Mode::HighPass, Mode::BandPass, Mode::BandSum].iter() {
+      let output32 = render::<f32>(*mode);
+      let output64 = render::<f64>(*mode);
+      for (a, b) in output32.iter().zip(output64.iter()) {
+        assert_approx_eq!(a, b, 1e-4);
+      }
+    }
+  }
+
+  #[test]
+  fn self_oscillation_at_the_given_frequency() {
+    let sample_rate = 44100.0;
+    let mut filter = OberheimSEM::new(sample_rate, 1000.0, 0.0);
+    filter.set_self_oscillation(true);
+    // the frequencies below the range of the cutoff are not clamped
+    for freq in [40usize, 440].iter() {
+      filter.set_self_oscillation_frequency(*freq as f64);
+      filter.start_self_oscillation();
+
+      let output: Vec<f64> = (0..44100).map(|_| filter.process(0.0)).collect();
+      let crossings = output
+        .windows(2)
+        .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+        .count();
+      let expected = (freq - 1)..=(freq + 1);
+      assert!(expected.contains(&crossings), "{} crossings", crossings);
+
+      let peak = output
+        .iter()
+        .fold(0.0f64, |peak, value| peak.max(value.abs()));
+      assert_approx_eq!(peak, 1.0, 1e-2);
+    }
+  }
+}
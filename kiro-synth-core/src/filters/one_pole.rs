@@ -0,0 +1,136 @@
+use crate::float::Float;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+  LowPass,
+  HighPass,
+}
+
+/// The simplest filter with a single pole, to smooth params, follow envelopes or block DC.
+///
+/// The cutoff can be given as a frequency or as a time constant, the time to reach 63% of a step.
+#[derive(Debug, Clone, Copy)]
+pub struct OnePole<F: Float> {
+  sample_rate: F,
+  mode: Mode,
+  coef: F,
+  state: F,
+}
+
+/// A low-pass passing the input through, until it is given a sample rate and a cutoff
+impl<F: Float> Default for OnePole<F> {
+  fn default() -> Self {
+    OnePole::new(F::one(), Mode::LowPass)
+  }
+}
+
+impl<F: Float> OnePole<F> {
+  /// Starts without filtering, as if the cutoff was infinite
+  pub fn new(sample_rate: F, mode: Mode) -> Self {
+    OnePole {
+      sample_rate,
+      mode,
+      coef: F::one(),
+      state: F::zero(),
+    }
+  }
+
+  pub fn set_mode(&mut self, mode: Mode) {
+    self.mode = mode;
+  }
+
+  pub fn get_mode(&self) -> Mode {
+    self.mode
+  }
+
+  /// Set the cutoff frequency in Hz
+  pub fn set_cutoff(&mut self, freq: F) {
+    self.coef = F::one() - (F::val(-2.0) * F::PI * freq / self.sample_rate).exp();
+  }
+
+  /// Set the cutoff as a time constant in seconds, shorter than a sample doesn't filter at all
+  pub fn set_time_constant(&mut self, seconds: F) {
+    self.coef = Self::time_constant_coef(seconds, self.sample_rate);
+  }
+
+  /// The coefficient for a time constant in seconds, for the followers that switch between several of them,
+  /// such as the attack and the release of an envelope follower
+  pub fn time_constant_coef(seconds: F, sample_rate: F) -> F {
+    let samples = seconds * sample_rate;
+    if samples <= F::one() {
+      F::one()
+    } else {
+      F::one() - (-samples.recip()).exp()
+    }
+  }
+
+  pub fn reset(&mut self) {
+    self.state = F::zero();
+  }
+
+  /// Jump to a value without going through the transition, as if it had been the input for a long time
+  pub fn reset_to(&mut self, value: F) {
+    self.state = value;
+  }
+
+  /// The output of the low-pass, which follows the input
+  pub fn get(&self) -> F {
+    self.state
+  }
+
+  pub fn process(&mut self, input: F) -> F {
+    self.state = self.state + (input - self.state) * self.coef;
+    match self.mode {
+      Mode::LowPass => self.state,
+      Mode::HighPass => input - self.state,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  const SAMPLE_RATE: f64 = 48000.0;
+
+  /// The amplitude of the output for a sine once the filter has settled
+  fn gain(mode: Mode, cutoff: f64, freq: f64) -> f64 {
+    let mut filter = OnePole::new(SAMPLE_RATE, mode);
+    filter.set_cutoff(cutoff);
+    let omega = 2.0 * std::f64::consts::PI * freq / SAMPLE_RATE;
+    (0..48000)
+      .map(|index| filter.process((omega * index as f64).sin()))
+      .skip(24000)
+      .fold(0.0, |peak, sample| sample.abs().max(peak))
+  }
+
+  #[test]
+  fn cutoff_at_minus_3_db() {
+    let half_power = 0.5f64.sqrt();
+    assert_approx_eq!(gain(Mode::LowPass, 100.0, 100.0), half_power, 0.01);
+    assert_approx_eq!(gain(Mode::HighPass, 100.0, 100.0), half_power, 0.01);
+  }
+
+  #[test]
+  fn dc_gain() {
+    for (mode, expected) in [(Mode::LowPass, 1.0), (Mode::HighPass, 0.0)].iter() {
+      let mut filter = OnePole::new(SAMPLE_RATE, *mode);
+      filter.set_cutoff(100.0);
+      let output = (0..48000).map(|_| filter.process(1.0)).last().unwrap();
+      assert_approx_eq!(output, *expected, 1e-6);
+    }
+  }
+
+  #[test]
+  fn time_constant() {
+    let mut filter = OnePole::new(1000.0, Mode::LowPass);
+    filter.set_time_constant(0.1);
+    let output = (0..100).map(|_| filter.process(1.0)).last().unwrap();
+    assert_approx_eq!(output, 1.0 - (-1.0f64).exp(), 0.01);
+
+    filter.set_time_constant(0.0);
+    filter.reset();
+    assert_eq!(filter.process(0.5), 0.5);
+  }
+}
@@ -91,3 +91,31 @@ impl<F: Float> VAOnePoleFilter<F> {
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn render<F: Float>(mode: Mode) -> Vec<f64> {
+    let mut filter = VAOnePoleFilter::new(F::val(44100.0), F::val(1000.0));
+    filter.set_mode(mode);
+    (0..512)
+      .map(|i| {
+        let input = F::val((i as f64 * 0.07).sin() + 0.5 * (i as f64 * 0.61).sin());
+        filter.process(input).to_f64().unwrap()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn f32_matches_f64() {
+    for mode in [Mode::LowPass, Mode::HighPass].iter() {
+      let output32 = render::<f32>(*mode);
+      let output64 = render::<f64>(*mode);
+      for (a, b) in output32.iter().zip(output64.iter()) {
+        assert_approx_eq!(a, b, 1e-4);
+      }
+    }
+  }
+}
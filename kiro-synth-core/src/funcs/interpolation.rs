@@ -14,3 +14,13 @@ pub fn linear_interpolation<F: Float>(x1: F, x2: F, y1: F, y2: F, x: F) -> F {
     dx * y2 + (F::one() - dx) * y1
   }
 }
+
+/// Cubic Hermite (Catmull-Rom) interpolation between y1 and y2,
+/// using y0 and y3 as the neighbour points and t in the range 0..1
+pub fn cubic_interpolation<F: Float>(y0: F, y1: F, y2: F, y3: F, t: F) -> F {
+  let half = F::val(0.5);
+  let c1 = half * (y2 - y0);
+  let c2 = y0 - F::val(2.5) * y1 + F::val(2.0) * y2 - half * y3;
+  let c3 = half * (y3 - y0) + F::val(1.5) * (y1 - y2);
+  ((c3 * t + c2) * t + c1) * t + y1
+}
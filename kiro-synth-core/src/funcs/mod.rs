@@ -2,4 +2,6 @@ pub mod concave_transforms;
 pub mod decibels;
 pub mod interpolation;
 pub mod parabolic_sine;
+pub mod random;
 pub mod signal_polarity;
+pub mod sine_table;
@@ -0,0 +1,89 @@
+use crate::float::Float;
+
+/// Seed used instead of zero, which would make the generator always return zero
+const DEFAULT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Fast pseudo-random number generator (xorshift64*), deterministic for a given seed
+#[derive(Debug, Clone)]
+pub struct Random {
+  state: u64,
+}
+
+impl Default for Random {
+  fn default() -> Self {
+    Random::new(DEFAULT_SEED)
+  }
+}
+
+impl Random {
+  pub fn new(seed: u64) -> Self {
+    let mut random = Random { state: 0 };
+    random.set_seed(seed);
+    random
+  }
+
+  /// A seed for one of several independent sequences derived from the same seed (splitmix64)
+  pub fn derive_seed(seed: u64, stream: u64) -> u64 {
+    let mut z = seed.wrapping_add(stream.wrapping_add(1).wrapping_mul(DEFAULT_SEED));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  /// Restart the sequence of numbers from a seed
+  pub fn set_seed(&mut self, seed: u64) {
+    self.state = if seed == 0 { DEFAULT_SEED } else { seed };
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.state ^= self.state >> 12;
+    self.state ^= self.state << 25;
+    self.state ^= self.state >> 27;
+    self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+  }
+
+  /// A value between 0.0 (inclusive) and 1.0 (exclusive)
+  pub fn unipolar<F: Float>(&mut self) -> F {
+    F::val((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64)
+  }
+
+  /// A value between -1.0 (inclusive) and 1.0 (exclusive)
+  pub fn bipolar<F: Float>(&mut self) -> F {
+    self.unipolar::<F>() * F::val(2.0) - F::one()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn same_seed_same_sequence() {
+    let mut a = Random::new(42);
+    let mut b = Random::new(42);
+    let mut c = Random::new(43);
+    let a: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+    let b: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+    let c: Vec<u64> = (0..8).map(|_| c.next_u64()).collect();
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+  }
+
+  #[test]
+  fn derived_seeds_differ() {
+    assert_eq!(Random::derive_seed(7, 1), Random::derive_seed(7, 1));
+    assert_ne!(Random::derive_seed(7, 1), Random::derive_seed(7, 2));
+    assert_ne!(Random::derive_seed(7, 1), Random::derive_seed(8, 1));
+  }
+
+  #[test]
+  fn ranges() {
+    let mut random = Random::new(0);
+    for _ in 0..1000 {
+      let value: f64 = random.unipolar();
+      assert!((0.0..1.0).contains(&value));
+      let value: f32 = random.bipolar();
+      assert!((-1.0..=1.0).contains(&value));
+    }
+  }
+}
@@ -0,0 +1,195 @@
+use crate::float::Float;
+
+/// Number of intervals of the table for a quarter of a cycle
+const QUARTER_SIZE: usize = 512;
+
+/// The first quarter of a sine cycle, from 0 to 1 both inclusive
+#[rustfmt::skip]
+const SINE_TABLE: [f64; QUARTER_SIZE + 1] = [
+  0.0, 0.003067956762965976, 0.006135884649154475, 0.00920375478205982,
+  0.012271538285719925, 0.0153392062849881, 0.01840672990580482, 0.021474080275469508,
+  0.024541228522912288, 0.02760814577896574, 0.030674803176636626, 0.03374117185137758,
+  0.03680722294135883, 0.03987292758773981, 0.04293825693494082, 0.04600318213091462,
+  0.049067674327418015, 0.052131704680283324, 0.055195244349689934, 0.05825826450043575,
+  0.06132073630220858, 0.06438263092985747, 0.06744391956366405, 0.07050457338961386,
+  0.07356456359966743, 0.07662386139203149, 0.07968243797143013, 0.08274026454937569,
+  0.0857973123444399, 0.0888535525825246, 0.09190895649713272, 0.09496349532963899,
+  0.0980171403295606, 0.10106986275482782, 0.10412163387205459, 0.10717242495680884,
+  0.11022220729388306, 0.11327095217756435, 0.11631863091190475, 0.11936521481099135,
+  0.1224106751992162, 0.12545498341154623, 0.12849811079379317, 0.13154002870288312,
+  0.13458070850712617, 0.13762012158648604, 0.1406582393328492, 0.14369503315029447,
+  0.14673047445536175, 0.1497645346773215, 0.15279718525844344, 0.15582839765426523,
+  0.15885814333386145, 0.16188639378011183, 0.16491312048996992, 0.16793829497473117,
+  0.17096188876030122, 0.17398387338746382, 0.17700422041214875, 0.18002290140569951,
+  0.18303988795514095, 0.18605515166344663, 0.1890686641498062, 0.19208039704989244,
+  0.19509032201612825, 0.19809841071795356, 0.2011046348420919, 0.20410896609281687,
+  0.20711137619221856, 0.2101118368804696, 0.21311031991609136, 0.21610679707621952,
+  0.2191012401568698, 0.2220936209732035, 0.22508391135979283, 0.22807208317088573,
+  0.2310581082806711, 0.23404195858354343, 0.2370236059943672, 0.2400030224487415,
+  0.24298017990326387, 0.2459550503357946, 0.24892760574572015, 0.25189781815421697,
+  0.25486565960451457, 0.257831102162159, 0.2607941179152755, 0.26375467897483135,
+  0.26671275747489837, 0.2696683255729151, 0.272621355449949, 0.27557181931095814,
+  0.27851968938505306, 0.28146493792575794, 0.2844075372112719, 0.2873474595447295,
+  0.29028467725446233, 0.29321916269425863, 0.2961508882436238, 0.2990798263080405,
+  0.3020059493192281, 0.3049292297354024, 0.30784964004153487, 0.3107671527496115,
+  0.3136817403988915, 0.31659337555616585, 0.3195020308160157, 0.32240767880106985,
+  0.3253102921622629, 0.3282098435790925, 0.33110630575987643, 0.3339996514420094,
+  0.33688985339222005, 0.33977688440682685, 0.3426607173119944, 0.3455413249639891,
+  0.34841868024943456, 0.3512927560855671, 0.35416352542049034, 0.35703096123343,
+  0.3598950365349881, 0.3627557243673972, 0.36561299780477385, 0.3684668299533723,
+  0.37131719395183754, 0.37416406297145793, 0.37700741021641826, 0.37984720892405116,
+  0.3826834323650898, 0.38551605384391885, 0.38834504669882625, 0.39117038430225387,
+  0.3939920400610481, 0.3968099874167103, 0.3996241998456468, 0.40243465085941843,
+  0.40524131400498986, 0.4080441628649787, 0.4108431710579039, 0.4136383122384345,
+  0.41642956009763715, 0.4192168883632239, 0.4220002707997997, 0.4247796812091088,
+  0.4275550934302821, 0.4303264813400826, 0.43309381885315196, 0.4358570799222555,
+  0.43861623853852766, 0.44137126873171667, 0.4441221445704292, 0.44686884016237416,
+  0.44961132965460654, 0.4523495872337709, 0.45508358712634384, 0.4578133035988772,
+  0.46053871095824, 0.46325978355186015, 0.4659764957679662, 0.4686888220358279,
+  0.47139673682599764, 0.47410021465054997, 0.4767992300633221, 0.479493757660153,
+  0.4821837720791227, 0.48486924800079106, 0.487550160148436, 0.49022648328829116,
+  0.49289819222978404, 0.49556526182577254, 0.49822766697278187, 0.5008853826112407,
+  0.5035383837257176, 0.5061866453451552, 0.508830142543107, 0.5114688504379703,
+  0.5141027441932217, 0.5167317990176499, 0.5193559901655896, 0.5219752929371544,
+  0.524589682678469, 0.5271991347819013, 0.5298036246862946, 0.5324031278771979,
+  0.5349976198870972, 0.5375870762956454, 0.5401714727298929, 0.5427507848645159,
+  0.5453249884220465, 0.5478940591731002, 0.5504579729366048, 0.5530167055800275,
+  0.5555702330196022, 0.5581185312205561, 0.560661576197336, 0.5631993440138341,
+  0.5657318107836131, 0.5682589526701315, 0.5707807458869673, 0.5732971666980422,
+  0.5758081914178453, 0.5783137964116556, 0.5808139580957645, 0.5833086529376983,
+  0.5857978574564389, 0.5882815482226452, 0.5907597018588742, 0.5932322950397998,
+  0.5956993044924334, 0.5981607069963423, 0.600616479383869, 0.6030665985403482,
+  0.6055110414043255, 0.6079497849677736, 0.6103828062763095, 0.6128100824294097,
+  0.6152315905806268, 0.6176473079378039, 0.6200572117632891, 0.62246127937415,
+  0.6248594881423863, 0.6272518154951441, 0.629638238914927, 0.6320187359398091,
+  0.6343932841636455, 0.6367618612362842, 0.6391244448637757, 0.6414810128085832,
+  0.6438315428897914, 0.6461760129833163, 0.6485144010221124, 0.6508466849963809,
+  0.6531728429537768, 0.6554928529996153, 0.6578066932970786, 0.6601143420674205,
+  0.6624157775901718, 0.6647109782033448, 0.6669999223036375, 0.669282588346636,
+  0.6715589548470183, 0.673829000378756, 0.6760927035753159, 0.6783500431298615,
+  0.680600997795453, 0.6828455463852481, 0.6850836677727004, 0.687315340891759,
+  0.6895405447370668, 0.6917592583641577, 0.693971460889654, 0.696177131491463,
+  0.6983762494089729, 0.7005687939432483, 0.7027547444572253, 0.7049340803759049,
+  0.7071067811865475, 0.7092728264388656, 0.7114321957452164, 0.7135848687807935,
+  0.7157308252838186, 0.7178700450557317, 0.7200025079613817, 0.7221281939292153,
+  0.7242470829514669, 0.726359155084346, 0.7284643904482252, 0.7305627692278276,
+  0.7326542716724128, 0.7347388780959634, 0.7368165688773698, 0.7388873244606151,
+  0.7409511253549591, 0.7430079521351217, 0.745057785441466, 0.7471006059801801,
+  0.7491363945234593, 0.7511651319096864, 0.7531867990436124, 0.7552013768965365,
+  0.7572088465064845, 0.759209188978388, 0.7612023854842618, 0.7631884172633813,
+  0.765167265622459, 0.7671389119358204, 0.7691033376455796, 0.7710605242618137,
+  0.773010453362737, 0.7749531065948738, 0.7768884656732324, 0.7788165123814759,
+  0.7807372285720944, 0.7826505961665757, 0.7845565971555752, 0.7864552135990858,
+  0.7883464276266062, 0.79023022143731, 0.7921065773002124, 0.7939754775543372,
+  0.7958369046088835, 0.797690840943391, 0.799537269107905, 0.8013761717231401,
+  0.8032075314806448, 0.8050313311429637, 0.8068475535437992, 0.808656181588175,
+  0.8104571982525948, 0.8122505865852039, 0.8140363297059483, 0.8158144108067338,
+  0.8175848131515837, 0.8193475200767969, 0.8211025149911046, 0.8228497813758263,
+  0.8245893027850253, 0.8263210628456634, 0.8280450452577558, 0.829761233794523,
+  0.8314696123025452, 0.8331701647019132, 0.83486287498638, 0.8365477272235119,
+  0.838224705554838, 0.8398937941959994, 0.8415549774368983, 0.8432082396418454,
+  0.844853565249707, 0.846490938774052, 0.8481203448032971, 0.8497417680008524,
+  0.8513551931052652, 0.8529606049303636, 0.8545579883654005, 0.8561473283751945,
+  0.8577286100002721, 0.8593018183570084, 0.8608669386377673, 0.8624239561110405,
+  0.8639728561215867, 0.865513624090569, 0.8670462455156926, 0.8685707059713409,
+  0.8700869911087113, 0.8715950866559511, 0.8730949784182901, 0.8745866522781761,
+  0.8760700941954066, 0.8775452902072612, 0.8790122264286334, 0.8804708890521608,
+  0.8819212643483549, 0.8833633386657316, 0.8847970984309378, 0.8862225301488806,
+  0.8876396204028539, 0.8890483558546646, 0.8904487232447579, 0.8918407093923427,
+  0.8932243011955153, 0.8945994856313826, 0.8959662497561851, 0.8973245807054183,
+  0.8986744656939538, 0.9000158920161603, 0.901348847046022, 0.9026733182372588,
+  0.9039892931234433, 0.9052967593181188, 0.9065957045149153, 0.9078861164876662,
+  0.9091679830905223, 0.9104412922580671, 0.9117060320054299, 0.9129621904283981,
+  0.9142097557035307, 0.9154487160882678, 0.9166790599210427, 0.9179007756213904,
+  0.9191138516900578, 0.9203182767091105, 0.9215140393420419, 0.9227011283338785,
+  0.9238795325112867, 0.9250492407826776, 0.9262102421383113, 0.9273625256504011,
+  0.9285060804732155, 0.9296408958431813, 0.9307669610789837, 0.9318842655816681,
+  0.9329927988347388, 0.9340925504042589, 0.9351835099389475, 0.9362656671702783,
+  0.937339011912575, 0.9384035340631081, 0.9394592236021899, 0.9405060705932683,
+  0.9415440651830208, 0.9425731976014469, 0.9435934581619604, 0.9446048372614803,
+  0.9456073253805213, 0.9466009130832835, 0.9475855910177411, 0.9485613499157303,
+  0.9495281805930367, 0.9504860739494817, 0.9514350209690083, 0.9523750127197659,
+  0.9533060403541938, 0.9542280951091057, 0.9551411683057707, 0.9560452513499964,
+  0.9569403357322089, 0.9578264130275329, 0.9587034748958716, 0.9595715130819845,
+  0.9604305194155658, 0.9612804858113206, 0.9621214042690416, 0.9629532668736839,
+  0.9637760657954398, 0.9645897932898126, 0.9653944416976894, 0.9661900034454126,
+  0.9669764710448521, 0.9677538370934755, 0.9685220942744173, 0.9692812353565485,
+  0.970031253194544, 0.9707721407289504, 0.9715038909862518, 0.9722264970789363,
+  0.9729399522055601, 0.9736442496508119, 0.9743393827855759, 0.9750253450669941,
+  0.9757021300385286, 0.9763697313300211, 0.9770281426577544, 0.9776773578245099,
+  0.9783173707196277, 0.9789481753190622, 0.9795697656854405, 0.9801821359681173,
+  0.9807852804032304, 0.9813791933137546, 0.9819638691095552, 0.9825393022874412,
+  0.9831054874312163, 0.9836624192117303, 0.984210092386929, 0.9847485018019042,
+  0.9852776423889412, 0.9857975091675674, 0.9863080972445987, 0.9868094018141854,
+  0.9873014181578584, 0.9877841416445722, 0.9882575677307495, 0.9887216919603238,
+  0.989176509964781, 0.9896220174632008, 0.9900582102622971, 0.990485084256457,
+  0.99090263542778, 0.9913108598461154, 0.9917097536690995, 0.9920993131421918,
+  0.99247953459871, 0.9928504144598651, 0.9932119492347945, 0.9935641355205953,
+  0.9939069700023561, 0.9942404494531879, 0.9945645707342554, 0.9948793307948056,
+  0.9951847266721968, 0.9954807554919269, 0.9957674144676598, 0.996044700901252,
+  0.996312612182778, 0.9965711457905548, 0.9968202992911657, 0.997060070339483,
+  0.9972904566786902, 0.9975114561403035, 0.9977230666441916, 0.997925286198596,
+  0.9981181129001492, 0.9983015449338929, 0.9984755805732948, 0.9986402181802653,
+  0.9987954562051724, 0.9989412931868569, 0.9990777277526454, 0.9992047586183639,
+  0.9993223845883495, 0.9994306045554617, 0.9995294175010931, 0.9996188224951786,
+  0.9996988186962042, 0.9997694053512153, 0.9998305817958234, 0.9998823474542126,
+  0.9999247018391445, 0.9999576445519639, 0.9999811752826011, 0.9999952938095762,
+  1.0,
+];
+
+/// Sine from a lookup table with linear interpolation, cheaper than `sin` and with an error below 1e-5.
+/// The phase is in cycles, so a full cycle goes from 0.0 to 1.0.
+pub fn sin_lut<F: Float>(phase: F) -> F {
+  let phase = phase - phase.floor();
+  let position = phase * F::val(4 * QUARTER_SIZE);
+  let index = position.to_usize().unwrap_or(0).min(4 * QUARTER_SIZE - 1);
+  let fraction = position - F::val(index);
+  let quadrant = index / QUARTER_SIZE;
+  let index = index % QUARTER_SIZE;
+
+  // the rest of the quadrants mirror the first one
+  let (current, next) = match quadrant {
+    0 | 2 => (SINE_TABLE[index], SINE_TABLE[index + 1]),
+    _ => (
+      SINE_TABLE[QUARTER_SIZE - index],
+      SINE_TABLE[QUARTER_SIZE - index - 1],
+    ),
+  };
+  let (current, next) = (F::val(current), F::val(next));
+  let value = current + (next - current) * fraction;
+  if quadrant < 2 {
+    value
+  } else {
+    value.neg()
+  }
+}
+
+/// Cosine from the sine lookup table, with the phase in cycles
+pub fn cos_lut<F: Float>(phase: F) -> F {
+  sin_lut(phase + F::val(0.25))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn sine_within_tolerance() {
+    for index in -1000..=3000 {
+      let phase = index as f64 / 1999.0;
+      let expected = (2.0 * std::f64::consts::PI * phase).sin();
+      assert!((sin_lut(phase) - expected).abs() < 1e-5, "phase {}", phase);
+      let expected = (2.0 * std::f64::consts::PI * phase).cos();
+      assert!((cos_lut(phase) - expected).abs() < 1e-5, "phase {}", phase);
+    }
+  }
+
+  #[test]
+  fn sine_f32() {
+    for index in 0..=1000 {
+      let phase = index as f32 / 1000.0;
+      let expected = (2.0 * std::f32::consts::PI * phase).sin();
+      assert!((sin_lut(phase) - expected).abs() < 1e-5, "phase {}", phase);
+    }
+  }
+}
@@ -9,4 +9,7 @@ pub mod float;
 pub mod funcs;
 pub mod meters;
 pub mod oscillators;
+pub mod oversample;
+pub mod resampler;
+pub mod sequencer;
 pub mod waveforms;
@@ -1,4 +1,8 @@
 pub mod level_meter;
+pub mod rms_meter;
 pub mod rms_online;
+pub mod true_peak;
 
 pub use level_meter::PeakMeter;
+pub use rms_meter::RmsMeter;
+pub use true_peak::TruePeak;
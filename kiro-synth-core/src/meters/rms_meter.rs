@@ -0,0 +1,64 @@
+use crate::filters::one_pole::{Mode, OnePole};
+use crate::float::Float;
+use crate::funcs::decibels::Decibels;
+
+/// Root mean square level with an exponential integration time, which gives a reading
+/// closer to the perceived loudness than the peaks.
+#[derive(Debug, Clone)]
+pub struct RmsMeter<F: Float> {
+  mean_square: OnePole<F>,
+}
+
+impl<F: Float> RmsMeter<F> {
+  pub fn new(sample_rate: F, integration_seconds: F) -> Self {
+    let mut mean_square = OnePole::new(sample_rate, Mode::LowPass);
+    mean_square.set_time_constant(integration_seconds);
+    RmsMeter { mean_square }
+  }
+
+  pub fn reset(&mut self) {
+    self.mean_square.reset();
+  }
+
+  pub fn process(&mut self, value: F) {
+    self.mean_square.process(value * value);
+  }
+
+  /// The RMS as an amplitude
+  pub fn get_rms(&self) -> F {
+    self.mean_square.get().sqrt()
+  }
+
+  /// The RMS in dB
+  pub fn get_level(&self) -> F {
+    Decibels::from_amplitude(self.get_rms()).value()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::RmsMeter;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn sine_level() {
+    let mut meter = RmsMeter::<f64>::new(1000.0, 0.1);
+    for index in 0..2000 {
+      meter.process((2.0 * std::f64::consts::PI * index as f64 / 50.0).sin());
+    }
+    assert_approx_eq!(meter.get_rms(), 0.5f64.sqrt(), 0.02);
+    assert_approx_eq!(meter.get_level(), -3.0, 0.3);
+  }
+
+  #[test]
+  fn slow_release() {
+    let mut meter = RmsMeter::<f64>::new(1000.0, 0.1);
+    (0..2000).for_each(|_| meter.process(1.0));
+    (0..50).for_each(|_| meter.process(0.0));
+    assert!(meter.get_rms() > 0.7);
+    (0..2000).for_each(|_| meter.process(0.0));
+    assert!(meter.get_rms() < 0.001);
+    meter.reset();
+    assert_eq!(meter.get_rms(), 0.0);
+  }
+}
@@ -0,0 +1,116 @@
+use crate::float::Float;
+
+/// Number of interpolated positions for every sample, the first one being the sample itself
+const PHASES: usize = 4;
+
+/// Number of samples used to interpolate every position
+const TAPS: usize = 16;
+
+/// Estimates the peaks of the continuous signal, which can go over the samples between them
+/// and clip once converted to analog or resampled. It interpolates the signal at four times
+/// the sample rate with a windowed sinc, so its readings are delayed by half of its taps.
+#[derive(Debug, Clone)]
+pub struct TruePeak<F: Float> {
+  coefficients: [[F; TAPS]; PHASES],
+  history: [F; TAPS],
+  position: usize,
+}
+
+impl<F: Float> TruePeak<F> {
+  pub fn new() -> Self {
+    let mut coefficients = [[F::zero(); TAPS]; PHASES];
+    let half = F::val(TAPS / 2);
+    for (phase, taps) in coefficients.iter_mut().enumerate() {
+      let fraction = F::val(phase) / F::val(PHASES);
+      for (tap, coefficient) in taps.iter_mut().enumerate() {
+        // the distance from the oldest sample of the history to the interpolated position
+        let x = fraction + half - F::one() - F::val(tap);
+        let sinc = if x.abs() < F::epsilon() {
+          F::one()
+        } else {
+          (F::PI * x).sin() / (F::PI * x)
+        };
+        let window = F::val(0.5) * (F::one() + (F::PI * x / half).cos());
+        *coefficient = sinc * window;
+      }
+      // unity gain for the DC in every phase
+      let sum = taps
+        .iter()
+        .fold(F::zero(), |sum, coefficient| sum + *coefficient);
+      for coefficient in taps.iter_mut() {
+        *coefficient = *coefficient / sum;
+      }
+    }
+
+    TruePeak {
+      coefficients,
+      history: [F::zero(); TAPS],
+      position: 0,
+    }
+  }
+
+  pub fn reset(&mut self) {
+    self.history = [F::zero(); TAPS];
+    self.position = 0;
+  }
+
+  /// Add a sample and return the highest absolute value of the signal between the samples
+  /// of eight and seven samples ago
+  pub fn process(&mut self, value: F) -> F {
+    self.history[self.position] = value;
+    self.position = (self.position + 1) % TAPS;
+
+    let mut peak = F::zero();
+    for taps in self.coefficients.iter() {
+      let mut sample = F::zero();
+      for (tap, coefficient) in taps.iter().enumerate() {
+        sample = sample + self.history[(self.position + tap) % TAPS] * *coefficient;
+      }
+      peak = peak.max(sample.abs());
+    }
+    peak
+  }
+}
+
+impl<F: Float> Default for TruePeak<F> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::TruePeak;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn inter_sample_peaks() {
+    // a sine at a quarter of the sample rate sampled 45 degrees away from its peaks
+    let amplitude = 1.2;
+    let samples: Vec<f64> = (0..200)
+      .map(|index| amplitude * (std::f64::consts::FRAC_PI_2 * (index as f64 + 0.5)).sin())
+      .collect();
+    let sample_peak = samples
+      .iter()
+      .fold(0.0f64, |peak, sample| peak.max(sample.abs()));
+    assert!(sample_peak < 1.0, "sample peak {}", sample_peak);
+
+    let mut true_peak = TruePeak::new();
+    let peaks: Vec<f64> = samples
+      .iter()
+      .map(|sample| true_peak.process(*sample))
+      .collect();
+    let peak = peaks[100..]
+      .iter()
+      .fold(0.0f64, |peak, sample| peak.max(*sample));
+    assert!(peak > 1.0);
+    assert_approx_eq!(peak, amplitude, 0.05);
+  }
+
+  #[test]
+  fn constant_signal() {
+    let mut true_peak = TruePeak::<f32>::new();
+    let peak = (0..32).fold(0.0, |_, _| true_peak.process(-0.5));
+    assert_approx_eq!(peak, 0.5, 1e-6);
+  }
+}
@@ -0,0 +1,91 @@
+use crate::filters::one_pole::{Mode, OnePole};
+use crate::float::Float;
+use crate::funcs::random::Random;
+
+/// Average time between the random targets of the drift
+const TARGET_SECONDS: f64 = 0.5;
+
+/// Slow random wander of the pitch, in cents, emulating the instability of analog oscillators
+#[derive(Debug, Clone)]
+pub struct Drift<F: Float> {
+  random: Random,
+  depth: F,
+  target: F,
+  /// Smooths the jumps between the targets
+  value: OnePole<F>,
+  samples_per_target: usize,
+  samples_left: usize,
+}
+
+impl<F: Float> Drift<F> {
+  pub fn new(sample_rate: F, seed: u64) -> Self {
+    let target_seconds = F::val(TARGET_SECONDS);
+    let target_samples = target_seconds * sample_rate;
+    let mut value = OnePole::new(sample_rate, Mode::LowPass);
+    value.set_time_constant(target_seconds);
+    Drift {
+      random: Random::new(seed),
+      depth: F::zero(),
+      target: F::zero(),
+      value,
+      samples_per_target: target_samples.to_usize().unwrap_or(1).max(1),
+      samples_left: 0,
+    }
+  }
+
+  /// The maximum deviation in cents, zero disables the drift
+  pub fn set_depth(&mut self, cents: F) {
+    self.depth = cents.max(F::zero());
+  }
+
+  pub fn get_depth(&self) -> F {
+    self.depth
+  }
+
+  /// Restart the sequence of random targets
+  pub fn set_seed(&mut self, seed: u64) {
+    self.random.set_seed(seed);
+  }
+
+  /// Generate the next deviation in cents, always between `-depth` and `depth`
+  pub fn generate(&mut self) -> F {
+    if self.depth <= F::zero() {
+      return F::zero();
+    }
+
+    if self.samples_left == 0 {
+      let half = self.samples_per_target / 2;
+      let samples = half + self.random.next_u64() as usize % self.samples_per_target;
+      self.samples_left = samples.max(1);
+      self.target = self.random.bipolar();
+    }
+    self.samples_left -= 1;
+
+    self.value.process(self.target) * self.depth
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn disabled_without_depth() {
+    let mut drift = Drift::new(1000.0, 1);
+    assert!((0..10000).all(|_| drift.generate() == 0.0f64));
+  }
+
+  #[test]
+  fn slow_and_bounded() {
+    let mut drift = Drift::new(1000.0, 1);
+    drift.set_depth(5.0);
+    let output: Vec<f64> = (0..20000).map(|_| drift.generate()).collect();
+    assert!(output.iter().all(|cents| cents.abs() <= 5.0));
+    assert!(output.iter().any(|cents| cents.abs() > 1.0));
+    let max_step = output
+      .windows(2)
+      .map(|pair| (pair[1] - pair[0]).abs())
+      .fold(0.0, f64::max);
+    assert!(max_step < 0.05);
+  }
+}
@@ -1,4 +1,5 @@
 use crate::float::Float;
+use crate::funcs::signal_polarity::bipolar_to_unipolar;
 use crate::oscillators::clamp_modulo;
 use crate::oscillators::osc_waveform::OscWaveform;
 
@@ -6,17 +7,32 @@ use crate::oscillators::osc_waveform::OscWaveform;
 
 // TODO Mode: free-running, synchronized, one-shot
 
+/// The range of the output of the LFO before applying the depth
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Polarity {
+  /// From -1.0 to 1.0
+  Bipolar,
+  /// From 0.0 to 1.0
+  Unipolar,
+}
+
 #[derive(Debug, Clone)]
 pub struct Lfo<F: Float> {
   waveform: OscWaveform<F>,
   rate: F,
   phase: F,
   depth: F,
+  slew_seconds: F,
+  polarity: Polarity,
+  invert: bool,
+  /// The seed for the random waveforms, which keep their own one otherwise
+  seed: Option<u64>,
 
   modulo: F,
   phase_inc: F,
   phase_inc_invalidated: bool,
   inv_sample_rate: F,
+  wrapped: bool,
 }
 
 impl<F: Float> Lfo<F> {
@@ -28,11 +44,16 @@ impl<F: Float> Lfo<F> {
       rate: F::one(),
       phase: F::zero(),
       depth: F::one(),
+      slew_seconds: F::zero(),
+      polarity: Polarity::Bipolar,
+      invert: false,
+      seed: None,
 
       modulo,
       phase_inc: F::zero(),
       phase_inc_invalidated: true,
       inv_sample_rate: sample_rate.recip(),
+      wrapped: false,
     }
   }
 
@@ -40,6 +61,8 @@ impl<F: Float> Lfo<F> {
   pub fn set_waveform(&mut self, waveform: OscWaveform<F>) {
     self.waveform = waveform;
     self.reset_modulo();
+    self.update_slew();
+    self.update_seed();
     // FIXME figure out how to avoid clips after changing the waveform and the module
   }
 
@@ -59,10 +82,39 @@ impl<F: Float> Lfo<F> {
     self.depth = depth;
   }
 
+  /// Set the time of the transitions between the held values of the sample and hold waveform
+  pub fn set_slew_seconds(&mut self, seconds: F) {
+    self.slew_seconds = seconds.max(F::zero());
+    self.update_slew();
+  }
+
+  pub fn get_slew_seconds(&self) -> F {
+    self.slew_seconds
+  }
+
+  /// Set the range of the output
+  pub fn set_polarity(&mut self, polarity: Polarity) {
+    self.polarity = polarity;
+  }
+
+  pub fn get_polarity(&self) -> Polarity {
+    self.polarity
+  }
+
+  /// Set whether the output is negated
+  pub fn set_invert(&mut self, invert: bool) {
+    self.invert = invert;
+  }
+
+  pub fn is_inverted(&self) -> bool {
+    self.invert
+  }
+
   /// Set the sample rate
   pub fn set_sample_rate(&mut self, sample_rate: F) {
     self.inv_sample_rate = sample_rate.recip();
     self.phase_inc_invalidated = true;
+    self.update_slew();
   }
 
   /// Reset the LFO
@@ -70,6 +122,16 @@ impl<F: Float> Lfo<F> {
     self.reset_modulo();
   }
 
+  /// The position in the cycle of the next value, from 0.0 to 1.0
+  pub fn get_modulo(&self) -> F {
+    self.modulo
+  }
+
+  /// Whether the last call to generate completed a cycle
+  pub fn has_wrapped(&self) -> bool {
+    self.wrapped
+  }
+
   /// Generate the next value
   pub fn generate(&mut self) -> F {
     if self.phase_inc_invalidated {
@@ -77,10 +139,38 @@ impl<F: Float> Lfo<F> {
     }
 
     let signal = self.waveform.generate(self.modulo, self.phase_inc);
-    self.modulo = clamp_modulo(self.modulo + self.phase_inc);
+    let modulo = self.modulo + self.phase_inc;
+    self.wrapped = modulo >= F::one() || modulo < F::zero();
+    self.modulo = clamp_modulo(modulo);
+
+    let signal = match self.polarity {
+      Polarity::Bipolar => signal,
+      Polarity::Unipolar => bipolar_to_unipolar(signal),
+    };
+    let signal = if self.invert { -signal } else { signal };
     signal * self.depth
   }
 
+  fn update_slew(&mut self) {
+    if let OscWaveform::SampleHold(ref mut waveform) = self.waveform {
+      waveform.set_slew_samples(self.slew_seconds / self.inv_sample_rate);
+    }
+  }
+
+  /// Restart the random waveforms, such as the sample and hold, from a seed, now and after any
+  /// change of waveform
+  pub fn set_seed(&mut self, seed: u64) {
+    self.seed = Some(seed);
+    self.update_seed();
+  }
+
+  fn update_seed(&mut self) {
+    if let (OscWaveform::SampleHold(ref mut waveform), Some(seed)) = (&mut self.waveform, self.seed)
+    {
+      waveform.set_seed(seed);
+    }
+  }
+
   fn reset_modulo(&mut self) {
     self.modulo = clamp_modulo(self.waveform.initial_modulo() + self.phase);
   }
@@ -1,44 +1,64 @@
 use crate::float::Float;
+use crate::waveforms::additive::Additive;
+use crate::waveforms::sample_hold::SampleHold;
 use crate::waveforms::saw_blep::SawBlep;
+use crate::waveforms::saw_dpw::SawDpw;
 use crate::waveforms::saw_trivial::SawTrivial;
+use crate::waveforms::sine_lut::SineLut;
 use crate::waveforms::sine_parabolic::SineParabolic;
 use crate::waveforms::triangle_dpw2x::TriangleDpw2x;
 use crate::waveforms::triangle_trivial::TriangleTrivial;
+use crate::waveforms::wavetable::Wavetable;
 use crate::waveforms::Waveform;
 
 #[derive(Debug, Clone)]
 pub enum OscWaveform<F: Float> {
+  SineLut(SineLut),
   SineParabolic(SineParabolic),
   SawTrivial(SawTrivial),
   SawBlep(SawBlep<F>),
+  SawDpw(SawDpw<F>),
   TriangleTrivial(TriangleTrivial),
   TriangleDpw2x(TriangleDpw2x<F>),
+  Wavetable(Wavetable<F>),
+  Additive(Additive<F>),
+  SampleHold(SampleHold<F>),
 }
 
 impl<F: Float> Default for OscWaveform<F> {
   fn default() -> Self {
-    OscWaveform::SineParabolic(SineParabolic)
+    OscWaveform::SineLut(SineLut)
   }
 }
 
 impl<F: Float> OscWaveform<F> {
   pub fn initial_modulo(&self) -> F {
     match self {
+      OscWaveform::SineLut(wf) => wf.initial_modulo(),
       OscWaveform::SineParabolic(wf) => wf.initial_modulo(),
       OscWaveform::SawTrivial(wf) => wf.initial_modulo(),
       OscWaveform::SawBlep(wf) => wf.initial_modulo(),
+      OscWaveform::SawDpw(wf) => wf.initial_modulo(),
       OscWaveform::TriangleTrivial(wf) => wf.initial_modulo(),
       OscWaveform::TriangleDpw2x(wf) => wf.initial_modulo(),
+      OscWaveform::Wavetable(wf) => wf.initial_modulo(),
+      OscWaveform::Additive(wf) => wf.initial_modulo(),
+      OscWaveform::SampleHold(wf) => wf.initial_modulo(),
     }
   }
 
   pub fn generate(&mut self, modulo: F, phase_inc: F) -> F {
     match self {
+      OscWaveform::SineLut(wf) => wf.generate(modulo, phase_inc),
       OscWaveform::SineParabolic(wf) => wf.generate(modulo, phase_inc),
       OscWaveform::SawTrivial(wf) => wf.generate(modulo, phase_inc),
       OscWaveform::SawBlep(wf) => wf.generate(modulo, phase_inc),
+      OscWaveform::SawDpw(wf) => wf.generate(modulo, phase_inc),
       OscWaveform::TriangleTrivial(wf) => wf.generate(modulo, phase_inc),
       OscWaveform::TriangleDpw2x(wf) => wf.generate(modulo, phase_inc),
+      OscWaveform::Wavetable(wf) => wf.generate(modulo, phase_inc),
+      OscWaveform::Additive(wf) => wf.generate(modulo, phase_inc),
+      OscWaveform::SampleHold(wf) => wf.generate(modulo, phase_inc),
     }
   }
 }
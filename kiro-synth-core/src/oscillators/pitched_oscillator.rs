@@ -1,8 +1,57 @@
 use crate::float::Float;
 use crate::oscillators::clamp_modulo;
+use crate::oscillators::drift::Drift;
 use crate::oscillators::osc_pitch_shift::OscPitchShift;
 use crate::oscillators::osc_waveform::OscWaveform;
 
+/// How an oscillator follows the cycles of another one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncMode {
+  /// No sync
+  Off,
+  /// The phase is reset every time the master oscillator starts a cycle
+  Hard,
+  /// The phase is reset when the master oscillator starts a cycle,
+  /// but only if it is already close to the beginning of its own cycle
+  Soft,
+}
+
+impl SyncMode {
+  pub fn count() -> usize {
+    3
+  }
+
+  pub fn from<F: Float>(value: F) -> Self {
+    match value.round().to_usize().unwrap_or(0) {
+      1 => SyncMode::Hard,
+      2 => SyncMode::Soft,
+      _ => SyncMode::Off,
+    }
+  }
+}
+
+/// How the frequency modulation is applied to the pitch of an oscillator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FmMode {
+  /// The modulation is added to the pitch in semitones
+  Exponential,
+  /// The modulation is added to the frequency in Hz (DX style)
+  Linear,
+}
+
+impl FmMode {
+  pub fn count() -> usize {
+    2
+  }
+
+  pub fn from<F: Float>(value: F) -> Self {
+    match value.round().to_usize().unwrap_or(0) {
+      1 => FmMode::Linear,
+      _ => FmMode::Exponential,
+    }
+  }
+}
+
 #[derive(Debug)]
 pub struct PitchedOscillator<F: Float> {
   waveform: OscWaveform<F>,
@@ -10,11 +59,18 @@ pub struct PitchedOscillator<F: Float> {
   pitch_shift: OscPitchShift<F>,
   amplitude: F,
   amp_mod: F,
+  fm_mode: FmMode,
+  freq_mod: F,
+  pitch_mod: F,
 
   modulo: F,
   phase_inc: F,
   phase_inc_invalidated: bool,
   inv_sample_rate: F,
+  wrapped: bool,
+
+  drift: Drift<F>,
+  drift_cents: F,
 }
 
 impl<F: Float> PitchedOscillator<F> {
@@ -29,14 +85,26 @@ impl<F: Float> PitchedOscillator<F> {
       pitch_shift,
       amplitude: F::one(),
       amp_mod: F::zero(),
+      fm_mode: FmMode::Exponential,
+      freq_mod: F::zero(),
+      pitch_mod: F::zero(),
 
       modulo,
       phase_inc: F::zero(),
       phase_inc_invalidated: true,
       inv_sample_rate: sample_rate.recip(),
+      wrapped: false,
+
+      drift: Drift::new(sample_rate, 0),
+      drift_cents: F::zero(),
     }
   }
 
+  /// The maximum distance of the phase to the beginning of the cycle for the soft sync to happen
+  pub fn soft_sync_window() -> F {
+    F::val(0.1)
+  }
+
   /// Set the waveform
   pub fn set_waveform(&mut self, waveform: OscWaveform<F>) {
     self.waveform = waveform;
@@ -45,6 +113,11 @@ impl<F: Float> PitchedOscillator<F> {
     // self.phase_inc_invalidated = true; // TODO really necessary ???
   }
 
+  /// Change the waveform keeping the phase, so it can be switched while the oscillator is playing
+  pub fn switch_waveform(&mut self, waveform: OscWaveform<F>) {
+    self.waveform = waveform;
+  }
+
   /// Set the pitch frequency
   pub fn set_pitch_frequency(&mut self, pitch_freq: F) {
     self.pitch_freq = pitch_freq;
@@ -75,12 +148,49 @@ impl<F: Float> PitchedOscillator<F> {
     self.phase_inc_invalidated = true;
   }
 
-  /// Set the frequency modulation
+  /// Set the frequency modulation, in semitones or Hz depending on the FM mode
   pub fn set_frequency_modulation(&mut self, freq_mod: F) {
-    self.pitch_shift.set_modulation(freq_mod);
+    self.freq_mod = freq_mod;
+    self.update_frequency_modulation();
+  }
+
+  /// Set whether the frequency modulation is exponential or linear
+  pub fn set_fm_mode(&mut self, fm_mode: FmMode) {
+    self.fm_mode = fm_mode;
+    self.update_frequency_modulation();
+  }
+
+  /// Set the modulation of the pitch in semitones, added to the exponential frequency modulation
+  pub fn set_pitch_modulation(&mut self, semitones: F) {
+    self.pitch_mod = semitones;
+    self.update_frequency_modulation();
+  }
+
+  fn update_frequency_modulation(&mut self) {
+    let semitones = match self.fm_mode {
+      FmMode::Exponential => self.freq_mod,
+      FmMode::Linear => F::zero(),
+    };
+    self.pitch_shift.set_modulation(semitones + self.pitch_mod);
     self.phase_inc_invalidated = true;
   }
 
+  /// Set the maximum deviation in cents of the slow random drift of the pitch, zero disables it
+  pub fn set_drift(&mut self, cents: F) {
+    self.drift.set_depth(cents);
+  }
+
+  /// Set the seed for the drift, so every oscillator wanders differently
+  pub fn set_drift_seed(&mut self, seed: u64) {
+    self.drift.set_seed(seed);
+  }
+
+  /// The frequency of the last generated sample, including the drift
+  pub fn get_frequency(&self) -> F {
+    let drift = F::val(2.0).powf(self.drift_cents / F::val(1200.0));
+    self.phase_inc * drift / self.inv_sample_rate
+  }
+
   /// Set amplitude
   pub fn set_amplitude(&mut self, amplitude: F) {
     self.amplitude = amplitude;
@@ -102,19 +212,196 @@ impl<F: Float> PitchedOscillator<F> {
     self.modulo = self.waveform.initial_modulo();
   }
 
+  /// Whether the last call to generate completed a cycle
+  pub fn has_wrapped(&self) -> bool {
+    self.wrapped
+  }
+
+  /// Synchronise with the beginning of a cycle from a master oscillator
+  pub fn sync(&mut self, mode: SyncMode) {
+    let initial_modulo = self.waveform.initial_modulo();
+    match mode {
+      SyncMode::Off => {}
+      SyncMode::Hard => self.modulo = initial_modulo,
+      SyncMode::Soft => {
+        let mut distance = (self.modulo - initial_modulo).abs();
+        distance = distance.min(F::one() - distance);
+        if distance < Self::soft_sync_window() {
+          self.modulo = initial_modulo;
+        }
+      }
+    }
+  }
+
   /// Generate the next value
   pub fn generate(&mut self) -> F {
     if self.phase_inc_invalidated {
       self.update_phase_inc();
     }
 
-    let signal = self.waveform.generate(self.modulo, self.phase_inc);
-    self.modulo = clamp_modulo(self.modulo + self.phase_inc);
+    let mut phase_inc = self.phase_inc;
+    if self.drift.get_depth() > F::zero() {
+      self.drift_cents = self.drift.generate();
+      phase_inc = phase_inc * (self.drift_cents * F::val(core::f64::consts::LN_2 / 1200.0)).exp();
+    }
+
+    let signal = self.waveform.generate(self.modulo, phase_inc);
+    let modulo = self.modulo + phase_inc;
+    self.wrapped = modulo >= F::one() || modulo < F::zero();
+    self.modulo = clamp_modulo(modulo);
     signal * self.amplitude + self.amp_mod
   }
 
   fn update_phase_inc(&mut self) {
-    let freq = self.pitch_freq * self.pitch_shift.multiplier();
+    let mut freq = self.pitch_freq * self.pitch_shift.multiplier();
+    if self.fm_mode == FmMode::Linear {
+      freq = freq + self.freq_mod;
+    }
     self.phase_inc = freq * self.inv_sample_rate;
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use crate::waveforms::sine_parabolic::SineParabolic;
+
+  fn render_synced(mode: SyncMode, slave_freq: f64) -> Vec<f64> {
+    let sample_rate = 10000.0;
+    let waveform = OscWaveform::SineParabolic(SineParabolic);
+    let mut master = PitchedOscillator::new(sample_rate, waveform.clone(), 100.0);
+    let mut slave = PitchedOscillator::new(sample_rate, waveform, slave_freq);
+    (0..1000)
+      .map(|_| {
+        master.generate();
+        if master.has_wrapped() {
+          slave.sync(mode);
+        }
+        slave.generate()
+      })
+      .collect()
+  }
+
+  fn max_jump(samples: &[f64]) -> f64 {
+    samples
+      .windows(2)
+      .map(|pair| (pair[1] - pair[0]).abs())
+      .fold(0.0, f64::max)
+  }
+
+  /// Render a carrier modulated by a sine modulator, returning the output and the number of cycles
+  fn render_fm(fm_mode: FmMode, depth: f64) -> (Vec<f64>, usize) {
+    let sample_rate = 10000.0;
+    let waveform = OscWaveform::SineParabolic(SineParabolic);
+    let mut modulator = PitchedOscillator::new(sample_rate, waveform.clone(), 50.0);
+    let mut carrier = PitchedOscillator::new(sample_rate, waveform, 200.0);
+    carrier.set_fm_mode(fm_mode);
+    let mut cycles = 0;
+    let output = (0..10000)
+      .map(|_| {
+        carrier.set_frequency_modulation(modulator.generate() * depth);
+        let value = carrier.generate();
+        if carrier.has_wrapped() {
+          cycles += 1;
+        }
+        value
+      })
+      .collect();
+    (output, cycles)
+  }
+
+  /// The minimum and maximum instantaneous frequencies of a 440 Hz oscillator
+  fn frequency_range(drift: f64) -> (f64, f64) {
+    let waveform = OscWaveform::SineParabolic(SineParabolic);
+    let mut osc = PitchedOscillator::new(1000.0, waveform, 440.0);
+    osc.set_drift(drift);
+    (0..20000).fold((f64::MAX, f64::MIN), |(min, max), _| {
+      osc.generate();
+      let freq = osc.get_frequency();
+      (min.min(freq), max.max(freq))
+    })
+  }
+
+  #[test]
+  fn steady_pitch_without_drift() {
+    let (min, max) = frequency_range(0.0);
+    assert_eq!(min, 440.0);
+    assert_eq!(max, 440.0);
+  }
+
+  #[test]
+  fn pitch_drift() {
+    // 5 cents are about 1.27 Hz at 440 Hz
+    let (min, max) = frequency_range(5.0);
+    assert!(min < 439.9 && min > 438.7);
+    assert!(max > 440.1 && max < 441.3);
+  }
+
+  #[test]
+  fn fm_mode_from() {
+    assert_eq!(FmMode::from(0.0), FmMode::Exponential);
+    assert_eq!(FmMode::from(1.0), FmMode::Linear);
+  }
+
+  #[test]
+  fn linear_fm_without_modulation() {
+    let (unmodulated, _) = render_fm(FmMode::Exponential, 0.0);
+    let (linear, cycles) = render_fm(FmMode::Linear, 0.0);
+    assert_eq!(linear, unmodulated);
+    assert_eq!(cycles, 200);
+  }
+
+  #[test]
+  fn linear_fm_differs_from_exponential_fm() {
+    // one octave of exponential modulation against the same peak deviation in Hz
+    let (exponential, exponential_cycles) = render_fm(FmMode::Exponential, 12.0);
+    let (linear, linear_cycles) = render_fm(FmMode::Linear, 200.0);
+    assert!(linear != exponential);
+
+    // linear FM keeps the carrier frequency on average while exponential FM raises it
+    assert!((199..=201).contains(&linear_cycles));
+    assert!(exponential_cycles > 210);
+  }
+
+  #[test]
+  fn pitch_modulation_with_both_fm_modes() {
+    for fm_mode in [FmMode::Exponential, FmMode::Linear].iter() {
+      let waveform = OscWaveform::SineParabolic(SineParabolic);
+      let mut osc = PitchedOscillator::new(1000.0f64, waveform, 100.0);
+      osc.set_fm_mode(*fm_mode);
+      osc.set_pitch_modulation(12.0);
+      osc.generate();
+      assert!((osc.get_frequency() - 200.0).abs() < 1e-9);
+    }
+  }
+
+  #[test]
+  fn sync_mode_from() {
+    assert_eq!(SyncMode::from(0.0), SyncMode::Off);
+    assert_eq!(SyncMode::from(1.0), SyncMode::Hard);
+    assert_eq!(SyncMode::from(2.0), SyncMode::Soft);
+  }
+
+  #[test]
+  fn soft_sync_is_smoother_than_hard_sync() {
+    let free = render_synced(SyncMode::Off, 230.0);
+    let hard = render_synced(SyncMode::Hard, 230.0);
+    let soft = render_synced(SyncMode::Soft, 230.0);
+
+    assert!(hard != free);
+    assert!(soft != hard);
+    assert!(max_jump(&hard) > 0.5);
+    assert!(max_jump(&soft) < max_jump(&hard));
+  }
+
+  #[test]
+  fn soft_sync_when_close_to_the_cycle_start() {
+    let free = render_synced(SyncMode::Off, 205.0);
+    let hard = render_synced(SyncMode::Hard, 205.0);
+    let soft = render_synced(SyncMode::Soft, 205.0);
+
+    assert!(soft != free);
+    assert_eq!(soft, hard);
+  }
+}
@@ -0,0 +1,124 @@
+use crate::filters::biquad::Biquad;
+use crate::float::Float;
+
+/// The Q of the two sections of a 4th order Butterworth low pass
+const SECTION_QS: [f64; 2] = [0.541_196_1, 1.306_563];
+
+/// The cutoff of the anti-aliasing filter relative to the original sample rate, below its Nyquist
+const CUTOFF_RATIO: f64 = 0.45;
+
+/// Runs a generator at a multiple of the sample rate, filtering out what would alias before
+/// keeping one of every `factor` samples.
+///
+/// It costs as many calls to the generator as the factor, so it is meant for the processes
+/// creating a lot of high frequency content, such as deep frequency modulation.
+#[derive(Debug, Clone)]
+pub struct Oversample<F: Float> {
+  sample_rate: F,
+  factor: usize,
+  filters: [Biquad<F>; 2],
+}
+
+impl<F: Float> Oversample<F> {
+  pub fn new(sample_rate: F, factor: usize) -> Self {
+    let mut oversample = Oversample {
+      sample_rate,
+      factor: factor.max(1),
+      filters: [Biquad::new(), Biquad::new()],
+    };
+    oversample.update_filters();
+    oversample
+  }
+
+  pub fn set_sample_rate(&mut self, sample_rate: F) {
+    self.sample_rate = sample_rate;
+    self.update_filters();
+  }
+
+  /// Set how many times the generator runs for every output sample, 1 passes it through
+  pub fn set_factor(&mut self, factor: usize) {
+    self.factor = factor.max(1);
+    self.update_filters();
+  }
+
+  pub fn get_factor(&self) -> usize {
+    self.factor
+  }
+
+  /// The sample rate the generator has to run at
+  pub fn oversampled_rate(&self) -> F {
+    self.sample_rate * F::val(self.factor)
+  }
+
+  pub fn reset(&mut self) {
+    for filter in self.filters.iter_mut() {
+      filter.reset();
+    }
+  }
+
+  /// Call the generator `factor` times, with the index of the sub-sample, and return one filtered sample
+  pub fn process<G: FnMut(usize) -> F>(&mut self, mut generate: G) -> F {
+    if self.factor == 1 {
+      return generate(0);
+    }
+
+    let mut output = F::zero();
+    for index in 0..self.factor {
+      output = self
+        .filters
+        .iter_mut()
+        .fold(generate(index), |sample, filter| filter.process(sample));
+    }
+    output
+  }
+
+  fn update_filters(&mut self) {
+    let oversampled_rate = self.oversampled_rate();
+    let cutoff = self.sample_rate * F::val(CUTOFF_RATIO);
+    for (filter, q) in self.filters.iter_mut().zip(SECTION_QS.iter()) {
+      filter.set_low_pass(oversampled_rate, cutoff, F::val(*q));
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const SAMPLE_RATE: f64 = 1000.0;
+
+  /// The peak output for a sine generated at the oversampled rate once the filter has settled
+  fn peak(factor: usize, freq: f64) -> f64 {
+    let mut oversample = Oversample::new(SAMPLE_RATE, factor);
+    let omega = 2.0 * std::f64::consts::PI * freq / oversample.oversampled_rate();
+    let mut time = 0;
+    (0..2000)
+      .map(|_| {
+        oversample.process(|_| {
+          time += 1;
+          (omega * time as f64).sin()
+        })
+      })
+      .skip(1000)
+      .fold(0.0, |peak, sample| sample.abs().max(peak))
+  }
+
+  #[test]
+  fn factor_one_passes_through() {
+    let mut oversample = Oversample::new(SAMPLE_RATE, 0);
+    assert_eq!(oversample.get_factor(), 1);
+    assert_eq!(oversample.process(|index| index as f64 + 0.5), 0.5);
+  }
+
+  #[test]
+  fn keeps_low_frequencies() {
+    assert!((peak(4, 50.0) - 1.0).abs() < 0.01);
+  }
+
+  #[test]
+  fn filters_what_would_alias() {
+    // 700 Hz would fold back to 300 Hz at the original sample rate
+    assert!(peak(1, 700.0) > 0.9);
+    assert!(peak(4, 700.0) < 0.2);
+  }
+}
@@ -0,0 +1,133 @@
+use crate::float::Float;
+use crate::funcs::interpolation::cubic_interpolation;
+
+/// Reads a source buffer at an arbitrary rate using cubic interpolation between samples.
+///
+/// A ratio of 1.0 plays the source as it is, 2.0 plays it twice as fast (an octave up)
+/// and 0.5 twice as slow (an octave down).
+pub struct Resampler<'a, F: Float> {
+  source: &'a [F],
+  position: F,
+  looping: bool,
+}
+
+impl<'a, F: Float> Resampler<'a, F> {
+  pub fn new(source: &'a [F]) -> Self {
+    Resampler {
+      source,
+      position: F::zero(),
+      looping: false,
+    }
+  }
+
+  /// When looping, the reading position wraps around the end of the source,
+  /// otherwise the output is silent once the end is reached.
+  pub fn set_looping(&mut self, looping: bool) {
+    self.looping = looping;
+  }
+
+  pub fn is_looping(&self) -> bool {
+    self.looping
+  }
+
+  pub fn set_position(&mut self, position: F) {
+    self.position = position;
+  }
+
+  pub fn get_position(&self) -> F {
+    self.position
+  }
+
+  pub fn reset(&mut self) {
+    self.position = F::zero();
+  }
+
+  pub fn is_finished(&self) -> bool {
+    !self.looping && self.position >= F::val(self.source.len())
+  }
+
+  pub fn process(&mut self, ratio: F) -> F {
+    if self.source.is_empty() || self.is_finished() {
+      return F::zero();
+    }
+
+    let index = self.position.floor();
+    let t = self.position - index;
+    let index = index.to_isize().unwrap();
+
+    let output = cubic_interpolation(
+      self.sample(index - 1),
+      self.sample(index),
+      self.sample(index + 1),
+      self.sample(index + 2),
+      t,
+    );
+
+    self.position = self.position + ratio;
+    if self.looping {
+      let len = F::val(self.source.len());
+      self.position = self.position % len;
+      if self.position < F::zero() {
+        self.position = self.position + len;
+      }
+    }
+
+    output
+  }
+
+  fn sample(&self, index: isize) -> F {
+    let len = self.source.len() as isize;
+    if self.looping {
+      self.source[index.rem_euclid(len) as usize]
+    } else if index >= 0 && index < len {
+      self.source[index as usize]
+    } else {
+      F::zero()
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn sine(period: f64, len: usize) -> Vec<f64> {
+    (0..len)
+      .map(|i| (2.0 * f64::PI * i as f64 / period).sin())
+      .collect()
+  }
+
+  #[test]
+  fn ratio_one_is_passthrough() {
+    let source = sine(64.0, 256);
+    let mut resampler = Resampler::new(&source);
+    for sample in source.iter() {
+      assert_eq!(resampler.process(1.0).to_bits(), sample.to_bits());
+    }
+    assert!(resampler.is_finished());
+    assert_eq!(resampler.process(1.0), 0.0);
+  }
+
+  #[test]
+  fn ratio_two_halves_period() {
+    let source = sine(64.0, 256);
+    let mut resampler = Resampler::new(&source);
+    let output: Vec<f64> = (0..128).map(|_| resampler.process(2.0)).collect();
+    for (i, sample) in output.iter().enumerate() {
+      assert_approx_eq!(sample, (2.0 * f64::PI * i as f64 / 32.0).sin());
+    }
+    assert!(resampler.is_finished());
+  }
+
+  #[test]
+  fn fractional_ratio() {
+    let source = sine(64.0, 256);
+    let mut resampler = Resampler::new(&source);
+    resampler.set_looping(true);
+    for i in 0..512 {
+      let expected = (2.0 * f64::PI * (i as f64 * 0.75 % 256.0) / 64.0).sin();
+      assert_approx_eq!(resampler.process(0.75), expected, 1e-3);
+    }
+  }
+}
@@ -0,0 +1,3 @@
+pub mod step_sequencer;
+
+pub use step_sequencer::{Step, StepSequencer};
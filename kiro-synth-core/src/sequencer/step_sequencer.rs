@@ -0,0 +1,184 @@
+use crate::float::Float;
+
+pub const MAX_STEPS: usize = 16;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Step<F: Float> {
+  pub value: F,
+  /// When the gate is off the step outputs zero
+  pub gate: bool,
+  /// When enabled the value slides towards the next step value during the step
+  pub slide: bool,
+}
+
+impl<F: Float> Step<F> {
+  pub fn new(value: F) -> Self {
+    Step {
+      value,
+      gate: true,
+      slide: false,
+    }
+  }
+
+  pub fn with_gate(self, gate: bool) -> Self {
+    Step { gate, ..self }
+  }
+
+  pub fn with_slide(self, slide: bool) -> Self {
+    Step { slide, ..self }
+  }
+}
+
+/// Sequence of up to [`MAX_STEPS`] values advanced by the position of a transport measured in beats.
+///
+/// As the current step is derived from the transport position, the sequencer keeps in sync
+/// even if the number of steps or the division are changed while running.
+#[derive(Debug, Clone)]
+pub struct StepSequencer<F: Float> {
+  steps: [Step<F>; MAX_STEPS],
+  num_steps: usize,
+  division: F,
+  current_step: usize,
+  gate: bool,
+}
+
+impl<F: Float> Default for StepSequencer<F> {
+  fn default() -> Self {
+    StepSequencer {
+      steps: [Step::new(F::zero()); MAX_STEPS],
+      num_steps: MAX_STEPS,
+      division: F::val(4),
+      current_step: 0,
+      gate: true,
+    }
+  }
+}
+
+impl<F: Float> StepSequencer<F> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Set the values for the steps, with the gate on and without slide.
+  /// The number of steps becomes the number of values, up to [`MAX_STEPS`].
+  pub fn set_steps(&mut self, values: &[F]) {
+    let num_steps = values.len().min(MAX_STEPS);
+    for (step, value) in self.steps.iter_mut().zip(values.iter()) {
+      *step = Step::new(*value);
+    }
+    self.set_num_steps(num_steps);
+  }
+
+  pub fn set_step(&mut self, index: usize, step: Step<F>) {
+    if index < MAX_STEPS {
+      self.steps[index] = step;
+    }
+  }
+
+  pub fn get_step(&self, index: usize) -> Option<&Step<F>> {
+    self.steps.get(index)
+  }
+
+  /// Set the number of steps played, from 1 to [`MAX_STEPS`]
+  pub fn set_num_steps(&mut self, num_steps: usize) {
+    self.num_steps = if num_steps == 0 {
+      1
+    } else {
+      num_steps.min(MAX_STEPS)
+    };
+  }
+
+  pub fn get_num_steps(&self) -> usize {
+    self.num_steps
+  }
+
+  /// Set the number of steps per beat (4 for sixteenth notes)
+  pub fn set_division(&mut self, division: F) {
+    if division > F::zero() {
+      self.division = division;
+    }
+  }
+
+  pub fn get_division(&self) -> F {
+    self.division
+  }
+
+  /// The index of the step for the last call to process
+  pub fn get_current_step(&self) -> usize {
+    self.current_step
+  }
+
+  /// Whether the gate of the current step is on
+  pub fn is_gate_on(&self) -> bool {
+    self.gate
+  }
+
+  /// Generate the value for a position of the transport in beats
+  pub fn process(&mut self, beats: F) -> F {
+    let position = (beats * self.division).max(F::zero());
+    let step_position = position.floor();
+    let index = step_position.to_usize().unwrap_or(0) % self.num_steps;
+    let step = self.steps[index];
+
+    self.current_step = index;
+    self.gate = step.gate;
+
+    if !step.gate {
+      F::zero()
+    } else if step.slide {
+      let next = self.steps[(index + 1) % self.num_steps].value;
+      let t = position - step_position;
+      step.value + (next - step.value) * t
+    } else {
+      step.value
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn steps_in_order() {
+    let mut seq = StepSequencer::<f64>::new();
+    seq.set_steps(&[1.0, 2.0, 3.0]);
+    seq.set_division(2.0);
+
+    let values: Vec<f64> = (0..8).map(|i| seq.process(i as f64 * 0.5)).collect();
+    assert_eq!(values, vec![1.0, 2.0, 3.0, 1.0, 2.0, 3.0, 1.0, 2.0]);
+    assert_eq!(seq.get_current_step(), 1);
+  }
+
+  #[test]
+  fn gate_and_slide() {
+    let mut seq = StepSequencer::<f64>::new();
+    seq.set_steps(&[1.0, 2.0]);
+    seq.set_division(1.0);
+    seq.set_step(0, Step::new(1.0).with_slide(true));
+    seq.set_step(1, Step::new(2.0).with_gate(false));
+
+    assert_approx_eq!(seq.process(0.0), 1.0);
+    assert_approx_eq!(seq.process(0.5), 1.5);
+    assert!(seq.is_gate_on());
+    assert_approx_eq!(seq.process(1.5), 0.0);
+    assert!(!seq.is_gate_on());
+  }
+
+  #[test]
+  fn change_num_steps_while_running() {
+    let mut seq = StepSequencer::<f64>::new();
+    seq.set_steps(&[1.0, 2.0, 3.0, 4.0]);
+    seq.set_division(1.0);
+    assert_approx_eq!(seq.process(3.0), 4.0);
+
+    seq.set_num_steps(2);
+    assert_approx_eq!(seq.process(3.0), 2.0);
+    assert_approx_eq!(seq.process(4.0), 1.0);
+
+    seq.set_num_steps(0);
+    assert_eq!(seq.get_num_steps(), 1);
+    assert_approx_eq!(seq.process(5.0), 1.0);
+  }
+}
@@ -0,0 +1,147 @@
+use crate::float::Float;
+use crate::waveforms::Waveform;
+
+/// The maximum number of harmonics of an additive waveform
+pub const MAX_HARMONICS: usize = 32;
+
+/// Sum of sines at the harmonics of the fundamental, every one with its own amplitude and phase.
+///
+/// The harmonics above the Nyquist frequency are skipped, so it never aliases.
+#[derive(Debug, Clone)]
+pub struct Additive<F: Float> {
+  amplitudes: [F; MAX_HARMONICS],
+  phase_cos: [F; MAX_HARMONICS],
+  phase_sin: [F; MAX_HARMONICS],
+  num_harmonics: usize,
+}
+
+impl<F: Float> Default for Additive<F> {
+  fn default() -> Self {
+    let mut amplitudes = [F::zero(); MAX_HARMONICS];
+    amplitudes[0] = F::one();
+    Additive {
+      amplitudes,
+      phase_cos: [F::one(); MAX_HARMONICS],
+      phase_sin: [F::zero(); MAX_HARMONICS],
+      num_harmonics: 1,
+    }
+  }
+}
+
+impl<F: Float> Additive<F> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Set the amplitudes of the harmonics, starting by the fundamental. The rest are silent.
+  pub fn set_harmonic_amplitudes(&mut self, amplitudes: &[F]) {
+    self.num_harmonics = amplitudes.len().min(MAX_HARMONICS);
+    for (index, amplitude) in self.amplitudes.iter_mut().enumerate() {
+      *amplitude = amplitudes.get(index).cloned().unwrap_or_else(F::zero);
+    }
+  }
+
+  pub fn with_harmonic_amplitudes(mut self, amplitudes: &[F]) -> Self {
+    self.set_harmonic_amplitudes(amplitudes);
+    self
+  }
+
+  /// Set the phases of the harmonics in cycles (from 0.0 to 1.0), starting by the fundamental.
+  /// The rest start at zero.
+  pub fn set_harmonic_phases(&mut self, phases: &[F]) {
+    for index in 0..MAX_HARMONICS {
+      let phase = phases.get(index).cloned().unwrap_or_else(F::zero);
+      let angle = phase * F::val(2.0) * F::PI;
+      self.phase_cos[index] = angle.cos();
+      self.phase_sin[index] = angle.sin();
+    }
+  }
+
+  pub fn with_harmonic_phases(mut self, phases: &[F]) -> Self {
+    self.set_harmonic_phases(phases);
+    self
+  }
+}
+
+impl<F: Float> Waveform<F> for Additive<F> {
+  fn generate(&mut self, modulo: F, phase_inc: F) -> F {
+    let angle = modulo * F::val(2.0) * F::PI;
+    let (sin1, cos1) = (angle.sin(), angle.cos());
+    let two_cos1 = F::val(2.0) * cos1;
+
+    // sin(n·x) and cos(n·x) from the previous harmonics, with a single sin and cos per sample
+    let (mut sin_prev, mut cos_prev) = (F::zero(), F::one());
+    let (mut sin_n, mut cos_n) = (sin1, cos1);
+    let nyquist = F::val(0.5);
+    let mut output = F::zero();
+    for index in 0..self.num_harmonics {
+      if F::val(index + 1) * phase_inc.abs() >= nyquist {
+        break;
+      }
+      let amplitude = self.amplitudes[index];
+      if amplitude != F::zero() {
+        let sample = sin_n * self.phase_cos[index] + cos_n * self.phase_sin[index];
+        output = output + amplitude * sample;
+      }
+      let sin_next = two_cos1 * sin_n - sin_prev;
+      let cos_next = two_cos1 * cos_n - cos_prev;
+      sin_prev = sin_n;
+      cos_prev = cos_n;
+      sin_n = sin_next;
+      cos_n = cos_next;
+    }
+    output
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn render(additive: &mut Additive<f64>, phase_inc: f64) -> Vec<f64> {
+    (0..100)
+      .map(|index| additive.generate(index as f64 * phase_inc % 1.0, phase_inc))
+      .collect()
+  }
+
+  #[test]
+  fn fundamental_is_a_sine() {
+    let mut additive = Additive::new().with_harmonic_amplitudes(&[1.0]);
+    let output = render(&mut additive, 0.01);
+    for (index, sample) in output.iter().enumerate() {
+      let expected = (2.0 * std::f64::consts::PI * index as f64 * 0.01).sin();
+      assert!((sample - expected).abs() < 1e-9);
+    }
+  }
+
+  #[test]
+  fn harmonic_series_approximates_a_saw() {
+    let amplitudes: Vec<f64> = (1..=MAX_HARMONICS).map(|n| 1.0 / n as f64).collect();
+    let mut additive = Additive::new().with_harmonic_amplitudes(&amplitudes);
+    // a descending ramp from π/2 to -π/2, checked away from the discontinuity at the cycle start
+    for index in 100..900 {
+      let modulo = index as f64 * 0.001;
+      let expected = (std::f64::consts::PI - 2.0 * std::f64::consts::PI * modulo) / 2.0;
+      assert!((additive.generate(modulo, 0.001) - expected).abs() < 0.1);
+    }
+  }
+
+  #[test]
+  fn harmonics_above_nyquist_are_skipped() {
+    let mut additive = Additive::new().with_harmonic_amplitudes(&[0.0, 0.0, 0.0, 1.0]);
+    // the 4th harmonic of a fundamental at a fifth of the sample rate is above the Nyquist
+    let above_nyquist = render(&mut additive, 0.2);
+    assert!(above_nyquist.iter().all(|sample| *sample == 0.0));
+    let below_nyquist = render(&mut additive, 0.1);
+    assert!(below_nyquist.iter().any(|sample| sample.abs() > 0.5));
+  }
+
+  #[test]
+  fn phases() {
+    let mut additive = Additive::<f64>::new()
+      .with_harmonic_amplitudes(&[1.0])
+      .with_harmonic_phases(&[0.25]);
+    // a quarter of a cycle turns the sine into a cosine
+    assert!((additive.generate(0.0, 0.01) - 1.0).abs() < 1e-9);
+  }
+}
@@ -1,12 +1,17 @@
 use crate::float::Float;
 
+pub mod additive;
 pub mod exponential;
 pub mod saw_blep;
+pub mod saw_dpw;
 pub mod saw_trivial;
+pub mod sample_hold;
+pub mod sine_lut;
 pub mod sine_parabolic;
 pub mod square_trivial;
 pub mod triangle_dpw2x;
 pub mod triangle_trivial;
+pub mod wavetable;
 
 pub trait Waveform<F: Float> {
   fn initial_modulo(&self) -> F {
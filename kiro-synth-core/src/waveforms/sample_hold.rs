@@ -0,0 +1,110 @@
+use crate::float::Float;
+use crate::funcs::random::Random;
+use crate::waveforms::Waveform;
+
+/// Sample and hold of random values, a new one for every cycle.
+///
+/// With some slew the transitions between the held values are linear ramps instead of jumps.
+#[derive(Debug, Clone, Default)]
+pub struct SampleHold<F: Float> {
+  random: Random,
+  started: bool,
+  last_modulo: F,
+  /// The length of the transitions in samples, zero for jumps
+  slew_samples: F,
+  from: F,
+  target: F,
+  ramp_samples: F,
+  value: F,
+}
+
+impl<F: Float> SampleHold<F> {
+  pub fn new(seed: u64) -> Self {
+    SampleHold {
+      random: Random::new(seed),
+      ..Self::default()
+    }
+  }
+
+  /// Restart the sequence of random values
+  pub fn set_seed(&mut self, seed: u64) {
+    self.random.set_seed(seed);
+  }
+
+  /// Set the length of the transitions between the held values
+  pub fn set_slew_samples(&mut self, samples: F) {
+    self.slew_samples = samples.max(F::zero());
+  }
+
+  pub fn get_slew_samples(&self) -> F {
+    self.slew_samples
+  }
+}
+
+impl<F: Float> Waveform<F> for SampleHold<F> {
+  fn reset(&mut self) {
+    self.started = false;
+  }
+
+  fn generate(&mut self, modulo: F, _phase_inc: F) -> F {
+    if !self.started || modulo < self.last_modulo {
+      self.from = if self.started { self.value } else { F::zero() };
+      self.target = self.random.bipolar();
+      self.ramp_samples = F::zero();
+      self.started = true;
+    }
+    self.last_modulo = modulo;
+
+    self.ramp_samples = self.ramp_samples + F::one();
+    self.value = if self.ramp_samples >= self.slew_samples {
+      self.target
+    } else {
+      let ramp = self.ramp_samples / self.slew_samples;
+      self.from + (self.target - self.from) * ramp
+    };
+    self.value
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  const CYCLE_SAMPLES: usize = 100;
+
+  fn render(slew_samples: f64) -> Vec<f64> {
+    let mut waveform = SampleHold::new(1);
+    waveform.set_slew_samples(slew_samples);
+    let phase_inc = 1.0 / CYCLE_SAMPLES as f64;
+    (0..3 * CYCLE_SAMPLES)
+      .map(|index| waveform.generate((index as f64 * phase_inc) % 1.0, phase_inc))
+      .collect()
+  }
+
+  #[test]
+  fn holds_random_values() {
+    let output = render(0.0);
+    let first = output[CYCLE_SAMPLES - 1];
+    let second = output[2 * CYCLE_SAMPLES - 1];
+    assert!(output[..CYCLE_SAMPLES].iter().all(|value| *value == first));
+    let held = &output[CYCLE_SAMPLES..2 * CYCLE_SAMPLES];
+    assert!(held.iter().all(|value| *value == second));
+    assert_ne!(first, second);
+  }
+
+  #[test]
+  fn slew_ramps_between_the_held_values() {
+    let held = render(0.0);
+    let (first, second) = (held[CYCLE_SAMPLES - 1], held[2 * CYCLE_SAMPLES - 1]);
+
+    let output = render(10.0);
+    assert_eq!(output[CYCLE_SAMPLES - 1], first);
+    for step in 1..=10 {
+      let expected = first + (second - first) * step as f64 / 10.0;
+      assert_approx_eq!(output[CYCLE_SAMPLES - 1 + step], expected);
+    }
+    let rest = &output[CYCLE_SAMPLES + 10..2 * CYCLE_SAMPLES];
+    assert!(rest.iter().all(|value| *value == second));
+  }
+}
@@ -0,0 +1,115 @@
+use crate::float::Float;
+use crate::funcs::signal_polarity::unipolar_to_bipolar;
+use crate::waveforms::Waveform;
+
+/// DPW (Differentiated Parabolic Waveform) Saw Oscillator
+/// https://sci-hub.se/https://ieeexplore.ieee.org/document/1395943
+///
+/// The parabola of the trivial saw has no discontinuities, so differentiating it gives a saw
+/// with much less aliasing, cheaper than the BLEP corrections.
+#[derive(Debug, Clone)]
+pub struct SawDpw<F: Float> {
+  z1: F,
+  primed: bool,
+}
+
+impl<F: Float> Default for SawDpw<F> {
+  fn default() -> Self {
+    SawDpw {
+      z1: F::zero(),
+      primed: false,
+    }
+  }
+}
+
+impl<F: Float> SawDpw<F> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+impl<F: Float> Waveform<F> for SawDpw<F> {
+  fn initial_modulo(&self) -> F {
+    F::val(0.5)
+  }
+
+  fn reset(&mut self) {
+    self.z1 = F::zero();
+    self.primed = false;
+  }
+
+  fn generate(&mut self, modulo: F, phase_inc: F) -> F {
+    let saw = unipolar_to_bipolar(modulo);
+    let step = F::val(2.0) * phase_inc;
+
+    // start from the parabola of the previous sample to avoid a spike after a reset
+    if !self.primed {
+      self.primed = true;
+      let previous = saw - step;
+      self.z1 = previous * previous;
+    }
+
+    let parabola = saw * saw;
+    let derivative = parabola - self.z1;
+    self.z1 = parabola;
+
+    if step.abs() <= F::epsilon() {
+      saw
+    } else {
+      derivative / (F::val(2.0) * step)
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::waveforms::saw_trivial::SawTrivial;
+
+  const NUM_SAMPLES: usize = 8192;
+
+  fn render<W: Waveform<f64>>(waveform: &mut W, phase_inc: f64) -> Vec<f64> {
+    (0..NUM_SAMPLES)
+      .map(|index| waveform.generate((0.5 + index as f64 * phase_inc) % 1.0, phase_inc))
+      .collect()
+  }
+
+  /// The amplitude of the component of the signal at a frequency
+  fn amplitude(signal: &[f64], phase_inc: f64) -> f64 {
+    let (mut re, mut im) = (0.0, 0.0);
+    for (index, sample) in signal.iter().enumerate() {
+      let phase = 2.0 * std::f64::consts::PI * phase_inc * index as f64;
+      re += sample * phase.cos();
+      im += sample * phase.sin();
+    }
+    2.0 * (re * re + im * im).sqrt() / signal.len() as f64
+  }
+
+  /// The energy that is not in the harmonics below the Nyquist frequency
+  fn aliasing(signal: &[f64], phase_inc: f64) -> f64 {
+    let total: f64 = signal.iter().map(|sample| sample * sample).sum::<f64>() / signal.len() as f64;
+    let harmonics: f64 = (1..)
+      .map(|harmonic| harmonic as f64 * phase_inc)
+      .take_while(|freq| *freq < 0.5)
+      .map(|freq| amplitude(signal, freq).powi(2) / 2.0)
+      .sum();
+    total - harmonics
+  }
+
+  #[test]
+  fn less_aliasing_than_the_trivial_saw() {
+    let phase_inc = 0.0917;
+    let dpw = aliasing(&render(&mut SawDpw::new(), phase_inc), phase_inc);
+    let trivial = aliasing(&render(&mut SawTrivial::default(), phase_inc), phase_inc);
+    assert!(dpw < trivial * 0.5, "dpw {} trivial {}", dpw, trivial);
+  }
+
+  #[test]
+  fn fundamental_amplitude() {
+    let phase_inc = 0.01;
+    let output = render(&mut SawDpw::new(), phase_inc);
+    let expected = 2.0 / std::f64::consts::PI;
+    assert!((amplitude(&output, phase_inc) - expected).abs() < 0.01);
+    assert!(output.iter().all(|sample| sample.abs() <= 1.01));
+  }
+}
@@ -0,0 +1,25 @@
+use crate::float::Float;
+use crate::funcs::sine_table::sin_lut;
+use crate::waveforms::Waveform;
+
+/// Sine from the shared lookup table, more accurate than the parabolic approximation and cheaper than `sin`
+#[derive(Debug, Clone)]
+pub struct SineLut;
+
+impl Default for SineLut {
+  fn default() -> Self {
+    SineLut
+  }
+}
+
+impl SineLut {
+  pub fn new() -> Self {
+    SineLut
+  }
+}
+
+impl<F: Float> Waveform<F> for SineLut {
+  fn generate(&mut self, modulo: F, _phase_inc: F) -> F {
+    sin_lut(modulo)
+  }
+}
@@ -0,0 +1,141 @@
+use crate::float::Float;
+use crate::funcs::interpolation::cubic_interpolation;
+use crate::waveforms::Waveform;
+
+/// The number of samples for the tables of single cycle waveforms
+pub const TABLE_SIZE: usize = 2048;
+
+/// How the samples of a table are read between its points, trading CPU for quality
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+  /// The closest sample, the cheapest and with the most aliasing
+  Nearest,
+  Linear,
+  /// Cubic Hermite interpolation using four samples
+  Cubic,
+}
+
+impl Interpolation {
+  pub fn count() -> usize {
+    3
+  }
+
+  pub fn from<F: Float>(value: F) -> Self {
+    match value.round().to_usize().unwrap_or(1) {
+      0 => Interpolation::Nearest,
+      2 => Interpolation::Cubic,
+      _ => Interpolation::Linear,
+    }
+  }
+}
+
+/// A single cycle waveform read from a table, interpolating between its samples
+#[derive(Debug, Clone)]
+pub struct Wavetable<F: Float> {
+  table: &'static [F],
+  interpolation: Interpolation,
+}
+
+impl<F: Float> Wavetable<F> {
+  /// A wavetable with linear interpolation
+  pub fn new(table: &'static [F]) -> Self {
+    Wavetable {
+      table,
+      interpolation: Interpolation::Linear,
+    }
+  }
+
+  pub fn with_interpolation(self, interpolation: Interpolation) -> Self {
+    Wavetable {
+      interpolation,
+      ..self
+    }
+  }
+
+  pub fn table(&self) -> &'static [F] {
+    self.table
+  }
+
+  pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+    self.interpolation = interpolation;
+  }
+
+  pub fn get_interpolation(&self) -> Interpolation {
+    self.interpolation
+  }
+}
+
+impl<F: Float> Waveform<F> for Wavetable<F> {
+  fn generate(&mut self, modulo: F, _phase_inc: F) -> F {
+    let len = self.table.len();
+    if len == 0 {
+      return F::zero();
+    }
+
+    let position = modulo * F::val(len);
+    let index = position.floor();
+    let t = position - index;
+    let index = index.to_usize().unwrap_or(0) % len;
+    let y1 = self.table[index];
+    let y2 = self.table[(index + 1) % len];
+    match self.interpolation {
+      Interpolation::Nearest if t < F::val(0.5) => y1,
+      Interpolation::Nearest => y2,
+      Interpolation::Linear => y1 + (y2 - y1) * t,
+      Interpolation::Cubic => {
+        let y0 = self.table[(index + len - 1) % len];
+        let y3 = self.table[(index + 2) % len];
+        cubic_interpolation(y0, y1, y2, y3, t)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  static TABLE: [f64; 4] = [0.0, 1.0, 0.0, -1.0];
+
+  #[test]
+  fn generate() {
+    let mut wavetable = Wavetable::new(&TABLE);
+    assert_approx_eq!(wavetable.generate(0.0, 0.0), 0.0);
+    assert_approx_eq!(wavetable.generate(0.25, 0.0), 1.0);
+    assert_approx_eq!(wavetable.generate(0.375, 0.0), 0.5);
+    assert_approx_eq!(wavetable.generate(0.875, 0.0), -0.5);
+  }
+
+  /// The largest error reading a sine from a coarse table
+  fn sine_error(interpolation: Interpolation) -> f64 {
+    let table: Vec<f64> = (0..32)
+      .map(|index| (2.0 * std::f64::consts::PI * index as f64 / 32.0).sin())
+      .collect();
+    let table: &'static [f64] = Box::leak(table.into_boxed_slice());
+    let mut wavetable = Wavetable::new(table).with_interpolation(interpolation);
+    (0..1000)
+      .map(|index| {
+        let modulo = index as f64 / 1000.0;
+        let expected = (2.0 * std::f64::consts::PI * modulo).sin();
+        (wavetable.generate(modulo, 0.0) - expected).abs()
+      })
+      .fold(0.0, f64::max)
+  }
+
+  #[test]
+  fn interpolation_quality() {
+    let nearest = sine_error(Interpolation::Nearest);
+    let linear = sine_error(Interpolation::Linear);
+    let cubic = sine_error(Interpolation::Cubic);
+    assert!(cubic < linear && linear < nearest);
+    assert!(cubic < 1e-3);
+  }
+
+  #[test]
+  fn interpolation_from() {
+    assert_eq!(Interpolation::from(0.0), Interpolation::Nearest);
+    assert_eq!(Interpolation::from(1.0), Interpolation::Linear);
+    assert_eq!(Interpolation::from(2.0), Interpolation::Cubic);
+  }
+}
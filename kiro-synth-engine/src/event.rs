@@ -1,5 +1,5 @@
 use crate::float::Float;
-use crate::program::{ParamRef, SourceRef};
+use crate::program::{InsertOrder, ParamRef, SourceRef};
 
 #[derive(Debug, Clone)]
 pub enum Message<F: Float> {
@@ -11,6 +11,23 @@ pub enum Message<F: Float> {
     key: u8,
     velocity: F,
   },
+  /// Release all the playing voices
+  AllNotesOff,
+  /// Silence all the voices after a short fade out, without waiting for their release
+  AllSoundOff,
+  /// Set the tempo of the transport in beats per minute
+  Tempo {
+    bpm: F,
+  },
+  /// Aftertouch for all the voices
+  ChannelPressure {
+    pressure: F,
+  },
+  /// Aftertouch for the voices playing a key
+  PolyPressure {
+    key: u8,
+    pressure: F,
+  },
   ParamValue {
     param_ref: ParamRef,
     value: F,
@@ -19,6 +36,10 @@ pub enum Message<F: Float> {
     param_ref: ParamRef,
     change: F,
   },
+  /// Drop the values of a param still waiting for their timestamp, ie. the rest of a ramp interrupted by a new one
+  CancelParamValues {
+    param_ref: ParamRef,
+  },
   ModulationUpdate {
     source_ref: SourceRef,
     param_ref: ParamRef,
@@ -28,10 +49,21 @@ pub enum Message<F: Float> {
     source_ref: SourceRef,
     param_ref: ParamRef,
   },
+  /// Change the phase offset in cycles of an existing modulation from an LFO
+  ModulationPhase {
+    source_ref: SourceRef,
+    param_ref: ParamRef,
+    phase: F,
+  },
+  /// Change the order of the insert effects, applied between blocks so the effects keep their state
+  InsertOrder {
+    order: InsertOrder,
+  },
 }
 
 #[derive(Debug, Clone)]
 pub struct Event<F: Float> {
+  /// Delay in microseconds from when the synth receives the event to when it is applied, zero for no delay
   pub timestamp: u64,
   pub message: Message<F>,
 }
@@ -1,11 +1,26 @@
 use kiro_synth_core::float::Float;
 
+use crate::transport::Transport;
 use crate::waveforms::{LfoWaveforms, OscWaveforms};
 
 #[derive(Debug, Clone, Default)]
 pub struct SynthGlobals<F: Float> {
   pub osc_waveforms: OscWaveforms<F>,
   pub lfo_waveforms: LfoWaveforms<F>,
+  pub transport: Transport<F>,
+  /// Amount of the slow random drift of the oscillators, from 0.0 (steady) to 1.0
+  pub warmth: F,
+  /// Number of samples to fade in the voices when they start, to avoid clicks
+  pub fade_in_samples: usize,
+  /// Number of samples to fade out the voices when they are freed, to avoid clicks
+  pub fade_out_samples: usize,
+  /// Number of samples after which the voices are released without waiting for the note off,
+  /// for one-shot sounds such as drum hits. Zero waits for the note off.
+  pub fixed_length_samples: usize,
+  /// Whether the oscillators use their cheaper waveforms because there are too many voices playing
+  pub reduced_quality: bool,
+  /// Whether the oscillators modulated by another oscillator run oversampled, to reduce the aliasing
+  pub fm_oversampling: bool,
 }
 
 impl<F: Float> SynthGlobals<F> {
@@ -13,6 +28,13 @@ impl<F: Float> SynthGlobals<F> {
     SynthGlobals {
       osc_waveforms: OscWaveforms::new(),
       lfo_waveforms: LfoWaveforms::new(),
+      transport: Transport::default(),
+      warmth: F::zero(),
+      fade_in_samples: 0,
+      fade_out_samples: 0,
+      fixed_length_samples: 0,
+      reduced_quality: false,
+      fm_oversampling: false,
     }
   }
 }
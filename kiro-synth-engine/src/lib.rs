@@ -13,6 +13,7 @@ pub mod event;
 pub mod globals;
 pub mod program;
 pub mod synth;
+pub mod transport;
 pub mod waveforms;
 
 pub use kiro_synth_core::float;
@@ -14,6 +14,7 @@ pub(crate) enum Processor<F: Float> {
   Filter(filter::Processor<F>),
   Lfo(lfo::Processor<F>),
   Osc(osc::Processor<F>),
+  Sequencer(sequencer::Processor<F>),
   Out(SignalRef, SignalRef),
 }
 
@@ -30,6 +31,7 @@ impl<F: Float> Processor<F> {
       Block::EG(eg_block) => Processor::EG(envgen::Processor::new(sample_rate, eg_block)),
       Block::Lfo(lfo_block) => Processor::Lfo(lfo::Processor::new(sample_rate, lfo_block)),
       Block::Osc(osc_block) => Processor::Osc(osc::Processor::new(sample_rate, osc_block)),
+      Block::Sequencer(seq_block) => Processor::Sequencer(sequencer::Processor::new(seq_block)),
       Block::Expr(expr_block) => Processor::Expr(expr::Processor::new(expr_block)),
       Block::Filter(filt_block) => {
         Processor::Filter(filter::Processor::new(sample_rate, filt_block))
@@ -48,10 +50,20 @@ impl<F: Float> Processor<F> {
       Processor::Filter(ref mut proc) => proc.reset(),
       Processor::Lfo(ref mut proc) => proc.reset(),
       Processor::Osc(ref mut proc) => proc.reset(),
+      Processor::Sequencer(ref mut proc) => proc.reset(),
       Processor::Out(ref _left, ref _right) => {}
     }
   }
 
+  /// Reseed the blocks with random behaviours, the rest ignore it
+  pub fn set_random_seed(&mut self, seed: u64) {
+    match self {
+      Processor::Lfo(ref mut proc) => proc.set_random_seed(seed),
+      Processor::Osc(ref mut proc) => proc.set_random_seed(seed),
+      _ => {}
+    }
+  }
+
   pub fn process<'b>(
     &mut self,
     signals: &mut SignalBus<'b, F>,
@@ -62,10 +74,15 @@ impl<F: Float> Processor<F> {
       Processor::Const(value, signal) => signals[*signal].set(*value),
       Processor::Param(param_ref) => {
         if let Some((_, param)) = program.get_param(*param_ref) {
-          let mut value = F::zero();
+          let mut value = program.get_macro_offset(*param_ref);
           for modulation in program.get_param_modulations(*param_ref) {
             if let Some(source) = program.get_source(modulation.source_ref) {
-              let source_signal = signals[source.signal].get();
+              let source_signal = match source.lfo {
+                Some(ref lfo) if modulation.phase != F::zero() => {
+                  lfo::shifted_output(lfo, signals, synth_globals, modulation.phase)
+                }
+                _ => signals[source.signal].get(),
+              };
               value = value + source_signal * modulation.amount;
             }
           }
@@ -81,6 +98,7 @@ impl<F: Float> Processor<F> {
       Processor::Filter(ref mut proc) => proc.process(signals, program),
       Processor::Lfo(ref mut proc) => proc.process(signals, program, synth_globals),
       Processor::Osc(ref mut proc) => proc.process(signals, program, synth_globals),
+      Processor::Sequencer(ref mut proc) => proc.process(signals, program, synth_globals),
       Processor::Out(ref left, ref right) => {
         let voice = program.voice();
         let left_value = signals[*left].consume();
@@ -9,9 +9,13 @@ pub struct Inputs {
   pub left: SignalRef,
   pub right: SignalRef,
   pub velocity: SignalRef,
+  /// Gain for a velocity of 0.0, from 0.0 (silent) to 1.0
+  pub velocity_floor: SignalRef,
   pub amplitude: SignalRef,
   pub amp_mod: SignalRef,
   pub eg_mod: SignalRef,
+  /// Amount of soft saturation, from 0.0 (transparent) to 1.0
+  pub saturation: SignalRef,
   pub pan: SignalRef,
   pub pan_mod: SignalRef,
 }
@@ -35,14 +39,16 @@ pub(crate) struct Processor<F: Float> {
 }
 
 impl<F: Float> Processor<F> {
-  pub fn new(_sample_rate: F, block: Block) -> Self {
+  pub fn new(sample_rate: F, block: Block) -> Self {
     Processor {
-      dca: DCA::new(),
+      dca: DCA::new(sample_rate),
       block,
     }
   }
 
-  pub fn reset(&mut self) {}
+  pub fn reset(&mut self) {
+    self.dca.reset();
+  }
 
   pub fn process<'a>(&mut self, signals: &mut SignalBus<'a, F>, _program: &Program<F>) {
     let Block { inputs, outputs } = self.block.clone();
@@ -50,9 +56,11 @@ impl<F: Float> Processor<F> {
       left,
       right,
       velocity,
+      velocity_floor,
       amplitude,
       amp_mod,
       eg_mod,
+      saturation,
       pan,
       pan_mod,
     } = inputs;
@@ -62,9 +70,11 @@ impl<F: Float> Processor<F> {
     } = outputs;
 
     signals[velocity].if_updated(|value| self.dca.set_velocity(value));
+    signals[velocity_floor].if_updated(|value| self.dca.set_velocity_floor(value));
     signals[amplitude].if_updated(|value| self.dca.set_amplitude_db(value));
     signals[amp_mod].if_updated(|value| self.dca.set_amp_mod_db(value));
     signals[eg_mod].if_updated(|value| self.dca.set_eg_mod(value));
+    signals[saturation].if_updated(|value| self.dca.set_saturation(value));
     signals[pan].if_updated(|value| self.dca.set_pan(value));
     signals[pan_mod].if_updated(|value| self.dca.set_pan_mod(value));
 
@@ -13,13 +13,16 @@ pub struct Inputs {
   pub mode: SignalRef,
   pub legato: SignalRef,
   pub reset_to_zero: SignalRef,
+  /// How much the velocity shortens (positive) or lengthens (negative) the attack, from -1.0 to 1.0
+  pub velocity_to_attack: SignalRef,
+  /// How much the velocity shortens (positive) or lengthens (negative) the decay, from -1.0 to 1.0
+  pub velocity_to_decay: SignalRef,
 }
 
 #[derive(Debug, Clone)]
 pub struct Outputs {
   pub normal: SignalRef,
   pub biased: SignalRef,
-  pub voice_off: SignalRef,
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +34,11 @@ pub struct Block {
 #[derive(Debug)]
 pub(crate) struct Processor<F: Float> {
   envgen: EnvGen<F>,
+  attack: F,
+  decay: F,
+  velocity: F,
+  velocity_to_attack: F,
+  velocity_to_decay: F,
   block: Block,
 }
 
@@ -38,14 +46,40 @@ impl<F: Float> Processor<F> {
   pub fn new(sample_rate: F, block: Block) -> Self {
     Processor {
       envgen: EnvGen::new(sample_rate),
+      attack: F::zero(),
+      decay: F::zero(),
+      velocity: F::zero(),
+      velocity_to_attack: F::zero(),
+      velocity_to_decay: F::zero(),
       block,
     }
   }
 
+  /// The factor applied to a time given the velocity and the amount of velocity sensitivity.
+  /// It never gets to zero so positive times are kept positive.
+  fn velocity_scale(velocity: F, amount: F) -> F {
+    (F::one() - amount * velocity).max(F::val(0.05))
+  }
+
+  fn update_attack(&mut self) {
+    let scale = Self::velocity_scale(self.velocity, self.velocity_to_attack);
+    self.envgen.set_attack_time_sec(self.attack * scale);
+  }
+
+  fn update_decay(&mut self) {
+    let scale = Self::velocity_scale(self.velocity, self.velocity_to_decay);
+    self.envgen.set_decay_time_sec(self.decay * scale);
+  }
+
   pub fn reset(&mut self) {
     self.envgen.reset()
   }
 
+  /// Whether the envelope has finished (ie. after its release)
+  pub fn is_off(&self) -> bool {
+    self.envgen.is_off()
+  }
+
   pub fn process<'a>(&mut self, signals: &mut SignalBus<'a, F>, program: &Program<F>) {
     let Block { inputs, outputs } = self.block.clone();
     let Inputs {
@@ -56,15 +90,43 @@ impl<F: Float> Processor<F> {
       mode,
       legato: _,
       reset_to_zero: _,
+      velocity_to_attack,
+      velocity_to_decay,
     } = inputs;
-    let Outputs {
-      normal,
-      biased,
-      voice_off,
-    } = outputs;
+    let Outputs { normal, biased } = outputs;
 
     let voice = program.voice();
 
+    let mut attack_updated = false;
+    let mut decay_updated = false;
+    signals[voice.velocity].if_updated(|value| {
+      self.velocity = value;
+      attack_updated = true;
+      decay_updated = true;
+    });
+    signals[attack].if_updated(|value| {
+      self.attack = value;
+      attack_updated = true;
+    });
+    signals[velocity_to_attack].if_updated(|value| {
+      self.velocity_to_attack = value;
+      attack_updated = true;
+    });
+    signals[decay].if_updated(|value| {
+      self.decay = value;
+      decay_updated = true;
+    });
+    signals[velocity_to_decay].if_updated(|value| {
+      self.velocity_to_decay = value;
+      decay_updated = true;
+    });
+    if attack_updated {
+      self.update_attack();
+    }
+    if decay_updated {
+      self.update_decay();
+    }
+
     signals[voice.trigger].if_updated(|value| {
       if value > F::zero() {
         self.envgen.start();
@@ -77,8 +139,6 @@ impl<F: Float> Processor<F> {
       }
     });
 
-    signals[attack].if_updated(|value| self.envgen.set_attack_time_sec(value));
-    signals[decay].if_updated(|value| self.envgen.set_decay_time_sec(value));
     signals[sustain].if_updated(|value| self.envgen.set_sustain_level(value));
     signals[release].if_updated(|value| self.envgen.set_release_time_sec(value));
 
@@ -93,9 +153,5 @@ impl<F: Float> Processor<F> {
 
     signals[normal].set(self.envgen.generate());
     signals[biased].set(self.envgen.biased_output());
-
-    if self.envgen.is_off() {
-      signals[voice_off].set(F::one());
-    }
   }
 }
@@ -38,6 +38,25 @@ pub struct Block<F: Float> {
   pub output: SignalRef,
 }
 
+impl<F: Float> Block<F> {
+  /// Whether the expression reads a signal in any of its operations
+  pub fn reads_signal(&self, signal: SignalRef) -> bool {
+    self.ops.iter().any(|op| match op {
+      Op::Signal(signal_ref)
+      | Op::AddSignal(_, signal_ref)
+      | Op::AddSignalValue(signal_ref, _)
+      | Op::AddSignalParam(signal_ref, _)
+      | Op::MulSignal(_, signal_ref)
+      | Op::MulSignalValue(signal_ref, _)
+      | Op::MulSignalParam(signal_ref, _) => *signal_ref == signal,
+      Op::AddSignals(signal_ref1, signal_ref2) | Op::MulSignals(signal_ref1, signal_ref2) => {
+        *signal_ref1 == signal || *signal_ref2 == signal
+      }
+      _ => false,
+    })
+  }
+}
+
 #[derive(Default)]
 pub struct ExprBuilder<F: Float> {
   ops: Vec<Op<F>, MaxOps>,
@@ -2,6 +2,7 @@ use kiro_synth_core::filters::freq_control::FreqControl;
 use kiro_synth_core::filters::oberheim_sem::{self, OberheimSEM};
 use kiro_synth_core::filters::va_one_pole::{self, VAOnePoleFilter};
 use kiro_synth_core::float::Float;
+use kiro_synth_core::funcs::decibels::Decibels;
 
 use crate::program::{Program, SignalRef};
 use crate::signal::SignalBus;
@@ -41,7 +42,22 @@ pub struct Params {
   pub mode: SignalRef,
   pub freq: SignalRef,
   pub freq_mod: SignalRef,
+  /// How much the cutoff follows the note pitch, 1.0 moves it the same semitones as the note.
+  /// Negative values move it in the opposite direction.
+  pub key_track: SignalRef,
+  /// The key where the key tracking doesn't change the cutoff, which goes up above it and down below it
+  pub key_track_center: SignalRef,
   pub q: SignalRef,
+  /// How much the velocity raises (positive) or lowers (negative) the resonance, from -1.0 to 1.0.
+  /// The resulting resonance is kept in the range of the Q, so it never self oscillates.
+  pub velocity_to_q: SignalRef,
+  /// Compensate the level of the output so it doesn't get louder as the resonance rises
+  pub q_compensation: SignalRef,
+  /// Gain in dB applied to the input before filtering
+  pub gain: SignalRef,
+  /// Use the filter as an oscillator: the input is muted and the resonant filters
+  /// oscillate as a sine at the note pitch
+  pub self_osc: SignalRef,
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +70,16 @@ pub struct Block {
 #[derive(Debug)]
 pub(crate) struct Processor<F: Float> {
   mode: Mode,
+  freq_mod: F,
+  key_track: F,
+  key_track_center: F,
+  note_pitch: F,
+  q: F,
+  velocity: F,
+  velocity_to_q: F,
+  q_compensation: bool,
+  self_osc: bool,
+  input_gain: F,
   va_one_pole: VAOnePoleFilter<F>,
   oberheim_sem: OberheimSEM<F>,
   block: Block,
@@ -63,6 +89,16 @@ impl<F: Float> Processor<F> {
   pub fn new(sample_rate: F, block: Block) -> Self {
     Processor {
       mode: Mode::PassThrough,
+      freq_mod: F::zero(),
+      key_track: F::zero(),
+      key_track_center: F::val(60),
+      note_pitch: F::zero(),
+      q: F::zero(),
+      velocity: F::zero(),
+      velocity_to_q: F::zero(),
+      q_compensation: false,
+      self_osc: false,
+      input_gain: F::one(),
       va_one_pole: VAOnePoleFilter::new(sample_rate, FreqControl::default_frequency()),
       oberheim_sem: OberheimSEM::new(
         sample_rate,
@@ -92,7 +128,35 @@ impl<F: Float> Processor<F> {
     }
   }
 
-  fn set_freq_mod(&mut self, freq_mod: F) {
+  fn set_self_osc(&mut self, self_osc: bool, note_pitch: F) {
+    if self_osc != self.self_osc {
+      self.self_osc = self_osc;
+      self.oberheim_sem.set_self_oscillation(self_osc);
+      if self_osc {
+        self.oberheim_sem.set_self_oscillation_frequency(note_pitch);
+        self.oberheim_sem.start_self_oscillation();
+      } else {
+        self.oberheim_sem.reset();
+      }
+    }
+  }
+
+  fn is_self_oscillating(&self) -> bool {
+    self.self_osc && matches!(self.mode, Mode::OberheimSEM(_))
+  }
+
+  /// The modulation of the cutoff in semitones, including the key tracking
+  fn freq_modulation(&self) -> F {
+    if self.key_track == F::zero() || self.note_pitch <= F::zero() {
+      self.freq_mod
+    } else {
+      let note = F::val(69) + F::val(12) * (self.note_pitch / F::val(440)).log2();
+      self.freq_mod + (note - self.key_track_center) * self.key_track
+    }
+  }
+
+  fn update_freq_mod(&mut self) {
+    let freq_mod = self.freq_modulation();
     match self.mode {
       Mode::PassThrough => {}
       Mode::VAOnePole(_) => self.va_one_pole.set_frequency_modulation(freq_mod),
@@ -101,6 +165,18 @@ impl<F: Float> Processor<F> {
   }
 
   fn set_q(&mut self, q: F) {
+    self.q = q;
+    self.update_q();
+  }
+
+  /// The Q from 0.0 to 1.0 after applying the velocity
+  fn resonance(&self) -> F {
+    let q = self.q + self.velocity_to_q * self.velocity;
+    q.max(F::zero()).min(F::one())
+  }
+
+  fn update_q(&mut self) {
+    let q = self.resonance();
     match self.mode {
       Mode::PassThrough => {}
       Mode::VAOnePole(_) => {}
@@ -116,26 +192,242 @@ impl<F: Float> Processor<F> {
     }
   }
 
-  pub fn process<'a>(&mut self, signals: &mut SignalBus<'a, F>, _program: &Program<F>) {
+  pub fn process<'a>(&mut self, signals: &mut SignalBus<'a, F>, program: &Program<F>) {
     let Params {
       mode,
       freq,
       freq_mod,
+      key_track,
+      key_track_center,
       q,
+      velocity_to_q,
+      q_compensation,
+      gain,
+      self_osc,
     } = self.block.params;
 
+    let voice = program.voice();
+
     signals[mode].if_updated(|value| self.set_mode(value));
     signals[freq].if_updated(|value| self.set_freq(value));
-    signals[freq_mod].if_updated(|value| self.set_freq_mod(value));
+    let mut freq_mod_invalidated = false;
+    signals[freq_mod].if_updated(|value| {
+      self.freq_mod = value;
+      freq_mod_invalidated = true;
+    });
+    signals[key_track].if_updated(|value| {
+      self.key_track = value;
+      freq_mod_invalidated = true;
+    });
+    signals[key_track_center].if_updated(|value| {
+      self.key_track_center = value;
+      freq_mod_invalidated = true;
+    });
+    signals[voice.note_pitch].if_updated(|value| {
+      self.note_pitch = value;
+      freq_mod_invalidated = true;
+    });
+    if freq_mod_invalidated {
+      self.update_freq_mod();
+    }
     signals[q].if_updated(|value| self.set_q(value));
+    signals[voice.velocity].if_updated(|value| {
+      self.velocity = value;
+      self.update_q();
+    });
+    signals[velocity_to_q].if_updated(|value| {
+      self.velocity_to_q = value;
+      self.update_q();
+    });
+    signals[q_compensation].if_updated(|value| self.q_compensation = value > F::zero());
+    signals[gain].if_updated(|value| self.input_gain = Decibels::new(value).to_amplitude());
+
+    let note_pitch = signals[voice.note_pitch].get();
+    signals[self_osc].if_updated(|value| self.set_self_osc(value > F::zero(), note_pitch));
+    if self.self_osc {
+      let oberheim_sem = &mut self.oberheim_sem;
+      signals[voice.note_pitch]
+        .if_updated(|value| oberheim_sem.set_self_oscillation_frequency(value));
+      signals[voice.trigger].if_updated(|value| {
+        if value > F::zero() {
+          self.oberheim_sem.start_self_oscillation();
+        }
+      });
+    }
 
-    let input = signals[self.block.input].get();
+    let input = if self.is_self_oscillating() {
+      F::zero()
+    } else {
+      signals[self.block.input].get() * self.input_gain
+    };
     let output = match self.mode {
       Mode::PassThrough => input,
       Mode::VAOnePole(_) => self.va_one_pole.process(input),
+      Mode::OberheimSEM(_) if self.q_compensation => {
+        let output = self.oberheim_sem.process(input);
+        output * self.oberheim_sem.get_resonance_compensation()
+      }
       Mode::OberheimSEM(_) => self.oberheim_sem.process(input),
     };
 
     signals[self.block.output].set(output);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use assert_approx_eq::assert_approx_eq;
+
+  use crate::key_freqs::KEY_FREQ;
+  use crate::program::ProgramBuilder;
+  use crate::signal::Signal;
+  use kiro_synth_core::funcs::random::Random;
+
+  fn block(builder: &mut ProgramBuilder<f64>) -> Block {
+    Block {
+      input: builder.signal(),
+      params: Params {
+        mode: builder.signal(),
+        freq: builder.signal(),
+        freq_mod: builder.signal(),
+        key_track: builder.signal(),
+        key_track_center: builder.signal(),
+        q: builder.signal(),
+        velocity_to_q: builder.signal(),
+        q_compensation: builder.signal(),
+        gain: builder.signal(),
+        self_osc: builder.signal(),
+      },
+      output: builder.signal(),
+    }
+  }
+
+  #[test]
+  fn input_gain() {
+    let mut builder = ProgramBuilder::new();
+    let block = block(&mut builder);
+    let program = builder.build();
+    let mut signals = vec![Signal::new(0.0f64); program.get_signals_count()];
+    let mut signals = SignalBus::new(signals.as_mut_slice());
+    let mut processor = Processor::new(44100.0, block.clone());
+
+    signals[block.input].set(0.5);
+    processor.process(&mut signals, &program);
+    assert_approx_eq!(signals[block.output].get(), 0.5);
+
+    signals[block.params.gain].set(20.0 * 2.0f64.log10());
+    processor.process(&mut signals, &program);
+    assert_approx_eq!(signals[block.output].get(), 1.0);
+
+    signals[block.params.gain].set(-20.0 * 2.0f64.log10());
+    processor.process(&mut signals, &program);
+    assert_approx_eq!(signals[block.output].get(), 0.25);
+  }
+  /// The peak of the output for a sine at the cutoff frequency, played with some velocity
+  fn peak_at_cutoff(velocity: f64, velocity_to_q: f64) -> f64 {
+    let sample_rate = 44100.0;
+    let mut builder = ProgramBuilder::new();
+    let block = block(&mut builder);
+    let voice = builder.voice().clone();
+    let program = builder.build();
+    let mut signals = vec![Signal::new(0.0f64); program.get_signals_count()];
+    let mut signals = SignalBus::new(signals.as_mut_slice());
+    let mut processor = Processor::new(sample_rate, block.clone());
+
+    signals[block.params.mode].set(3.0);
+    signals[block.params.freq].set(1000.0);
+    signals[block.params.q].set(0.3);
+    signals[block.params.velocity_to_q].set(velocity_to_q);
+    signals[voice.velocity].set(velocity);
+    let mut peak = 0.0f64;
+    for index in 0..4410 {
+      let phase = 2.0 * std::f64::consts::PI * 1000.0 * index as f64 / sample_rate;
+      signals[block.input].set(phase.sin());
+      processor.process(&mut signals, &program);
+      if index > 2205 {
+        peak = peak.max(signals[block.output].get().abs());
+      }
+    }
+    peak
+  }
+
+  #[test]
+  fn velocity_to_resonance() {
+    let soft = peak_at_cutoff(0.2, 0.5);
+    let hard = peak_at_cutoff(1.0, 0.5);
+    assert!(hard > soft * 1.5);
+
+    // without the mapping the velocity does not change the resonance
+    assert_approx_eq!(peak_at_cutoff(0.2, 0.0), peak_at_cutoff(1.0, 0.0), 1e-9);
+    // and a negative mapping reduces it
+    assert!(peak_at_cutoff(1.0, -0.3) < peak_at_cutoff(0.2, -0.3));
+  }
+
+  /// The RMS level of the output of the low pass for white noise
+  fn noise_level(q: f64, q_compensation: bool) -> f64 {
+    let mut builder = ProgramBuilder::new();
+    let block = block(&mut builder);
+    let program = builder.build();
+    let mut signals = vec![Signal::new(0.0f64); program.get_signals_count()];
+    let mut signals = SignalBus::new(signals.as_mut_slice());
+    let mut processor = Processor::new(44100.0, block.clone());
+
+    signals[block.params.mode].set(3.0);
+    signals[block.params.freq].set(1000.0);
+    signals[block.params.q].set(q);
+    signals[block.params.q_compensation].set(if q_compensation { 1.0 } else { 0.0 });
+    let mut random = Random::new(1);
+    let mut energy = 0.0;
+    for index in 0..44100 {
+      signals[block.input].set(random.bipolar());
+      processor.process(&mut signals, &program);
+      if index >= 22050 {
+        energy += signals[block.output].get().powi(2);
+      }
+    }
+    (energy / 22050.0).sqrt()
+  }
+
+  #[test]
+  fn resonance_compensation() {
+    let resonances = [0.0, 0.25, 0.5, 0.75, 1.0];
+    let level = noise_level(0.0, true);
+    for q in resonances.iter() {
+      let ratio = noise_level(*q, true) / level;
+      assert!(ratio > 0.8 && ratio < 1.25, "q {}: {}", q, ratio);
+    }
+
+    // without the compensation the resonant peak makes it much louder
+    assert!(noise_level(1.0, false) > 4.0 * noise_level(0.0, false));
+  }
+
+  #[test]
+  fn key_tracking_around_the_center() {
+    let mut builder = ProgramBuilder::new();
+    let block = block(&mut builder);
+    let voice = builder.voice().clone();
+    let program = builder.build();
+    let mut signals = vec![Signal::new(0.0f64); program.get_signals_count()];
+    let mut signals = SignalBus::new(signals.as_mut_slice());
+    let mut processor = Processor::new(44100.0, block.clone());
+
+    signals[block.params.mode].set(3.0);
+    signals[block.params.key_track_center].set(64.0);
+    let mut modulation = |key: usize, key_track: f64| {
+      signals[voice.note_pitch].set(KEY_FREQ[key] as f64);
+      signals[block.params.key_track].set(key_track);
+      processor.process(&mut signals, &program);
+      processor.freq_modulation()
+    };
+
+    assert_approx_eq!(modulation(64, 1.0), 0.0, 1e-4);
+    assert_approx_eq!(modulation(76, 1.0), 12.0, 1e-4);
+    assert_approx_eq!(modulation(52, 1.0), -12.0, 1e-4);
+    assert_approx_eq!(modulation(76, 0.5), 6.0, 1e-4);
+    assert_approx_eq!(modulation(52, 0.5), -6.0, 1e-4);
+    assert_approx_eq!(modulation(76, -0.5), -6.0, 1e-4);
+    assert_approx_eq!(modulation(76, 0.0), 0.0, 1e-4);
+  }
+}
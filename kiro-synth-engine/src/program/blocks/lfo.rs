@@ -1,4 +1,6 @@
-use kiro_synth_core::oscillators::lfo::Lfo;
+use kiro_synth_core::funcs::signal_polarity::bipolar_to_unipolar;
+use kiro_synth_core::oscillators::clamp_modulo;
+use kiro_synth_core::oscillators::lfo::{Lfo, Polarity};
 
 use crate::float::Float;
 use crate::globals::SynthGlobals;
@@ -11,43 +13,110 @@ pub struct Inputs {
   pub rate: SignalRef,
   pub phase: SignalRef,
   pub depth: SignalRef,
+  /// The transition time between the held values of the sample and hold, in seconds or beats
+  pub slew: SignalRef,
+  /// Whether the slew is in beats, synced with the tempo of the transport
+  pub slew_sync: SignalRef,
+  /// Zero for a bipolar output (-1.0 to 1.0) and one for an unipolar output (0.0 to 1.0)
+  pub polarity: SignalRef,
+  /// Whether the output is negated
+  pub invert: SignalRef,
 }
 
 #[derive(Debug, Clone)]
 pub struct Block {
   pub inputs: Inputs,
   pub output: SignalRef,
+  /// Set to one when a cycle is completed and zero otherwise
+  pub sync_out: SignalRef,
+  /// The position in the cycle of the output, from 0.0 to 1.0
+  pub phase_out: SignalRef,
+}
+
+/// The output of an LFO shifted by a fraction of its cycle, so a modulation can follow it out of phase.
+///
+/// The waveform is sampled straight from the phase of the LFO, so the slew of the sample and hold is not applied.
+pub(crate) fn shifted_output<'a, F: Float>(
+  block: &Block,
+  signals: &SignalBus<'a, F>,
+  synth_globals: &SynthGlobals<F>,
+  offset: F,
+) -> F {
+  let Inputs {
+    shape,
+    depth,
+    polarity,
+    invert,
+    ..
+  } = block.inputs;
+  let shape = signals[shape].get().to_usize().unwrap_or(0);
+  let mut waveform = synth_globals.lfo_waveforms.waveform(shape).clone();
+  let modulo = clamp_modulo(signals[block.phase_out].get() + offset);
+  let signal = waveform.generate(modulo, F::zero());
+  let signal = if signals[polarity].get() > F::zero() {
+    bipolar_to_unipolar(signal)
+  } else {
+    signal
+  };
+  let signal = if signals[invert].get() > F::zero() {
+    -signal
+  } else {
+    signal
+  };
+  signal * signals[depth].get()
 }
 
 #[derive(Debug)]
 pub(crate) struct Processor<F: Float> {
   lfo: Lfo<F>,
   block: Block,
+  slew: F,
+  slew_sync: bool,
+  tempo: F,
 }
 
 impl<F: Float> Processor<F> {
   pub fn new(sample_rate: F, block: Block) -> Self {
     let lfo = Lfo::new(sample_rate);
 
-    Processor { lfo, block }
+    Processor {
+      lfo,
+      block,
+      slew: F::zero(),
+      slew_sync: false,
+      tempo: F::zero(),
+    }
   }
 
   pub fn reset(&mut self) {
     self.lfo.reset()
   }
 
+  pub fn set_random_seed(&mut self, seed: u64) {
+    self.lfo.set_seed(seed)
+  }
+
   pub fn process<'a>(
     &mut self,
     signals: &mut SignalBus<'a, F>,
     _program: &Program<F>,
     synth_globals: &SynthGlobals<F>,
   ) {
-    let Block { inputs, output } = self.block.clone();
+    let Block {
+      inputs,
+      output,
+      sync_out,
+      phase_out,
+    } = self.block.clone();
     let Inputs {
       shape,
       rate,
       phase,
       depth,
+      slew,
+      slew_sync,
+      polarity,
+      invert,
     } = inputs;
 
     signals[shape].if_updated(|value| {
@@ -61,7 +130,47 @@ impl<F: Float> Processor<F> {
     signals[rate].if_updated(|value| self.lfo.set_rate(value));
     signals[phase].if_updated(|value| self.lfo.set_phase(value));
     signals[depth].if_updated(|value| self.lfo.set_depth(value));
+    signals[polarity].if_updated(|value| {
+      let polarity = if value > F::zero() {
+        Polarity::Unipolar
+      } else {
+        Polarity::Bipolar
+      };
+      self.lfo.set_polarity(polarity)
+    });
+    signals[invert].if_updated(|value| self.lfo.set_invert(value > F::zero()));
 
+    let mut slew_invalidated = false;
+    signals[slew].if_updated(|value| {
+      self.slew = value;
+      slew_invalidated = true;
+    });
+    signals[slew_sync].if_updated(|value| {
+      self.slew_sync = value > F::zero();
+      slew_invalidated = true;
+    });
+    let tempo = synth_globals.transport.get_tempo();
+    if self.slew_sync && tempo != self.tempo {
+      slew_invalidated = true;
+    }
+    self.tempo = tempo;
+    if slew_invalidated {
+      let seconds = if self.slew_sync {
+        self.slew * F::val(60) / tempo
+      } else {
+        self.slew
+      };
+      self.lfo.set_slew_seconds(seconds);
+    }
+
+    signals[phase_out].set(self.lfo.get_modulo());
     signals[output].set(self.lfo.generate());
+
+    let wrapped = if self.lfo.has_wrapped() {
+      F::one()
+    } else {
+      F::zero()
+    };
+    signals[sync_out].set(wrapped);
   }
 }
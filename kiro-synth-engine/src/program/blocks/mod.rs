@@ -4,3 +4,4 @@ pub mod expr;
 pub mod filter;
 pub mod lfo;
 pub mod osc;
+pub mod sequencer;
@@ -1,33 +1,116 @@
+use kiro_synth_core::funcs::decibels::Decibels;
 use kiro_synth_core::oscillators::osc_waveform::OscWaveform;
-use kiro_synth_core::oscillators::pitched_oscillator::PitchedOscillator;
+use kiro_synth_core::oscillators::pitched_oscillator::{FmMode, PitchedOscillator, SyncMode};
+use kiro_synth_core::oversample::Oversample;
 
 use crate::float::Float;
 use crate::globals::SynthGlobals;
 use crate::program::{Program, SignalRef};
 use crate::signal::SignalBus;
 
+/// Deviation of the pitch in cents for the maximum warmth
+const MAX_DRIFT_CENTS: f64 = 8.0;
+
+/// How many samples the oscillators modulated by another one generate per sample when oversampling
+const FM_OVERSAMPLING_FACTOR: usize = 4;
+
+/// Which LFO resets the phase of an oscillator at the beginning of its cycles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LfoSync {
+  /// No sync
+  Off,
+  /// The phase is reset with the cycles of the LFO 1
+  Lfo1,
+  /// The phase is reset with the cycles of the LFO 2
+  Lfo2,
+}
+
+impl LfoSync {
+  pub fn count() -> usize {
+    3
+  }
+
+  pub fn from<F: Float>(value: F) -> Self {
+    match value.round().to_usize().unwrap_or(0) {
+      1 => LfoSync::Lfo1,
+      2 => LfoSync::Lfo2,
+      _ => LfoSync::Off,
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct Inputs {
   pub shape: SignalRef,
   pub amplitude: SignalRef,
+  /// The amplitude in dB, used instead of `amplitude` when the level is in dB
+  pub amplitude_db: SignalRef,
+  /// Whether the level comes from `amplitude_db` instead of the linear `amplitude`
+  pub level_db: SignalRef,
   pub amp_mod: SignalRef,
   pub octaves: SignalRef,
   pub semitones: SignalRef,
   pub cents: SignalRef,
   pub note_pitch: SignalRef,
   pub pitch_bend: SignalRef,
+  /// The modulation of the pitch in semitones, such as from a pitch envelope
+  pub pitch_mod: SignalRef,
   pub freq_mod: SignalRef,
+  /// Whether the frequency modulation is exponential (in semitones) or linear (in Hz)
+  pub fm_mode: SignalRef,
+  /// The sync mode: off, hard or soft
+  pub sync_mode: SignalRef,
+  /// The sync output of the master oscillator
+  pub sync_in: SignalRef,
+  /// The LFO that resets the phase at the beginning of its cycles: off, lfo1 or lfo2
+  pub lfo_sync: SignalRef,
+  /// The sync output of the LFO 1
+  pub lfo1_sync_in: SignalRef,
+  /// The sync output of the LFO 2
+  pub lfo2_sync_in: SignalRef,
+  /// The lowest key for which the oscillator sounds
+  pub key_low: SignalRef,
+  /// The highest key for which the oscillator sounds
+  pub key_high: SignalRef,
+  /// Whether the oscillator ignores the note and runs at a fixed frequency
+  pub fixed_freq: SignalRef,
+  /// The frequency in Hz when the frequency is fixed
+  pub freq_hz: SignalRef,
 }
 
 #[derive(Debug, Clone)]
 pub struct Block {
   pub inputs: Inputs,
   pub output: SignalRef,
+  /// Set to one when a cycle is completed and zero otherwise
+  pub sync_out: SignalRef,
 }
 
 #[derive(Debug)]
 pub(crate) struct Processor<F: Float> {
   osc: PitchedOscillator<F>,
+  sample_rate: F,
+  oversample: Oversample<F>,
+  /// Whether the frequency is modulated by another oscillator, known once the program is seen
+  fm_carrier: Option<bool>,
+  oversampled: bool,
+  freq_mod: F,
+  last_freq_mod: F,
+  wrapped: bool,
+  shape: usize,
+  amplitude: F,
+  amplitude_db: F,
+  level_db: bool,
+  reduced_quality: bool,
+  sync_mode: SyncMode,
+  lfo_sync: LfoSync,
+  warmth: F,
+  key: F,
+  key_low: F,
+  key_high: F,
+  note_pitch: F,
+  fixed_freq: bool,
+  freq_hz: F,
   block: Block,
 }
 
@@ -36,49 +119,238 @@ impl<F: Float> Processor<F> {
     let waveform = OscWaveform::default();
     let osc = PitchedOscillator::new(sample_rate, waveform, F::zero());
 
-    Processor { osc, block }
+    Processor {
+      osc,
+      sample_rate,
+      oversample: Oversample::new(sample_rate, FM_OVERSAMPLING_FACTOR),
+      fm_carrier: None,
+      oversampled: false,
+      freq_mod: F::zero(),
+      last_freq_mod: F::zero(),
+      wrapped: false,
+      shape: 0,
+      amplitude: F::zero(),
+      amplitude_db: F::zero(),
+      level_db: false,
+      reduced_quality: false,
+      sync_mode: SyncMode::Off,
+      lfo_sync: LfoSync::Off,
+      warmth: F::zero(),
+      key: F::zero(),
+      key_low: F::zero(),
+      key_high: F::val(127),
+      note_pitch: F::zero(),
+      fixed_freq: false,
+      freq_hz: F::zero(),
+      block,
+    }
   }
 
   pub fn reset(&mut self) {
-    self.osc.reset()
+    self.osc.reset();
+    self.oversample.reset();
+  }
+
+  /// Replace the seed of the drift, derived by the synth from the indices of the voice and the block
+  /// so every oscillator drifts with a different random sequence
+  pub fn set_random_seed(&mut self, seed: u64) {
+    self.osc.set_drift_seed(seed)
   }
 
   pub fn process<'a>(
     &mut self,
     signals: &mut SignalBus<'a, F>,
-    _program: &Program<F>,
+    program: &Program<F>,
     synth_globals: &SynthGlobals<F>,
   ) {
-    let Block { inputs, output } = self.block.clone();
+    let Block {
+      inputs,
+      output,
+      sync_out,
+    } = self.block.clone();
     let Inputs {
       shape,
       amplitude,
+      amplitude_db,
+      level_db,
       amp_mod,
       octaves,
       semitones,
       cents,
       note_pitch,
       pitch_bend,
+      pitch_mod,
       freq_mod,
+      fm_mode,
+      sync_mode,
+      sync_in,
+      lfo_sync,
+      lfo1_sync_in,
+      lfo2_sync_in,
+      key_low,
+      key_high,
+      fixed_freq,
+      freq_hz,
     } = inputs;
 
     signals[shape].if_updated(|value| {
-      self.osc.set_waveform(
-        synth_globals
-          .osc_waveforms
-          .waveform(value.to_usize().unwrap())
-          .clone(),
-      )
+      self.shape = value.to_usize().unwrap();
+      self.reduced_quality = synth_globals.reduced_quality;
+      let waveform = self.waveform(synth_globals);
+      self.osc.set_waveform(waveform)
+    });
+    // switch the waveform keeping the phase, as the voice is already playing
+    if self.reduced_quality != synth_globals.reduced_quality {
+      self.reduced_quality = synth_globals.reduced_quality;
+      let waveform = self.waveform(synth_globals);
+      self.osc.switch_waveform(waveform);
+    }
+
+    let mut amplitude_updated = false;
+    signals[amplitude].if_updated(|value| {
+      self.amplitude = value;
+      amplitude_updated = true;
     });
-    signals[amplitude].if_updated(|value| self.osc.set_amplitude(value));
+    signals[amplitude_db].if_updated(|value| {
+      self.amplitude_db = value;
+      amplitude_updated = true;
+    });
+    signals[level_db].if_updated(|value| {
+      self.level_db = value > F::zero();
+      amplitude_updated = true;
+    });
+    if amplitude_updated {
+      let amplitude = if self.level_db {
+        Decibels::new(self.amplitude_db).to_amplitude()
+      } else {
+        self.amplitude
+      };
+      self.osc.set_amplitude(amplitude);
+    }
+
     signals[amp_mod].if_updated(|value| self.osc.set_amplitude_modulation(value));
     signals[octaves].if_updated(|value| self.osc.set_octaves(value));
     signals[semitones].if_updated(|value| self.osc.set_semitones(value));
     signals[cents].if_updated(|value| self.osc.set_cents(value));
-    signals[note_pitch].if_updated(|value| self.osc.set_pitch_frequency(value));
+
+    let mut pitch_updated = false;
+    signals[note_pitch].if_updated(|value| {
+      self.note_pitch = value;
+      pitch_updated = true;
+    });
+    signals[fixed_freq].if_updated(|value| {
+      self.fixed_freq = value > F::zero();
+      pitch_updated = true;
+    });
+    signals[freq_hz].if_updated(|value| {
+      self.freq_hz = value;
+      pitch_updated = true;
+    });
+    if pitch_updated {
+      let pitch = if self.fixed_freq {
+        self.freq_hz
+      } else {
+        self.note_pitch
+      };
+      self.osc.set_pitch_frequency(pitch);
+    }
+
     signals[pitch_bend].if_updated(|value| self.osc.set_pitch_bend(value));
-    signals[freq_mod].if_updated(|value| self.osc.set_frequency_modulation(value));
+    signals[pitch_mod].if_updated(|value| self.osc.set_pitch_modulation(value));
+    signals[fm_mode].if_updated(|value| self.osc.set_fm_mode(FmMode::from(value)));
+    signals[freq_mod].if_updated(|value| self.freq_mod = value);
+
+    signals[sync_mode].if_updated(|value| self.sync_mode = SyncMode::from(value));
+    signals[lfo_sync].if_updated(|value| self.lfo_sync = LfoSync::from(value));
+
+    if self.warmth != synth_globals.warmth {
+      self.warmth = synth_globals.warmth;
+      self.osc.set_drift(self.warmth * F::val(MAX_DRIFT_CENTS));
+    }
+
+    signals[program.voice().key].if_updated(|value| self.key = value);
+    signals[key_low].if_updated(|value| self.key_low = value);
+    signals[key_high].if_updated(|value| self.key_high = value);
+
+    if self.sync_mode != SyncMode::Off && signals[sync_in].get() > F::zero() {
+      self.osc.sync(self.sync_mode);
+    }
+
+    let lfo_sync_in = match self.lfo_sync {
+      LfoSync::Off => F::zero(),
+      LfoSync::Lfo1 => signals[lfo1_sync_in].get(),
+      LfoSync::Lfo2 => signals[lfo2_sync_in].get(),
+    };
+    if lfo_sync_in > F::zero() {
+      self.osc.sync(SyncMode::Hard);
+    }
+
+    let block = &self.block;
+    let fm_carrier = *self
+      .fm_carrier
+      .get_or_insert_with(|| program.is_fm_carrier(block));
+    let oversampled = fm_carrier && synth_globals.fm_oversampling;
+    if self.oversampled != oversampled {
+      self.oversampled = oversampled;
+      let sample_rate = if oversampled {
+        self.oversample.oversampled_rate()
+      } else {
+        self.sample_rate
+      };
+      self.osc.set_sample_rate(sample_rate);
+      self.oversample.reset();
+    }
+
+    // the oscillator keeps running out of its key range so it can still be the source of sync
+    let sample = if self.oversampled {
+      self.generate_oversampled()
+    } else {
+      if self.freq_mod != self.last_freq_mod {
+        self.osc.set_frequency_modulation(self.freq_mod);
+      }
+      let sample = self.osc.generate();
+      self.wrapped = self.osc.has_wrapped();
+      sample
+    };
+    self.last_freq_mod = self.freq_mod;
+    if self.key_low <= self.key && self.key <= self.key_high {
+      signals[output].set(sample);
+    } else {
+      signals[output].set(F::zero());
+    }
+
+    let wrapped = if self.wrapped { F::one() } else { F::zero() };
+    signals[sync_out].set(wrapped);
+  }
+
+  /// Generate several samples per sample, with the frequency modulation interpolated between them,
+  /// so the sidebands above the Nyquist frequency are filtered out instead of folding back
+  fn generate_oversampled(&mut self) -> F {
+    let Processor {
+      osc,
+      oversample,
+      freq_mod,
+      last_freq_mod,
+      ..
+    } = self;
+    let factor = F::val(oversample.get_factor());
+    let mut wrapped = false;
+    let sample = oversample.process(|index| {
+      let fraction = F::val(index + 1) / factor;
+      osc.set_frequency_modulation(*last_freq_mod + (*freq_mod - *last_freq_mod) * fraction);
+      let sample = osc.generate();
+      wrapped = wrapped || osc.has_wrapped();
+      sample
+    });
+    self.wrapped = wrapped;
+    sample
+  }
 
-    signals[output].set(self.osc.generate());
+  fn waveform(&self, synth_globals: &SynthGlobals<F>) -> OscWaveform<F> {
+    if self.reduced_quality {
+      synth_globals.osc_waveforms.reduced_waveform(self.shape)
+    } else {
+      synth_globals.osc_waveforms.waveform(self.shape).clone()
+    }
   }
 }
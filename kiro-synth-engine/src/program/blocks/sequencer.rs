@@ -0,0 +1,90 @@
+use heapless::consts;
+use heapless::Vec;
+
+use kiro_synth_core::sequencer::{Step, StepSequencer};
+
+use crate::float::Float;
+use crate::globals::SynthGlobals;
+use crate::program::{Program, SignalRef};
+use crate::signal::SignalBus;
+
+pub type MaxSteps = consts::U16;
+
+#[derive(Debug, Clone)]
+pub struct Inputs {
+  /// Number of steps per beat
+  pub division: SignalRef,
+  /// Number of steps played, from 1 to 16
+  pub length: SignalRef,
+}
+
+#[derive(Debug, Clone)]
+pub struct Outputs {
+  pub value: SignalRef,
+  pub gate: SignalRef,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block<F: Float> {
+  pub steps: Vec<Step<F>, MaxSteps>,
+  pub inputs: Inputs,
+  pub outputs: Outputs,
+}
+
+impl<F: Float> Block<F> {
+  /// Create a block with steps for the values, with the gate on and without slide
+  pub fn with_values(values: &[F], inputs: Inputs, outputs: Outputs) -> Self {
+    let steps = values.iter().map(|value| Step::new(*value)).collect();
+    Block {
+      steps,
+      inputs,
+      outputs,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub(crate) struct Processor<F: Float> {
+  sequencer: StepSequencer<F>,
+  block: Block<F>,
+}
+
+impl<F: Float> Processor<F> {
+  pub fn new(block: Block<F>) -> Self {
+    let mut sequencer = StepSequencer::new();
+    for (index, step) in block.steps.iter().enumerate() {
+      sequencer.set_step(index, *step);
+    }
+    sequencer.set_num_steps(block.steps.len());
+
+    Processor { sequencer, block }
+  }
+
+  pub fn reset(&mut self) {}
+
+  pub fn process<'a>(
+    &mut self,
+    signals: &mut SignalBus<'a, F>,
+    _program: &Program<F>,
+    synth_globals: &SynthGlobals<F>,
+  ) {
+    let Inputs { division, length } = self.block.inputs.clone();
+    let Outputs { value, gate } = self.block.outputs.clone();
+
+    signals[division].if_updated(|value| self.sequencer.set_division(value));
+    signals[length].if_updated(|value| {
+      self
+        .sequencer
+        .set_num_steps(value.round().to_usize().unwrap_or(1))
+    });
+
+    let beats = synth_globals.transport.get_beats();
+    signals[value].set(self.sequencer.process(beats));
+    let gate_value = if self.sequencer.is_gate_on() {
+      F::one()
+    } else {
+      F::zero()
+    };
+    signals[gate].set(gate_value);
+  }
+}
@@ -1,12 +1,16 @@
 use heapless::Vec;
 
 use crate::float::Float;
+use crate::program::blocks::{envgen, lfo, osc};
 use crate::program::blocks::expr::{self, ExprBuilder, OpRef};
+use crate::program::drum_map::DrumMap;
+use crate::program::macros::{Macro, MacroTarget, Macros};
 use crate::program::modulations::Modulations;
 use crate::program::references::{BlockRef, ParamRef, SignalRef, SignalRefs, SourceRef};
+use crate::program::routing::OscRouting;
 use crate::program::{
-  Block, MaxBlocks, MaxParams, MaxSources, Param, ParamBlock, ParamValues, Program, Source,
-  VoiceBlock,
+  Block, InsertOrder, MasterBlock, MaxBlocks, MaxParams, MaxSources, Param, ParamBlock,
+  ParamValues, Program, Source, VoiceBlock,
 };
 use crate::signal::Signal;
 
@@ -14,10 +18,14 @@ use crate::signal::Signal;
 pub struct ProgramBuilder<'a, F: Float> {
   signal_refs: SignalRefs,
   voice: VoiceBlock,
+  amp_eg: Option<envgen::Block>,
+  master: MasterBlock,
   sources: Vec<Source<'a>, MaxSources>,
   params: Vec<Param<'a, F>, MaxParams>,
   blocks: Vec<Block<F>, MaxBlocks>,
   modulations: Modulations<F>,
+  macros: Macros<F>,
+  drum_map: DrumMap<F>,
 }
 
 impl<'a, F: Float> ProgramBuilder<'a, F> {
@@ -27,10 +35,12 @@ impl<'a, F: Float> ProgramBuilder<'a, F> {
     let voice = VoiceBlock {
       key: signal_refs.create(),
       velocity: signal_refs.create(),
+      pressure: signal_refs.create(),
       note_pitch: signal_refs.create(),
       gate: signal_refs.create(),
       trigger: signal_refs.create(),
       off: signal_refs.create(),
+      amp_env: signal_refs.create(),
       output_left: signal_refs.create(),
       output_right: signal_refs.create(),
     };
@@ -38,10 +48,14 @@ impl<'a, F: Float> ProgramBuilder<'a, F> {
     ProgramBuilder {
       signal_refs,
       voice,
+      amp_eg: None,
+      master: MasterBlock::default(),
       sources: Vec::new(),
       params: Vec::new(),
       blocks: Vec::new(),
       modulations: Modulations::default(),
+      macros: Macros::default(),
+      drum_map: DrumMap::default(),
     }
   }
 
@@ -64,7 +78,24 @@ impl<'a, F: Float> ProgramBuilder<'a, F> {
   }
 
   pub fn source(&mut self, name: &'a str, signal: SignalRef) -> SourceRef {
-    let source = Source { id: name, signal };
+    let source = Source {
+      id: name,
+      signal,
+      lfo: None,
+    };
+
+    self.sources.push(source).unwrap();
+
+    SourceRef(self.sources.len() - 1)
+  }
+
+  /// A source from the output of an LFO, which the modulations can sample with a phase offset
+  pub fn lfo_source(&mut self, name: &'a str, lfo: &lfo::Block) -> SourceRef {
+    let source = Source {
+      id: name,
+      signal: lfo.output,
+      lfo: Some(lfo.clone()),
+    };
 
     self.sources.push(source).unwrap();
 
@@ -80,6 +111,7 @@ impl<'a, F: Float> ProgramBuilder<'a, F> {
       id,
       values,
       value,
+      precise_value: None,
       out_signal_ref,
       mod_signal_ref,
     };
@@ -104,6 +136,22 @@ impl<'a, F: Float> ProgramBuilder<'a, F> {
       .unwrap();
   }
 
+  pub fn modulation_phase<P: Into<ParamRef>>(&mut self, param: P, source_ref: SourceRef, phase: F) {
+    self
+      .modulations
+      .update_phase(param.into(), source_ref, phase);
+  }
+
+  /// Link a param to several target params, which will be changed at once by the value of the macro
+  pub fn macro_control<P: Into<ParamRef>>(&mut self, param: P, targets: &[MacroTarget<F>]) {
+    self.macros.add(Macro::new(param.into(), targets)).unwrap();
+  }
+
+  /// Set fixed frequencies for some keys, such as for drum patches
+  pub fn drum_map(&mut self, drum_map: DrumMap<F>) {
+    self.drum_map = drum_map;
+  }
+
   pub fn expr<B: Fn(&mut ExprBuilder<F>) -> OpRef>(&mut self, build_expr: B) -> expr::Block<F> {
     let mut expr_builder = ExprBuilder::new();
     build_expr(&mut expr_builder);
@@ -116,20 +164,79 @@ impl<'a, F: Float> ProgramBuilder<'a, F> {
     block_ref
   }
 
+  /// Add the blocks for the oscillators connecting their sync and FM inputs as defined by the routing,
+  /// and in an order that processes the sources of the routes first.
+  pub fn oscillators(&mut self, oscillators: &[osc::Block], routing: &OscRouting) {
+    for index in routing.processing_order() {
+      if let Some(osc) = oscillators.get(index) {
+        let mut osc = osc.clone();
+        if let Some(source) = routing.sync_source(index).and_then(|i| oscillators.get(i)) {
+          osc.inputs.sync_in = source.sync_out;
+        }
+        if let Some(source) = routing.fm_source(index).and_then(|i| oscillators.get(i)) {
+          // the routed oscillator is added to the frequency modulation already patched
+          let (freq_mod, source_output) = (osc.inputs.freq_mod, source.output);
+          let fm = self.expr(|expr| expr.add_signals(freq_mod, source_output));
+          osc.inputs.freq_mod = fm.output;
+          self.block(Block::Expr(fm));
+        }
+        self.block(Block::Osc(osc));
+      }
+    }
+  }
+
   pub fn out(&mut self, left: SignalRef, right: SignalRef) -> BlockRef {
     let block_ref = BlockRef(self.blocks.len());
     self.blocks.push(Block::Out { left, right }).unwrap();
     block_ref
   }
 
-  pub fn build(self) -> Program<'a, F> {
+  /// Set the inputs for the amp envelope of the voices. Without it the voices are just gated by the keys.
+  pub fn amp_envelope(&mut self, inputs: envgen::Inputs) -> envgen::Outputs {
+    let outputs = envgen::Outputs {
+      normal: self.voice.amp_env,
+      biased: self.signal_refs.create(),
+    };
+    self.amp_eg = Some(envgen::Block {
+      inputs,
+      outputs: outputs.clone(),
+    });
+    outputs
+  }
+
+  pub fn master(&mut self, master: MasterBlock) {
+    self.master = master;
+  }
+
+  pub fn build(mut self) -> Program<'a, F> {
+    if self.amp_eg.is_none() {
+      let zero = self.const_zero();
+      let one = self.const_one();
+      self.amp_envelope(envgen::Inputs {
+        attack: zero,
+        decay: zero,
+        sustain: one,
+        release: zero,
+        mode: zero,
+        legato: zero,
+        reset_to_zero: zero,
+        velocity_to_attack: zero,
+        velocity_to_decay: zero,
+      });
+    }
+
     Program {
       signals_count: self.signal_refs.count(),
       voice: self.voice,
+      amp_eg: self.amp_eg.unwrap(),
+      master: self.master,
       sources: self.sources,
       params: self.params,
       blocks: self.blocks,
       modulations: self.modulations,
+      macros: self.macros,
+      drum_map: self.drum_map,
+      insert_order: InsertOrder::new(),
     }
   }
 }
@@ -0,0 +1,63 @@
+use heapless::consts;
+use heapless::Vec;
+
+use crate::float::Float;
+use crate::key_freqs::KEY_FREQ;
+
+pub type MaxDrumKeys = consts::U128;
+
+/// Fixed frequencies for some keys, so every key of a drum patch can have its own pitch.
+/// The keys without a frequency use the standard tuning.
+#[derive(Debug, Clone, Default)]
+pub struct DrumMap<F: Float> {
+  keys: Vec<(u8, F), MaxDrumKeys>,
+}
+
+impl<F: Float> DrumMap<F> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Set the frequency in Hz for a key, replacing the previous one
+  pub fn set(&mut self, key: u8, freq: F) {
+    match self.position(key) {
+      Some(position) => self.keys[position].1 = freq,
+      None => self.keys.push((key & 0x7f, freq)).unwrap(),
+    }
+  }
+
+  pub fn with(mut self, key: u8, freq: F) -> Self {
+    self.set(key, freq);
+    self
+  }
+
+  /// Go back to the standard tuning for a key
+  pub fn unset(&mut self, key: u8) {
+    if let Some(position) = self.position(key) {
+      self.keys.swap_remove(position);
+    }
+  }
+
+  pub fn get(&self, key: u8) -> Option<F> {
+    self.position(key).map(|position| self.keys[position].1)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.keys.is_empty()
+  }
+
+  /// The frequency of a key, from the map or from the standard tuning when it is not mapped
+  pub fn key_freq(&self, key: u8) -> F {
+    self
+      .get(key)
+      .unwrap_or_else(|| F::val(KEY_FREQ[(key & 0x7f) as usize]))
+  }
+
+  fn position(&self, key: u8) -> Option<usize> {
+    let key = key & 0x7f;
+    self
+      .keys
+      .iter()
+      .position(|(mapped_key, _)| *mapped_key == key)
+  }
+}
@@ -0,0 +1,138 @@
+use generic_array::typenum::marker_traits::Unsigned;
+use generic_array::GenericArray;
+use heapless::consts;
+use heapless::Vec;
+
+use crate::float::Float;
+use crate::program::{MaxParams, Param, ParamRef};
+
+pub type MaxMacros = consts::U8;
+pub type MaxMacroTargets = consts::U8;
+
+/// How the value of a macro, from 0.0 to 1.0, is shaped before scaling it for a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacroCurve {
+  Linear,
+  /// Quadratic, with more resolution for the lower values
+  Exponential,
+  /// Square root, with more resolution for the higher values
+  Logarithmic,
+}
+
+impl MacroCurve {
+  pub fn apply<F: Float>(self, value: F) -> F {
+    let value = value.max(F::zero()).min(F::one());
+    match self {
+      MacroCurve::Linear => value,
+      MacroCurve::Exponential => value * value,
+      MacroCurve::Logarithmic => value.sqrt(),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct MacroTarget<F: Float> {
+  pub param_ref: ParamRef,
+  /// The change of the target param for the full macro, as a proportion of its range from -1.0 to 1.0
+  pub depth: F,
+  pub curve: MacroCurve,
+}
+
+impl<F: Float> MacroTarget<F> {
+  pub fn new(param_ref: ParamRef, depth: F) -> Self {
+    MacroTarget {
+      param_ref,
+      depth,
+      curve: MacroCurve::Linear,
+    }
+  }
+
+  pub fn with_curve(self, curve: MacroCurve) -> Self {
+    MacroTarget { curve, ..self }
+  }
+}
+
+/// A param that changes several params at once, for performance control
+#[derive(Debug, Clone)]
+pub struct Macro<F: Float> {
+  /// The param with the value of the macro, from 0.0 to 1.0
+  pub param_ref: ParamRef,
+  pub targets: Vec<MacroTarget<F>, MaxMacroTargets>,
+  value: Option<F>,
+}
+
+impl<F: Float> Macro<F> {
+  pub fn new(param_ref: ParamRef, targets: &[MacroTarget<F>]) -> Self {
+    let mut macro_targets = Vec::new();
+    for target in targets.iter().take(MaxMacroTargets::to_usize()) {
+      drop(macro_targets.push(target.clone()));
+    }
+    Macro {
+      param_ref,
+      targets: macro_targets,
+      value: None,
+    }
+  }
+}
+
+/// The macros of a program and the offsets they add to the values of their targets
+#[derive(Debug, Clone)]
+pub struct Macros<F: Float> {
+  macros: Vec<Macro<F>, MaxMacros>,
+  offsets: GenericArray<F, MaxParams>,
+}
+
+impl<F: Float> Default for Macros<F> {
+  fn default() -> Self {
+    Macros {
+      macros: Vec::new(),
+      offsets: GenericArray::default(),
+    }
+  }
+}
+
+impl<F: Float> Macros<F> {
+  pub fn add(&mut self, macro_control: Macro<F>) -> Option<usize> {
+    self.macros.push(macro_control).ok()?;
+    Some(self.macros.len() - 1)
+  }
+
+  pub fn get_macros(&self) -> &[Macro<F>] {
+    &self.macros
+  }
+
+  /// The amount added by the macros to a param
+  pub fn offset(&self, param_ref: ParamRef) -> F {
+    let index: usize = param_ref.into();
+    self.offsets.get(index).cloned().unwrap_or_else(F::zero)
+  }
+
+  /// Recalculate the offsets when the value of any macro has changed
+  pub fn update(&mut self, params: &[Param<F>]) {
+    let mut changed = false;
+    for macro_control in self.macros.iter_mut() {
+      let index: usize = macro_control.param_ref.into();
+      let value = params.get(index).map(|param| param.value.get());
+      if value != macro_control.value {
+        macro_control.value = value;
+        changed = true;
+      }
+    }
+
+    if changed {
+      for offset in self.offsets.iter_mut() {
+        *offset = F::zero();
+      }
+      for macro_control in self.macros.iter() {
+        let value = macro_control.value.unwrap_or_else(F::zero);
+        for target in macro_control.targets.iter() {
+          let index: usize = target.param_ref.into();
+          if let (Some(param), Some(offset)) = (params.get(index), self.offsets.get_mut(index)) {
+            let range = param.values.max - param.values.min;
+            *offset = *offset + target.curve.apply(value) * target.depth * range;
+          }
+        }
+      }
+    }
+  }
+}
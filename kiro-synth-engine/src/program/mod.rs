@@ -1,7 +1,10 @@
 pub mod blocks;
 pub mod builder;
+pub mod drum_map;
+pub mod macros;
 pub mod modulations;
 pub mod references;
+pub mod routing;
 
 use std::ops::{Deref, DerefMut};
 
@@ -13,19 +16,38 @@ use crate::signal::Signal;
 
 use blocks::*;
 pub use builder::ProgramBuilder;
+use drum_map::DrumMap;
+use macros::Macros;
 use modulations::Modulations;
 pub use references::*;
 
-pub type MaxSignals = consts::U256;
+pub type MaxSignals = consts::U512;
 pub type MaxSources = consts::U32;
 pub type MaxModulations = consts::U4;
 pub type MaxParams = consts::U128;
 pub type MaxBlocks = consts::U128;
+pub type MaxInsertEffects = consts::U8;
+
+/// The slots of the insert effects in the order they process the output
+pub type InsertOrder = Vec<u8, MaxInsertEffects>;
 
 #[derive(Debug, Clone)]
 pub struct Source<'a> {
   pub id: &'a str,
   pub signal: SignalRef,
+  /// The LFO generating the signal, so the modulations can sample it with a phase offset
+  pub lfo: Option<lfo::Block>,
+}
+
+/// How the normalized range from 0.0 to 1.0 maps into the param values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamScale {
+  Linear,
+  /// Geometric mapping, for frequencies. Falls back to linear when the minimum is not positive.
+  Logarithmic,
+  /// Quadratic mapping, the values grow with the square of the normalized position (t * t),
+  /// giving more resolution to the lower values, for times. Not a true exponential curve.
+  Exponential,
 }
 
 #[derive(Debug, Clone)]
@@ -35,6 +57,9 @@ pub struct ParamValues<F: Float> {
   pub min: F,
   pub max: F,
   pub resolution: F,
+  pub scale: ParamScale,
+  /// Whether the values are choices, such as the enumerations or the booleans, rather than amounts
+  pub discrete: bool,
 }
 
 impl<F: Float> ParamValues<F> {
@@ -44,6 +69,65 @@ impl<F: Float> ParamValues<F> {
       ..self
     }
   }
+
+  pub fn with_scale(self, scale: ParamScale) -> Self {
+    Self { scale, ..self }
+  }
+
+  pub fn with_discrete(self, discrete: bool) -> Self {
+    Self { discrete, ..self }
+  }
+
+  /// Whether the values are choices that can not be interpolated, as for the enumerations or the booleans.
+  /// The params with whole steps that are amounts, such as the semitones or the keys, are not discrete.
+  pub fn is_discrete(&self) -> bool {
+    self.discrete
+  }
+
+  fn is_geometric(&self) -> bool {
+    self.scale == ParamScale::Logarithmic && self.min > F::zero()
+  }
+
+  /// Map a value into the normalized range from 0.0 to 1.0
+  pub fn normalize(&self, value: F) -> F {
+    let range = self.max - self.min;
+    if range <= F::zero() {
+      return F::zero();
+    }
+
+    let value = value.max(self.min).min(self.max);
+    if self.is_geometric() {
+      (value / self.min).ln() / (self.max / self.min).ln()
+    } else {
+      let t = (value - self.min) / range;
+      match self.scale {
+        ParamScale::Exponential => t.sqrt(),
+        _ => t,
+      }
+    }
+  }
+
+  /// Map a normalized value from 0.0 to 1.0 into the param range, rounded to the param resolution
+  pub fn denormalize(&self, t: F) -> F {
+    let t = t.max(F::zero()).min(F::one());
+    let value = if self.is_geometric() {
+      self.min * (self.max / self.min).powf(t)
+    } else {
+      let t = match self.scale {
+        ParamScale::Exponential => t * t,
+        _ => t,
+      };
+      self.min + (self.max - self.min) * t
+    };
+
+    let value = if self.resolution > F::zero() {
+      self.min + ((value - self.min) / self.resolution).round() * self.resolution
+    } else {
+      value
+    };
+
+    value.max(self.min).min(self.max)
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +135,8 @@ pub struct Param<'a, F: Float> {
   pub id: &'a str,
   pub values: ParamValues<F>,
   pub value: Signal<F>,
+  /// The value in double precision when it was set with [`Synth::set_param_f64`](crate::synth::Synth::set_param_f64)
+  pub precise_value: Option<f64>,
   pub out_signal_ref: SignalRef,
   pub mod_signal_ref: SignalRef,
 }
@@ -80,6 +166,8 @@ pub enum Block<F: Float> {
 
   Osc(osc::Block),
 
+  Sequencer(sequencer::Block<F>),
+
   Out { left: SignalRef, right: SignalRef },
 }
 
@@ -87,22 +175,59 @@ pub enum Block<F: Float> {
 pub struct VoiceBlock {
   pub key: SignalRef,
   pub velocity: SignalRef,
+  pub pressure: SignalRef,
   pub note_pitch: SignalRef,
   pub gate: SignalRef,
   pub trigger: SignalRef,
   pub off: SignalRef,
+  /// Output of the amp envelope, applied to the output of the voice
+  pub amp_env: SignalRef,
   pub output_left: SignalRef,
   pub output_right: SignalRef,
 }
 
+/// Params applied by the synth to the mix of all the voices
+#[derive(Debug, Clone, Default)]
+pub struct MasterBlock {
+  /// Gain in dB before the effects
+  pub pre_fx_gain: Option<ParamRef>,
+  /// Gain in dB of the final output
+  pub gain: Option<ParamRef>,
+  /// Amount of the voices of this program sent to the effects bus, from 0.0 to 1.0
+  pub fx_send: Option<ParamRef>,
+  /// Amount of the effects bus return mixed into the output, from 0.0 to 1.0
+  pub fx_return: Option<ParamRef>,
+  /// Cutoff in Hz of the high-pass filter of the output
+  pub high_pass_freq: Option<ParamRef>,
+  /// Whether the high-pass filter of the output is enabled
+  pub high_pass: Option<ParamRef>,
+  /// Cutoff in Hz of the low-pass filter of the output
+  pub low_pass_freq: Option<ParamRef>,
+  /// Whether the low-pass filter of the output is enabled
+  pub low_pass: Option<ParamRef>,
+  /// Pan of the output, from -1.0 (left) to 1.0 (right)
+  pub pan: Option<ParamRef>,
+  /// Waveform of the global LFO, which runs freely even when no voices are playing
+  pub lfo_shape: Option<ParamRef>,
+  /// Rate in Hz of the global LFO
+  pub lfo_rate: Option<ParamRef>,
+  /// Amount of the global LFO added to the pan of the output, from 0.0 to 1.0
+  pub lfo_to_pan: Option<ParamRef>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Program<'a, F: Float> {
   signals_count: usize,
   voice: VoiceBlock,
+  amp_eg: envgen::Block,
+  master: MasterBlock,
   sources: Vec<Source<'a>, MaxSources>,
   params: Vec<Param<'a, F>, MaxParams>,
   blocks: Vec<Block<F>, MaxBlocks>,
   modulations: Modulations<F>,
+  macros: Macros<F>,
+  drum_map: DrumMap<F>,
+  insert_order: InsertOrder,
 }
 
 impl<'a, F: Float> Program<'a, F> {
@@ -114,6 +239,15 @@ impl<'a, F: Float> Program<'a, F> {
     &self.voice
   }
 
+  /// The envelope applied to the amplitude of every voice, which ends the voice when it is off
+  pub fn amp_eg(&self) -> &envgen::Block {
+    &self.amp_eg
+  }
+
+  pub fn master(&self) -> &MasterBlock {
+    &self.master
+  }
+
   //  pub fn get_params_count(&self) -> usize {
   //    self.params.len()
   //  }
@@ -148,11 +282,13 @@ impl<'a, F: Float> Program<'a, F> {
   //        .map(|param_index| (param_index, &self.params[param_index]))
   //  }
 
-  //  pub fn get_param_ref(&self, id: &'a str) -> Option<ParamRef> {
-  //    self.params.iter()
-  //        .position(|param| param.id == id)
-  //        .map(|param_index| ParamRef(param_index))
-  //  }
+  pub fn get_param_ref(&self, id: &str) -> Option<ParamRef> {
+    self
+      .params
+      .iter()
+      .position(|param| param.id == id)
+      .map(ParamRef)
+  }
 
   pub fn get_param_signal(&self, param: ParamRef) -> &Signal<F> {
     &self.params[param.0].value
@@ -171,6 +307,11 @@ impl<'a, F: Float> Program<'a, F> {
     self.modulations.update(param_ref, source_ref, amount)
   }
 
+  /// Sample an LFO source at a phase offset in cycles for one of its modulations
+  pub fn update_modulation_phase(&mut self, param_ref: ParamRef, source_ref: SourceRef, phase: F) {
+    self.modulations.update_phase(param_ref, source_ref, phase)
+  }
+
   pub fn delete_modulation(
     &mut self,
     param_ref: ParamRef,
@@ -179,6 +320,10 @@ impl<'a, F: Float> Program<'a, F> {
     self.modulations.delete(param_ref, source_ref)
   }
 
+  pub fn clear_modulations(&mut self) {
+    self.modulations = Modulations::default();
+  }
+
   pub fn get_param_modulations(&self, param_ref: ParamRef) -> modulations::Iter<F> {
     self.modulations.get_param_modulations(param_ref)
   }
@@ -187,6 +332,47 @@ impl<'a, F: Float> Program<'a, F> {
   //   self.modulations.for_each_modulation(param_ref, process)
   // }
 
+  pub fn get_macros(&self) -> &[macros::Macro<F>] {
+    self.macros.get_macros()
+  }
+
+  /// The amount added to a param by the macros linked to it
+  pub fn get_macro_offset(&self, param_ref: ParamRef) -> F {
+    self.macros.offset(param_ref)
+  }
+
+  /// Update the amounts added by the macros to their targets after any change of their values
+  pub fn update_macros(&mut self) {
+    self.macros.update(&self.params);
+  }
+
+  pub fn get_drum_map(&self) -> &DrumMap<F> {
+    &self.drum_map
+  }
+
+  /// Set fixed frequencies for some keys, such as for drum patches
+  pub fn set_drum_map(&mut self, drum_map: DrumMap<F>) {
+    self.drum_map = drum_map;
+  }
+
+  /// The frequency played by a key, taking into account the drum map
+  pub fn key_freq(&self, key: u8) -> F {
+    self.drum_map.key_freq(key)
+  }
+
+  /// The slots of the insert effects in the order they process the output. It can be partial,
+  /// the insert effects missing from it go after the ones in it, in the order they were added.
+  pub fn get_insert_order(&self) -> &[u8] {
+    &self.insert_order
+  }
+
+  pub fn set_insert_order(&mut self, order: &[u8]) {
+    self.insert_order.clear();
+    for slot in order.iter() {
+      self.insert_order.push(*slot).ok();
+    }
+  }
+
   pub fn reset_params(&mut self) {
     for param in self.params.iter_mut() {
       param.value.set(param.values.initial_value);
@@ -208,7 +394,29 @@ impl<'a, F: Float> Program<'a, F> {
     self.sources.get(source.0)
   }
 
+  pub fn get_source_ref(&self, id: &str) -> Option<SourceRef> {
+    self
+      .sources
+      .iter()
+      .position(|source| source.id == id)
+      .map(SourceRef)
+  }
+
   pub fn get_blocks(&self) -> &[Block<F>] {
     &*self.blocks
   }
+
+  /// Whether the frequency of an oscillator is modulated by the output of another oscillator
+  pub fn is_fm_carrier(&self, osc: &osc::Block) -> bool {
+    let freq_mod = osc.inputs.freq_mod;
+    self.blocks.iter().any(|block| match block {
+      Block::Osc(source) => source.output == freq_mod,
+      // the FM routes are summed to the modulation of the patch
+      Block::Expr(fm) if fm.output == freq_mod => self.blocks.iter().any(|block| match block {
+        Block::Osc(source) => fm.reads_signal(source.output),
+        _ => false,
+      }),
+      _ => false,
+    })
+  }
 }
@@ -21,11 +21,17 @@ pub enum Error {
 pub struct Modulation<F: Float> {
   pub source_ref: SourceRef,
   pub amount: F,
+  /// Offset in cycles (0.5 for 180 degrees) at which an LFO source is sampled for this modulation
+  pub phase: F,
 }
 
 impl<F: Float> Modulation<F> {
   pub fn new(source_ref: SourceRef, amount: F) -> Self {
-    Modulation { source_ref, amount }
+    Modulation {
+      source_ref,
+      amount,
+      phase: F::zero(),
+    }
   }
 }
 
@@ -34,6 +40,7 @@ impl<F: Float> Default for Modulation<F> {
     Modulation {
       source_ref: SourceRef::new(0),
       amount: F::zero(),
+      phase: F::zero(),
     }
   }
 }
@@ -108,10 +115,18 @@ impl<'a, F: Float> Modulations<F> {
 
     node.data.source_ref = source_ref;
     node.data.amount = amount;
+    node.data.phase = F::zero();
 
     Ok(())
   }
 
+  /// Change the phase offset of an existing modulation, it does nothing if there is no such modulation
+  pub fn update_phase(&mut self, param_ref: ParamRef, source_ref: SourceRef, phase: F) {
+    if let Some((_prev, head)) = self.find(param_ref, source_ref) {
+      self.pool.get_mut(head).data.phase = phase;
+    }
+  }
+
   pub fn delete(&mut self, param_ref: ParamRef, source_ref: SourceRef) -> Result<(), Error> {
     match self.find(param_ref, source_ref) {
       Some((prev, head)) => {
@@ -267,6 +282,7 @@ mod tests {
     let m = Modulation::new(source_ref, 1.2);
     assert_eq!(m.source_ref, source_ref);
     assert_eq!(m.amount, 1.2);
+    assert_eq!(m.phase, 0.0);
   }
 
   #[test]
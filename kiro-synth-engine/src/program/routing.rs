@@ -0,0 +1,247 @@
+use heapless::consts;
+use heapless::Vec;
+use typenum::marker_traits::Unsigned;
+
+pub type MaxOscillators = consts::U8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+  /// The oscillator index is out of the routing
+  UnknownOscillator,
+  /// An oscillator can not be routed into itself
+  SelfReference,
+  /// The route would make an oscillator depend on its own output
+  Cycle,
+}
+
+/// Sync and FM routes between the oscillators of a program.
+///
+/// Every oscillator can be synced to and frequency modulated by another one.
+/// Invalid routes are rejected so the oscillators can always be processed in the order of their dependencies.
+#[derive(Debug, Clone, Default)]
+pub struct OscRouting {
+  sync: Vec<Option<usize>, MaxOscillators>,
+  fm: Vec<Option<usize>, MaxOscillators>,
+}
+
+impl OscRouting {
+  pub fn new(count: usize) -> Self {
+    let mut routing = OscRouting::default();
+    for _ in 0..count.min(MaxOscillators::to_usize()) {
+      routing.sync.push(None).unwrap();
+      routing.fm.push(None).unwrap();
+    }
+    routing
+  }
+
+  pub fn len(&self) -> usize {
+    self.sync.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.sync.is_empty()
+  }
+
+  pub fn sync_source(&self, osc: usize) -> Option<usize> {
+    self.sync.get(osc).cloned().flatten()
+  }
+
+  pub fn fm_source(&self, osc: usize) -> Option<usize> {
+    self.fm.get(osc).cloned().flatten()
+  }
+
+  /// Sync an oscillator to the cycles of the source one, or remove its sync with `None`
+  pub fn set_sync_source(&mut self, osc: usize, source: Option<usize>) -> Result<(), Error> {
+    self.validate(osc, source)?;
+    self.sync[osc] = source;
+    Ok(())
+  }
+
+  /// Modulate the frequency of an oscillator with the output of the source one, or remove it with `None`
+  pub fn set_fm_source(&mut self, osc: usize, source: Option<usize>) -> Result<(), Error> {
+    self.validate(osc, source)?;
+    self.fm[osc] = source;
+    Ok(())
+  }
+
+  fn validate(&self, osc: usize, source: Option<usize>) -> Result<(), Error> {
+    if osc >= self.len() {
+      return Err(Error::UnknownOscillator);
+    }
+    match source {
+      None => Ok(()),
+      Some(source) if source >= self.len() => Err(Error::UnknownOscillator),
+      Some(source) if source == osc => Err(Error::SelfReference),
+      Some(source) if self.depends_on(source, osc) => Err(Error::Cycle),
+      Some(_) => Ok(()),
+    }
+  }
+
+  /// Whether the output of an oscillator depends on another one through any route
+  fn depends_on(&self, osc: usize, other: usize) -> bool {
+    let mut pending: Vec<usize, MaxOscillators> = Vec::new();
+    let mut visited: Vec<usize, MaxOscillators> = Vec::new();
+    pending.push(osc).unwrap();
+    visited.push(osc).unwrap();
+    while let Some(current) = pending.pop() {
+      for source in self.sources(current).iter().flatten() {
+        if *source == other {
+          return true;
+        }
+        if !visited.contains(source) {
+          visited.push(*source).unwrap();
+          pending.push(*source).unwrap();
+        }
+      }
+    }
+    false
+  }
+
+  fn sources(&self, osc: usize) -> [Option<usize>; 2] {
+    [self.sync_source(osc), self.fm_source(osc)]
+  }
+
+  /// The indices of the oscillators sorted so the sources of every route go before their destinations
+  pub fn processing_order(&self) -> Vec<usize, MaxOscillators> {
+    let mut order: Vec<usize, MaxOscillators> = Vec::new();
+    while order.len() < self.len() {
+      for osc in 0..self.len() {
+        let ready = self
+          .sources(osc)
+          .iter()
+          .flatten()
+          .all(|source| order.contains(source));
+        if !order.contains(&osc) && ready {
+          order.push(osc).unwrap();
+        }
+      }
+    }
+    order
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::program::blocks::osc;
+  use crate::program::{Block, ProgramBuilder};
+
+  fn osc_block(builder: &mut ProgramBuilder<f32>) -> osc::Block {
+    let zero = builder.const_zero();
+    osc::Block {
+      inputs: osc::Inputs {
+        shape: zero,
+        amplitude: zero,
+        amplitude_db: zero,
+        level_db: zero,
+        amp_mod: zero,
+        octaves: zero,
+        semitones: zero,
+        cents: zero,
+        note_pitch: zero,
+        pitch_bend: zero,
+        pitch_mod: zero,
+        freq_mod: zero,
+        fm_mode: zero,
+        sync_mode: zero,
+        sync_in: zero,
+        lfo_sync: zero,
+        lfo1_sync_in: zero,
+        lfo2_sync_in: zero,
+        key_low: zero,
+        key_high: zero,
+        fixed_freq: zero,
+        freq_hz: zero,
+      },
+      output: builder.signal(),
+      sync_out: builder.signal(),
+    }
+  }
+
+  #[test]
+  fn self_reference_is_rejected() {
+    let mut routing = OscRouting::new(4);
+    assert_eq!(routing.set_fm_source(1, Some(1)), Err(Error::SelfReference));
+    assert_eq!(
+      routing.set_sync_source(2, Some(2)),
+      Err(Error::SelfReference)
+    );
+    assert_eq!(routing.fm_source(1), None);
+    assert_eq!(routing.sync_source(2), None);
+    assert_eq!(
+      routing.set_fm_source(4, Some(1)),
+      Err(Error::UnknownOscillator)
+    );
+  }
+
+  #[test]
+  fn cycles_are_rejected() {
+    let mut routing = OscRouting::new(4);
+    routing.set_fm_source(1, Some(0)).unwrap();
+    routing.set_sync_source(2, Some(1)).unwrap();
+    assert_eq!(routing.set_fm_source(0, Some(2)), Err(Error::Cycle));
+    assert_eq!(routing.set_sync_source(0, Some(1)), Err(Error::Cycle));
+    assert_eq!(routing.fm_source(0), None);
+    assert_eq!(routing.sync_source(0), None);
+
+    // removing a route is always valid
+    routing.set_fm_source(1, None).unwrap();
+    routing.set_fm_source(0, Some(2)).unwrap();
+  }
+
+  #[test]
+  fn processing_order_follows_the_routes() {
+    let mut routing = OscRouting::new(4);
+    assert_eq!(&routing.processing_order()[..], &[0, 1, 2, 3]);
+
+    routing.set_fm_source(0, Some(1)).unwrap();
+    routing.set_sync_source(1, Some(3)).unwrap();
+    assert_eq!(&routing.processing_order()[..], &[2, 3, 1, 0]);
+  }
+
+  #[test]
+  fn oscillators_chain() {
+    let mut builder = ProgramBuilder::new();
+    let oscs = [
+      osc_block(&mut builder),
+      osc_block(&mut builder),
+      osc_block(&mut builder),
+    ];
+
+    // OSC3 syncs OSC2, which modulates OSC1, so they are processed in reverse order
+    let mut routing = OscRouting::new(3);
+    routing.set_fm_source(0, Some(1)).unwrap();
+    routing.set_sync_source(1, Some(2)).unwrap();
+    builder.oscillators(&oscs, &routing);
+    let program = builder.build();
+
+    let blocks = program.get_blocks();
+    let processed: std::vec::Vec<osc::Block> = blocks
+      .iter()
+      .filter_map(|block| match block {
+        Block::Osc(osc) => Some(osc.clone()),
+        _ => None,
+      })
+      .collect();
+
+    assert_eq!(processed.len(), 3);
+    assert_eq!(processed[0].output, oscs[2].output);
+    assert_eq!(processed[1].output, oscs[1].output);
+    assert_eq!(processed[1].inputs.sync_in, oscs[2].sync_out);
+    assert_eq!(processed[2].output, oscs[0].output);
+
+    // the FM route is summed to the modulation given to the oscillator
+    let fm = blocks
+      .iter()
+      .find_map(|block| match block {
+        Block::Expr(fm) if fm.output == processed[2].inputs.freq_mod => Some(fm),
+        _ => None,
+      })
+      .unwrap();
+    assert!(fm.reads_signal(oscs[0].inputs.freq_mod));
+    assert!(fm.reads_signal(oscs[1].output));
+    assert!(program.is_fm_carrier(&processed[2]));
+    assert!(!program.is_fm_carrier(&processed[1]));
+  }
+}
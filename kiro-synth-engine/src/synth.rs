@@ -1,24 +1,314 @@
+use core::cmp::Ordering;
 use heapless::consts;
 use heapless::Vec;
 use ringbuf::Consumer;
 use typenum::marker_traits::Unsigned;
 
+use kiro_synth_core::effects::{Processor, WetMeter};
+use kiro_synth_core::filters::biquad::Biquad;
+use kiro_synth_core::funcs::decibels::Decibels;
+use kiro_synth_core::funcs::random::Random;
+use kiro_synth_core::oscillators::lfo::Lfo;
+
 use crate::event::{Event, Message};
 use crate::float::Float;
 use crate::globals::SynthGlobals;
-use crate::program::Program;
-use crate::voice::Voice;
+use crate::program::{InsertOrder, MaxInsertEffects, ParamRef, Program};
+use crate::voice::{Voice, VoiceState};
 
 pub type MaxVoices = consts::U32;
+pub type MaxPrograms = consts::U4;
+pub type MaxKeyZones = consts::U8;
+pub type MaxHeldKeys = consts::U16;
+pub type MaxPendingMessages = consts::U64;
+
+/// Time in seconds to fade in the voices when they start, by default
+pub const DEFAULT_FADE_IN_SECONDS: f64 = 0.002;
+
+/// Time in seconds to fade out the voices when they are freed, by default
+pub const DEFAULT_FADE_OUT_SECONDS: f64 = 0.002;
+
+/// Time in seconds that a voice has to be playing before it can be stolen, by default
+pub const DEFAULT_MIN_STEAL_AGE_SECONDS: f64 = 0.05;
+
+/// Seed of the random pan positions, so the same notes are always panned in the same way
+const RANDOM_PAN_SEED: u64 = 0x5eed_9a4e;
+
+/// Seed of the voices when there is no random seed, so every voice and block still follows its own sequence
+const VOICES_SEED: u64 = 0x5eed_7015;
+
+/// The sequences derived from the random seed, after the ones of the voices
+const RANDOM_PAN_STREAM: u64 = 0x100;
+const MASTER_LFO_STREAM: u64 = 0x101;
+
+/// The note rendered to measure the level of a program, C4, held for the given seconds
+/// and released for as long again
+const AUTO_LEVEL_KEY: u8 = 60;
+const AUTO_LEVEL_NOTE_SECONDS: f64 = 0.5;
+
+/// A range of keys (both inclusive) that triggers voices of one of the synth programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyZone {
+  pub low: u8,
+  pub high: u8,
+  pub program: usize,
+}
+
+impl KeyZone {
+  pub fn new(low: u8, high: u8, program: usize) -> Self {
+    KeyZone { low, high, program }
+  }
+
+  pub fn contains(&self, key: u8) -> bool {
+    self.low <= key && key <= self.high
+  }
+}
+
+/// How long the glide between two notes takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlideMode {
+  /// The same time for any interval
+  Time,
+  /// A constant rate in semitones per second, so larger intervals take proportionally longer
+  Rate,
+}
+
+impl GlideMode {
+  pub fn count() -> usize {
+    2
+  }
+
+  pub fn from<F: Float>(value: F) -> Self {
+    match value.round().to_usize().unwrap_or(0) {
+      1 => GlideMode::Rate,
+      _ => GlideMode::Time,
+    }
+  }
+}
+
+/// Which of the held keys sounds in mono mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotePriority {
+  /// The last key pressed
+  Last,
+  /// The lowest key held
+  Low,
+  /// The highest key held
+  High,
+}
+
+impl NotePriority {
+  pub fn count() -> usize {
+    3
+  }
+
+  pub fn from<F: Float>(value: F) -> Self {
+    match value.round().to_usize().unwrap_or(0) {
+      1 => NotePriority::Low,
+      2 => NotePriority::High,
+      _ => NotePriority::Last,
+    }
+  }
+}
+
+/// What to do with a new note when all the voices are playing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StealPolicy {
+  /// No voice is stolen, the new note is not played
+  Never,
+  /// The oldest voice is stolen once it reaches the minimum age, otherwise the new note is dropped
+  DropNewNote,
+  /// The oldest voice is stolen even when it is younger than the minimum age
+  StealOldest,
+}
+
+/// Synth using single precision floats, suitable for real-time audio.
+/// The params can still be set and reported in double precision with
+/// [`set_param_f64`](Synth::set_param_f64) and [`get_param_f64`](Synth::get_param_f64).
+pub type Synth32<'a> = Synth<'a, f32>;
+
+/// Synth using double precision floats, suitable for offline rendering
+pub type Synth64<'a> = Synth<'a, f64>;
+
+/// Float type used by default, `f64` when the `f64` feature is enabled
+#[cfg(not(feature = "f64"))]
+pub type SynthFloat = f32;
+
+/// Float type used by default, `f64` when the `f64` feature is enabled
+#[cfg(feature = "f64")]
+pub type SynthFloat = f64;
+
+/// Synth using the default float type
+pub type DefaultSynth<'a> = Synth<'a, SynthFloat>;
+
+/// Keeps the amplitude for a gain in dB to only recalculate it when it changes
+struct Gain<F: Float> {
+  db: F,
+  amplitude: F,
+}
+
+impl<F: Float> Gain<F> {
+  fn new() -> Self {
+    Gain {
+      db: F::zero(),
+      amplitude: F::one(),
+    }
+  }
+
+  fn amplitude(&mut self, db: F) -> F {
+    if db != self.db {
+      self.db = db;
+      self.amplitude = Decibels::new(db).to_amplitude();
+    }
+    self.amplitude
+  }
+}
+
+/// The levels of the wet signal of the send effect and the insert effects, by their slots
+struct EffectMeters<F: Float> {
+  enabled: bool,
+  send: WetMeter<F>,
+  inserts: Vec<WetMeter<F>, MaxInsertEffects>,
+}
+
+impl<F: Float> EffectMeters<F> {
+  fn new(sample_rate: F, enabled: bool) -> Self {
+    let mut inserts = Vec::new();
+    while inserts.push(WetMeter::new(sample_rate)).is_ok() {}
+    EffectMeters {
+      enabled,
+      send: WetMeter::new(sample_rate),
+      inserts,
+    }
+  }
+
+  fn reset(&mut self) {
+    self.send.reset();
+    for meter in self.inserts.iter_mut() {
+      meter.reset();
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MasterFilterMode {
+  HighPass,
+  LowPass,
+}
+
+/// Gentle filter of 12 dB/oct for the stereo output, only recalculated when the cutoff changes
+struct MasterFilter<F: Float> {
+  mode: MasterFilterMode,
+  freq: F,
+  left: Biquad<F>,
+  right: Biquad<F>,
+}
+
+impl<F: Float> MasterFilter<F> {
+  fn new(mode: MasterFilterMode) -> Self {
+    MasterFilter {
+      mode,
+      freq: F::zero(),
+      left: Biquad::new(),
+      right: Biquad::new(),
+    }
+  }
+
+  fn process(&mut self, sample_rate: F, freq: F, left: F, right: F) -> (F, F) {
+    if freq != self.freq {
+      self.freq = freq;
+      let freq = freq.max(F::one()).min(sample_rate * F::val(0.49));
+      let q = F::val(core::f64::consts::FRAC_1_SQRT_2);
+      match self.mode {
+        MasterFilterMode::HighPass => self.left.set_high_pass(sample_rate, freq, q),
+        MasterFilterMode::LowPass => self.left.set_low_pass(sample_rate, freq, q),
+      }
+      self.right = self.left.clone();
+    }
+    (self.left.process(left), self.right.process(right))
+  }
+
+  /// Restart from silence when enabled again, and recalculate after a change of sample rate
+  fn reset(&mut self) {
+    self.freq = F::zero();
+    self.left.reset();
+    self.right.reset();
+  }
+}
+
+/// LFO shared by all the voices and running all the time, for modulations of the output such as an auto-pan
+struct MasterLfo<F: Float> {
+  lfo: Lfo<F>,
+  shape: Option<usize>,
+}
+
+impl<F: Float> MasterLfo<F> {
+  fn new(sample_rate: F) -> Self {
+    MasterLfo {
+      lfo: Lfo::new(sample_rate),
+      shape: None,
+    }
+  }
+
+  fn generate(&mut self, globals: &SynthGlobals<F>, shape: usize, rate: F) -> F {
+    if self.shape != Some(shape) {
+      self.shape = Some(shape);
+      let waveform = globals.lfo_waveforms.waveform(shape).clone();
+      self.lfo.set_waveform(waveform);
+    }
+    self.lfo.set_rate(rate);
+    self.lfo.generate()
+  }
+}
 
 pub struct Synth<'a, F: Float> {
   sample_rate: F,
   events: Consumer<Event<F>>,
-  program: Program<'a, F>,
+  /// The messages of the events with a timestamp, with the sample when they are due,
+  /// sorted so the next one is at the end
+  pending_messages: Vec<(u64, Message<F>), MaxPendingMessages>,
+  /// The messages with a timestamp that did not fit in the pending messages
+  dropped_messages: usize,
+  elapsed_samples: u64,
+  /// The programs and the voices are too big to keep the synth on the stack, so they are allocated
+  /// once when the synth is built and never reallocated while playing
+  programs: Box<Vec<Program<'a, F>, MaxPrograms>>,
+  key_zones: Vec<KeyZone, MaxKeyZones>,
   globals: SynthGlobals<F>,
-  voices: Vec<Voice<F>, MaxVoices>,
+  voices: Box<[Voice<F>]>,
   active_voices: Vec<usize, MaxVoices>,
   free_voices: Vec<usize, MaxVoices>,
+  channel_pressure: F,
+  pre_fx_gain: Gain<F>,
+  master_gain: Gain<F>,
+  high_pass: MasterFilter<F>,
+  low_pass: MasterFilter<F>,
+  master_lfo: MasterLfo<F>,
+  master_pan: F,
+  send_effect: Option<&'a mut (dyn Processor<F> + Send)>,
+  insert_effects: Vec<&'a mut (dyn Processor<F> + Send), MaxInsertEffects>,
+  effect_meters: EffectMeters<F>,
+  mono: bool,
+  mono_voice: Option<usize>,
+  held_keys: Vec<u8, MaxHeldKeys>,
+  note_priority: NotePriority,
+  glide_time: F,
+  glide_mode: GlideMode,
+  glide_rate: F,
+  glide_threshold: F,
+  legato_retrigger_velocity: F,
+  poly_glide: bool,
+  /// The program and the pitch of the last voice released in poly mode
+  last_released: Option<(usize, F)>,
+  min_steal_age: F,
+  min_steal_age_samples: usize,
+  steal_policy: StealPolicy,
+  quality_scaling: bool,
+  quality_threshold: usize,
+  random_pan_amount: F,
+  random_pan: Random,
+  random_seed: Option<u64>,
+  output_channels: usize,
 }
 
 impl<'a, F: Float> Synth<'a, F> {
@@ -26,149 +316,3774 @@ impl<'a, F: Float> Synth<'a, F> {
     sample_rate: F,
     events: Consumer<Event<F>>,
     program: Program<'a, F>,
-    globals: SynthGlobals<F>,
+    mut globals: SynthGlobals<F>,
   ) -> Self {
-    let mut voices: Vec<Voice<F>, MaxVoices> = Vec::new();
+    globals.transport.set_sample_rate(sample_rate);
+    let fade_in_seconds = F::val(DEFAULT_FADE_IN_SECONDS);
+    globals.fade_in_samples = Self::seconds_to_samples(fade_in_seconds, sample_rate);
+    let fade_out_seconds = F::val(DEFAULT_FADE_OUT_SECONDS);
+    globals.fade_out_samples = Self::seconds_to_samples(fade_out_seconds, sample_rate);
+    let min_steal_age = F::val(DEFAULT_MIN_STEAL_AGE_SECONDS);
+
+    let mut voices = std::vec::Vec::with_capacity(MaxVoices::to_usize());
     let mut free_voices: Vec<usize, MaxVoices> = Vec::new();
     for index in 0..MaxVoices::to_usize() {
-      drop(voices.push(Voice::new(sample_rate, &program)));
+      voices.push(Voice::new(sample_rate, 0, &program));
       free_voices.push(MaxVoices::to_usize() - index - 1).unwrap();
     }
+    let voices = voices.into_boxed_slice();
 
-    Synth {
+    let mut programs = Box::new(Vec::new());
+    drop(programs.push(program));
+
+    let mut synth = Synth {
       sample_rate,
       events,
-      program,
+      pending_messages: Vec::new(),
+      dropped_messages: 0,
+      elapsed_samples: 0,
+      programs,
+      key_zones: Vec::new(),
       globals,
       voices,
       active_voices: Vec::new(),
       free_voices,
+      channel_pressure: F::zero(),
+      pre_fx_gain: Gain::new(),
+      master_gain: Gain::new(),
+      high_pass: MasterFilter::new(MasterFilterMode::HighPass),
+      low_pass: MasterFilter::new(MasterFilterMode::LowPass),
+      master_lfo: MasterLfo::new(sample_rate),
+      master_pan: F::zero(),
+      send_effect: None,
+      insert_effects: Vec::new(),
+      effect_meters: EffectMeters::new(sample_rate, false),
+      mono: false,
+      mono_voice: None,
+      held_keys: Vec::new(),
+      note_priority: NotePriority::Last,
+      glide_time: F::zero(),
+      glide_mode: GlideMode::Time,
+      glide_rate: F::zero(),
+      glide_threshold: F::zero(),
+      legato_retrigger_velocity: F::zero(),
+      poly_glide: false,
+      last_released: None,
+      min_steal_age,
+      min_steal_age_samples: Self::seconds_to_samples(min_steal_age, sample_rate),
+      steal_policy: StealPolicy::Never,
+      quality_scaling: false,
+      quality_threshold: MaxVoices::to_usize(),
+      random_pan_amount: F::zero(),
+      random_pan: Random::new(RANDOM_PAN_SEED),
+      random_seed: None,
+      output_channels: 2,
+    };
+    for index in 0..synth.voices.len() {
+      synth.seed_voice(index);
     }
+    synth
   }
 
   pub fn get_sample_rate(&self) -> F {
     self.sample_rate
   }
 
+  /// Change the sample rate, such as when the audio device changes it, keeping the times given in seconds.
+  /// The playing voices are stopped, and the effects have to be updated separately.
+  pub fn set_sample_rate(&mut self, sample_rate: F) {
+    let fade_in_seconds = self.get_fade_in_time();
+    let fade_out_seconds = self.get_fade_out_time();
+    let fixed_length_seconds = self.get_fixed_length();
+    self.free_all_voices();
+    self.mono_voice = None;
+
+    self.sample_rate = sample_rate;
+    self.globals.transport.set_sample_rate(sample_rate);
+    self.high_pass.reset();
+    self.low_pass.reset();
+    self.master_lfo.lfo.set_sample_rate(sample_rate);
+    self.effect_meters = EffectMeters::new(sample_rate, self.effect_meters.enabled);
+    self.set_fade_in_time(fade_in_seconds);
+    self.set_fade_out_time(fade_out_seconds);
+    self.set_fixed_length(fixed_length_seconds);
+    self.set_min_steal_age(self.min_steal_age);
+    for voice in self.voices.iter_mut() {
+      let program_index = voice.get_program_index();
+      *voice = Voice::new(sample_rate, program_index, &self.programs[program_index]);
+    }
+    for index in 0..self.voices.len() {
+      self.seed_voice(index);
+    }
+  }
+
+  /// The pan of the output in the last frame, including the modulation of the global LFO
+  pub fn get_master_pan(&self) -> F {
+    self.master_pan
+  }
+
+  /// The main program, the one receiving the param and modulation messages
   pub fn get_program(&self) -> &Program<'a, F> {
-    &self.program
+    &self.programs[0]
   }
 
-  pub fn get_last_voice(&self) -> Option<&Voice<F>> {
-    self.active_voices.last().map(|index| &self.voices[*index])
+  /// Add another program that can be assigned to key zones. Returns its index.
+  /// It takes an even share of the voices, as many as the synth has divided by the maximum number of programs,
+  /// and more can be reserved for it with [`reserve_voices`](Self::reserve_voices).
+  pub fn add_program(&mut self, program: Program<'a, F>) -> Option<usize> {
+    self.programs.push(program).ok()?;
+    let program_index = self.programs.len() - 1;
+    let share = MaxVoices::to_usize() / MaxPrograms::to_usize();
+    self.reserve_voices(program_index, share);
+    Some(program_index)
   }
 
-  pub fn get_num_active_voices(&self) -> usize {
-    self.active_voices.len()
+  /// Build up to `count` of the free voices for a program before playing it. The voices are taken from
+  /// the ones built for other programs, the ones with more free voices first, as the notes only play
+  /// the voices already built for their program. Returns how many free voices are ready for the program.
+  pub fn reserve_voices(&mut self, program_index: usize, count: usize) -> usize {
+    if program_index >= self.programs.len() {
+      return 0;
+    }
+    let mut free_counts: Vec<usize, MaxPrograms> = Vec::new();
+    free_counts.resize(self.programs.len(), 0).unwrap();
+    for index in self.free_voices.iter() {
+      free_counts[self.voices[*index].get_program_index()] += 1;
+    }
+    let mut ready = free_counts[program_index];
+    while ready < count {
+      let donor = (0..self.programs.len())
+        .filter(|donor| *donor != program_index && free_counts[*donor] > 0)
+        .max_by_key(|donor| free_counts[*donor]);
+      let donor = match donor {
+        Some(donor) => donor,
+        None => break,
+      };
+      // the voices on top of the free ones are the first to be allocated
+      let voices = &self.voices;
+      let position = self
+        .free_voices
+        .iter()
+        .rposition(|index| voices[*index].get_program_index() == donor);
+      if let Some(index) = position.map(|position| self.free_voices[position]) {
+        let program = &self.programs[program_index];
+        self.voices[index] = Voice::new(self.sample_rate, program_index, program);
+        self.seed_voice(index);
+        free_counts[donor] -= 1;
+        ready += 1;
+      }
+    }
+    ready
   }
 
-  pub fn prepare(&mut self) {
-    while let Some(Event {
-      timestamp: _,
-      message,
-    }) = self.events.pop()
-    {
-      match message {
-        Message::NoteOn { key, velocity } => self.note_on(key, velocity),
-        Message::NoteOff { key, velocity } => self.note_off(key, velocity),
-        Message::ParamValue { param_ref, value } => {
-          if let Some((_, param)) = self.program.get_param_mut(param_ref) {
-            println!("{} = {:?}", param.id, value);
-            param.value.set(value)
-          }
-        }
-        Message::ParamChange { param_ref, change } => {
-          if let Some((_, param)) = self.program.get_param_mut(param_ref) {
-            let value: F = param.value.get() + change;
-            let value = value.max(param.values.min).min(param.values.max);
-            println!("{} = {:?}", param.id, value);
-            param.value.set(value);
-          }
-        }
-        Message::ModulationUpdate {
-          source_ref,
-          param_ref,
-          amount,
-        } => {
-          if let Some(source) = self.program.get_source(source_ref) {
-            let source_id = source.id;
-            if let Some((_, param)) = self.program.get_param(param_ref) {
-              println!("{} -> {} {:?}", source_id, param.id, amount);
-            }
-            self
-              .program
-              .update_modulation(param_ref, source_ref, amount)
-              .unwrap(); // TODO handle error
+  /// Set a param of the main program from a normalized value between 0.0 and 1.0
+  pub fn set_param_normalized(&mut self, param_ref: ParamRef, t: F) {
+    if let Some((_, param)) = self.programs[0].get_param_mut(param_ref) {
+      let value = param.values.denormalize(t);
+      param.value.set(value);
+    }
+  }
+
+  /// Get a param of the main program as a normalized value between 0.0 and 1.0
+  pub fn get_param_normalized(&self, param_ref: ParamRef) -> Option<F> {
+    self.programs[0]
+      .get_param(param_ref)
+      .map(|(_, param)| param.values.normalize(param.value.get()))
+  }
+
+  /// Set a param of the main program from a double precision value, such as from the UI or the automation,
+  /// while the audio path runs with the precision of the synth. The value is limited to the range of the param
+  /// before the conversion, and reported back as it was set by [`get_param_f64`](Self::get_param_f64).
+  pub fn set_param_f64(&mut self, param_ref: ParamRef, value: f64) {
+    if let Some((_, param)) = self.programs[0].get_param_mut(param_ref) {
+      let min = param.values.min.to_f64().unwrap_or(value);
+      let max = param.values.max.to_f64().unwrap_or(value);
+      let value = value.max(min).min(max);
+      param.value.set(F::val(value));
+      param.precise_value = Some(value);
+    }
+  }
+
+  /// Get a param of the main program in double precision. It is the value given to
+  /// [`set_param_f64`](Self::set_param_f64) unless the param has been changed in other ways since then.
+  pub fn get_param_f64(&self, param_ref: ParamRef) -> Option<f64> {
+    let (_, param) = self.programs[0].get_param(param_ref)?;
+    let value = param.value.get();
+    match param.precise_value {
+      Some(precise) if F::val(precise) == value => Some(precise),
+      _ => value.to_f64(),
+    }
+  }
+
+  /// Perturb the continuous params of the main program by a random fraction of their normalized range
+  /// scaled by the amount (from 0.0 to 1.0). The discrete params, such as the waveforms or the modes, are not changed.
+  /// The result is always the same for a given patch and seed.
+  pub fn randomize(&mut self, amount: F, rng_seed: u64) {
+    self.randomize_params(amount, rng_seed, false);
+  }
+
+  /// Like [`randomize`](Self::randomize) but changing also the discrete params
+  pub fn randomize_all(&mut self, amount: F, rng_seed: u64) {
+    self.randomize_params(amount, rng_seed, true);
+  }
+
+  fn randomize_params(&mut self, amount: F, rng_seed: u64, discrete: bool) {
+    if amount <= F::zero() {
+      return;
+    }
+
+    let mut random = Random::new(rng_seed);
+    for param in self.programs[0].get_params_mut().iter_mut() {
+      let offset = random.bipolar::<F>() * amount;
+      if discrete || !param.values.is_discrete() {
+        let t = param.values.normalize(param.value.get()) + offset;
+        let value = param.values.denormalize(t);
+        param.value.set(value);
+      }
+    }
+  }
+
+  /// Set the params of the main program interpolating between the params with the same id in two patches,
+  /// from `a` (0.0) to `b` (1.0). The continuous params are blended linearly and the discrete ones switch at 0.5.
+  /// A param found in only one of the patches takes its value from it, and a param found in none is not changed.
+  pub fn morph(&mut self, a: &Program<F>, b: &Program<F>, t: F) {
+    let t = t.max(F::zero()).min(F::one());
+    let half = F::val(0.5);
+    for param in self.programs[0].get_params_mut().iter_mut() {
+      let value_a = Self::param_value_by_id(a, param.id);
+      let value_b = Self::param_value_by_id(b, param.id);
+      let value = match (value_a, value_b) {
+        (Some(value_a), Some(value_b)) if param.values.is_discrete() => {
+          if t < half {
+            value_a
+          } else {
+            value_b
           }
         }
-        Message::ModulationDelete {
-          source_ref,
-          param_ref,
-        } => {
-          self
-            .program
-            .delete_modulation(param_ref, source_ref)
-            .unwrap(); // TODO handle error
-        }
-      }
+        (Some(value_a), Some(value_b)) => value_a + (value_b - value_a) * t,
+        (Some(value), None) | (None, Some(value)) => value,
+        (None, None) => continue,
+      };
+      let value = value.max(param.values.min).min(param.values.max);
+      param.value.set(value);
     }
   }
 
-  fn note_on(&mut self, key: u8, velocity: F) {
-    if let Some(index) = self.allocate_voice(key, velocity) {
-      self.active_voices.push(index).unwrap();
-      self.voices[index].note_on(&self.program, key, velocity);
-      println!("{:?}", self.active_voices);
+  fn param_value_by_id(program: &Program<F>, id: &str) -> Option<F> {
+    let param_ref = program.get_param_ref(id)?;
+    Some(program.get_param_signal(param_ref).get())
+  }
+
+  /// In mono mode only one voice of the main program is played. Pressing a key while others are held
+  /// glides to the new note without retriggering, and releasing it glides back to the last held key.
+  pub fn set_mono(&mut self, mono: bool) {
+    self.mono = mono;
+    self.mono_voice = None;
+    self.held_keys.clear();
+  }
+
+  pub fn is_mono(&self) -> bool {
+    self.mono
+  }
+
+  /// Choose which of the held keys sounds in mono mode, as in different classic monosynths
+  pub fn set_note_priority(&mut self, priority: NotePriority) {
+    self.note_priority = priority;
+  }
+
+  pub fn get_note_priority(&self) -> NotePriority {
+    self.note_priority
+  }
+
+  /// Set the time in seconds to glide between notes in mono mode
+  pub fn set_glide_time(&mut self, seconds: F) {
+    self.glide_time = seconds.max(F::zero());
+  }
+
+  pub fn get_glide_time(&self) -> F {
+    self.glide_time
+  }
+
+  /// Choose between gliding during the glide time or at the glide rate
+  pub fn set_glide_mode(&mut self, mode: GlideMode) {
+    self.glide_mode = mode;
+  }
+
+  pub fn get_glide_mode(&self) -> GlideMode {
+    self.glide_mode
+  }
+
+  /// Set the speed of the glide in semitones per second for the rate mode. Zero disables the glide.
+  pub fn set_glide_rate(&mut self, semitones_per_second: F) {
+    self.glide_rate = semitones_per_second.max(F::zero());
+  }
+
+  pub fn get_glide_rate(&self) -> F {
+    self.glide_rate
+  }
+
+  /// Set the largest interval in semitones that glides, larger ones jump to the new note.
+  /// Zero glides for any interval.
+  pub fn set_glide_threshold(&mut self, semitones: F) {
+    self.glide_threshold = semitones.max(F::zero());
+  }
+
+  pub fn get_glide_threshold(&self) -> F {
+    self.glide_threshold
+  }
+
+  /// Set the velocity from which an overlapping note in mono mode retriggers the envelopes,
+  /// so the accented notes are articulated while the soft ones are played legato.
+  /// Zero never retriggers.
+  pub fn set_legato_retrigger_velocity(&mut self, velocity: F) {
+    self.legato_retrigger_velocity = velocity.max(F::zero()).min(F::one());
+  }
+
+  pub fn get_legato_retrigger_velocity(&self) -> F {
+    self.legato_retrigger_velocity
+  }
+
+  /// In poly mode, start every note at the pitch of the last released voice of its program and glide
+  /// to its own pitch, with the glide time or rate. The first note has nothing to glide from.
+  pub fn set_poly_glide(&mut self, enabled: bool) {
+    self.poly_glide = enabled;
+  }
+
+  pub fn is_poly_glide(&self) -> bool {
+    self.poly_glide
+  }
+
+  /// Set the minimum time in seconds that a voice has to be playing before it can be stolen
+  /// for a new note, to avoid the artifacts of cutting the notes that just started.
+  pub fn set_min_steal_age(&mut self, seconds: F) {
+    self.min_steal_age = seconds.max(F::zero());
+    self.min_steal_age_samples = (self.min_steal_age * self.sample_rate)
+      .round()
+      .to_usize()
+      .unwrap_or(0);
+  }
+
+  pub fn get_min_steal_age(&self) -> F {
+    self.min_steal_age
+  }
+
+  /// Allow stealing voices for the new notes when all of them are busy, which is disabled by default
+  pub fn set_steal_policy(&mut self, policy: StealPolicy) {
+    self.steal_policy = policy;
+  }
+
+  pub fn get_steal_policy(&self) -> StealPolicy {
+    self.steal_policy
+  }
+
+  /// Set the time in seconds to fade in the voices when they start, so they don't click
+  /// even with an instant attack. Zero disables the fade in.
+  pub fn set_fade_in_time(&mut self, seconds: F) {
+    let seconds = seconds.max(F::zero());
+    self.globals.fade_in_samples = Self::seconds_to_samples(seconds, self.sample_rate);
+  }
+
+  pub fn get_fade_in_time(&self) -> F {
+    F::val(self.globals.fade_in_samples) / self.sample_rate
+  }
+
+  /// Set the time in seconds to fade out the voices when their envelope finishes,
+  /// instead of cutting their output. Zero disables the fade out.
+  pub fn set_fade_out_time(&mut self, seconds: F) {
+    let seconds = seconds.max(F::zero());
+    self.globals.fade_out_samples = Self::seconds_to_samples(seconds, self.sample_rate);
+  }
+
+  pub fn get_fade_out_time(&self) -> F {
+    F::val(self.globals.fade_out_samples) / self.sample_rate
+  }
+
+  /// Set a fixed length in seconds for the notes, after which the voices are released even if
+  /// the note off doesn't arrive, such as for drum hits driven by triggers. Zero waits for the note off.
+  pub fn set_fixed_length(&mut self, seconds: F) {
+    let seconds = seconds.max(F::zero());
+    self.globals.fixed_length_samples = Self::seconds_to_samples(seconds, self.sample_rate);
+  }
+
+  pub fn get_fixed_length(&self) -> F {
+    F::val(self.globals.fixed_length_samples) / self.sample_rate
+  }
+
+  /// Pan every new note to a random position within some range, from 0.0 (centered) to 1.0
+  /// (anywhere from left to right). The positions follow a fixed sequence from the seed, so
+  /// renders are reproducible.
+  pub fn set_random_pan_amount(&mut self, amount: F) {
+    self.random_pan_amount = amount.max(F::zero()).min(F::one());
+    self.random_pan.set_seed(self.random_pan_seed());
+  }
+
+  pub fn get_random_pan_amount(&self) -> F {
+    self.random_pan_amount
+  }
+
+  /// Output a single channel with 1, summing the left and right at -3 dB so hard panned voices keep
+  /// their power, or stereo with 2. In mono both channels of the frames from `process` are the same.
+  pub fn set_output_channels(&mut self, channels: usize) {
+    self.output_channels = if channels > 1 { 2 } else { 1 };
+  }
+
+  pub fn get_output_channels(&self) -> usize {
+    self.output_channels
+  }
+
+  /// Make every random behaviour, such as the drift of the oscillators, the random pan or the sample
+  /// and hold LFOs, follow its own sequence derived from a single seed, so two renders with the same
+  /// seed and events are identical. The voices built later are seeded from it too.
+  pub fn set_random_seed(&mut self, seed: u64) {
+    self.random_seed = Some(seed);
+    self.random_pan.set_seed(self.random_pan_seed());
+    let master_lfo_seed = Random::derive_seed(seed, MASTER_LFO_STREAM);
+    self.master_lfo.lfo.set_seed(master_lfo_seed);
+    for index in 0..self.voices.len() {
+      self.seed_voice(index);
     }
   }
 
-  fn note_off(&mut self, key: u8, _velocity: F) {
-    for active_voice_index in 0..self.active_voices.len() {
-      let voice_index = self.active_voices[active_voice_index];
-      let voice = &mut self.voices[voice_index];
-      if voice.get_key(&self.program) == key {
-        voice.note_off(&self.program)
-      }
+  pub fn get_random_seed(&self) -> Option<u64> {
+    self.random_seed
+  }
+
+  fn random_pan_seed(&self) -> u64 {
+    match self.random_seed {
+      Some(seed) => Random::derive_seed(seed, RANDOM_PAN_STREAM),
+      None => RANDOM_PAN_SEED,
     }
   }
 
-  fn allocate_voice(&mut self, _key: u8, _velocity: F) -> Option<usize> {
-    self.free_voices.pop()
+  fn seed_voice(&mut self, index: usize) {
+    let seed = self.random_seed.unwrap_or(VOICES_SEED);
+    self.voices[index].set_random_seed(Random::derive_seed(seed, index as u64));
   }
 
-  pub fn process(&mut self) -> (F, F) {
-    let (mut left, mut right) = (F::zero(), F::zero());
+  fn next_random_pan(&mut self) -> F {
+    if self.random_pan_amount > F::zero() {
+      self.random_pan.bipolar::<F>() * self.random_pan_amount
+    } else {
+      F::zero()
+    }
+  }
 
-    let mut freed_voices = false;
-    let mut active_voice_index = 0;
-    while active_voice_index < self.active_voices.len() {
-      let voice_index = self.active_voices[active_voice_index];
-      let voice = &mut self.voices[voice_index];
+  fn seconds_to_samples(seconds: F, sample_rate: F) -> usize {
+    (seconds * sample_rate).round().to_usize().unwrap_or(0)
+  }
 
-      voice.process(&mut self.program, &self.globals);
-      let (voice_left, voice_right) = voice.output(&self.program);
-      left = left + voice_left;
-      right = right + voice_right;
+  /// Set the amount of the slow random drift of the pitch of the oscillators, emulating analog
+  /// instability. From 0.0 (steady) to 1.0
+  pub fn set_warmth(&mut self, warmth: F) {
+    self.globals.warmth = warmth.max(F::zero()).min(F::one());
+  }
 
-      if voice.is_off(&self.program) {
-        self.active_voices.swap_remove(active_voice_index);
-        self.free_voices.push(voice_index).unwrap();
-        freed_voices = true;
-      } else {
-        active_voice_index += 1;
+  pub fn get_warmth(&self) -> F {
+    self.globals.warmth
+  }
+
+  /// Reduce the quality of the oscillators, using their cheaper waveforms, while there are more
+  /// active voices than the threshold, to keep the CPU usage bounded. The full quality is restored
+  /// once the polyphony drops back to the threshold.
+  pub fn set_quality_scaling(&mut self, enabled: bool, threshold: usize) {
+    self.quality_scaling = enabled;
+    self.quality_threshold = threshold;
+  }
+
+  pub fn get_quality_scaling(&self) -> (bool, usize) {
+    (self.quality_scaling, self.quality_threshold)
+  }
+
+  /// Whether the voices are currently using the reduced quality
+  pub fn is_quality_reduced(&self) -> bool {
+    self.globals.reduced_quality
+  }
+
+  /// Run the oscillators modulated by another oscillator at several times the sample rate,
+  /// so deep FM does not alias. It multiplies the CPU usage of those oscillators.
+  pub fn set_fm_oversampling(&mut self, enabled: bool) {
+    self.globals.fm_oversampling = enabled;
+  }
+
+  pub fn is_fm_oversampling(&self) -> bool {
+    self.globals.fm_oversampling
+  }
+
+  /// Level match the presets while browsing them, by rendering a C4 note and setting the master
+  /// gain so its peak is at `target_dbfs`. It returns the new gain in dB, or `None` when there is
+  /// no master gain param or the note is silent.
+  ///
+  /// The note is rendered by a scratch voice of the first program, without the effects, the master
+  /// filters nor the transport, so the state of the synth is kept. It is too slow to be called while
+  /// a host is playing the synth.
+  pub fn auto_level(&mut self, target_dbfs: F) -> Option<F> {
+    let gain_ref = self.programs[0].master().gain?;
+    let program = &mut self.programs[0];
+    let pre_fx_gain = Self::param_value(program, program.master().pre_fx_gain, F::zero());
+
+    let mut voice = Voice::new(self.sample_rate, 0, program);
+    voice.note_on(program, AUTO_LEVEL_KEY, F::one(), F::zero());
+    let note_seconds = F::val(AUTO_LEVEL_NOTE_SECONDS);
+    let note_samples = Self::seconds_to_samples(note_seconds, self.sample_rate);
+    let mut peak = F::zero();
+    for index in 0..note_samples * 2 {
+      if index == note_samples {
+        voice.note_off(program);
       }
+      voice.process(program, &self.globals);
+      let (left, right) = voice.output(program);
+      peak = peak.max(left.abs()).max(right.abs());
     }
 
-    if freed_voices {
-      println!("{:?}", self.active_voices);
+    if peak <= F::zero() {
+      return None;
     }
+    let (_, param) = program.get_param_mut(gain_ref)?;
+    let gain = target_dbfs - pre_fx_gain - Decibels::from_amplitude(peak).value();
+    let gain = gain.max(param.values.min).min(param.values.max);
+    param.value.set(gain);
+    Some(gain)
+  }
 
-    self.program.update_params();
+  /// Set the tempo of the transport in beats per minute
+  pub fn set_tempo(&mut self, tempo: F) {
+    self.globals.transport.set_tempo(tempo);
+  }
 
-    (left, right)
+  pub fn get_tempo(&self) -> F {
+    self.globals.transport.get_tempo()
   }
-}
 
-pub struct VoiceIter<'a, F: Float + 'a, I>(I)
-where
-  I: Iterator<Item = &'a Voice<F>>;
+  /// The position of the transport in beats
+  pub fn get_beats(&self) -> F {
+    self.globals.transport.get_beats()
+  }
+
+  /// Set the effect shared by all the voices through the send/return bus.
+  /// Only its wet signal is used as the return, its dry/wet mix is ignored.
+  pub fn set_send_effect(&mut self, effect: &'a mut (dyn Processor<F> + Send)) {
+    self.send_effect = Some(effect);
+    self.effect_meters.send.reset();
+  }
+
+  /// Set the effect applied to the mix of all the voices, after the send/return bus and before the master gain.
+  /// It is synced with the transport, so it can be a rhythmic effect such as [`Gate`].
+  /// It replaces all the insert effects added before.
+  ///
+  /// [`Gate`]: kiro_synth_core::effects::gate::Gate
+  pub fn set_insert_effect(&mut self, effect: &'a mut (dyn Processor<F> + Send)) {
+    self.insert_effects.clear();
+    drop(self.insert_effects.push(effect));
+    self.effect_meters.reset();
+  }
+
+  /// Add an effect to the chain of insert effects, returning its slot, or `None` when the chain is full.
+  /// The effects process the output in the order of their slots unless the program gives another one.
+  pub fn add_insert_effect(&mut self, effect: &'a mut (dyn Processor<F> + Send)) -> Option<usize> {
+    let slot = self.insert_effects.len();
+    let slot = self.insert_effects.push(effect).ok().map(|_| slot)?;
+    self.effect_meters.inserts[slot].reset();
+    Some(slot)
+  }
+
+  /// Remove the effect in a slot, the effects in the slots after it move one slot down
+  pub fn remove_insert_effect(&mut self, slot: usize) -> Option<&'a mut (dyn Processor<F> + Send)> {
+    if slot >= self.insert_effects.len() {
+      return None;
+    }
+    let order: InsertOrder = self
+      .insert_order()
+      .iter()
+      .filter_map(|other| match (*other as usize).cmp(&slot) {
+        Ordering::Less => Some(*other),
+        Ordering::Equal => None,
+        Ordering::Greater => Some(other - 1),
+      })
+      .collect();
+    self.programs[0].set_insert_order(&order);
+
+    let mut after: Vec<_, MaxInsertEffects> = Vec::new();
+    while self.insert_effects.len() > slot + 1 {
+      drop(after.push(self.insert_effects.pop().unwrap()));
+    }
+    let removed = self.insert_effects.pop();
+    while let Some(effect) = after.pop() {
+      drop(self.insert_effects.push(effect));
+    }
+    // the meters would not follow the effects moved down
+    self.effect_meters.reset();
+    removed
+  }
+
+  pub fn get_num_insert_effects(&self) -> usize {
+    self.insert_effects.len()
+  }
+
+  /// The slots of the insert effects in the order they process the output
+  pub fn insert_order(&self) -> InsertOrder {
+    let num_slots = self.insert_effects.len();
+    let mut order = InsertOrder::new();
+    for slot in self.programs[0].get_insert_order().iter() {
+      if (*slot as usize) < num_slots && !order.contains(slot) {
+        order.push(*slot).ok();
+      }
+    }
+    for slot in 0..num_slots as u8 {
+      if !order.contains(&slot) {
+        order.push(slot).ok();
+      }
+    }
+    order
+  }
+
+  /// Move the insert effect processing at one position to another one, shifting the ones in between
+  pub fn move_insert_effect(&mut self, from: usize, to: usize) {
+    let mut order = self.insert_order();
+    if from < order.len() && to < order.len() {
+      let slot = order[from];
+      if from < to {
+        order.copy_within(from + 1..=to, from);
+      } else {
+        order.copy_within(to..from, to + 1);
+      }
+      order[to] = slot;
+      self.programs[0].set_insert_order(&order);
+    }
+  }
+
+  /// Measure the level of the wet signal of the effects, which adds some processing per frame.
+  /// The meters are reset when it is disabled.
+  pub fn set_effect_metering(&mut self, enabled: bool) {
+    self.effect_meters.enabled = enabled;
+    if !enabled {
+      self.effect_meters.reset();
+    }
+  }
+
+  pub fn is_effect_metering(&self) -> bool {
+    self.effect_meters.enabled
+  }
+
+  /// The level of the wet signal of the send effect, measured while the effect metering is enabled
+  pub fn get_send_effect_meter(&self) -> &WetMeter<F> {
+    &self.effect_meters.send
+  }
+
+  /// The level of the wet signal of the insert effect in a slot, before its dry/wet mix.
+  /// It is measured while the effect metering is enabled.
+  pub fn get_insert_effect_meter(&self, slot: usize) -> Option<&WetMeter<F>> {
+    self
+      .effect_meters
+      .inserts
+      .get(slot)
+      .filter(|_| slot < self.insert_effects.len())
+  }
+
+  /// The delay in samples added to the output by the processing, so a host can compensate it.
+  /// Only the stages in series with the output add to it, the send/return bus runs in parallel
+  /// with the dry signal and does not delay it.
+  pub fn latency_samples(&self) -> usize {
+    self
+      .insert_effects
+      .iter()
+      .map(|effect| effect.get_latency_samples())
+      .sum()
+  }
+
+  pub fn get_key_zones(&self) -> &[KeyZone] {
+    self.key_zones.as_ref()
+  }
+
+  /// Split or layer the keyboard. Overlapping zones are layered and keys out of any zone are not played.
+  /// Without key zones the main program is played by the whole keyboard.
+  pub fn set_key_zones(&mut self, key_zones: &[KeyZone]) {
+    self.key_zones.clear();
+    for key_zone in key_zones.iter().take(MaxKeyZones::to_usize()) {
+      self.key_zones.push(*key_zone).unwrap();
+    }
+  }
+
+  pub fn get_last_voice(&self) -> Option<&Voice<F>> {
+    self.active_voices.last().map(|index| &self.voices[*index])
+  }
+
+  /// Number of voices playing or releasing
+  pub fn get_num_active_voices(&self) -> usize {
+    self.active_voices.len()
+  }
+
+  /// Number of voices whose key has been released but are still sounding
+  pub fn get_num_releasing_voices(&self) -> usize {
+    self
+      .active_voices
+      .iter()
+      .filter(|index| self.voices[**index].get_state() == VoiceState::Releasing)
+      .count()
+  }
+
+  pub fn prepare(&mut self) {
+    while let Some(Event { timestamp, message }) = self.events.pop() {
+      if timestamp == 0 {
+        self.handle_message(message);
+      } else {
+        self.schedule_message(timestamp, message);
+      }
+    }
+  }
+
+  /// Number of messages with a timestamp dropped because there was no room to keep them until they were due
+  pub fn get_num_dropped_messages(&self) -> usize {
+    self.dropped_messages
+  }
+
+  /// Keep a message to apply it some microseconds later. When there is no room for it, a pending param value
+  /// superseded by a later one is dropped to make room, otherwise the message itself is dropped and counted,
+  /// so the messages are never applied before they are due nor out of order.
+  fn schedule_message(&mut self, delay_us: u64, message: Message<F>) {
+    let sample_rate = self.sample_rate.to_f64().unwrap_or_default();
+    let due = self.elapsed_samples + (delay_us as f64 * sample_rate * 1e-6).round() as u64;
+    let is_full = self.pending_messages.len() == self.pending_messages.capacity();
+    if is_full && !self.drop_superseded_param_value(due, &message) {
+      self.dropped_messages += 1;
+      return;
+    }
+    drop(self.pending_messages.push((due, message)));
+    // the messages due at the same sample keep the order they were received
+    let mut index = self.pending_messages.len() - 1;
+    while index > 0 && self.pending_messages[index - 1].0 <= due {
+      self.pending_messages.swap(index - 1, index);
+      index -= 1;
+    }
+  }
+
+  /// Drop the next pending param value that another one of the same param would overwrite afterwards,
+  /// either pending or the message about to be scheduled at `due`. Returns whether there was one.
+  fn drop_superseded_param_value(&mut self, due: u64, message: &Message<F>) -> bool {
+    // the pending messages are sorted from the last to be applied to the next one
+    let pending = &self.pending_messages;
+    let superseded = (0..pending.len())
+      .rev()
+      .find(|index| match pending[*index] {
+        (pending_due, Message::ParamValue { param_ref, .. }) => {
+          pending[..*index]
+            .iter()
+            .any(|(_, later)| Self::is_param_value(later, param_ref))
+            || (due >= pending_due && Self::is_param_value(message, param_ref))
+        }
+        _ => false,
+      });
+    if let Some(index) = superseded {
+      self.remove_pending_message(index);
+    }
+    superseded.is_some()
+  }
+
+  fn is_param_value(message: &Message<F>, param_ref: ParamRef) -> bool {
+    match message {
+      Message::ParamValue {
+        param_ref: other, ..
+      } => *other == param_ref,
+      _ => false,
+    }
+  }
+
+  fn remove_pending_message(&mut self, index: usize) {
+    self.pending_messages[index..].rotate_left(1);
+    self.pending_messages.pop();
+  }
+
+  fn cancel_param_values(&mut self, param_ref: ParamRef) {
+    let mut index = self.pending_messages.len();
+    while index > 0 {
+      index -= 1;
+      if Self::is_param_value(&self.pending_messages[index].1, param_ref) {
+        self.remove_pending_message(index);
+      }
+    }
+  }
+
+  fn apply_pending_messages(&mut self) {
+    while let Some(&(due, _)) = self.pending_messages.last() {
+      if due > self.elapsed_samples {
+        break;
+      }
+      if let Some((_, message)) = self.pending_messages.pop() {
+        self.handle_message(message);
+      }
+    }
+  }
+
+  /// Apply a message straight away, for the events that come from the audio thread itself
+  pub fn handle_message(&mut self, message: Message<F>) {
+    match message {
+      Message::NoteOn { key, velocity } => self.note_on(key, velocity),
+      Message::NoteOff { key, velocity } => self.note_off(key, velocity),
+      Message::AllNotesOff => self.all_notes_off(),
+      Message::AllSoundOff => self.all_sound_off(),
+      Message::Tempo { bpm } => self.set_tempo(bpm),
+      Message::ChannelPressure { pressure } => self.channel_pressure(pressure),
+      Message::PolyPressure { key, pressure } => self.poly_pressure(key, pressure),
+      Message::ParamValue { param_ref, value } => {
+        if let Some((_, param)) = self.programs[0].get_param_mut(param_ref) {
+          param.value.set(value)
+        }
+      }
+      Message::ParamChange { param_ref, change } => {
+        if let Some((_, param)) = self.programs[0].get_param_mut(param_ref) {
+          let value: F = param.value.get() + change;
+          let value = value.max(param.values.min).min(param.values.max);
+          param.value.set(value);
+        }
+      }
+      Message::CancelParamValues { param_ref } => self.cancel_param_values(param_ref),
+      Message::ModulationUpdate {
+        source_ref,
+        param_ref,
+        amount,
+      } => {
+        if self.programs[0].get_source(source_ref).is_some() {
+          self.programs[0]
+            .update_modulation(param_ref, source_ref, amount)
+            .unwrap(); // TODO handle error
+        }
+      }
+      Message::ModulationDelete {
+        source_ref,
+        param_ref,
+      } => {
+        self.programs[0]
+          .delete_modulation(param_ref, source_ref)
+          .unwrap(); // TODO handle error
+      }
+      Message::ModulationPhase {
+        source_ref,
+        param_ref,
+        phase,
+      } => {
+        self.programs[0].update_modulation_phase(param_ref, source_ref, phase);
+      }
+      Message::InsertOrder { order } => self.programs[0].set_insert_order(&order),
+    }
+  }
+
+  fn note_on(&mut self, key: u8, velocity: F) {
+    if self.mono {
+      self.mono_note_on(key, velocity);
+    } else if self.key_zones.is_empty() {
+      self.program_note_on(0, key, velocity);
+    } else {
+      for zone_index in 0..self.key_zones.len() {
+        let zone = self.key_zones[zone_index];
+        if zone.contains(key) && zone.program < self.programs.len() {
+          self.program_note_on(zone.program, key, velocity);
+        }
+      }
+    }
+  }
+
+  /// Play a note with a free voice of a program, or with a voice stolen from it once it has faded out.
+  /// Returns the voice that plays the note.
+  fn program_note_on(&mut self, program_index: usize, key: u8, velocity: F) -> Option<usize> {
+    let voices = &self.voices;
+    let position = self
+      .free_voices
+      .iter()
+      .rposition(|index| voices[*index].get_program_index() == program_index);
+    if let Some(position) = position {
+      let index = self.free_voices.swap_remove(position);
+      self.active_voices.push(index).unwrap();
+      self.start_voice(index, key, velocity);
+      return Some(index);
+    }
+
+    let index = self.steal_voice(program_index)?;
+    let samples = self.globals.fade_out_samples;
+    self.voices[index].steal(&self.programs[program_index], samples, key, velocity);
+    Some(index)
+  }
+
+  fn start_voice(&mut self, index: usize, key: u8, velocity: F) {
+    let program_index = self.voices[index].get_program_index();
+    let pan = self.next_random_pan();
+    let program = &self.programs[program_index];
+    let voice = &mut self.voices[index];
+    voice.note_on(program, key, velocity, pan);
+    voice.set_pressure(program, self.channel_pressure);
+
+    if let Some(pitch) = self.poly_glide_pitch(program_index) {
+      self.voices[index].set_note_pitch(&self.programs[program_index], pitch);
+      let samples = self.glide_samples(index, key);
+      self.voices[index].glide_to(&self.programs[program_index], key, samples);
+    }
+  }
+
+  /// The pitch for a new note of a program to glide from in poly mode
+  fn poly_glide_pitch(&self, program_index: usize) -> Option<F> {
+    match self.last_released {
+      Some((program, pitch)) if self.poly_glide && !self.mono && program == program_index => {
+        Some(pitch)
+      }
+      _ => None,
+    }
+  }
+
+  fn active_mono_voice(&self) -> Option<usize> {
+    self
+      .mono_voice
+      .filter(|index| self.voices[*index].get_state() != VoiceState::Free)
+  }
+
+  /// The number of samples for a voice to glide from its current pitch to a key
+  fn glide_samples(&self, voice_index: usize, key: u8) -> usize {
+    let program = &self.programs[self.voices[voice_index].get_program_index()];
+    let pitch = self.voices[voice_index].get_note_pitch(program);
+    let target = program.key_freq(key);
+    let semitones = F::val(12.0) * (target / pitch).log2().abs();
+    if self.glide_threshold > F::zero() && semitones > self.glide_threshold {
+      return 0;
+    }
+
+    let seconds = match self.glide_mode {
+      GlideMode::Time => self.glide_time,
+      GlideMode::Rate if self.glide_rate > F::zero() => semitones / self.glide_rate,
+      GlideMode::Rate => F::zero(),
+    };
+    (seconds * self.sample_rate).round().to_usize().unwrap_or(0)
+  }
+
+  fn mono_note_on(&mut self, key: u8, velocity: F) {
+    let legato = !self.held_keys.is_empty();
+    let sounding_key = self.priority_key();
+    self.release_held_key(key);
+    if self.held_keys.len() == MaxHeldKeys::to_usize() {
+      self.held_keys.rotate_left(1);
+      self.held_keys.pop();
+    }
+    self.held_keys.push(key).unwrap();
+
+    // a key without priority is only held, to sound once the keys with priority are released
+    let priority_key = self.priority_key().unwrap_or(key);
+    if legato && priority_key != key && Some(priority_key) == sounding_key {
+      return;
+    }
+    let key = priority_key;
+
+    match self.active_mono_voice() {
+      Some(index) if legato => {
+        let samples = self.glide_samples(index, key);
+        let program = &self.programs[0];
+        let voice = &mut self.voices[index];
+        voice.glide_to(program, key, samples);
+        let threshold = self.legato_retrigger_velocity;
+        if threshold > F::zero() && velocity >= threshold {
+          voice.retrigger(program, velocity);
+        }
+      }
+      Some(index) => {
+        let pan = self.next_random_pan();
+        let program = &self.programs[0];
+        let voice = &mut self.voices[index];
+        voice.note_on(program, key, velocity, pan);
+        voice.set_pressure(program, self.channel_pressure);
+      }
+      None => {
+        self.mono_voice = self.program_note_on(0, key, velocity);
+      }
+    }
+  }
+
+  /// Remove a key from the held ones keeping the order of the rest
+  fn release_held_key(&mut self, key: u8) {
+    if let Some(position) = self.held_keys.iter().position(|held_key| *held_key == key) {
+      self.held_keys[position..].rotate_left(1);
+      self.held_keys.pop();
+    }
+  }
+
+  /// The held key that sounds in mono mode
+  fn priority_key(&self) -> Option<u8> {
+    match self.note_priority {
+      NotePriority::Last => self.held_keys.last().cloned(),
+      NotePriority::Low => self.held_keys.iter().min().cloned(),
+      NotePriority::High => self.held_keys.iter().max().cloned(),
+    }
+  }
+
+  fn mono_note_off(&mut self, key: u8) {
+    let was_sounding = self.priority_key() == Some(key);
+    self.release_held_key(key);
+
+    if let Some(index) = self.active_mono_voice() {
+      match self.priority_key() {
+        Some(previous_key) if was_sounding => {
+          let samples = self.glide_samples(index, previous_key);
+          self.voices[index].glide_to(&self.programs[0], previous_key, samples);
+        }
+        Some(_) => {}
+        None => self.voices[index].note_off(&self.programs[0]),
+      }
+    }
+  }
+
+  fn note_off(&mut self, key: u8, _velocity: F) {
+    if self.mono {
+      self.mono_note_off(key);
+      return;
+    }
+
+    for active_voice_index in 0..self.active_voices.len() {
+      let voice_index = self.active_voices[active_voice_index];
+      let voice = &mut self.voices[voice_index];
+      let program = &self.programs[voice.get_program_index()];
+      if voice.is_stopping() {
+        voice.cancel_next_note(key);
+      } else if voice.get_state() == VoiceState::Playing && voice.get_key(program) == key {
+        voice.note_off(program);
+        self.last_released = Some((voice.get_program_index(), voice.get_note_pitch(program)));
+      }
+    }
+  }
+
+  /// Release all the playing voices, letting them finish their release stage
+  pub fn all_notes_off(&mut self) {
+    self.held_keys.clear();
+    for voice_index in self.active_voices.iter() {
+      let voice = &mut self.voices[*voice_index];
+      if voice.get_state() == VoiceState::Playing {
+        voice.note_off(&self.programs[voice.get_program_index()]);
+      }
+    }
+  }
+
+  /// Stop all the active voices without waiting for their release, only fading them out so they don't click
+  pub fn all_sound_off(&mut self) {
+    let fade_out_samples = self.globals.fade_out_samples;
+    if fade_out_samples == 0 {
+      self.free_all_voices();
+    } else {
+      self.held_keys.clear();
+      self.last_released = None;
+      for voice_index in self.active_voices.iter() {
+        let voice = &mut self.voices[*voice_index];
+        voice.stop(&self.programs[voice.get_program_index()], fade_out_samples);
+      }
+    }
+  }
+
+  /// Free all the voices straight away, for when nothing is going to be output meanwhile
+  fn free_all_voices(&mut self) {
+    self.held_keys.clear();
+    self.last_released = None;
+    while let Some(voice_index) = self.active_voices.pop() {
+      self.voices[voice_index].free();
+      self.free_voices.push(voice_index).unwrap();
+    }
+  }
+
+  fn channel_pressure(&mut self, pressure: F) {
+    self.channel_pressure = pressure;
+    for voice_index in self.active_voices.iter() {
+      let voice = &mut self.voices[*voice_index];
+      voice.set_pressure(&self.programs[voice.get_program_index()], pressure);
+    }
+  }
+
+  fn poly_pressure(&mut self, key: u8, pressure: F) {
+    for voice_index in self.active_voices.iter() {
+      let voice = &mut self.voices[*voice_index];
+      let program = &self.programs[voice.get_program_index()];
+      if voice.get_key(program) == key {
+        voice.set_pressure(program, pressure);
+      }
+    }
+  }
+
+  /// Find the oldest active voice of a program to steal, preferring the releasing ones, as long as it is
+  /// old enough or the policy allows stealing the young ones. The voices are never rebuilt for another
+  /// program here, as that only happens out of the audio thread with [`reserve_voices`](Self::reserve_voices).
+  fn steal_voice(&self, program_index: usize) -> Option<usize> {
+    let min_age = match self.steal_policy {
+      StealPolicy::Never => return None,
+      StealPolicy::DropNewNote => self.min_steal_age_samples,
+      StealPolicy::StealOldest => 0,
+    };
+    let voices = &self.voices;
+    self
+      .active_voices
+      .iter()
+      .cloned()
+      .filter(|index| voices[*index].get_program_index() == program_index)
+      .filter(|index| !voices[*index].is_stopping())
+      .filter(|index| voices[*index].get_age() >= min_age)
+      .max_by_key(|index| {
+        let voice = &voices[*index];
+        (voice.get_state() == VoiceState::Releasing, voice.get_age())
+      })
+  }
+
+  pub fn process(&mut self) -> (F, F) {
+    let (left, right) = self.process_frame(0, |_, _, _| {});
+    if self.output_channels == 1 {
+      let mono = (left + right) * F::val(core::f64::consts::FRAC_1_SQRT_2);
+      (mono, mono)
+    } else {
+      (left, right)
+    }
+  }
+
+  /// Process a block of interleaved frames with as many samples as output channels
+  pub fn process_block(&mut self, out: &mut [F]) {
+    let channels = self.output_channels;
+    for frame in out.chunks_exact_mut(channels) {
+      let (left, right) = self.process();
+      frame[0] = left;
+      if channels > 1 {
+        frame[1] = right;
+      }
+    }
+  }
+
+  /// Process a block writing the output of every voice into its own buffer, for multi-output routing,
+  /// and the master bus, with the effects and the master gain, into `master_out`.
+  ///
+  /// The buffers contain interleaved stereo frames, the shortest one determines the length of the block.
+  /// When a voice starts sounding it takes the first buffer not used by another voice, and keeps it
+  /// until it finishes, so the first note goes to the first buffer, the second note played while the
+  /// first one sounds goes to the second, and so on. The voices are written before the effects and the
+  /// master gain, and the ones that find all the buffers used are summed into the last one as the default bus.
+  pub fn process_block_multi(&mut self, master_out: &mut [F], per_voice_out: &mut [&mut [F]]) {
+    let num_outputs = per_voice_out.len();
+    let num_frames = per_voice_out
+      .iter()
+      .map(|out| out.len() / 2)
+      .fold(master_out.len() / 2, usize::min);
+    for out in per_voice_out.iter_mut() {
+      for sample in out.iter_mut() {
+        *sample = F::zero();
+      }
+    }
+
+    for frame in 0..num_frames {
+      let (left, right) = self.process_frame(num_outputs, |output, left, right| {
+        // the outputs might have been taken from a block with more buffers
+        let output = output.min(num_outputs.saturating_sub(1));
+        if let Some(out) = per_voice_out.get_mut(output) {
+          out[frame * 2] = out[frame * 2] + left;
+          out[frame * 2 + 1] = out[frame * 2 + 1] + right;
+        }
+      });
+      master_out[frame * 2] = left;
+      master_out[frame * 2 + 1] = right;
+    }
+  }
+
+  /// The first of some outputs that no active voice is using, or the last one when all of them are used
+  fn free_voice_output(&self, num_outputs: usize) -> usize {
+    let voices = &self.voices;
+    let used = |output: usize| {
+      self
+        .active_voices
+        .iter()
+        .any(|index| voices[*index].get_output() == Some(output))
+    };
+    (0..num_outputs)
+      .find(|output| !used(*output))
+      .unwrap_or(num_outputs - 1)
+  }
+
+  /// Process a frame passing the output of every active voice to `voice_tap`, along with the one of
+  /// `num_outputs` that the voice has taken, or zero when there are no outputs.
+  fn process_frame<T: FnMut(usize, F, F)>(
+    &mut self,
+    num_outputs: usize,
+    mut voice_tap: T,
+  ) -> (F, F) {
+    let (mut left, mut right) = (F::zero(), F::zero());
+    let (mut send_left, mut send_right) = (F::zero(), F::zero());
+
+    self.apply_pending_messages();
+
+    for program in self.programs.iter_mut() {
+      program.update_macros();
+    }
+
+    self.globals.reduced_quality =
+      self.quality_scaling && self.active_voices.len() > self.quality_threshold;
+
+    let mut active_voice_index = 0;
+    while active_voice_index < self.active_voices.len() {
+      let voice_index = self.active_voices[active_voice_index];
+      if num_outputs > 0 && self.voices[voice_index].get_output().is_none() {
+        let output = self.free_voice_output(num_outputs);
+        self.voices[voice_index].set_output(output);
+      }
+      let voice = &mut self.voices[voice_index];
+      let program = &mut self.programs[voice.get_program_index()];
+
+      voice.process(program, &self.globals);
+      let (voice_left, voice_right) = voice.output(program);
+      voice_tap(voice.get_output().unwrap_or(0), voice_left, voice_right);
+      left = left + voice_left;
+      right = right + voice_right;
+
+      let send = Self::param_value(program, program.master().fx_send, F::zero());
+      send_left = send_left + voice_left * send;
+      send_right = send_right + voice_right * send;
+
+      let next_note = if voice.is_stopped() {
+        voice.take_next_note()
+      } else {
+        None
+      };
+      if let Some((key, velocity)) = next_note {
+        self.start_voice(voice_index, key, velocity);
+        active_voice_index += 1;
+      } else if voice.is_stopped() || voice.is_off(program) {
+        voice.free();
+        self.active_voices.swap_remove(active_voice_index);
+        self.free_voices.push(voice_index).unwrap();
+      } else {
+        active_voice_index += 1;
+      }
+    }
+
+    let program = &self.programs[0];
+    let master = program.master();
+
+    let pre_fx_gain = Self::param_value(program, master.pre_fx_gain, F::zero());
+    let pre_fx_gain = self.pre_fx_gain.amplitude(pre_fx_gain);
+    left = left * pre_fx_gain;
+    right = right * pre_fx_gain;
+
+    let beats = self.globals.transport.get_beats();
+
+    let insert_order = self.insert_order();
+    let meters = &mut self.effect_meters;
+
+    if let Some(effect) = self.send_effect.as_mut() {
+      if effect.is_bypassed() {
+        meters.send.reset();
+      } else {
+        effect.set_beats(beats);
+        let fx_return = Self::param_value(program, master.fx_return, F::one());
+        let (send_left, send_right) = (send_left * pre_fx_gain, send_right * pre_fx_gain);
+        let (return_left, return_right) = if meters.enabled {
+          effect.process_wet_metered(send_left, send_right, &mut meters.send)
+        } else {
+          effect.process_wet(send_left, send_right)
+        };
+        left = left + return_left * fx_return;
+        right = right + return_right * fx_return;
+      }
+    }
+
+    for slot in insert_order.iter() {
+      let slot = *slot as usize;
+      let effect = &mut self.insert_effects[slot];
+      effect.set_beats(beats);
+      let (effect_left, effect_right) = if meters.enabled {
+        effect.process_frame_metered(left, right, &mut meters.inserts[slot])
+      } else {
+        effect.process_frame(left, right)
+      };
+      left = effect_left;
+      right = effect_right;
+    }
+
+    if Self::param_value(program, master.high_pass, F::zero()) > F::zero() {
+      let freq = Self::param_value(program, master.high_pass_freq, F::val(20.0));
+      let filtered = self.high_pass.process(self.sample_rate, freq, left, right);
+      left = filtered.0;
+      right = filtered.1;
+    } else {
+      self.high_pass.reset();
+    }
+
+    if Self::param_value(program, master.low_pass, F::zero()) > F::zero() {
+      let freq = Self::param_value(program, master.low_pass_freq, F::val(20000.0));
+      let filtered = self.low_pass.process(self.sample_rate, freq, left, right);
+      left = filtered.0;
+      right = filtered.1;
+    } else {
+      self.low_pass.reset();
+    }
+
+    // the global LFO keeps running between the notes
+    let lfo_shape = Self::param_value(program, master.lfo_shape, F::zero());
+    let lfo_shape = lfo_shape.to_usize().unwrap_or(0);
+    let lfo_rate = Self::param_value(program, master.lfo_rate, F::one());
+    let lfo = self.master_lfo.generate(&self.globals, lfo_shape, lfo_rate);
+    let lfo_to_pan = Self::param_value(program, master.lfo_to_pan, F::zero());
+    let pan = Self::param_value(program, master.pan, F::zero()) + lfo * lfo_to_pan;
+    self.master_pan = pan.max(-F::one()).min(F::one());
+    // balance the channels so the output is not changed when centered
+    left = left * (F::one() - self.master_pan).min(F::one());
+    right = right * (F::one() + self.master_pan).min(F::one());
+
+    let master_gain = Self::param_value(program, master.gain, F::zero());
+    let master_gain = self.master_gain.amplitude(master_gain);
+    left = left * master_gain;
+    right = right * master_gain;
+
+    for program in self.programs.iter_mut() {
+      program.update_params();
+    }
+
+    self.globals.transport.advance();
+    self.elapsed_samples += 1;
+
+    (left, right)
+  }
+
+  fn param_value(program: &Program<F>, param_ref: Option<ParamRef>, default: F) -> F {
+    param_ref
+      .map(|param_ref| program.get_param_signal(param_ref).get())
+      .unwrap_or(default)
+  }
+}
+
+pub struct VoiceIter<'a, F: Float + 'a, I>(I)
+where
+  I: Iterator<Item = &'a Voice<F>>;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use assert_approx_eq::assert_approx_eq;
+  use heapless::consts::U16;
+  use ringbuf::{Producer, RingBuffer};
+
+  use kiro_synth_core::effects::delay::Delay;
+  use kiro_synth_core::effects::gate::Gate;
+  use kiro_synth_core::effects::limiter::Limiter;
+
+  use crate::key_freqs::KEY_FREQ;
+  use crate::program::blocks::{envgen, filter, lfo, osc, sequencer};
+  use crate::program::drum_map::DrumMap;
+  use crate::program::macros::{MacroCurve, MacroTarget};
+  use crate::program::routing::OscRouting;
+  use crate::program::{
+    Block, MasterBlock, ParamScale, ParamValues, Program, ProgramBuilder, SignalRef,
+  };
+
+  fn synth<'a>() -> (Synth<'a, f32>, Producer<Event<f32>>) {
+    let (producer, consumer) = RingBuffer::new(16).split();
+    let program = ProgramBuilder::new().build();
+    let synth = Synth::new(44100.0, consumer, program, SynthGlobals::new());
+    (synth, producer)
+  }
+
+  fn send(synth: &mut Synth<f32>, events: &mut Producer<Event<f32>>, message: Message<f32>) {
+    drop(events.push(Event::new(0u64, message)));
+    synth.prepare();
+  }
+
+  fn note_on(synth: &mut Synth<f32>, events: &mut Producer<Event<f32>>, key: u8) {
+    send(synth, events, Message::NoteOn { key, velocity: 1.0 });
+  }
+
+  fn active_programs(synth: &Synth<f32>) -> std::vec::Vec<usize> {
+    let mut programs: std::vec::Vec<usize> = synth
+      .active_voices
+      .iter()
+      .map(|index| synth.voices[*index].get_program_index())
+      .collect();
+    programs.sort_unstable();
+    programs
+  }
+
+  /// An oscillator playing the pitch of the voice, or a fixed frequency, with the rest of its inputs at zero
+  fn test_osc<F: Float>(
+    builder: &mut ProgramBuilder<F>,
+    amplitude: SignalRef,
+    freq_hz: Option<F>,
+  ) -> osc::Block {
+    let voice = builder.voice().clone();
+    let zero = builder.const_zero();
+    let fixed_freq = if freq_hz.is_some() {
+      builder.const_one()
+    } else {
+      zero
+    };
+    let freq_hz = freq_hz.map_or(zero, |freq_hz| builder.const_value(freq_hz));
+    osc::Block {
+      inputs: osc::Inputs {
+        shape: zero,
+        amplitude,
+        amplitude_db: zero,
+        level_db: zero,
+        amp_mod: zero,
+        octaves: zero,
+        semitones: zero,
+        cents: zero,
+        note_pitch: voice.note_pitch,
+        pitch_bend: zero,
+        pitch_mod: zero,
+        freq_mod: zero,
+        fm_mode: zero,
+        sync_mode: zero,
+        sync_in: zero,
+        lfo_sync: zero,
+        lfo1_sync_in: zero,
+        lfo2_sync_in: zero,
+        key_low: zero,
+        key_high: builder.const_value(F::val(127)),
+        fixed_freq,
+        freq_hz,
+      },
+      output: builder.signal(),
+      sync_out: builder.signal(),
+    }
+  }
+
+  #[test]
+  fn key_zones_split() {
+    let (mut synth, mut events) = synth();
+    let program_b = synth.add_program(ProgramBuilder::new().build()).unwrap();
+    synth.reserve_voices(program_b, 1);
+    synth.set_key_zones(&[KeyZone::new(0, 59, 0), KeyZone::new(60, 127, program_b)]);
+
+    note_on(&mut synth, &mut events, 40);
+    assert_eq!(active_programs(&synth), vec![0]);
+
+    note_on(&mut synth, &mut events, 72);
+    assert_eq!(active_programs(&synth), vec![0, program_b]);
+  }
+
+  #[test]
+  fn key_zones_layer_and_gaps() {
+    let (mut synth, mut events) = synth();
+    let program_b = synth.add_program(ProgramBuilder::new().build()).unwrap();
+    synth.reserve_voices(program_b, 1);
+    synth.set_key_zones(&[KeyZone::new(0, 100, 0), KeyZone::new(50, 100, program_b)]);
+
+    note_on(&mut synth, &mut events, 110);
+    assert!(active_programs(&synth).is_empty());
+
+    note_on(&mut synth, &mut events, 60);
+    assert_eq!(active_programs(&synth), vec![0, program_b]);
+  }
+
+  #[test]
+  fn without_key_zones() {
+    let (mut synth, mut events) = synth();
+    synth.add_program(ProgramBuilder::new().build()).unwrap();
+
+    note_on(&mut synth, &mut events, 110);
+    assert_eq!(active_programs(&synth), vec![0]);
+  }
+
+  #[test]
+  fn key_zones_without_reserved_voices() {
+    let (mut synth, mut events) = synth();
+    let program_b = synth.add_program(ProgramBuilder::new().build()).unwrap();
+    synth.set_key_zones(&[KeyZone::new(0, 59, 0), KeyZone::new(60, 127, program_b)]);
+
+    // the program plays its share of the voices out of the box
+    let share = MaxVoices::to_usize() / MaxPrograms::to_usize();
+    for key in 60..(60 + share as u8 + 1) {
+      note_on(&mut synth, &mut events, key);
+    }
+    assert_eq!(active_programs(&synth), vec![program_b; share]);
+
+    // the voices of the main program are not rebuilt while playing
+    let main_voices = MaxVoices::to_usize() - share;
+    for key in 0..(main_voices as u8) {
+      note_on(&mut synth, &mut events, key);
+    }
+    assert_eq!(synth.get_num_active_voices(), MaxVoices::to_usize());
+
+    synth.all_sound_off();
+    for _ in 0..=synth.globals.fade_out_samples {
+      synth.process();
+    }
+    assert_eq!(synth.reserve_voices(program_b, share + 1), share + 1);
+    note_on(&mut synth, &mut events, 72);
+    assert_eq!(active_programs(&synth), vec![program_b]);
+  }
+
+  #[test]
+  fn reserve_voices_from_the_programs_with_more_free_voices() {
+    let (mut synth, _) = synth();
+    let share = MaxVoices::to_usize() / MaxPrograms::to_usize();
+    let program_b = synth.add_program(ProgramBuilder::new().build()).unwrap();
+    let program_c = synth.add_program(ProgramBuilder::new().build()).unwrap();
+
+    // the second program takes the voices from the main program, not from the first one
+    let free_voices = |synth: &Synth<f32>, program: usize| {
+      let free_voices = synth.free_voices.iter();
+      let programs = free_voices.map(|index| synth.voices[*index].get_program_index());
+      programs.filter(|index| *index == program).count()
+    };
+    assert_eq!(free_voices(&synth, program_b), share);
+    assert_eq!(free_voices(&synth, program_c), share);
+    assert_eq!(free_voices(&synth, 0), MaxVoices::to_usize() - 2 * share);
+  }
+
+  fn param_values(min: f32, max: f32, resolution: f32, scale: ParamScale) -> ParamValues<f32> {
+    ParamValues {
+      initial_value: min,
+      origin: min,
+      min,
+      max,
+      resolution,
+      scale,
+      discrete: false,
+    }
+  }
+
+  #[test]
+  fn param_normalized() {
+    let (_, consumer) = RingBuffer::new(1).split();
+    let mut builder = ProgramBuilder::new();
+    let linear = builder.param("linear", param_values(-1.0, 1.0, 0.01, ParamScale::Linear));
+    let log = builder.param(
+      "log",
+      param_values(80.0, 18000.0, 10.0, ParamScale::Logarithmic),
+    );
+    let exp = builder.param(
+      "exp",
+      param_values(0.0, 10.0, 0.01, ParamScale::Exponential),
+    );
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+
+    synth.set_param_normalized(linear.reference, 0.5);
+    synth.set_param_normalized(log.reference, 0.5);
+    synth.set_param_normalized(exp.reference, 0.5);
+
+    let value = |synth: &Synth<f32>, param_ref| {
+      synth
+        .get_program()
+        .get_param(param_ref)
+        .unwrap()
+        .1
+        .value
+        .get()
+    };
+    assert_approx_eq!(value(&synth, linear.reference), 0.0, 1e-6);
+    assert_approx_eq!(
+      value(&synth, log.reference),
+      (80.0f32 * 18000.0).sqrt(),
+      10.0
+    );
+    assert_approx_eq!(value(&synth, exp.reference), 2.5, 1e-4);
+
+    assert_approx_eq!(
+      synth.get_param_normalized(linear.reference).unwrap(),
+      0.5,
+      1e-6
+    );
+    assert_approx_eq!(
+      synth.get_param_normalized(log.reference).unwrap(),
+      0.5,
+      1e-3
+    );
+    assert_approx_eq!(
+      synth.get_param_normalized(exp.reference).unwrap(),
+      0.5,
+      1e-4
+    );
+
+    synth.set_param_normalized(log.reference, 2.0);
+    assert_approx_eq!(value(&synth, log.reference), 18000.0, 1e-3);
+  }
+
+  #[test]
+  fn pressure_modulation() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let pressure = builder.source("pressure", builder.voice().pressure);
+    let cutoff = builder.param(
+      "cutoff",
+      param_values(0.0, 10000.0, 1.0, ParamScale::Linear),
+    );
+    builder.modulation(&cutoff, pressure, 1000.0);
+    builder.block(Block::Param(cutoff.clone()));
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+
+    let cutoff_values = |synth: &Synth<f32>| -> std::vec::Vec<(u8, f32)> {
+      let program = synth.get_program();
+      let mut values: std::vec::Vec<(u8, f32)> = synth
+        .active_voices
+        .iter()
+        .map(|index| {
+          let voice = &synth.voices[*index];
+          let value = voice.get_signals()[cutoff.out_signal_ref.0].get();
+          (voice.get_key(program), value)
+        })
+        .collect();
+      values.sort_by_key(|(key, _)| *key);
+      values
+    };
+
+    note_on(&mut synth, &mut events, 60);
+    synth.process();
+    assert_eq!(cutoff_values(&synth), vec![(60, 0.0)]);
+
+    send(
+      &mut synth,
+      &mut events,
+      Message::ChannelPressure { pressure: 0.25 },
+    );
+    synth.process();
+    assert_eq!(cutoff_values(&synth), vec![(60, 250.0)]);
+
+    send(
+      &mut synth,
+      &mut events,
+      Message::ChannelPressure { pressure: 0.5 },
+    );
+    note_on(&mut synth, &mut events, 64);
+    synth.process();
+    assert_eq!(cutoff_values(&synth), vec![(60, 500.0), (64, 500.0)]);
+
+    send(
+      &mut synth,
+      &mut events,
+      Message::PolyPressure {
+        key: 64,
+        pressure: 1.0,
+      },
+    );
+    synth.process();
+    assert_eq!(cutoff_values(&synth), vec![(60, 500.0), (64, 1000.0)]);
+  }
+
+  fn envgen_program<'a>(decay: f32, sustain: f32, release: f32) -> Program<'a, f32> {
+    adsr_program(0.0, decay, sustain, release, 0.0)
+  }
+
+  fn adsr_program<'a>(
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    velocity_to_attack: f32,
+  ) -> Program<'a, f32> {
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let inputs = envgen::Inputs {
+      attack: builder.const_value(attack),
+      decay: builder.const_value(decay),
+      sustain: builder.const_value(sustain),
+      release: builder.const_value(release),
+      mode: zero,
+      legato: zero,
+      reset_to_zero: zero,
+      velocity_to_attack: builder.const_value(velocity_to_attack),
+      velocity_to_decay: zero,
+    };
+    builder.amp_envelope(inputs);
+    builder.out(one, one);
+    builder.build()
+  }
+
+  fn seconds_until_free(synth: &mut Synth<f32>, sample_rate: f32) -> f32 {
+    let mut samples = 0;
+    while synth.get_num_active_voices() > 0 && samples < 10 * sample_rate as usize {
+      assert_eq!(synth.get_num_releasing_voices(), 1);
+      synth.process();
+      samples += 1;
+    }
+    samples as f32 / sample_rate
+  }
+
+  #[test]
+  fn sample_rate_change_keeps_the_times() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = envgen_program(0.1, 1.0, 0.5);
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_fade_in_time(0.002);
+    synth.set_fade_out_time(0.0);
+    note_on(&mut synth, &mut events, 60);
+    synth.process();
+
+    let sample_rate = 4000.0;
+    synth.set_sample_rate(sample_rate);
+    assert_eq!(synth.get_sample_rate(), sample_rate);
+    assert_eq!(synth.get_num_active_voices(), 0);
+    assert_approx_eq!(synth.get_fade_in_time(), 0.002);
+    assert_eq!(synth.globals.fade_in_samples, 8);
+
+    note_on(&mut synth, &mut events, 60);
+    for _ in 0..100 {
+      synth.process();
+    }
+    send(
+      &mut synth,
+      &mut events,
+      Message::NoteOff {
+        key: 60,
+        velocity: 0.0,
+      },
+    );
+    let seconds = seconds_until_free(&mut synth, sample_rate);
+    assert!(
+      seconds > 0.45 && seconds <= 0.51,
+      "released after {}s",
+      seconds
+    );
+  }
+
+  #[test]
+  fn release_frees_voice_when_silent() {
+    let sample_rate = 1000.0;
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = envgen_program(0.1, 1.0, 2.0);
+    let mut synth = Synth::new(sample_rate, consumer, program, SynthGlobals::new());
+
+    note_on(&mut synth, &mut events, 60);
+    for _ in 0..100 {
+      synth.process();
+    }
+    assert_eq!(synth.get_num_active_voices(), 1);
+    assert_eq!(synth.get_num_releasing_voices(), 0);
+
+    send(
+      &mut synth,
+      &mut events,
+      Message::NoteOff {
+        key: 60,
+        velocity: 0.0,
+      },
+    );
+    let seconds = seconds_until_free(&mut synth, sample_rate);
+    assert!(
+      seconds > 1.9 && seconds <= 2.01,
+      "released after {}s",
+      seconds
+    );
+    assert_eq!(
+      synth.voices[synth.free_voices[synth.free_voices.len() - 1]].get_state(),
+      VoiceState::Free
+    );
+  }
+
+  #[test]
+  fn release_with_zero_sustain() {
+    let sample_rate = 1000.0;
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = envgen_program(1.0, 0.0, 2.0);
+    let mut synth = Synth::new(sample_rate, consumer, program, SynthGlobals::new());
+
+    note_on(&mut synth, &mut events, 60);
+    for _ in 0..100 {
+      synth.process();
+    }
+
+    send(
+      &mut synth,
+      &mut events,
+      Message::NoteOff {
+        key: 60,
+        velocity: 0.0,
+      },
+    );
+    let seconds = seconds_until_free(&mut synth, sample_rate);
+    assert!(
+      seconds > 1.5 && seconds <= 2.01,
+      "released after {}s",
+      seconds
+    );
+  }
+
+  #[test]
+  fn params_in_double_precision() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let freq = param_values(20.0, 20000.0, 0.0, ParamScale::Logarithmic);
+    let freq = builder.param("freq", freq);
+    builder.block(Block::Param(freq.clone()));
+    builder.out(freq.out_signal_ref, freq.out_signal_ref);
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+
+    let precise: f64 = 1_234.567_890_123_4;
+    synth.set_param_f64(freq.reference, precise);
+    assert_eq!(synth.get_param_f64(freq.reference), Some(precise));
+    note_on(&mut synth, &mut events, 60);
+    let (left, _) = synth.process();
+    assert_approx_eq!(left as f64, precise, precise * f32::EPSILON as f64);
+
+    // limited to the range of the param
+    synth.set_param_f64(freq.reference, 1e6);
+    assert_eq!(synth.get_param_f64(freq.reference), Some(20000.0));
+
+    // changed in single precision it reports the new value
+    let message = Message::ParamValue {
+      param_ref: freq.reference,
+      value: 440.5,
+    };
+    send(&mut synth, &mut events, message);
+    assert_eq!(synth.get_param_f64(freq.reference), Some(440.5));
+  }
+
+  #[test]
+  fn param_values_applied_at_their_timestamp() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let level = builder.param("level", param_values(0.0, 1.0, 0.0, ParamScale::Linear));
+    builder.block(Block::Param(level.clone()));
+    builder.out(level.out_signal_ref, level.out_signal_ref);
+    // one sample every millisecond
+    let mut synth = Synth::new(1000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+
+    note_on(&mut synth, &mut events, 60);
+    for (timestamp, value) in [(2000u64, 0.5), (4000, 1.0)].iter() {
+      let message = Message::ParamValue {
+        param_ref: level.reference,
+        value: *value,
+      };
+      events.push(Event::new(*timestamp, message)).unwrap();
+    }
+    synth.prepare();
+
+    let output: std::vec::Vec<f32> = (0..6).map(|_| synth.process().0).collect();
+    assert_eq!(output, vec![0.0, 0.0, 0.5, 0.5, 1.0, 1.0]);
+  }
+
+  #[test]
+  fn param_values_cancelled_by_a_new_ramp() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let level = builder.param("level", param_values(0.0, 1.0, 0.0, ParamScale::Linear));
+    builder.block(Block::Param(level.clone()));
+    builder.out(level.out_signal_ref, level.out_signal_ref);
+    // one sample every millisecond
+    let mut synth = Synth::new(1000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+    let param_ref = level.reference;
+
+    note_on(&mut synth, &mut events, 60);
+    for step in 1..=4 {
+      let value = step as f32 * 0.25;
+      let message = Message::ParamValue { param_ref, value };
+      events.push(Event::new(step * 1000, message)).unwrap();
+    }
+    synth.prepare();
+    let mut output: std::vec::Vec<f32> = (0..3).map(|_| synth.process().0).collect();
+
+    // a second change arrives in the middle of the ramp, from the value reached
+    let message = Message::CancelParamValues { param_ref };
+    events.push(Event::now(message)).unwrap();
+    for (timestamp, value) in [(1000u64, 0.25), (2000, 0.0)].iter() {
+      let message = Message::ParamValue {
+        param_ref,
+        value: *value,
+      };
+      events.push(Event::new(*timestamp, message)).unwrap();
+    }
+    synth.prepare();
+    output.extend((0..4).map(|_| synth.process().0));
+
+    assert_eq!(output, vec![0.0, 0.25, 0.5, 0.5, 0.25, 0.0, 0.0]);
+  }
+
+  #[test]
+  fn full_pending_messages_keep_their_order() {
+    let (mut events, consumer) = RingBuffer::new(128).split();
+    let mut builder = ProgramBuilder::new();
+    let level = builder.param("level", param_values(0.0, 1.0, 0.0, ParamScale::Linear));
+    builder.block(Block::Param(level.clone()));
+    builder.out(level.out_signal_ref, level.out_signal_ref);
+    // one sample every millisecond
+    let mut synth = Synth::new(1000.0, consumer, builder.build(), SynthGlobals::new());
+    let param_ref = level.reference;
+
+    // a ramp with more steps than room for pending messages, between a note on and its note off
+    let num_steps = MaxPendingMessages::to_u64() + 8;
+    let note_on = Message::NoteOn {
+      key: 60,
+      velocity: 1.0,
+    };
+    events.push(Event::new(1000, note_on)).unwrap();
+    for step in 1..=num_steps {
+      let value = step as f32 / num_steps as f32;
+      let message = Message::ParamValue { param_ref, value };
+      events.push(Event::new(step * 1000, message)).unwrap();
+    }
+    let note_off = Message::NoteOff {
+      key: 60,
+      velocity: 0.0,
+    };
+    let timestamp = (num_steps + 1) * 1000;
+    events.push(Event::new(timestamp, note_off)).unwrap();
+    synth.prepare();
+
+    // the intermediate values made room for the rest and nothing was applied before it was due
+    assert_eq!(synth.get_num_dropped_messages(), 0);
+    assert_eq!(synth.get_param_f64(param_ref), Some(0.0));
+    assert_eq!(synth.get_num_active_voices(), 0);
+
+    for _ in 0..=num_steps {
+      synth.process();
+    }
+    assert_eq!(synth.get_param_f64(param_ref), Some(1.0));
+    assert_eq!(synth.get_num_active_voices(), 1);
+    assert_eq!(synth.get_num_releasing_voices(), 0);
+
+    synth.process();
+    assert_eq!(synth.get_num_releasing_voices(), 1);
+  }
+
+  #[test]
+  fn full_pending_messages_drop_the_new_message() {
+    let (mut events, consumer) = RingBuffer::new(128).split();
+    let mut builder = ProgramBuilder::new();
+    let level = builder.param("level", param_values(0.0, 1.0, 0.0, ParamScale::Linear));
+    let mut synth = Synth::new(1000.0, consumer, builder.build(), SynthGlobals::new());
+    let param_ref = level.reference;
+
+    for key in 0..MaxPendingMessages::to_u8() {
+      let message = Message::NoteOn { key, velocity: 1.0 };
+      events.push(Event::new(1000, message)).unwrap();
+    }
+    let message = Message::ParamValue {
+      param_ref,
+      value: 1.0,
+    };
+    events.push(Event::new(1000, message)).unwrap();
+    synth.prepare();
+
+    assert_eq!(synth.get_num_dropped_messages(), 1);
+    synth.process();
+    synth.process();
+    assert_eq!(synth.get_param_f64(param_ref), Some(0.0));
+  }
+
+  #[test]
+  fn master_gains() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let gain_values = param_values(-24.0, 24.0, 0.0, ParamScale::Linear).with_initial_value(0.0);
+    let pre_fx_gain = builder.param("pre-fx-gain", gain_values.clone());
+    let gain = builder.param("gain", gain_values);
+    builder.master(MasterBlock {
+      pre_fx_gain: Some(pre_fx_gain.reference),
+      gain: Some(gain.reference),
+      ..MasterBlock::default()
+    });
+    let output = builder.const_value(0.25);
+    builder.out(output, output);
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+
+    note_on(&mut synth, &mut events, 60);
+    let (left, right) = synth.process();
+    assert_approx_eq!(left, 0.25);
+    assert_approx_eq!(right, 0.25);
+
+    let six_db = 20.0 * 2.0f32.log10();
+    let message = Message::ParamValue {
+      param_ref: gain.reference,
+      value: six_db,
+    };
+    send(&mut synth, &mut events, message);
+    let (left, right) = synth.process();
+    assert_approx_eq!(left, 0.5, 1e-5);
+    assert_approx_eq!(right, 0.5, 1e-5);
+
+    let message = Message::ParamValue {
+      param_ref: pre_fx_gain.reference,
+      value: -2.0 * six_db,
+    };
+    send(&mut synth, &mut events, message);
+    let (left, right) = synth.process();
+    assert_approx_eq!(left, 0.125, 1e-5);
+    assert_approx_eq!(right, 0.125, 1e-5);
+  }
+
+  fn render_patch<F: Float>(frames: usize) -> std::vec::Vec<(f64, f64)> {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::<F>::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let osc = test_osc(&mut builder, one, None);
+    let filter = filter::Block {
+      input: osc.output,
+      params: filter::Params {
+        mode: builder.const_value(F::val(3)),
+        freq: builder.const_value(F::val(2000.0)),
+        freq_mod: zero,
+        key_track: zero,
+        key_track_center: zero,
+        q: builder.const_value(F::val(2.0)),
+        velocity_to_q: zero,
+        q_compensation: zero,
+        gain: zero,
+        self_osc: zero,
+      },
+      output: builder.signal(),
+    };
+    builder.block(Block::Osc(osc));
+    builder.block(Block::Filter(filter.clone()));
+    builder.out(filter.output, filter.output);
+    let mut synth = Synth::new(F::val(44100.0), consumer, builder.build(), SynthGlobals::new());
+
+    let message = Message::NoteOn {
+      key: 69,
+      velocity: F::one(),
+    };
+    drop(events.push(Event::new(0u64, message)));
+    synth.prepare();
+
+    (0..frames)
+      .map(|_| {
+        let (left, right) = synth.process();
+        (left.to_f64().unwrap(), right.to_f64().unwrap())
+      })
+      .collect()
+  }
+
+  #[test]
+  fn f32_matches_f64() {
+    let output32 = render_patch::<f32>(2048);
+    let output64 = render_patch::<f64>(2048);
+    for ((left32, right32), (left64, right64)) in output32.iter().zip(output64.iter()) {
+      assert_approx_eq!(left32, left64, 1e-3);
+      assert_approx_eq!(right32, right64, 1e-3);
+    }
+  }
+
+  #[test]
+  fn all_notes_off_and_all_sound_off() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut synth = Synth::new(44100.0, consumer, envgen_program(0.1, 1.0, 2.0), SynthGlobals::new());
+
+    for key in [60, 64, 67].iter() {
+      note_on(&mut synth, &mut events, *key);
+    }
+    for _ in 0..100 {
+      synth.process();
+    }
+    assert_eq!(synth.get_num_active_voices(), 3);
+    assert_eq!(synth.get_num_releasing_voices(), 0);
+
+    send(&mut synth, &mut events, Message::AllNotesOff);
+    assert_eq!(synth.get_num_active_voices(), 3);
+    assert_eq!(synth.get_num_releasing_voices(), 3);
+    let (left, right) = synth.process();
+    assert!(left.abs() > 0.0 && right.abs() > 0.0);
+
+    // the sound is stopped after the fade out instead of waiting for the release
+    send(&mut synth, &mut events, Message::AllSoundOff);
+    assert_eq!(synth.get_num_active_voices(), 3);
+    process_fade_out(&mut synth);
+    assert_eq!(synth.get_num_active_voices(), 0);
+    assert_eq!(synth.process(), (0.0, 0.0));
+    assert_eq!(synth.process(), (0.0, 0.0));
+  }
+
+  /// Process the samples that the voices being stopped take to fade out
+  fn process_fade_out(synth: &mut Synth<f32>) {
+    for _ in 0..=synth.globals.fade_out_samples {
+      synth.process();
+    }
+  }
+
+  #[test]
+  fn handle_message_without_the_events_queue() {
+    let (_events, consumer) = RingBuffer::new(16).split();
+    let mut synth = Synth::new(44100.0, consumer, envgen_program(0.1, 1.0, 2.0), SynthGlobals::new());
+
+    synth.handle_message(Message::NoteOn {
+      key: 60,
+      velocity: 1.0,
+    });
+    assert_eq!(synth.get_num_active_voices(), 1);
+
+    synth.handle_message(Message::NoteOff {
+      key: 60,
+      velocity: 1.0,
+    });
+    assert_eq!(synth.get_num_releasing_voices(), 1);
+  }
+
+  struct GainEffect(f32);
+
+  impl Processor<f32> for GainEffect {
+    fn get_mix(&self) -> f32 {
+      1.0
+    }
+
+    fn set_mix(&mut self, _mix: f32) {}
+
+    fn is_bypassed(&self) -> bool {
+      false
+    }
+
+    fn set_bypass(&mut self, _bypass: bool) {}
+
+    fn process_wet(&mut self, left: f32, right: f32) -> (f32, f32) {
+      (left * self.0, right * self.0)
+    }
+  }
+
+  fn send_program<'a>(send: f32) -> Program<'a, f32> {
+    let mut builder = ProgramBuilder::new();
+    let send_values = param_values(0.0, 1.0, 0.01, ParamScale::Linear).with_initial_value(send);
+    let fx_send = builder.param("fx-send", send_values);
+    builder.master(MasterBlock {
+      fx_send: Some(fx_send.reference),
+      ..MasterBlock::default()
+    });
+    let output = builder.const_value(0.25);
+    builder.out(output, output);
+    builder.build()
+  }
+
+  fn render_send(send_a: f32, send_b: f32) -> f32 {
+    let mut effect = GainEffect(4.0);
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = send_program(send_a);
+    let mut synth = Synth::new(44100.0, consumer, program, SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+    let program_b = synth.add_program(send_program(send_b)).unwrap();
+    synth.reserve_voices(program_b, 1);
+    synth.set_key_zones(&[KeyZone::new(0, 59, 0), KeyZone::new(60, 127, program_b)]);
+    synth.set_send_effect(&mut effect);
+
+    note_on(&mut synth, &mut events, 40);
+    note_on(&mut synth, &mut events, 80);
+    let (left, right) = synth.process();
+    assert_approx_eq!(left, right);
+    left
+  }
+
+  #[test]
+  fn effect_meters() {
+    let (mut send, mut insert) = (GainEffect(0.5), GainEffect(0.25));
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut synth = Synth::new(1000.0, consumer, send_program(1.0), SynthGlobals::new());
+    synth.set_send_effect(&mut send);
+    assert_eq!(synth.add_insert_effect(&mut insert), Some(0));
+    assert!(synth.get_insert_effect_meter(1).is_none());
+
+    // not measured by default
+    note_on(&mut synth, &mut events, 60);
+    synth.process();
+    let send_db = synth.get_send_effect_meter().get_level_db();
+    assert_eq!(send_db, f32::NEG_INFINITY);
+
+    synth.set_effect_metering(true);
+    for _ in 0..100 {
+      synth.process();
+    }
+    let send_db = synth.get_send_effect_meter().get_level_db();
+    assert_approx_eq!(send_db, Decibels::from_amplitude(0.125).value(), 0.01);
+    // the insert processes the dry signal plus the return of the send
+    let insert_db = synth.get_insert_effect_meter(0).unwrap().get_level_db();
+    assert_approx_eq!(insert_db, Decibels::from_amplitude(0.09375).value(), 0.01);
+
+    synth.set_effect_metering(false);
+    let insert_db = synth.get_insert_effect_meter(0).unwrap().get_level_db();
+    assert_eq!(insert_db, f32::NEG_INFINITY);
+  }
+
+  #[test]
+  fn send_return_bus() {
+    // without sends only the dry signal of both voices
+    assert_approx_eq!(render_send(0.0, 0.0), 0.5);
+    // both voices are sent to the effect with a return gain of 4
+    assert_approx_eq!(render_send(1.0, 1.0), 0.5 + 4.0 * 0.5);
+    // the voice with a send level of 0 is not in the return
+    assert_approx_eq!(render_send(1.0, 0.0), 0.5 + 4.0 * 0.25);
+    assert_approx_eq!(render_send(0.0, 0.5), 0.5 + 4.0 * 0.125);
+  }
+
+  #[test]
+  fn insert_gate_synced_to_the_transport() {
+    let mut gate = Gate::new(100.0);
+    gate.set_pattern(&[true, false]);
+    gate.set_division(1.0);
+
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut synth = Synth::new(100.0, consumer, send_program(0.0), SynthGlobals::new());
+    synth.set_tempo(60.0);
+    synth.set_insert_effect(&mut gate);
+    note_on(&mut synth, &mut events, 60);
+
+    let output: std::vec::Vec<f32> = (0..400).map(|_| synth.process().0).collect();
+    for (beat, level) in [0.25, 0.0, 0.25, 0.0].iter().enumerate() {
+      let samples = &output[beat * 100 + 1..(beat + 1) * 100];
+      assert!(samples.iter().all(|sample| (sample - level).abs() < 1e-6));
+    }
+  }
+
+  #[test]
+  fn latency_of_the_lookahead_limiter() {
+    let (mut left_buffer, mut right_buffer) = ([0.0f32; 16], [0.0f32; 16]);
+    let mut limiter = Limiter::new(1000.0, &mut left_buffer, &mut right_buffer);
+    limiter.set_lookahead_seconds(0.008);
+
+    let (_, consumer) = RingBuffer::new(1).split();
+    let mut synth = Synth::new(1000.0, consumer, send_program(0.0), SynthGlobals::new());
+    let latency = synth.latency_samples();
+    synth.set_insert_effect(&mut limiter);
+    assert_eq!(synth.latency_samples(), latency + 8);
+  }
+
+  fn const_program<'a>(value: f32) -> Program<'a, f32> {
+    let mut builder = ProgramBuilder::new();
+    let output = builder.const_value(value);
+    builder.out(output, output);
+    builder.build()
+  }
+
+  #[test]
+  fn per_voice_outputs() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut synth = Synth::new(44100.0, consumer, const_program(0.25), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+    let program_b = synth.add_program(const_program(0.5)).unwrap();
+    synth.reserve_voices(program_b, 1);
+    synth.set_key_zones(&[KeyZone::new(0, 59, 0), KeyZone::new(60, 127, program_b)]);
+    note_on(&mut synth, &mut events, 40);
+    note_on(&mut synth, &mut events, 80);
+
+    // the notes take the buffers in the order they are played
+    let (mut master, mut first, mut second) = ([0.0f32; 8], [1.0f32; 8], [1.0f32; 8]);
+    synth.process_block_multi(&mut master, &mut [&mut first, &mut second]);
+    assert!(first.iter().all(|sample| (sample - 0.25).abs() < 1e-6));
+    assert!(second.iter().all(|sample| (sample - 0.5).abs() < 1e-6));
+    assert!(master.iter().all(|sample| (sample - 0.75).abs() < 1e-6));
+
+    // the voices without a buffer are summed into the last one
+    note_on(&mut synth, &mut events, 30);
+    let (mut master, mut first, mut second) = ([0.0f32; 8], [0.0f32; 8], [0.0f32; 8]);
+    synth.process_block_multi(&mut master, &mut [&mut first, &mut second]);
+    assert!(first.iter().all(|sample| (sample - 0.25).abs() < 1e-6));
+    assert!(second.iter().all(|sample| (sample - 0.75).abs() < 1e-6));
+    assert!(master.iter().all(|sample| (sample - 1.0).abs() < 1e-6));
+
+    // a freed buffer is taken by the next note
+    let note_off = Message::NoteOff {
+      key: 40,
+      velocity: 0.0,
+    };
+    send(&mut synth, &mut events, note_off);
+    process_fade_out(&mut synth);
+    note_on(&mut synth, &mut events, 50);
+    let (mut master, mut first, mut second) = ([0.0f32; 8], [0.0f32; 8], [0.0f32; 8]);
+    synth.process_block_multi(&mut master, &mut [&mut first, &mut second]);
+    assert!(first.iter().all(|sample| (sample - 0.25).abs() < 1e-6));
+    assert!(second.iter().all(|sample| (sample - 0.75).abs() < 1e-6));
+  }
+
+  fn randomize_program<'a>() -> Program<'a, f32> {
+    let mut builder = ProgramBuilder::new();
+    let freq = param_values(20.0, 20000.0, 0.0, ParamScale::Logarithmic);
+    builder.param("freq", freq.with_initial_value(1000.0));
+    let level = param_values(0.0, 1.0, 0.01, ParamScale::Linear);
+    builder.param("level", level.with_initial_value(0.5));
+    let mode = param_values(0.0, 3.0, 1.0, ParamScale::Linear).with_discrete(true);
+    builder.param("mode", mode.with_initial_value(1.0));
+    builder.build()
+  }
+
+  fn randomized_values(amount: f32, seed: u64, all: bool) -> std::vec::Vec<f32> {
+    let (_, consumer) = RingBuffer::new(16).split();
+    let mut synth = Synth::new(44100.0, consumer, randomize_program(), SynthGlobals::new());
+    if all {
+      synth.randomize_all(amount, seed);
+    } else {
+      synth.randomize(amount, seed);
+    }
+    let params = synth.get_program().get_params();
+    params.iter().map(|param| param.value.get()).collect()
+  }
+
+  #[test]
+  fn randomize() {
+    let initial: std::vec::Vec<f32> = randomize_program()
+      .get_params()
+      .iter()
+      .map(|param| param.values.initial_value)
+      .collect();
+    assert_eq!(randomized_values(0.0, 7, true), initial);
+
+    let randomized = randomized_values(1.0, 7, false);
+    assert_eq!(randomized, randomized_values(1.0, 7, false));
+    assert_ne!(randomized, randomized_values(1.0, 8, false));
+    assert_ne!(randomized[0], initial[0]);
+    assert_ne!(randomized[1], initial[1]);
+    // the discrete params are kept
+    assert_eq!(randomized[2], initial[2]);
+
+    for seed in 0..32 {
+      let randomized = randomized_values(1.0, seed, true);
+      let params = randomize_program();
+      for (value, param) in randomized.iter().zip(params.get_params()) {
+        assert!(param.values.min <= *value && *value <= param.values.max);
+        let steps = (value - param.values.min) / param.values.resolution.max(f32::EPSILON);
+        assert!(param.values.resolution == 0.0 || (steps - steps.round()).abs() < 1e-3);
+      }
+    }
+  }
+
+  fn morph_program<'a>(freq: f32, mode: f32, extra: Option<f32>) -> Program<'a, f32> {
+    let mut builder = ProgramBuilder::new();
+    // the resolution of the filter cutoffs
+    let values = param_values(20.0, 20000.0, 10.0, ParamScale::Logarithmic);
+    builder.param("freq", values.with_initial_value(freq));
+    let values = param_values(0.0, 3.0, 1.0, ParamScale::Linear).with_discrete(true);
+    builder.param("mode", values.with_initial_value(mode));
+    if let Some(extra) = extra {
+      let values = param_values(0.0, 1.0, 0.0, ParamScale::Linear);
+      builder.param("extra", values.with_initial_value(extra));
+    }
+    builder.build()
+  }
+
+  fn morphed_values(t: f32) -> std::vec::Vec<f32> {
+    let (_, consumer) = RingBuffer::new(16).split();
+    let program = morph_program(20.0, 0.0, Some(0.0));
+    let mut synth = Synth::new(44100.0, consumer, program, SynthGlobals::new());
+    let a = morph_program(100.0, 1.0, Some(0.25));
+    let b = morph_program(1000.0, 3.0, None);
+    synth.morph(&a, &b, t);
+    let params = synth.get_program().get_params();
+    params.iter().map(|param| param.value.get()).collect()
+  }
+
+  #[test]
+  fn morph() {
+    assert_eq!(morphed_values(0.0), vec![100.0, 1.0, 0.25]);
+    assert_eq!(morphed_values(0.25), vec![325.0, 1.0, 0.25]);
+    assert_eq!(morphed_values(0.5), vec![550.0, 3.0, 0.25]);
+    assert_eq!(morphed_values(1.0), vec![1000.0, 3.0, 0.25]);
+  }
+
+  #[test]
+  fn macro_moves_its_targets() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let cutoff = builder.param("cutoff", param_values(0.0, 100.0, 0.0, ParamScale::Linear));
+    let level = param_values(-1.0, 1.0, 0.0, ParamScale::Linear).with_initial_value(0.5);
+    let level = builder.param("level", level);
+    let macro_param = builder.param("macro", param_values(0.0, 1.0, 0.0, ParamScale::Linear));
+    let targets = [
+      MacroTarget::new(cutoff.reference, 0.5),
+      MacroTarget::new(level.reference, -0.25).with_curve(MacroCurve::Exponential),
+    ];
+    builder.macro_control(macro_param.reference, &targets);
+    builder.block(Block::Param(cutoff.clone()));
+    builder.block(Block::Param(level.clone()));
+    builder.out(cutoff.out_signal_ref, level.out_signal_ref);
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+
+    note_on(&mut synth, &mut events, 60);
+    let (left, right) = synth.process();
+    assert_approx_eq!(left, 0.0);
+    assert_approx_eq!(right, 0.5);
+
+    let message = Message::ParamValue {
+      param_ref: macro_param.reference,
+      value: 0.5,
+    };
+    send(&mut synth, &mut events, message);
+    let (left, right) = synth.process();
+    assert_approx_eq!(left, 25.0);
+    assert_approx_eq!(right, 0.5 - 0.25 * 0.25 * 2.0);
+
+    let message = Message::ParamValue {
+      param_ref: macro_param.reference,
+      value: 1.0,
+    };
+    send(&mut synth, &mut events, message);
+    let (left, right) = synth.process();
+    assert_approx_eq!(left, 50.0);
+    assert_approx_eq!(right, 0.0);
+  }
+
+  fn render_osc_key_range(key: u8) -> f32 {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let one = builder.const_one();
+    let mut osc = test_osc(&mut builder, one, None);
+    osc.inputs.key_low = builder.const_value(60.0);
+    osc.inputs.key_high = builder.const_value(72.0);
+    builder.out(osc.output, osc.output);
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+
+    note_on(&mut synth, &mut events, key);
+    (0..1000).map(|_| synth.process().0.abs()).fold(0.0, f32::max)
+  }
+
+  #[test]
+  fn osc_key_range() {
+    assert!(render_osc_key_range(60) > 0.5);
+    assert!(render_osc_key_range(72) > 0.5);
+    // out of the range the voice is played but the oscillator is silent
+    assert_eq!(render_osc_key_range(59), 0.0);
+    assert_eq!(render_osc_key_range(73), 0.0);
+  }
+
+  /// Render the wraps of the LFO selected and an oscillator playing the middle C synced to it,
+  /// with the LFO 1 at 10 Hz and the LFO 2 at 7 Hz
+  fn render_osc_lfo_sync(lfo_sync: osc::LfoSync) -> std::vec::Vec<(f32, f32)> {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let mut lfo = |rate: f32| lfo::Block {
+      inputs: lfo::Inputs {
+        shape: zero,
+        rate: builder.const_value(rate),
+        phase: zero,
+        depth: one,
+        slew: zero,
+        slew_sync: zero,
+        polarity: zero,
+        invert: zero,
+      },
+      output: builder.signal(),
+      sync_out: builder.signal(),
+      phase_out: builder.signal(),
+    };
+    let (lfo1, lfo2) = (lfo(10.0), lfo(7.0));
+    let mut osc = test_osc(&mut builder, one, None);
+    osc.inputs.lfo_sync = builder.const_value(lfo_sync as usize as f32);
+    osc.inputs.lfo1_sync_in = lfo1.sync_out;
+    osc.inputs.lfo2_sync_in = lfo2.sync_out;
+    let wraps = match lfo_sync {
+      osc::LfoSync::Lfo2 => lfo2.sync_out,
+      _ => lfo1.sync_out,
+    };
+    builder.out(wraps, osc.output);
+    builder.block(Block::Lfo(lfo1));
+    builder.block(Block::Lfo(lfo2));
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(1000.0, consumer, builder.build(), SynthGlobals::new());
+
+    note_on(&mut synth, &mut events, 60);
+    (0..1000).map(|_| synth.process()).collect()
+  }
+
+  fn lfo_wraps(output: &[(f32, f32)]) -> std::vec::Vec<usize> {
+    (0..output.len()).filter(|i| output[*i].0 > 0.0).collect()
+  }
+
+  /// Whether the oscillator restarts its cycle right when the LFO wraps
+  fn synced_at(output: &[(f32, f32)], wraps: &[usize]) -> bool {
+    let first_sample = output[0].1;
+    wraps
+      .iter()
+      .all(|index| (output[*index].1 - first_sample).abs() < 1e-4)
+  }
+
+  #[test]
+  fn osc_sync_to_lfo() {
+    let output = render_osc_lfo_sync(osc::LfoSync::Lfo1);
+    let lfo1_wraps = lfo_wraps(&output);
+    assert!(lfo1_wraps.len() >= 9);
+    assert!(synced_at(&output, &lfo1_wraps));
+
+    let output = render_osc_lfo_sync(osc::LfoSync::Lfo2);
+    let lfo2_wraps = lfo_wraps(&output);
+    assert!(lfo2_wraps.len() >= 6);
+    assert!(synced_at(&output, &lfo2_wraps));
+    assert!(!synced_at(&output, &lfo1_wraps));
+
+    let output = render_osc_lfo_sync(osc::LfoSync::Off);
+    assert!(!synced_at(&output, &lfo1_wraps));
+  }
+
+  /// The length in samples of the transition of a sample and hold LFO at 1 Hz to its second value
+  fn sample_hold_slew_samples(slew: f32, slew_sync: bool, tempo: f32) -> usize {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let lfo = lfo::Block {
+      inputs: lfo::Inputs {
+        shape: builder.const_value(3.0),
+        rate: one,
+        phase: zero,
+        depth: one,
+        slew: builder.const_value(slew),
+        slew_sync: if slew_sync { one } else { zero },
+        polarity: zero,
+        invert: zero,
+      },
+      output: builder.signal(),
+      sync_out: builder.signal(),
+      phase_out: builder.signal(),
+    };
+    builder.out(lfo.output, lfo.output);
+    builder.block(Block::Lfo(lfo));
+    let mut synth = Synth::new(1000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_out_time(0.0);
+    synth.set_tempo(tempo);
+
+    note_on(&mut synth, &mut events, 60);
+    let output: std::vec::Vec<f32> = (0..2000).map(|_| synth.process().0).collect();
+    let (first, second) = (output[900], output[1999]);
+    let ramp = |value: &&f32| **value != first && **value != second;
+    output[900..].iter().filter(ramp).count()
+  }
+
+  #[test]
+  fn sample_hold_slew() {
+    assert_eq!(sample_hold_slew_samples(0.0, false, 120.0), 0);
+    assert_eq!(sample_hold_slew_samples(0.1, false, 120.0), 99);
+    // half a beat at 120 bpm is a quarter of a second
+    assert_eq!(sample_hold_slew_samples(0.5, true, 120.0), 249);
+    assert_eq!(sample_hold_slew_samples(0.5, true, 60.0), 499);
+  }
+
+  /// Render a sine LFO at 10 Hz with a polarity and inverted or not
+  fn render_lfo_polarity(polarity: f32, invert: bool) -> std::vec::Vec<f32> {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let lfo = lfo::Block {
+      inputs: lfo::Inputs {
+        shape: zero,
+        rate: builder.const_value(10.0),
+        phase: zero,
+        depth: one,
+        slew: zero,
+        slew_sync: zero,
+        polarity: builder.const_value(polarity),
+        invert: if invert { one } else { zero },
+      },
+      output: builder.signal(),
+      sync_out: builder.signal(),
+      phase_out: builder.signal(),
+    };
+    builder.out(lfo.output, lfo.output);
+    builder.block(Block::Lfo(lfo));
+    let mut synth = Synth::new(1000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+
+    note_on(&mut synth, &mut events, 60);
+    // the first sample is rendered before the voice starts
+    synth.process();
+    (0..200).map(|_| synth.process().0).collect()
+  }
+
+  #[test]
+  fn lfo_polarity() {
+    let bipolar = render_lfo_polarity(0.0, false);
+    let unipolar = render_lfo_polarity(1.0, false);
+    assert!(bipolar.iter().any(|value| *value < -0.9));
+    assert!(unipolar.iter().all(|value| *value >= 0.0 && *value <= 1.0));
+    for (bipolar, unipolar) in bipolar.iter().zip(unipolar.iter()) {
+      assert_approx_eq!(*unipolar, 0.5 * *bipolar + 0.5);
+    }
+
+    let inverted = render_lfo_polarity(0.0, true);
+    for (bipolar, inverted) in bipolar.iter().zip(inverted.iter()) {
+      assert_approx_eq!(*inverted, -*bipolar);
+    }
+    let inverted = render_lfo_polarity(1.0, true);
+    for (unipolar, inverted) in unipolar.iter().zip(inverted.iter()) {
+      assert_approx_eq!(*inverted, -*unipolar);
+    }
+  }
+
+  fn samples_until_attack_end(velocity_to_attack: f32, velocity: f32) -> usize {
+    let sample_rate = 1000.0;
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = adsr_program(1.0, 1.0, 1.0, 1.0, velocity_to_attack);
+    let mut synth = Synth::new(sample_rate, consumer, program, SynthGlobals::new());
+    send(&mut synth, &mut events, Message::NoteOn { key: 60, velocity });
+    let mut samples = 0;
+    while synth.process().0 < 0.99 && samples < 10 * sample_rate as usize {
+      samples += 1;
+    }
+    samples
+  }
+
+  #[test]
+  fn velocity_to_attack() {
+    let soft = samples_until_attack_end(0.8, 0.1);
+    let hard = samples_until_attack_end(0.8, 1.0);
+    assert!(hard < soft / 2);
+    assert_eq!(
+      samples_until_attack_end(0.0, 0.1),
+      samples_until_attack_end(0.0, 1.0)
+    );
+
+    // the attack never gets to zero
+    assert!(samples_until_attack_end(2.0, 1.0) > 0);
+
+    // negative amounts make the attack longer for hard notes
+    assert!(samples_until_attack_end(-1.0, 1.0) > samples_until_attack_end(-1.0, 0.1));
+  }
+
+  fn sequencer_program<'a>(values: &[f32], division: f32) -> Program<'a, f32> {
+    let mut builder = ProgramBuilder::new();
+    let inputs = sequencer::Inputs {
+      division: builder.const_value(division),
+      length: builder.const_value(values.len() as f32),
+    };
+    let outputs = sequencer::Outputs {
+      value: builder.signal(),
+      gate: builder.signal(),
+    };
+    let output = outputs.value;
+    builder.block(Block::Sequencer(sequencer::Block::with_values(
+      values, inputs, outputs,
+    )));
+    builder.out(output, output);
+    builder.build()
+  }
+
+  /// Render a sample from the middle of every step
+  fn render_steps(synth: &mut Synth<f32>, samples_per_step: usize, count: usize) -> Vec<f32, U16> {
+    let mut values = Vec::new();
+    for _ in 0..count {
+      for sample in 0..samples_per_step {
+        let (left, _) = synth.process();
+        if sample == samples_per_step / 2 {
+          values.push(left).unwrap();
+        }
+      }
+    }
+    values
+  }
+
+  #[test]
+  fn step_sequencer_synced_to_the_transport() {
+    let sample_rate = 1000.0;
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = sequencer_program(&[1.0, 2.0, 3.0], 4.0);
+    let mut synth = Synth::new(sample_rate, consumer, program, SynthGlobals::new());
+    assert_approx_eq!(synth.get_tempo(), 120.0);
+
+    // 120 bpm with 4 steps per beat at 1000 Hz makes steps of 125 samples
+    note_on(&mut synth, &mut events, 60);
+    let values = render_steps(&mut synth, 125, 7);
+    assert_eq!(&values[..], &[1.0, 2.0, 3.0, 1.0, 2.0, 3.0, 1.0]);
+    assert_approx_eq!(synth.get_beats(), 7.0 / 4.0, 1e-3);
+
+    // halving the tempo doubles the length of the steps
+    send(&mut synth, &mut events, Message::Tempo { bpm: 60.0 });
+    let values = render_steps(&mut synth, 250, 3);
+    assert_eq!(&values[..], &[2.0, 3.0, 1.0]);
+  }
+
+  #[test]
+  fn filter_self_oscillation_at_the_note_pitch() {
+    let sample_rate = 44100.0;
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let filter = filter::Block {
+      input: builder.const_one(),
+      params: filter::Params {
+        mode: builder.const_value(3.0),
+        freq: builder.const_value(5000.0),
+        freq_mod: zero,
+        key_track: zero,
+        key_track_center: zero,
+        q: zero,
+        velocity_to_q: zero,
+        q_compensation: zero,
+        gain: zero,
+        self_osc: builder.const_one(),
+      },
+      output: builder.signal(),
+    };
+    builder.block(Block::Filter(filter.clone()));
+    builder.out(filter.output, filter.output);
+    let program = builder.build();
+
+    // A1 (55 Hz), below the range of the cutoff, and A4 (440 Hz) for one second
+    for (key, freq) in [(33u8, 55usize), (69, 440)].iter() {
+      let (mut events, consumer) = RingBuffer::new(16).split();
+      let mut synth = Synth::new(sample_rate, consumer, program.clone(), SynthGlobals::new());
+      note_on(&mut synth, &mut events, *key);
+      let output: std::vec::Vec<f32> = (0..44100).map(|_| synth.process().0).collect();
+
+      let crossings = output
+        .windows(2)
+        .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+        .count();
+      assert!((freq - 2..=freq + 2).contains(&crossings), "{} crossings", crossings);
+      let peak = output.iter().fold(0.0f32, |peak, value| peak.max(value.abs()));
+      assert_approx_eq!(peak, 1.0, 0.05);
+    }
+  }
+
+  #[test]
+  fn amp_eg_governs_the_voice_lifetime() {
+    let sample_rate = 1000.0;
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+
+    // a modulation envelope with a long release not routed anywhere
+    let mod_eg = envgen::Block {
+      inputs: envgen::Inputs {
+        attack: zero,
+        decay: zero,
+        sustain: one,
+        release: builder.const_value(10.0),
+        mode: zero,
+        legato: zero,
+        reset_to_zero: zero,
+        velocity_to_attack: zero,
+        velocity_to_decay: zero,
+      },
+      outputs: envgen::Outputs {
+        normal: builder.signal(),
+        biased: builder.signal(),
+      },
+    };
+    builder.block(Block::EG(mod_eg));
+    let amp_release = builder.const_value(0.1);
+    builder.amp_envelope(envgen::Inputs {
+      attack: zero,
+      decay: zero,
+      sustain: one,
+      release: amp_release,
+      mode: zero,
+      legato: zero,
+      reset_to_zero: zero,
+      velocity_to_attack: zero,
+      velocity_to_decay: zero,
+    });
+    builder.out(one, one);
+    let mut synth = Synth::new(sample_rate, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+
+    note_on(&mut synth, &mut events, 60);
+    for _ in 0..10 {
+      assert_approx_eq!(synth.process().0, 1.0);
+    }
+    send(&mut synth, &mut events, Message::NoteOff { key: 60, velocity: 0.0 });
+    let seconds = seconds_until_free(&mut synth, sample_rate);
+    assert!(seconds > 0.05 && seconds < 0.5, "{} seconds", seconds);
+  }
+
+  #[test]
+  fn drum_map_overrides_the_key_freq() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut program = const_program(1.0);
+    program.set_drum_map(DrumMap::new().with(36, 55.5).with(38, 180.0));
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_mono(true);
+
+    for (key, freq) in [(36, 55.5), (38, 180.0), (40, KEY_FREQ[40])].iter() {
+      note_on(&mut synth, &mut events, *key);
+      synth.process();
+      assert_approx_eq!(note_pitch(&synth), freq);
+      let note_off = Message::NoteOff {
+        key: *key,
+        velocity: 0.0,
+      };
+      send(&mut synth, &mut events, note_off);
+    }
+  }
+
+  #[test]
+  fn voices_fade_out_when_freed() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut synth = Synth::new(1000.0, consumer, const_program(1.0), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+    synth.set_fade_out_time(0.01);
+    note_on(&mut synth, &mut events, 60);
+    synth.process();
+
+    let note_off = Message::NoteOff {
+      key: 60,
+      velocity: 0.0,
+    };
+    send(&mut synth, &mut events, note_off);
+    let output: std::vec::Vec<f32> = (0..20).map(|_| synth.process().0).collect();
+    let decreasing = |pair: &[f32]| pair[1] < pair[0] || pair[1] == 0.0;
+    assert!(output.windows(2).all(decreasing));
+    assert!(output.windows(2).all(|pair| pair[0] - pair[1] < 0.1));
+    assert!(output[8] > 0.0);
+    assert_eq!(output[12], 0.0);
+    assert_eq!(synth.get_num_active_voices(), 0);
+  }
+
+  #[test]
+  fn voices_fade_in_when_started() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = adsr_program(0.0, 0.0, 1.0, 0.1, 0.0);
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_fade_in_time(0.004);
+    note_on(&mut synth, &mut events, 60);
+
+    // even with an instant attack the output ramps up from zero
+    let output: std::vec::Vec<f32> = (0..8).map(|_| synth.process().0).collect();
+    for (index, expected) in [0.2, 0.4, 0.6, 0.8, 1.0, 1.0].iter().enumerate() {
+      assert_approx_eq!(output[index], expected);
+    }
+
+    synth.set_fade_in_time(0.0);
+    note_on(&mut synth, &mut events, 62);
+    synth.process();
+    let voice = synth.get_last_voice().unwrap();
+    assert_approx_eq!(voice.output(synth.get_program()).0, 1.0);
+  }
+
+  #[test]
+  fn retriggered_voices_fade_in_from_their_level() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = adsr_program(0.0, 0.0, 1.0, 0.1, 0.0);
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_mono(true);
+    synth.set_fade_in_time(0.004);
+    note_on(&mut synth, &mut events, 60);
+    for _ in 0..10 {
+      synth.process();
+    }
+    let note_off = Message::NoteOff {
+      key: 60,
+      velocity: 0.0,
+    };
+    send(&mut synth, &mut events, note_off);
+    let releasing = synth.process().0;
+
+    // the mono voice is still sounding so it does not drop to zero
+    note_on(&mut synth, &mut events, 62);
+    let output: std::vec::Vec<f32> = (0..6).map(|_| synth.process().0).collect();
+    assert!(output[0] >= releasing, "{} -> {}", releasing, output[0]);
+    assert!(output.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert_approx_eq!(output[5], 1.0);
+  }
+
+  #[test]
+  fn voices_are_gated_without_amp_eg() {
+    let (mut synth, mut events) = synth();
+    // without the fade out the voice is freed right after the gate
+    synth.set_fade_out_time(0.0);
+    note_on(&mut synth, &mut events, 60);
+    synth.process();
+    assert_eq!(synth.get_num_active_voices(), 1);
+
+    send(&mut synth, &mut events, Message::NoteOff { key: 60, velocity: 0.0 });
+    synth.process();
+    synth.process();
+    assert_eq!(synth.get_num_active_voices(), 0);
+  }
+
+  fn note_pitch(synth: &Synth<f32>) -> f32 {
+    let voice = synth.get_last_voice().unwrap();
+    let note_pitch: usize = synth.get_program().voice().note_pitch.into();
+    voice.get_signals()[note_pitch].get()
+  }
+
+  #[test]
+  fn mono_glides_back_to_the_held_note() {
+    let sample_rate = 1000.0;
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = envgen_program(0.0, 1.0, 0.01);
+    let mut synth = Synth::new(sample_rate, consumer, program, SynthGlobals::new());
+    synth.set_mono(true);
+    synth.set_glide_time(0.1);
+    let c = KEY_FREQ[60];
+    let e = KEY_FREQ[64];
+
+    note_on(&mut synth, &mut events, 60);
+    synth.process();
+    assert_approx_eq!(note_pitch(&synth), c);
+
+    // press E while C is held: glides up without a new voice
+    note_on(&mut synth, &mut events, 64);
+    for _ in 0..50 {
+      synth.process();
+    }
+    assert_eq!(synth.get_num_active_voices(), 1);
+    assert!(note_pitch(&synth) > c && note_pitch(&synth) < e);
+    for _ in 0..50 {
+      synth.process();
+    }
+    assert_approx_eq!(note_pitch(&synth), e);
+
+    // release E: glides back to C and keeps sounding
+    send(&mut synth, &mut events, Message::NoteOff { key: 64, velocity: 0.0 });
+    for _ in 0..50 {
+      synth.process();
+    }
+    assert!(note_pitch(&synth) > c && note_pitch(&synth) < e);
+    for _ in 0..50 {
+      synth.process();
+    }
+    assert_approx_eq!(note_pitch(&synth), c);
+    assert_eq!(synth.get_num_active_voices(), 1);
+    assert_eq!(synth.get_num_releasing_voices(), 0);
+    assert_approx_eq!(synth.process().0, 1.0);
+
+    // release C: the note is off
+    send(&mut synth, &mut events, Message::NoteOff { key: 60, velocity: 0.0 });
+    assert_eq!(synth.get_num_releasing_voices(), 1);
+    assert!(seconds_until_free(&mut synth, sample_rate) < 0.1);
+  }
+
+  #[test]
+  fn poly_glide_from_the_released_voice() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = envgen_program(0.0, 1.0, 0.01);
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_poly_glide(true);
+    synth.set_glide_time(0.1);
+    let c = KEY_FREQ[60];
+    let e = KEY_FREQ[64];
+
+    // the first note has no previous pitch to glide from
+    note_on(&mut synth, &mut events, 60);
+    synth.process();
+    assert_approx_eq!(note_pitch(&synth), c);
+    send(&mut synth, &mut events, Message::NoteOff { key: 60, velocity: 0.0 });
+
+    // a new voice glides from the released one
+    note_on(&mut synth, &mut events, 64);
+    assert_eq!(synth.get_num_active_voices(), 2);
+    for _ in 0..50 {
+      synth.process();
+    }
+    assert!(note_pitch(&synth) > c && note_pitch(&synth) < e);
+    for _ in 0..50 {
+      synth.process();
+    }
+    assert_approx_eq!(note_pitch(&synth), e);
+
+    // without poly glide the note starts at its own pitch
+    synth.set_poly_glide(false);
+    send(&mut synth, &mut events, Message::NoteOff { key: 64, velocity: 0.0 });
+    note_on(&mut synth, &mut events, 60);
+    synth.process();
+    assert_approx_eq!(note_pitch(&synth), c);
+  }
+
+  /// The number of samples that a mono glide from `from` to `to` takes
+  fn glide_duration(mode: GlideMode, from: u8, to: u8) -> usize {
+    glide_duration_with_threshold(mode, 0.0, from, to)
+  }
+
+  fn glide_duration_with_threshold(mode: GlideMode, threshold: f32, from: u8, to: u8) -> usize {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = envgen_program(0.0, 1.0, 0.01);
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_mono(true);
+    synth.set_glide_mode(mode);
+    synth.set_glide_time(0.1);
+    synth.set_glide_rate(120.0);
+    synth.set_glide_threshold(threshold);
+
+    note_on(&mut synth, &mut events, from);
+    synth.process();
+    note_on(&mut synth, &mut events, to);
+    let target = KEY_FREQ[to as usize];
+    (1..10000)
+      .find(|_| {
+        synth.process();
+        (note_pitch(&synth) - target).abs() < 1e-3
+      })
+      .unwrap()
+  }
+
+  #[test]
+  fn glide_time_and_rate() {
+    assert_eq!(glide_duration(GlideMode::Time, 48, 60), 100);
+    assert_eq!(glide_duration(GlideMode::Time, 48, 72), 100);
+
+    // 120 semitones per second
+    assert_eq!(glide_duration(GlideMode::Rate, 48, 60), 100);
+    assert_eq!(glide_duration(GlideMode::Rate, 48, 72), 200);
+    assert_eq!(glide_duration(GlideMode::Rate, 72, 48), 200);
+  }
+
+  #[test]
+  fn glide_threshold() {
+    // only glide for intervals up to an octave
+    let duration = |mode, from, to| glide_duration_with_threshold(mode, 12.0, from, to);
+    assert_eq!(duration(GlideMode::Time, 48, 55), 100);
+    assert_eq!(duration(GlideMode::Time, 48, 60), 100);
+    assert_eq!(duration(GlideMode::Time, 48, 61), 1);
+    assert_eq!(duration(GlideMode::Rate, 60, 48), 100);
+    assert_eq!(duration(GlideMode::Rate, 60, 47), 1);
+  }
+
+  fn active_keys(synth: &Synth<f32>) -> std::vec::Vec<u8> {
+    let key: usize = synth.get_program().voice().key.into();
+    let mut keys: std::vec::Vec<u8> = synth
+      .active_voices
+      .iter()
+      .map(|index| synth.voices[*index].get_signals()[key].get() as u8)
+      .collect();
+    keys.sort_unstable();
+    keys
+  }
+
+  fn steal_synth<'a>(policy: StealPolicy) -> (Synth<'a, f32>, Producer<Event<f32>>) {
+    let (events, consumer) = RingBuffer::new(16).split();
+    let program = ProgramBuilder::new().build();
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_min_steal_age(0.1);
+    synth.set_steal_policy(policy);
+    (synth, events)
+  }
+
+  fn play_all_voices(synth: &mut Synth<f32>, events: &mut Producer<Event<f32>>) {
+    for key in 0..MaxVoices::to_usize() as u8 {
+      note_on(synth, events, key);
+    }
+    for _ in 0..10 {
+      synth.process();
+    }
+  }
+
+  #[test]
+  fn voices_are_not_stolen_by_default() {
+    let (mut events, consumer) = RingBuffer::new(64).split();
+    let program = ProgramBuilder::new().build();
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    assert_eq!(synth.get_steal_policy(), StealPolicy::Never);
+    assert!(synth.get_min_steal_age() > 0.0);
+
+    play_all_voices(&mut synth, &mut events);
+    for _ in 0..1000 {
+      synth.process();
+    }
+    note_on(&mut synth, &mut events, 100);
+    process_fade_out(&mut synth);
+    assert_eq!(active_keys(&synth).len(), MaxVoices::to_usize());
+    assert!(!active_keys(&synth).contains(&100));
+  }
+
+  #[test]
+  fn young_voices_are_not_stolen() {
+    let (mut synth, mut events) = steal_synth(StealPolicy::DropNewNote);
+    play_all_voices(&mut synth, &mut events);
+    note_on(&mut synth, &mut events, 100);
+    assert_eq!(active_keys(&synth).len(), MaxVoices::to_usize());
+    assert!(!active_keys(&synth).contains(&100));
+
+    // once they are old enough the oldest one is stolen
+    for _ in 0..100 {
+      synth.process();
+    }
+    note_on(&mut synth, &mut events, 100);
+    process_fade_out(&mut synth);
+    assert_eq!(active_keys(&synth).len(), MaxVoices::to_usize());
+    assert!(active_keys(&synth).contains(&100));
+  }
+
+  #[test]
+  fn young_voices_stolen_by_policy() {
+    let (mut synth, mut events) = steal_synth(StealPolicy::StealOldest);
+    play_all_voices(&mut synth, &mut events);
+    let note_off = Message::NoteOff {
+      key: 5,
+      velocity: 0.0,
+    };
+    send(&mut synth, &mut events, note_off);
+    note_on(&mut synth, &mut events, 100);
+    process_fade_out(&mut synth);
+    let keys = active_keys(&synth);
+    assert_eq!(keys.len(), MaxVoices::to_usize());
+    assert!(keys.contains(&100));
+    // the releasing voice is preferred
+    assert!(!keys.contains(&5));
+  }
+
+  #[test]
+  fn stolen_voices_fade_out_before_the_new_note() {
+    let (mut events, consumer) = RingBuffer::new(64).split();
+    let program = envgen_program(0.1, 1.0, 2.0);
+    let mut synth = Synth::new(44100.0, consumer, program, SynthGlobals::new());
+    synth.set_steal_policy(StealPolicy::StealOldest);
+    play_all_voices(&mut synth, &mut events);
+    for _ in 0..1000 {
+      synth.process();
+    }
+
+    // the output only moves by the steps of the fades, without jumps
+    note_on(&mut synth, &mut events, 100);
+    let globals = &synth.globals;
+    let fade_samples = globals.fade_out_samples.max(globals.fade_in_samples);
+    let max_step = 1.0 / fade_samples as f32 + 1e-4;
+    let mut last = synth.get_num_active_voices() as f32;
+    for _ in 0..4 * fade_samples {
+      let (left, _) = synth.process();
+      assert!((left - last).abs() <= max_step, "{} -> {}", last, left);
+      last = left;
+    }
+    assert!(active_keys(&synth).contains(&100));
+    assert_approx_eq!(last, MaxVoices::to_usize() as f32, 1e-4);
+  }
+
+  /// Render some voices playing a band-limited saw, with the quality scaling enabled or not
+  fn render_saw_voices(
+    keys: &[u8],
+    quality_threshold: Option<usize>,
+  ) -> (std::vec::Vec<f32>, bool) {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let one = builder.const_one();
+    let mut osc = test_osc(&mut builder, one, None);
+    osc.inputs.shape = builder.const_value(2.0);
+    builder.out(osc.output, osc.output);
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+    if let Some(threshold) = quality_threshold {
+      synth.set_quality_scaling(true, threshold);
+    }
+
+    for key in keys.iter() {
+      note_on(&mut synth, &mut events, *key);
+    }
+    let output = (0..1000).map(|_| synth.process().0).collect();
+    (output, synth.is_quality_reduced())
+  }
+
+  #[test]
+  fn quality_scaling_with_the_voice_count() {
+    let keys = [60, 64, 67];
+    let (full, reduced) = render_saw_voices(&keys, None);
+    assert!(!reduced);
+
+    // up to the threshold the voices keep the band-limited waveform
+    let (output, reduced) = render_saw_voices(&keys, Some(3));
+    assert!(!reduced);
+    assert_eq!(output, full);
+
+    // beyond it they switch to the trivial one
+    let (output, reduced) = render_saw_voices(&keys, Some(2));
+    assert!(reduced);
+    assert!(output
+      .iter()
+      .zip(full.iter())
+      .any(|(a, b)| (a - b).abs() > 1e-3));
+  }
+
+  #[test]
+  fn quality_restored_when_the_polyphony_drops() {
+    let (mut synth, mut events) = synth();
+    synth.set_quality_scaling(true, 1);
+    note_on(&mut synth, &mut events, 60);
+    note_on(&mut synth, &mut events, 64);
+    synth.process();
+    assert!(synth.is_quality_reduced());
+
+    synth.all_sound_off();
+    process_fade_out(&mut synth);
+    note_on(&mut synth, &mut events, 60);
+    synth.process();
+    assert!(!synth.is_quality_reduced());
+  }
+
+  /// The highest output of a mono synth after an overlapping note with some velocity
+  fn legato_peak(retrigger_velocity: f32, velocity: f32) -> f32 {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = adsr_program(0.0, 0.05, 0.5, 0.01, 0.0);
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+    synth.set_mono(true);
+    synth.set_legato_retrigger_velocity(retrigger_velocity);
+
+    note_on(&mut synth, &mut events, 60);
+    for _ in 0..500 {
+      synth.process();
+    }
+    assert_approx_eq!(synth.process().0, 0.5, 0.01);
+
+    let note_on = Message::NoteOn { key: 64, velocity };
+    send(&mut synth, &mut events, note_on);
+    (0..20).map(|_| synth.process().0).fold(0.0, f32::max)
+  }
+
+  #[test]
+  fn legato_retrigger_velocity() {
+    // a soft note stays legato in the sustain
+    assert_approx_eq!(legato_peak(0.6, 0.3), 0.5, 0.01);
+    // a hard one restarts the envelope
+    assert!(legato_peak(0.6, 0.9) > 0.9);
+    // without threshold they are all legato
+    assert_approx_eq!(legato_peak(0.0, 1.0), 0.5, 0.01);
+  }
+
+  /// The number of cycles in a second of a sine oscillator playing a key
+  fn osc_cycles(key: u8, fixed_freq: bool) -> usize {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let mut osc = test_osc(&mut builder, one, Some(100.0));
+    osc.inputs.fixed_freq = if fixed_freq { one } else { zero };
+    builder.out(osc.sync_out, osc.sync_out);
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(8000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+
+    note_on(&mut synth, &mut events, key);
+    (0..8000).filter(|_| synth.process().0 > 0.0).count()
+  }
+
+  #[test]
+  fn osc_fixed_freq_ignores_the_note() {
+    let cycles = osc_cycles(40, true);
+    assert!((99..=101).contains(&cycles), "{} cycles", cycles);
+    assert_eq!(osc_cycles(80, true), cycles);
+    assert!(osc_cycles(80, false) > osc_cycles(40, false));
+  }
+
+  /// The peak of a tone at the output with the master high-pass or low-pass at 1 kHz
+  fn master_filter_peak(tone_hz: f32, high_pass: bool, low_pass: bool) -> f32 {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let one = builder.const_one();
+    let switch = |enabled: bool| {
+      let value = if enabled { 1.0 } else { 0.0 };
+      param_values(0.0, 1.0, 1.0, ParamScale::Linear).with_initial_value(value)
+    };
+    let cutoff = param_values(20.0, 20000.0, 1.0, ParamScale::Linear).with_initial_value(1000.0);
+    let master = MasterBlock {
+      high_pass: Some(builder.param("high-pass", switch(high_pass)).reference),
+      high_pass_freq: Some(builder.param("high-pass-freq", cutoff.clone()).reference),
+      low_pass: Some(builder.param("low-pass", switch(low_pass)).reference),
+      low_pass_freq: Some(builder.param("low-pass-freq", cutoff).reference),
+      ..MasterBlock::default()
+    };
+    builder.master(master);
+    let osc = test_osc(&mut builder, one, Some(tone_hz));
+    builder.out(osc.output, osc.output);
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+
+    note_on(&mut synth, &mut events, 60);
+    (0..8820)
+      .map(|_| synth.process().0)
+      .skip(4410)
+      .fold(0.0, |peak, sample| peak.max(sample.abs()))
+  }
+
+  #[test]
+  fn master_high_pass_and_low_pass() {
+    assert_approx_eq!(master_filter_peak(50.0, false, false), 1.0, 0.01);
+    assert_approx_eq!(master_filter_peak(10000.0, false, false), 1.0, 0.01);
+
+    assert!(master_filter_peak(50.0, true, false) < 0.01);
+    assert_approx_eq!(master_filter_peak(10000.0, true, false), 1.0, 0.02);
+
+    assert_approx_eq!(master_filter_peak(50.0, false, true), 1.0, 0.02);
+    assert!(master_filter_peak(10000.0, false, true) < 0.02);
+  }
+
+  #[test]
+  fn fixed_length_releases_without_note_off() {
+    let sample_rate = 1000.0;
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = envgen_program(0.0, 1.0, 0.01);
+    let mut synth = Synth::new(sample_rate, consumer, program, SynthGlobals::new());
+    synth.set_fixed_length(0.1);
+
+    note_on(&mut synth, &mut events, 60);
+    let mut samples = 0;
+    while synth.get_num_releasing_voices() == 0 && samples < 1000 {
+      synth.process();
+      samples += 1;
+    }
+    assert_eq!(samples, 100);
+    assert!(seconds_until_free(&mut synth, sample_rate) < 0.1);
+  }
+
+  /// The pan positions of some successive notes
+  fn note_pans(random_pan_amount: f32) -> Vec<f32, U16> {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = adsr_program(0.0, 0.0, 1.0, 0.0, 0.0);
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_random_pan_amount(random_pan_amount);
+
+    let mut pans = Vec::new();
+    for key in 60..68 {
+      note_on(&mut synth, &mut events, key);
+      synth.process();
+      let index = *synth.active_voices.last().unwrap();
+      pans.push(synth.voices[index].get_pan()).unwrap();
+    }
+    pans
+  }
+
+  #[test]
+  fn random_pan_amount() {
+    assert!(note_pans(0.0).iter().all(|pan| *pan == 0.0));
+
+    let pans = note_pans(0.5);
+    assert!(pans.iter().all(|pan| pan.abs() <= 0.5));
+    assert!(pans.iter().any(|pan| *pan < -0.1) && pans.iter().any(|pan| *pan > 0.1));
+    assert_eq!(pans, note_pans(0.5));
+  }
+
+  #[test]
+  fn lfo_phase_offset_per_modulation() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let lfo = lfo::Block {
+      inputs: lfo::Inputs {
+        shape: zero,
+        rate: builder.const_value(10.0),
+        phase: zero,
+        depth: one,
+        slew: zero,
+        slew_sync: zero,
+        polarity: zero,
+        invert: zero,
+      },
+      output: builder.signal(),
+      sync_out: builder.signal(),
+      phase_out: builder.signal(),
+    };
+    let source = builder.lfo_source("lfo", &lfo);
+    let values = param_values(-1.0, 1.0, 0.0, ParamScale::Linear).with_initial_value(0.0);
+    let pan = builder.param("pan", values.clone());
+    let cutoff = builder.param("cutoff", values);
+    builder.modulation(&pan, source, 1.0);
+    builder.modulation(&cutoff, source, 1.0);
+    builder.modulation_phase(&cutoff, source, 0.5);
+    builder.block(Block::Lfo(lfo));
+    builder.block(Block::Param(pan.clone()));
+    builder.block(Block::Param(cutoff.clone()));
+    let mut synth = Synth::new(1000.0, consumer, builder.build(), SynthGlobals::new());
+
+    note_on(&mut synth, &mut events, 60);
+    let mut peaks = 0;
+    for _ in 0..200 {
+      synth.process();
+      let signals = synth.voices[synth.active_voices[0]].get_signals();
+      let pan = signals[pan.out_signal_ref.0].get();
+      let cutoff = signals[cutoff.out_signal_ref.0].get();
+      assert_approx_eq!(cutoff, -pan, 1e-3);
+      if pan.abs() > 0.9 {
+        assert!(pan * cutoff < 0.0);
+        peaks += 1;
+      }
+    }
+    assert!(peaks > 0);
+  }
+
+  /// An effect with a steady output, like the tail of a long reverb
+  struct SteadyEffect;
+
+  impl Processor<f32> for SteadyEffect {
+    fn get_mix(&self) -> f32 {
+      1.0
+    }
+
+    fn set_mix(&mut self, _mix: f32) {}
+
+    fn is_bypassed(&self) -> bool {
+      false
+    }
+
+    fn set_bypass(&mut self, _bypass: bool) {}
+
+    fn process_wet(&mut self, _left: f32, _right: f32) -> (f32, f32) {
+      (0.5, 0.5)
+    }
+  }
+
+  #[test]
+  fn global_lfo_pans_the_output_without_notes() {
+    let mut effect = SteadyEffect;
+    let (_events, consumer) = RingBuffer::<Event<f32>>::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let values = param_values(0.0, 10.0, 0.0, ParamScale::Linear);
+    let lfo_rate = builder.param("lfo-rate", values.clone().with_initial_value(5.0));
+    let lfo_to_pan = builder.param("lfo-to-pan", values.with_initial_value(1.0));
+    let master = MasterBlock {
+      lfo_rate: Some(lfo_rate.reference),
+      lfo_to_pan: Some(lfo_to_pan.reference),
+      ..MasterBlock::default()
+    };
+    builder.master(master);
+    let mut synth = Synth::new(1000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_insert_effect(&mut effect);
+
+    let output: std::vec::Vec<(f32, f32)> = (0..1000).map(|_| synth.process()).collect();
+    assert_eq!(synth.get_num_active_voices(), 0);
+    // one of the channels stays at the level of the effect while the other one is attenuated
+    assert!(output.iter().all(|(left, right)| left.max(*right) == 0.5));
+    let min_left = output.iter().map(|(left, _)| *left).fold(1.0, f32::min);
+    let min_right = output.iter().map(|(_, right)| *right).fold(1.0, f32::min);
+    assert!(min_left < 0.05 && min_right < 0.05);
+  }
+
+  /// The peak of an oscillator with a linear amplitude of one and -6 dB as the amplitude in dB
+  fn osc_level_peak(level_db: bool) -> f32 {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let mut osc = test_osc(&mut builder, one, Some(100.0));
+    osc.inputs.amplitude_db = builder.const_value(-6.0);
+    osc.inputs.level_db = if level_db { one } else { zero };
+    builder.out(osc.output, osc.output);
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(8000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+
+    note_on(&mut synth, &mut events, 60);
+    (0..8000)
+      .map(|_| synth.process().0)
+      .fold(0.0, |peak, sample| peak.max(sample.abs()))
+  }
+
+  #[test]
+  fn osc_level_in_db() {
+    assert_approx_eq!(osc_level_peak(false), 1.0, 0.01);
+    assert_approx_eq!(osc_level_peak(true), 0.501, 0.005);
+  }
+
+  /// A second of a drifting oscillator on the left and a sample and hold LFO on the right, for two
+  /// notes with a random pan, optionally seeded
+  fn random_render(seed: Option<u64>) -> std::vec::Vec<(f32, f32)> {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let lfo = lfo::Block {
+      inputs: lfo::Inputs {
+        shape: builder.const_value(3.0),
+        rate: builder.const_value(20.0),
+        phase: zero,
+        depth: one,
+        slew: zero,
+        slew_sync: zero,
+        polarity: zero,
+        invert: zero,
+      },
+      output: builder.signal(),
+      sync_out: builder.signal(),
+      phase_out: builder.signal(),
+    };
+    let osc = test_osc(&mut builder, one, None);
+    builder.out(osc.output, lfo.output);
+    builder.block(Block::Lfo(lfo));
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(8000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_warmth(1.0);
+    synth.set_random_pan_amount(1.0);
+    if let Some(seed) = seed {
+      synth.set_random_seed(seed);
+    }
+
+    note_on(&mut synth, &mut events, 60);
+    note_on(&mut synth, &mut events, 67);
+    (0..8000).map(|_| synth.process()).collect()
+  }
+
+  #[test]
+  fn random_seed_reproduces_renders() {
+    assert_eq!(random_render(Some(1)), random_render(Some(1)));
+    assert_ne!(random_render(Some(1)), random_render(Some(2)));
+    // the voices are seeded from their indices otherwise, not from the oscillators built before
+    assert_eq!(random_render(None), random_render(None));
+    assert_ne!(random_render(None), random_render(Some(1)));
+  }
+
+  /// The mean square of the left and right outputs for some keys, where the keys below 60 play a
+  /// 100 Hz tone hard left and the rest a 300 Hz tone hard right
+  fn output_power(keys: &[u8], channels: usize) -> (f32, f32) {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let one = builder.const_one();
+    let mut left = test_osc(&mut builder, one, Some(100.0));
+    left.inputs.key_high = builder.const_value(59.0);
+    let mut right = test_osc(&mut builder, one, Some(300.0));
+    right.inputs.key_low = builder.const_value(60.0);
+    builder.out(left.output, right.output);
+    builder.block(Block::Osc(left));
+    builder.block(Block::Osc(right));
+    let mut synth = Synth::new(8000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+    synth.set_output_channels(channels);
+
+    for key in keys.iter() {
+      note_on(&mut synth, &mut events, *key);
+    }
+    let mut out = [0.0; 16000];
+    synth.process_block(&mut out[..8000 * channels]);
+    let frames = out[..8000 * channels].chunks(channels);
+    let (left, right) = frames.fold((0.0, 0.0), |(left, right), frame| {
+      (left + frame[0].powi(2), right + frame[channels - 1].powi(2))
+    });
+    (left / 8000.0, right / 8000.0)
+  }
+
+  #[test]
+  fn mono_output_sums_both_channels() {
+    assert_eq!(output_power(&[40], 2).1, 0.0);
+    assert_eq!(output_power(&[80], 2).0, 0.0);
+
+    // a single channel with the power of both sides of the stereo output
+    let (left, right) = output_power(&[40], 2);
+    assert_approx_eq!(output_power(&[40], 1).0 * 2.0, left + right, 0.01);
+    let (left, right) = output_power(&[80], 2);
+    assert_approx_eq!(output_power(&[80], 1).0 * 2.0, left + right, 0.01);
+
+    let (left, right) = output_power(&[40, 80], 2);
+    assert_approx_eq!(left, 0.5, 0.01);
+    assert_approx_eq!(right, 0.5, 0.01);
+    assert_approx_eq!(output_power(&[40, 80], 1).0, 0.5, 0.01);
+  }
+
+  /// A soft clipper never going above 0.2, as the simplest distortion
+  struct Clipper;
+
+  impl Processor<f32> for Clipper {
+    fn get_mix(&self) -> f32 {
+      1.0
+    }
+
+    fn set_mix(&mut self, _mix: f32) {}
+
+    fn is_bypassed(&self) -> bool {
+      false
+    }
+
+    fn set_bypass(&mut self, _bypass: bool) {}
+
+    fn process_wet(&mut self, left: f32, right: f32) -> (f32, f32) {
+      ((left * 4.0).tanh() * 0.2, (right * 4.0).tanh() * 0.2)
+    }
+  }
+
+  /// The left output of a 100 Hz sine through a clipper in the first slot and a delay in the second
+  /// one, in the order given while playing, or in the order of their slots
+  fn insert_chain_render(order: Option<&[u8]>) -> std::vec::Vec<f32> {
+    let mut clipper = Clipper;
+    let mut buffer = [0.0f32; 100];
+    let mut delay = Delay::new(8000.0, &mut buffer);
+    delay.set_delay_seconds(0.01);
+    delay.set_feedback(0.5);
+    delay.set_mix(0.5);
+
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let one = builder.const_one();
+    let osc = test_osc(&mut builder, one, Some(100.0));
+    builder.out(osc.output, osc.output);
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(8000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fade_in_time(0.0);
+    assert_eq!(synth.add_insert_effect(&mut clipper), Some(0));
+    assert_eq!(synth.add_insert_effect(&mut delay), Some(1));
+
+    note_on(&mut synth, &mut events, 60);
+    let mut output: std::vec::Vec<f32> = (0..400).map(|_| synth.process().0).collect();
+    if let Some(order) = order {
+      let order = order.iter().cloned().collect();
+      send(&mut synth, &mut events, Message::InsertOrder { order });
+    }
+    output.extend((0..400).map(|_| synth.process().0));
+    output
+  }
+
+  #[test]
+  fn insert_effects_order() {
+    let clipper_first = insert_chain_render(None);
+    assert_eq!(insert_chain_render(Some(&[0, 1])), clipper_first);
+    assert!(clipper_first.iter().any(|sample| sample.abs() > 0.2));
+
+    // the repeats of the delay are clipped once the clipper goes after it
+    let delay_first = insert_chain_render(Some(&[1, 0]));
+    assert_eq!(delay_first[..400], clipper_first[..400]);
+    assert!(delay_first[400..].iter().all(|sample| sample.abs() <= 0.2));
+  }
+
+  #[test]
+  fn move_and_remove_insert_effects() {
+    let (mut a, mut b, mut c) = (Clipper, Clipper, Clipper);
+    let (_, consumer) = RingBuffer::<Event<f32>>::new(16).split();
+    let program = ProgramBuilder::new().build();
+    let mut synth = Synth::new(8000.0, consumer, program, SynthGlobals::new());
+    synth.add_insert_effect(&mut a);
+    synth.add_insert_effect(&mut b);
+    synth.add_insert_effect(&mut c);
+    assert_eq!(synth.insert_order()[..], [0, 1, 2]);
+
+    synth.move_insert_effect(0, 2);
+    assert_eq!(synth.insert_order()[..], [1, 2, 0]);
+    synth.move_insert_effect(2, 1);
+    assert_eq!(synth.insert_order()[..], [1, 0, 2]);
+    synth.move_insert_effect(3, 0);
+    assert_eq!(synth.insert_order()[..], [1, 0, 2]);
+
+    assert!(synth.remove_insert_effect(0).is_some());
+    assert_eq!(synth.get_num_insert_effects(), 2);
+    assert_eq!(synth.insert_order()[..], [0, 1]);
+    assert!(synth.remove_insert_effect(2).is_none());
+  }
+
+  /// The key sounding in mono mode while holding two keys, and after releasing the first one
+  fn mono_priority_keys(priority: NotePriority, first: u8, second: u8) -> (u8, u8) {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let program = envgen_program(0.0, 1.0, 0.01);
+    let mut synth = Synth::new(1000.0, consumer, program, SynthGlobals::new());
+    synth.set_mono(true);
+    synth.set_note_priority(priority);
+    let sounding_key = |synth: &Synth<f32>| {
+      let voice = &synth.voices[synth.active_voices[0]];
+      voice.get_key(&synth.programs[0])
+    };
+
+    note_on(&mut synth, &mut events, first);
+    note_on(&mut synth, &mut events, second);
+    synth.process();
+    let held = sounding_key(&synth);
+    let note_off = Message::NoteOff {
+      key: first,
+      velocity: 0.0,
+    };
+    send(&mut synth, &mut events, note_off);
+    synth.process();
+    assert_eq!(synth.get_num_active_voices(), 1);
+    (held, sounding_key(&synth))
+  }
+
+  #[test]
+  fn mono_note_priority() {
+    assert_eq!(mono_priority_keys(NotePriority::Last, 60, 64), (64, 64));
+    assert_eq!(mono_priority_keys(NotePriority::Last, 64, 60), (60, 60));
+    assert_eq!(mono_priority_keys(NotePriority::Low, 60, 64), (60, 64));
+    assert_eq!(mono_priority_keys(NotePriority::Low, 64, 60), (60, 60));
+    assert_eq!(mono_priority_keys(NotePriority::High, 60, 64), (64, 64));
+    assert_eq!(mono_priority_keys(NotePriority::High, 64, 60), (64, 60));
+  }
+
+  /// The fraction of the power of a 700 Hz carrier, deeply modulated by another oscillator at the
+  /// same frequency, that is not in its harmonics, so it comes from the sidebands folding back
+  fn fm_aliased_power(oversampling: bool) -> f64 {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let one = builder.const_one();
+    let amplitude = builder.const_value(12000.0);
+    let mut modulator = test_osc(&mut builder, amplitude, Some(700.0));
+    modulator.inputs.fm_mode = one;
+    let mut carrier = test_osc(&mut builder, one, Some(700.0));
+    carrier.inputs.fm_mode = one;
+    let mut routing = OscRouting::new(2);
+    routing.set_fm_source(1, Some(0)).unwrap();
+    builder.out(carrier.output, carrier.output);
+    builder.oscillators(&[modulator, carrier], &routing);
+    let mut synth = Synth::new(16000.0, consumer, builder.build(), SynthGlobals::new());
+    synth.set_fm_oversampling(oversampling);
+
+    note_on(&mut synth, &mut events, 60);
+    // 1600 samples are exactly 70 cycles, so the harmonics fall on every 70th bin
+    let samples: std::vec::Vec<f64> = (0..3200)
+      .map(|_| synth.process().0 as f64)
+      .skip(1600)
+      .collect();
+    let len = samples.len() as f64;
+    let total: f64 = samples.iter().map(|sample| sample * sample).sum();
+    let harmonics: f64 = (1..=11)
+      .map(|harmonic| {
+        let omega = 2.0 * std::f64::consts::PI * (70 * harmonic) as f64 / len;
+        let (re, im) = samples
+          .iter()
+          .enumerate()
+          .fold((0.0, 0.0), |(re, im), (index, sample)| {
+            let phase = omega * index as f64;
+            (re + sample * phase.cos(), im - sample * phase.sin())
+          });
+        2.0 * (re * re + im * im) / len
+      })
+      .sum();
+    (total - harmonics) / total
+  }
+
+  #[test]
+  fn fm_oversampling_reduces_aliasing() {
+    let aliased = fm_aliased_power(false);
+    let oversampled = fm_aliased_power(true);
+    assert!(aliased > 0.3);
+    assert!(oversampled < aliased * 0.25);
+  }
+
+  #[test]
+  fn auto_level_to_a_target_peak() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let gain_values = param_values(-24.0, 24.0, 0.0, ParamScale::Linear).with_initial_value(0.0);
+    let gain = builder.param("gain", gain_values);
+    builder.master(MasterBlock {
+      gain: Some(gain.reference),
+      ..MasterBlock::default()
+    });
+    let amplitude = builder.const_value(0.1);
+    let osc = test_osc(&mut builder, amplitude, None);
+    builder.out(osc.output, osc.output);
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+
+    // the sounding voices and the transport are not touched
+    note_on(&mut synth, &mut events, 64);
+    synth.process();
+    let beats = synth.get_beats();
+
+    // 0.1 is -20 dBFS, so the gain goes up 14 dB
+    let db = synth.auto_level(-6.0).unwrap();
+    assert_approx_eq!(db, 14.0, 0.1);
+    assert_eq!(synth.get_num_active_voices(), 1);
+    assert_eq!(synth.get_beats(), beats);
+
+    synth.free_all_voices();
+    note_on(&mut synth, &mut events, 60);
+    let peak = (0..22050)
+      .map(|_| synth.process().0.abs())
+      .fold(0.0, f32::max);
+    assert_approx_eq!(Decibels::from_amplitude(peak).value(), -6.0, 0.1);
+  }
+
+  #[test]
+  fn pitch_envelope_decays_to_the_note() {
+    let (mut events, consumer) = RingBuffer::new(16).split();
+    let mut builder = ProgramBuilder::new();
+    let zero = builder.const_zero();
+    let one = builder.const_one();
+    let pitch_eg = envgen::Block {
+      inputs: envgen::Inputs {
+        attack: zero,
+        decay: builder.const_value(0.5),
+        sustain: zero,
+        release: zero,
+        mode: zero,
+        legato: zero,
+        reset_to_zero: zero,
+        velocity_to_attack: zero,
+        velocity_to_decay: zero,
+      },
+      outputs: envgen::Outputs {
+        normal: builder.signal(),
+        biased: builder.signal(),
+      },
+    };
+    // one octave up at the start of the note
+    let pitch_mod = builder.expr(|expr| expr.mul_signal_value(pitch_eg.outputs.normal, 12.0));
+    let mut osc = test_osc(&mut builder, one, None);
+    osc.inputs.pitch_mod = pitch_mod.output;
+    builder.out(osc.sync_out, osc.output);
+    builder.block(Block::EG(pitch_eg));
+    builder.block(Block::Expr(pitch_mod));
+    builder.block(Block::Osc(osc));
+    let mut synth = Synth::new(44100.0, consumer, builder.build(), SynthGlobals::new());
+
+    // the cycles of the A4 during the first 50 ms, and during 100 ms two seconds later
+    note_on(&mut synth, &mut events, 69);
+    let mut count_cycles =
+      |samples: usize| (0..samples).filter(|_| synth.process().0 > 0.0).count();
+    let start_cycles = count_cycles(2205);
+    count_cycles(88200);
+    let end_cycles = count_cycles(4410);
+    assert!(start_cycles > 35, "{} cycles", start_cycles);
+    assert!((43..=45).contains(&end_cycles), "{} cycles", end_cycles);
+  }
+}
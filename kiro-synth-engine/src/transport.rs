@@ -0,0 +1,89 @@
+use kiro_synth_core::float::Float;
+
+/// Musical clock shared by all the voices, measured in beats
+#[derive(Debug, Clone)]
+pub struct Transport<F: Float> {
+  tempo: F,
+  /// The position is accumulated in double precision, as with single precision
+  /// the increment of one sample is lost after a few thousand beats
+  beats: f64,
+  beats_per_sample: f64,
+  inv_sample_rate: F,
+}
+
+impl<F: Float> Default for Transport<F> {
+  fn default() -> Self {
+    Transport::new(F::val(44100))
+  }
+}
+
+impl<F: Float> Transport<F> {
+  pub const DEFAULT_TEMPO: f64 = 120.0;
+
+  pub fn new(sample_rate: F) -> Self {
+    let mut transport = Transport {
+      tempo: F::val(Self::DEFAULT_TEMPO),
+      beats: 0.0,
+      beats_per_sample: 0.0,
+      inv_sample_rate: sample_rate.recip(),
+    };
+    transport.update_beats_per_sample();
+    transport
+  }
+
+  pub fn set_sample_rate(&mut self, sample_rate: F) {
+    self.inv_sample_rate = sample_rate.recip();
+    self.update_beats_per_sample();
+  }
+
+  /// Set the tempo in beats per minute
+  pub fn set_tempo(&mut self, tempo: F) {
+    if tempo > F::zero() {
+      self.tempo = tempo;
+      self.update_beats_per_sample();
+    }
+  }
+
+  pub fn get_tempo(&self) -> F {
+    self.tempo
+  }
+
+  /// The position in beats
+  pub fn get_beats(&self) -> F {
+    F::val(self.beats)
+  }
+
+  /// Move to a position in beats
+  pub fn set_beats(&mut self, beats: F) {
+    self.beats = beats.to_f64().unwrap_or_default();
+  }
+
+  /// Move forward one sample
+  pub fn advance(&mut self) {
+    self.beats += self.beats_per_sample;
+  }
+
+  fn update_beats_per_sample(&mut self) {
+    let beats_per_sample = self.tempo / F::val(60) * self.inv_sample_rate;
+    self.beats_per_sample = beats_per_sample.to_f64().unwrap_or_default();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn advance_past_thousands_of_beats() {
+    let sample_rate = 44100.0;
+    let mut transport = Transport::<f32>::new(sample_rate);
+    transport.set_beats(2000.0);
+
+    // one second at 120 bpm is two beats
+    for _ in 0..sample_rate as usize {
+      transport.advance();
+    }
+
+    assert!((transport.get_beats() - 2002.0).abs() < 1e-3);
+  }
+}
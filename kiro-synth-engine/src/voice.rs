@@ -1,20 +1,57 @@
 use core::ops::DerefMut;
 use heapless::Vec;
 
+use kiro_synth_core::funcs::random::Random;
+
 use crate::float::Float;
 use crate::globals::SynthGlobals;
-use crate::key_freqs::KEY_FREQ;
 use crate::processor::Processor;
+use crate::program::blocks::envgen;
 use crate::program::{Block, MaxBlocks, MaxSignals, Program};
 use crate::signal::{Signal, SignalBus};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceState {
+  /// Available for new notes
+  Free,
+  /// The key is still pressed
+  Playing,
+  /// The key has been released but the voice is still sounding
+  Releasing,
+}
+
 pub struct Voice<F: Float> {
+  state: VoiceState,
+  program_index: usize,
   signals: Vec<Signal<F>, MaxSignals>,
   processors: Vec<Processor<F>, MaxBlocks>,
+  amp_eg: envgen::Processor<F>,
+  glide_target: F,
+  glide_ratio: F,
+  glide_samples: usize,
+  age: usize,
+  fade_in_samples: usize,
+  /// The level to fade in from, as a voice retriggered while sounding does not start from silence
+  fade_in_from: F,
+  /// Samples left until the voice is released by itself, zero when it waits for the note off
+  fixed_length_left: usize,
+  /// The pan position given at the note on, from -1.0 (left) to 1.0 (right)
+  pan: F,
+  /// The amp envelope of the previous sample, to start the fade out from it
+  last_amp_env: F,
+  fade_out_amp: F,
+  fade_out_samples: usize,
+  fade_out_left: usize,
+  /// Fading out before its amp envelope has finished, as it has been stolen or the sound turned off
+  stopping: bool,
+  /// The key and velocity of the note to play once a stolen voice has faded out
+  next_note: Option<(u8, F)>,
+  /// The buffer the voice is written to when processing multiple outputs, taken when it starts sounding
+  output: Option<usize>,
 }
 
 impl<F: Float> Voice<F> {
-  pub(crate) fn new(sample_rate: F, program: &Program<F>) -> Self {
+  pub(crate) fn new(sample_rate: F, program_index: usize, program: &Program<F>) -> Self {
     let mut signals: Vec<Signal<F>, MaxSignals> = Vec::new();
     for _ in 0..program.get_signals_count() {
       signals.push(Signal::default()).unwrap();
@@ -31,12 +68,60 @@ impl<F: Float> Voice<F> {
 
     //    println!("voice::signals {:?}", signals.iter_mut().map(|s| (s.consume(), s.state())).collect::<Vec<(F, SignalState), MaxSignals>>());
 
+    let amp_eg = envgen::Processor::new(sample_rate, program.amp_eg().clone());
+
     Voice {
+      state: VoiceState::Free,
+      program_index,
       signals,
       processors,
+      amp_eg,
+      glide_target: F::zero(),
+      glide_ratio: F::one(),
+      glide_samples: 0,
+      age: 0,
+      fade_in_samples: 0,
+      fade_in_from: F::zero(),
+      fixed_length_left: 0,
+      pan: F::zero(),
+      last_amp_env: F::zero(),
+      fade_out_amp: F::zero(),
+      fade_out_samples: 0,
+      fade_out_left: 0,
+      stopping: false,
+      next_note: None,
+      output: None,
     }
   }
 
+  pub fn get_state(&self) -> VoiceState {
+    self.state
+  }
+
+  /// The number of samples processed since the last note on
+  pub fn get_age(&self) -> usize {
+    self.age
+  }
+
+  /// The index of the synth program this voice was built for
+  pub fn get_program_index(&self) -> usize {
+    self.program_index
+  }
+
+  /// The buffer given to the voice by [`process_block_multi`](crate::synth::Synth::process_block_multi)
+  pub fn get_output(&self) -> Option<usize> {
+    self.output
+  }
+
+  pub(crate) fn set_output(&mut self, output: usize) {
+    self.output = Some(output);
+  }
+
+  /// The pan position given at the last note on
+  pub fn get_pan(&self) -> F {
+    self.pan
+  }
+
   pub fn get_signals(&self) -> &[Signal<F>] {
     self.signals.as_ref()
   }
@@ -49,8 +134,49 @@ impl<F: Float> Voice<F> {
   //    self.signals[program.voice().velocity.0].get()
   //  }
 
+  /// The voice is off once its amp envelope has finished and it has faded out
   pub(crate) fn is_off(&self, program: &Program<F>) -> bool {
-    self.signals[program.voice().off.0].get() == F::one()
+    self.signals[program.voice().off.0].get() == F::one() && self.fade_out_left == 0
+  }
+
+  /// Whether the voice is being stopped with [`stop`](Self::stop) or [`steal`](Self::steal)
+  pub(crate) fn is_stopping(&self) -> bool {
+    self.stopping
+  }
+
+  /// The voice has been stopped and has finished its fade out
+  pub(crate) fn is_stopped(&self) -> bool {
+    self.stopping && self.fade_out_left == 0
+  }
+
+  /// Fade out the voice from its current level during some samples, without waiting for the release
+  pub(crate) fn stop(&mut self, program: &Program<F>, samples: usize) {
+    self.fade_out_amp = self.amplitude(program);
+    // one more sample as the fade is counted down before the first output
+    self.fade_out_samples = samples;
+    self.fade_out_left = if samples > 0 { samples + 1 } else { 0 };
+    self.stopping = true;
+    self.next_note = None;
+  }
+
+  /// Stop the voice to play another note once it has faded out
+  pub(crate) fn steal(&mut self, program: &Program<F>, samples: usize, key: u8, velocity: F) {
+    self.stop(program, samples);
+    self.next_note = Some((key, velocity));
+  }
+
+  /// Take the note to play after being stolen
+  pub(crate) fn take_next_note(&mut self) -> Option<(u8, F)> {
+    self.next_note.take()
+  }
+
+  /// Forget the note to play after being stolen when its key is released before it starts
+  pub(crate) fn cancel_next_note(&mut self, key: u8) {
+    if let Some((next_key, _)) = self.next_note {
+      if next_key == key {
+        self.next_note = None;
+      }
+    }
   }
 
   pub(crate) fn reset(&mut self, program: &Program<F>) {
@@ -71,33 +197,138 @@ impl<F: Float> Voice<F> {
     for proc in self.processors.iter_mut() {
       proc.reset();
     }
+    self.amp_eg.reset();
+  }
+
+  /// Reseed every block of the voice with its own seed derived from this one
+  pub(crate) fn set_random_seed(&mut self, seed: u64) {
+    for (index, proc) in self.processors.iter_mut().enumerate() {
+      proc.set_random_seed(Random::derive_seed(seed, index as u64));
+    }
   }
 
-  pub(crate) fn note_on(&mut self, program: &Program<F>, key: u8, velocity: F) {
+  /// Start a note at a pan position from -1.0 (left) to 1.0 (right), on top of the pan of the program
+  pub(crate) fn note_on(&mut self, program: &Program<F>, key: u8, velocity: F, pan: F) {
+    self.fade_in_from = match self.state {
+      VoiceState::Free => F::zero(),
+      _ => self.amplitude(program),
+    };
     self.reset(program);
+    self.state = VoiceState::Playing;
+    self.glide_samples = 0;
+    self.age = 0;
+    self.fade_out_left = 0;
+    self.stopping = false;
+    self.next_note = None;
+    self.output = None;
+    self.pan = pan.max(-F::one()).min(F::one());
     let voice = program.voice();
     self.signals[voice.key.0].set(F::val(key));
     self.signals[voice.velocity.0].set(velocity);
-    self.signals[voice.note_pitch.0].set(F::val(KEY_FREQ[(key & 0x7f) as usize]));
+    self.signals[voice.note_pitch.0].set(program.key_freq(key));
     self.signals[voice.gate.0].set(F::one());
     self.signals[voice.trigger.0].set(F::one());
   }
 
+  pub(crate) fn get_note_pitch(&self, program: &Program<F>) -> F {
+    self.signals[program.voice().note_pitch.0].get()
+  }
+
+  /// Set the pitch the note is playing at, such as the one to glide from
+  pub(crate) fn set_note_pitch(&mut self, program: &Program<F>, pitch: F) {
+    self.signals[program.voice().note_pitch.0].set(pitch);
+  }
+
+  /// Change the key without retriggering, gliding the pitch to the new note during some samples
+  pub(crate) fn glide_to(&mut self, program: &Program<F>, key: u8, samples: usize) {
+    let voice = program.voice();
+    let pitch = self.signals[voice.note_pitch.0].get();
+    let target = program.key_freq(key);
+    self.signals[voice.key.0].set(F::val(key));
+    if samples == 0 || pitch <= F::zero() {
+      self.glide_samples = 0;
+      self.signals[voice.note_pitch.0].set(target);
+    } else {
+      self.glide_target = target;
+      self.glide_ratio = (target / pitch).powf(F::val(samples).recip());
+      self.glide_samples = samples;
+    }
+  }
+
+  /// Restart the envelopes with a new velocity keeping the note and the state of the voice
+  pub(crate) fn retrigger(&mut self, program: &Program<F>, velocity: F) {
+    let voice = program.voice();
+    self.signals[voice.velocity.0].set(velocity);
+    self.signals[voice.trigger.0].set(F::one());
+  }
+
+  pub(crate) fn set_pressure(&mut self, program: &Program<F>, pressure: F) {
+    self.signals[program.voice().pressure.0].set(pressure);
+  }
+
   pub(crate) fn note_off(&mut self, program: &Program<F>) {
     self.signals[program.voice().gate.0].set(F::zero());
+    self.state = VoiceState::Releasing;
+  }
+
+  /// The voice is off (ie. its release has finished) and goes back to the free pool
+  pub(crate) fn free(&mut self) {
+    self.state = VoiceState::Free;
   }
 
   pub(crate) fn process(&mut self, program: &mut Program<F>, synth_globals: &SynthGlobals<F>) {
+    self.age += 1;
+    if self.age == 1 {
+      self.fade_in_samples = synth_globals.fade_in_samples;
+      self.fixed_length_left = synth_globals.fixed_length_samples;
+    }
+
+    if self.fixed_length_left > 0 {
+      self.fixed_length_left -= 1;
+      if self.fixed_length_left == 0 && self.state == VoiceState::Playing {
+        self.note_off(program);
+      }
+    }
+
+    if self.glide_samples > 0 {
+      self.glide_samples -= 1;
+      let note_pitch = &mut self.signals[program.voice().note_pitch.0];
+      let pitch = if self.glide_samples == 0 {
+        self.glide_target
+      } else {
+        note_pitch.get() * self.glide_ratio
+      };
+      note_pitch.set(pitch);
+    }
+
     let mut signals = SignalBus::new(self.signals.deref_mut());
 
     for processor in self.processors.iter_mut() {
       processor.process(&mut signals, program, synth_globals)
     }
 
+    // The amp envelope goes last and decides when the voice is off
+    let voice = program.voice();
+    let was_off = signals[voice.off].get() == F::one();
+    self.amp_eg.process(&mut signals, program);
+    if self.amp_eg.is_off() {
+      signals[voice.off].set(F::one());
+    }
+    if self.fade_out_left > 0 {
+      self.fade_out_left -= 1;
+    } else if self.amp_eg.is_off() && !was_off && !self.stopping {
+      // Fade out from the last level instead of cutting the output
+      if synth_globals.fade_out_samples > 0 {
+        self.fade_out_amp = self.last_amp_env;
+        self.fade_out_samples = synth_globals.fade_out_samples;
+        self.fade_out_left = self.fade_out_samples;
+      }
+    }
+    self.last_amp_env = signals[voice.amp_env].get();
+
     signals.update();
 
     // The trigger does an spike of 1 sample
-    let voice = program.voice();
     if signals[voice.trigger].get() > F::zero() {
       signals[voice.trigger].set(F::zero())
     }
@@ -105,11 +336,34 @@ impl<F: Float> Voice<F> {
     // println!("{:?}", self.signals.iter_mut().skip(3)/*.take(2)*/.map(|s| (s.get(), s.state())).collect::<Vec<(F, SignalState), MaxSignals>>());
   }
 
+  /// The gain applied to the output, following the amp envelope with the fades in and out
+  fn amplitude(&self, program: &Program<F>) -> F {
+    if self.fade_out_left > 0 {
+      let fade = F::val(self.fade_out_left) / F::val(self.fade_out_samples + 1);
+      return self.fade_out_amp * fade;
+    }
+    if self.stopping {
+      return F::zero();
+    }
+    let amp_env = self.signals[program.voice().amp_env.0].get();
+    // Fade in independently of the attack of the amp envelope
+    if self.age <= self.fade_in_samples {
+      let fade = F::val(self.age) / F::val(self.fade_in_samples + 1);
+      self.fade_in_from + (amp_env - self.fade_in_from) * fade
+    } else {
+      amp_env
+    }
+  }
+
   pub(crate) fn output(&self, program: &Program<F>) -> (F, F) {
     let voice = program.voice();
+    let amp_env = self.amplitude(program);
+    // Balance the channels so the centered voices are not changed
+    let left_amp = (F::one() - self.pan).min(F::one());
+    let right_amp = (F::one() + self.pan).min(F::one());
     (
-      self.signals[voice.output_left.0].get(),
-      self.signals[voice.output_right.0].get(),
+      self.signals[voice.output_left.0].get() * amp_env * left_amp,
+      self.signals[voice.output_right.0].get() * amp_env * right_amp,
     )
   }
 }
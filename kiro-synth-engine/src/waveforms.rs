@@ -2,11 +2,13 @@ use heapless::consts;
 use heapless::Vec;
 
 use kiro_synth_core::oscillators::osc_waveform::OscWaveform;
+use kiro_synth_core::waveforms::sample_hold::SampleHold;
 use kiro_synth_core::waveforms::saw_blep::{self, SawBlep};
 use kiro_synth_core::waveforms::saw_trivial::SawTrivial;
-use kiro_synth_core::waveforms::sine_parabolic::SineParabolic;
+use kiro_synth_core::waveforms::sine_lut::SineLut;
 use kiro_synth_core::waveforms::triangle_dpw2x::TriangleDpw2x;
 use kiro_synth_core::waveforms::triangle_trivial::TriangleTrivial;
+use kiro_synth_core::waveforms::wavetable::Interpolation;
 
 use crate::float::Float;
 
@@ -21,7 +23,7 @@ impl<F: Float> OscWaveforms<F> {
 
     waveforms
       .extend_from_slice(&[
-        ("sin", OscWaveform::SineParabolic(SineParabolic)),
+        ("sin", OscWaveform::SineLut(SineLut)),
         ("tri", OscWaveform::TriangleDpw2x(TriangleDpw2x::default())),
         (
           "saw",
@@ -52,6 +54,33 @@ impl<F: Float> OscWaveforms<F> {
   pub fn waveform(&self, index: usize) -> &OscWaveform<F> {
     &self.0[index].1
   }
+
+  /// A cheaper version of the waveform, with the trivial algorithm instead of the band-limited one,
+  /// for when the quality is reduced to bound the CPU usage
+  pub fn reduced_waveform(&self, index: usize) -> OscWaveform<F> {
+    match self.waveform(index) {
+      OscWaveform::SawBlep(_) | OscWaveform::SawDpw(_) => {
+        OscWaveform::SawTrivial(SawTrivial::default())
+      }
+      OscWaveform::TriangleDpw2x(_) => OscWaveform::TriangleTrivial(TriangleTrivial::default()),
+      waveform => waveform.clone(),
+    }
+  }
+
+  /// Choose how all the wavetables read their samples
+  pub fn set_wavetable_interpolation(&mut self, interpolation: Interpolation) {
+    for (_, waveform) in self.0.iter_mut() {
+      if let OscWaveform::Wavetable(wavetable) = waveform {
+        wavetable.set_interpolation(interpolation);
+      }
+    }
+  }
+
+  /// Add a waveform to the ones available for the oscillators. Returns its index.
+  pub fn add(&mut self, name: &'static str, waveform: OscWaveform<F>) -> Option<usize> {
+    self.0.push((name, waveform)).ok()?;
+    Some(self.0.len() - 1)
+  }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -62,12 +91,13 @@ impl<F: Float> LfoWaveforms<F> {
     let mut waveforms: Vec<(&'static str, OscWaveform<F>), MaxWaveforms> = heapless::Vec::new();
     waveforms
       .extend_from_slice(&[
-        ("sin", OscWaveform::SineParabolic(SineParabolic)),
+        ("sin", OscWaveform::SineLut(SineLut)),
         (
           "tri",
           OscWaveform::TriangleTrivial(TriangleTrivial::default()),
         ),
         ("saw", OscWaveform::SawTrivial(SawTrivial::default())),
+        ("s&h", OscWaveform::SampleHold(SampleHold::default())),
       ])
       .ok();
     LfoWaveforms(waveforms)
@@ -0,0 +1,111 @@
+//! The allocator counting the allocations replaces the global one, so this runs in its own test binary
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use ringbuf::RingBuffer;
+
+use kiro_synth_core::effects::limiter::Limiter;
+use kiro_synth_engine::event::{Event, Message};
+use kiro_synth_engine::globals::SynthGlobals;
+use kiro_synth_engine::program::blocks::envgen;
+use kiro_synth_engine::program::{Program, ProgramBuilder};
+use kiro_synth_engine::synth::{KeyZone, Synth};
+
+/// Counts the heap allocations of the threads that enable it, to check the audio path does not allocate
+struct CountingAllocator;
+
+thread_local! {
+  static COUNT_ALLOCATIONS: Cell<bool> = Cell::new(false);
+  static ALLOCATIONS: Cell<usize> = Cell::new(0);
+}
+
+fn count_allocation() {
+  // the thread locals are not available while a thread finishes
+  COUNT_ALLOCATIONS
+    .try_with(|enabled| {
+      if enabled.get() {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+      }
+    })
+    .ok();
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    count_allocation();
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    count_allocation();
+    System.realloc(ptr, layout, new_size)
+  }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations<T: FnOnce()>(f: T) -> usize {
+  ALLOCATIONS.with(|allocations| allocations.set(0));
+  COUNT_ALLOCATIONS.with(|enabled| enabled.set(true));
+  f();
+  COUNT_ALLOCATIONS.with(|enabled| enabled.set(false));
+  ALLOCATIONS.with(|allocations| allocations.get())
+}
+
+fn adsr_program<'a>(attack: f32, decay: f32, sustain: f32, release: f32) -> Program<'a, f32> {
+  let mut builder = ProgramBuilder::new();
+  let zero = builder.const_zero();
+  let one = builder.const_one();
+  let inputs = envgen::Inputs {
+    attack: builder.const_value(attack),
+    decay: builder.const_value(decay),
+    sustain: builder.const_value(sustain),
+    release: builder.const_value(release),
+    mode: zero,
+    legato: zero,
+    reset_to_zero: zero,
+    velocity_to_attack: zero,
+    velocity_to_decay: zero,
+  };
+  builder.amp_envelope(inputs);
+  builder.out(one, one);
+  builder.build()
+}
+
+#[test]
+fn processing_does_not_allocate() {
+  let (mut left_buffer, mut right_buffer) = ([0.0f32; 64], [0.0f32; 64]);
+  let mut limiter = Limiter::new(44100.0, &mut left_buffer, &mut right_buffer);
+  let (mut events, consumer) = RingBuffer::new(16).split();
+  let program = adsr_program(0.01, 0.1, 0.5, 0.1);
+  let mut synth = Synth::new(44100.0, consumer, program, SynthGlobals::new());
+  let program_b = synth.add_program(adsr_program(0.0, 0.2, 0.8, 0.2)).unwrap();
+  assert_eq!(synth.reserve_voices(program_b, 12), 12);
+  synth.set_key_zones(&[KeyZone::new(0, 59, 0), KeyZone::new(60, 127, program_b)]);
+  synth.add_insert_effect(&mut limiter).unwrap();
+  synth.set_effect_metering(true);
+
+  let mut out = [0.0f32; 512];
+  let allocations = count_allocations(|| {
+    for key in [40u8, 50, 60, 70].iter() {
+      let note_on = Message::NoteOn {
+        key: *key,
+        velocity: 1.0,
+      };
+      drop(events.push(Event::new(0u64, note_on)));
+    }
+    synth.prepare();
+    synth.process_block(&mut out);
+    drop(events.push(Event::new(0u64, Message::AllNotesOff)));
+    synth.prepare();
+    synth.process_block(&mut out);
+  });
+  assert_eq!(synth.get_num_active_voices(), 4);
+  assert_eq!(allocations, 0);
+}
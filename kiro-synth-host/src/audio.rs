@@ -24,6 +24,23 @@ pub trait AudioHandler: Send {
   fn prepare(&mut self, len: usize);
   fn next(&mut self) -> (f32, f32);
   fn finalize(&mut self);
+
+  /// Called when the audio device changes its sample rate
+  fn set_sample_rate(&mut self, sample_rate: u32);
+
+  /// Called with the number of channels of the audio device, 1 when it only has one
+  fn set_output_channels(&mut self, channels: usize);
+
+  /// Process a block of frames into separate buffers for the left and right channels
+  fn process_block(&mut self, left: &mut [f32], right: &mut [f32]) {
+    self.prepare(left.len());
+    for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+      let (next_left, next_right) = self.next();
+      *left = next_left;
+      *right = next_right;
+    }
+    self.finalize();
+  }
 }
 
 pub struct AudioDriver {
@@ -50,6 +67,8 @@ impl AudioDriver {
       .into();
 
     let channels = config.channels as usize;
+    // a mono device gets the sum of both channels instead of only the left one
+    handler.set_output_channels(channels.min(2));
 
     config.sample_rate = SampleRate(sample_rate);
     println!("Using default output stream config: {:?}", config);
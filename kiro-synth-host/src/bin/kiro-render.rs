@@ -0,0 +1,128 @@
+//! Render a preset playing a MIDI file or a list of notes into a WAV file, without audio devices nor UI:
+//!
+//! ```bash
+//! kiro-render --preset p.kiro --midi in.mid --out out.wav
+//! kiro-render --preset p.kiro --notes notes.txt --out out.wav --sample-rate 48000 --tail 4
+//! ```
+
+// the modules are shared with the host application, which is not a library
+#[path = "../midi/file.rs"]
+mod file;
+#[path = "../render.rs"]
+mod render;
+#[path = "../synth/wav.rs"]
+mod wav;
+
+// the shared modules refer to each other from where they are in the host
+mod midi {
+  pub(crate) use crate::file;
+}
+
+use anyhow::{anyhow, Result};
+
+use kiro_synth_engine::globals::SynthGlobals;
+use kiro_synth_program::kiro::KiroModule;
+use kiro_synth_program::preset::Preset;
+
+use crate::midi::file::MidiFile;
+use crate::render::OfflineRenderer;
+
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+const DEFAULT_TAIL_SECONDS: f64 = 2.0;
+
+const USAGE: &str = "usage: kiro-render [--preset <path>] (--midi <path> | --notes <path>) \
+  --out <path> [--sample-rate <hz>] [--tail <seconds>] [--seed <n>] \
+  [--auto-level <dbfs>] [--wav <path>]... [--wavetable-interpolation <nearest|linear|cubic>]";
+
+fn main() -> Result<()> {
+  let mut preset_path = None;
+  let mut midi_path = None;
+  let mut notes_path = None;
+  let mut out_path = None;
+  let mut wav_paths = Vec::new();
+  let mut wavetable_interpolation = None;
+  let mut sample_rate = DEFAULT_SAMPLE_RATE;
+  let mut tail_seconds = DEFAULT_TAIL_SECONDS;
+  let mut seed = None;
+  let mut auto_level = None;
+
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    let mut value = || {
+      args
+        .next()
+        .ok_or_else(|| anyhow!("missing value for {}", arg))
+    };
+    match arg.as_str() {
+      "--preset" => preset_path = Some(value()?),
+      "--midi" => midi_path = Some(value()?),
+      "--notes" => notes_path = Some(value()?),
+      "--out" => out_path = Some(value()?),
+      "--wav" => wav_paths.push(value()?),
+      "--wavetable-interpolation" => {
+        wavetable_interpolation = Some(wav::parse_interpolation(value()?.as_str())?)
+      }
+      "--sample-rate" => sample_rate = value()?.parse()?,
+      "--tail" => tail_seconds = value()?.parse()?,
+      "--seed" => seed = Some(value()?.parse()?),
+      "--auto-level" => auto_level = Some(value()?.parse()?),
+      _ => return Err(anyhow!("unexpected argument {}\n{}", arg, USAGE)),
+    }
+  }
+
+  let out_path = out_path.ok_or_else(|| anyhow!(USAGE))?;
+  let (messages, tempo_changes) = match (midi_path, notes_path) {
+    (Some(path), None) => {
+      let file = MidiFile::from_file(path)?;
+      println!(
+        "Loaded {} tracks lasting {:.2} seconds",
+        file.num_tracks,
+        file.duration()
+      );
+      (file.messages, file.tempo_changes)
+    }
+    (None, Some(path)) => {
+      let notes = render::parse_note_list(std::fs::read_to_string(path)?.as_str())?;
+      (notes, Vec::new())
+    }
+    _ => return Err(anyhow!(USAGE)),
+  };
+
+  let mut globals = SynthGlobals::new();
+  for path in wav_paths.iter() {
+    wav::load_osc_waveform(&mut globals.osc_waveforms, path)?;
+  }
+  if let Some(interpolation) = wavetable_interpolation {
+    globals
+      .osc_waveforms
+      .set_wavetable_interpolation(interpolation);
+  }
+
+  let (mut program, _) =
+    KiroModule::new_program(globals.lfo_waveforms.len(), globals.osc_waveforms.len());
+
+  if let Some(path) = preset_path {
+    Preset::from_json(std::fs::read_to_string(path)?.as_str())?.apply(&mut program);
+  }
+
+  let mut renderer = OfflineRenderer::new(sample_rate, program, globals);
+  if let Some(seed) = seed {
+    renderer.set_random_seed(seed);
+  }
+  if let Some(target_dbfs) = auto_level {
+    match renderer.auto_level(target_dbfs) {
+      Some(gain) => println!("Master gain set to {:.1} dB", gain),
+      None => println!("The preset could not be levelled"),
+    }
+  }
+  let frames = renderer.render(&messages, &tempo_changes, tail_seconds);
+  render::write_wav_file(&out_path, sample_rate, &frames)?;
+
+  println!(
+    "Rendered {:.2} seconds into {}",
+    frames.len() as f64 / f64::from(sample_rate),
+    out_path
+  );
+
+  Ok(())
+}
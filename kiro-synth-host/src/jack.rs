@@ -0,0 +1,160 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use thiserror::Error;
+
+use jack::{
+  AsyncClient, AudioOut, Client, ClientOptions, Control, Frames, MidiIn, NotificationHandler, Port,
+  ProcessHandler, ProcessScope,
+};
+
+use kiro_synth_midi::decoder::Decoder;
+
+use crate::audio::AudioHandler;
+use crate::midi::drivers::{MidiDecoderCallbacks, MidiHandler};
+
+#[derive(Error, Debug)]
+pub enum JackError {
+  #[error("Error creating the JACK client")]
+  ClientCreate(#[source] jack::Error),
+
+  #[error("Error registering a JACK port")]
+  PortRegister(#[source] jack::Error),
+
+  #[error("Error activating the JACK client")]
+  Activate(#[source] jack::Error),
+}
+
+/// A client of the JACK server that is not processing yet, to know its sample rate before creating the synth
+pub struct JackClient {
+  client: Client,
+}
+
+impl JackClient {
+  pub fn open(app_name: &str) -> Result<Self> {
+    let (client, status) =
+      Client::new(app_name, ClientOptions::NO_START_SERVER).map_err(JackError::ClientCreate)?;
+
+    println!("JACK client created with status {:?}", status);
+
+    Ok(JackClient { client })
+  }
+
+  pub fn sample_rate(&self) -> u32 {
+    self.client.sample_rate() as u32
+  }
+
+  /// Register the ports and start processing the MIDI input and the audio outputs
+  pub fn start<Audio, Midi>(
+    self,
+    midi_buffer: &'static mut [u8],
+    midi_handler: Midi,
+    audio_handler: Audio,
+  ) -> Result<JackDriver<Audio, Midi>>
+  where
+    Audio: AudioHandler + 'static,
+    Midi: MidiHandler + 'static,
+  {
+    let register_error = JackError::PortRegister;
+    let midi_in = self
+      .client
+      .register_port("midi_in", MidiIn::default())
+      .map_err(register_error)?;
+    let left_out = self
+      .client
+      .register_port("out_left", AudioOut::default())
+      .map_err(register_error)?;
+    let right_out = self
+      .client
+      .register_port("out_right", AudioOut::default())
+      .map_err(register_error)?;
+
+    let sample_rate = Arc::new(AtomicUsize::new(self.client.sample_rate()));
+
+    let notifications = JackNotifications {
+      sample_rate: sample_rate.clone(),
+    };
+
+    let process = JackProcess {
+      midi_in,
+      left_out,
+      right_out,
+      decoder: Decoder::new(midi_buffer),
+      midi_handler,
+      audio_handler,
+      sample_rate,
+      current_sample_rate: self.client.sample_rate(),
+    };
+
+    let client = self
+      .client
+      .activate_async(notifications, process)
+      .map_err(JackError::Activate)?;
+
+    println!("JACK client activated");
+
+    Ok(JackDriver { _client: client })
+  }
+}
+
+pub struct JackDriver<Audio: AudioHandler + 'static, Midi: MidiHandler + 'static> {
+  _client: AsyncClient<JackNotifications, JackProcess<Audio, Midi>>,
+}
+
+pub struct JackNotifications {
+  sample_rate: Arc<AtomicUsize>,
+}
+
+impl NotificationHandler for JackNotifications {
+  fn sample_rate(&mut self, _client: &Client, sample_rate: Frames) -> Control {
+    // the synth is updated from the process thread, before processing the next block
+    self
+      .sample_rate
+      .store(sample_rate as usize, Ordering::Relaxed);
+    Control::Continue
+  }
+}
+
+pub struct JackProcess<Audio: AudioHandler, Midi: MidiHandler> {
+  midi_in: Port<MidiIn>,
+  left_out: Port<AudioOut>,
+  right_out: Port<AudioOut>,
+  decoder: Decoder<'static>,
+  midi_handler: Midi,
+  audio_handler: Audio,
+  sample_rate: Arc<AtomicUsize>,
+  current_sample_rate: usize,
+}
+
+impl<Audio: AudioHandler, Midi: MidiHandler> ProcessHandler for JackProcess<Audio, Midi> {
+  fn process(&mut self, _client: &Client, scope: &ProcessScope) -> Control {
+    let sample_rate = self.sample_rate.load(Ordering::Relaxed);
+    if sample_rate != self.current_sample_rate {
+      self.current_sample_rate = sample_rate;
+      self.audio_handler.set_sample_rate(sample_rate as u32);
+    }
+
+    let frame_time = u64::from(scope.last_frame_time());
+    for midi in self.midi_in.iter(scope) {
+      let timestamp = frame_time + u64::from(midi.time);
+      let mut source = midi.bytes.iter().copied();
+      let mut callbacks = MidiDecoderCallbacks {
+        timestamp,
+        handler: &mut self.midi_handler,
+      };
+      self.decoder.decode(&mut source, &mut callbacks).ok();
+    }
+
+    let left = self.left_out.as_mut_slice(scope);
+    let right = self.right_out.as_mut_slice(scope);
+    self.audio_handler.process_block(left, right);
+
+    Control::Continue
+  }
+
+  fn buffer_size(&mut self, _client: &Client, _size: Frames) -> Control {
+    // the blocks are processed with the size of the buffers given on every cycle
+    Control::Continue
+  }
+}
@@ -1,30 +1,58 @@
 mod audio;
+#[cfg(feature = "jack")]
+mod jack;
 mod midi;
+mod osc;
 mod synth;
 pub mod ui;
 
 use std::sync::{Arc, Mutex};
+#[cfg(feature = "jack")]
+use std::thread;
+#[cfg(feature = "jack")]
+use std::time::Duration;
 
 use anyhow::Result;
+use druid::ExtEventSink;
 use ringbuf::RingBuffer;
+#[cfg(feature = "jack")]
+use ringbuf::{Consumer, Producer};
 
+use kiro_synth_core::effects::delay::Delay;
 use kiro_synth_core::float::Float;
 use kiro_synth_engine::event::Event;
+#[cfg(feature = "jack")]
+use kiro_synth_engine::event::Message as SynthMessage;
 use kiro_synth_engine::globals::SynthGlobals;
 use kiro_synth_engine::program::Program;
 use kiro_synth_engine::synth::Synth;
 use kiro_synth_midi::messages::Message as MidiMessage;
+use kiro_synth_program::kiro::KiroModule;
+use kiro_synth_program::preset::{Preset, PresetBank};
 
+#[cfg(not(feature = "jack"))]
 use crate::audio::AudioDriver;
-use crate::midi::drivers::{MidiDriver, MidiHandler};
+#[cfg(feature = "jack")]
+use crate::jack::JackClient;
+#[cfg(not(feature = "jack"))]
+use crate::midi::drivers::MidiDriver;
+use crate::midi::drivers::MidiHandler;
+use crate::midi::file::MidiFile;
 use crate::midi::mapper::MidiMapper;
-use crate::synth::program::kiro::KiroModule;
-use crate::synth::{SynthAudioHandler, SynthClient, SynthClientMutex, SynthFeedback};
-use crate::ui::Synth as SynthData;
+use crate::midi::player::{MidiPlayer, Sequence};
+use crate::osc::{OscConfig, OscServer};
+use crate::synth::wav;
+use crate::synth::{ParamRamp, SynthAudioHandler, SynthClient, SynthClientMutex, SynthFeedback};
+use crate::ui::{PresetState, Synth as SynthData, Ui, LOAD_PRESET};
 
+#[cfg(not(feature = "jack"))]
 const SAMPLE_RATE: u32 = 44100;
 
 const MIDI_BUFFER_SIZE: usize = 512;
+
+/// How often the program changes received by the audio thread are looked for
+#[cfg(feature = "jack")]
+const PROGRAM_CHANGES_PERIOD: Duration = Duration::from_millis(10);
 static mut MIDI_BUFFER: [u8; MIDI_BUFFER_SIZE] = [0; MIDI_BUFFER_SIZE];
 
 fn main() -> Result<()> {
@@ -32,7 +60,64 @@ fn main() -> Result<()> {
 
   let midi_buffer: &'static mut [u8] = unsafe { MIDI_BUFFER.as_mut() };
 
-  let synth_globals = SynthGlobals::new();
+  let mut preset_paths = Vec::new();
+  let mut bank_path = None;
+  let mut save_bank_path = None;
+  let mut wav_paths = Vec::new();
+  let mut wavetable_interpolation = None;
+  let mut osc_config_path = None;
+  let mut midi_file_path = None;
+  let mut midi_tracks: Option<Vec<usize>> = None;
+  let mut ui_scale = 1.0;
+  let mut param_ramp = None;
+  let mut midi_trace = false;
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--bank" => bank_path = args.next(),
+      "--save-bank" => save_bank_path = args.next(),
+      "--wav" => wav_paths.extend(args.next()),
+      "--wavetable-interpolation" => {
+        let name = args.next().unwrap_or_default();
+        wavetable_interpolation = Some(wav::parse_interpolation(name.as_str())?);
+      }
+      "--osc" => osc_config_path = args.next(),
+      "--midi-file" => midi_file_path = args.next(),
+      "--midi-tracks" => {
+        let tracks = args.next().unwrap_or_default();
+        let tracks = tracks.split(',').map(|track| track.trim().parse());
+        midi_tracks = Some(tracks.collect::<Result<_, _>>()?);
+      }
+      "--ui-scale" => ui_scale = args.next().unwrap_or_default().parse()?,
+      "--param-ramp" => {
+        let duration_ms = args.next().unwrap_or_default().parse()?;
+        param_ramp = Some(ParamRamp {
+          duration_ms,
+          ..ParamRamp::default()
+        });
+      }
+      "--midi-trace" => midi_trace = true,
+      _ => preset_paths.push(arg),
+    }
+  }
+
+  // with JACK the sample rate is the one of the server
+  #[cfg(feature = "jack")]
+  let jack_client = JackClient::open("kiro-synth")?;
+  #[cfg(feature = "jack")]
+  let sample_rate = jack_client.sample_rate();
+  #[cfg(not(feature = "jack"))]
+  let sample_rate = SAMPLE_RATE;
+
+  let mut synth_globals = SynthGlobals::new();
+
+  for path in wav_paths.iter() {
+    wav::load_osc_waveform(&mut synth_globals.osc_waveforms, path)?;
+  }
+  if let Some(interpolation) = wavetable_interpolation {
+    let osc_waveforms = &mut synth_globals.osc_waveforms;
+    osc_waveforms.set_wavetable_interpolation(interpolation);
+  }
 
   // EVENTS
 
@@ -54,55 +139,177 @@ fn main() -> Result<()> {
 
   // PROGRAM
 
-  let (program, module) = KiroModule::new_program(
+  let (mut program, module) = KiroModule::new_program(
     synth_globals.lfo_waveforms.len(),
     synth_globals.osc_waveforms.len(),
   );
 
+  // the presets can be selected with MIDI program changes, the first one is loaded at start.
+  // The preset files go after the presets of the bank.
+  let mut preset_bank = match bank_path {
+    Some(path) => PresetBank::load(path)?,
+    None => PresetBank::new(),
+  };
+  for path in preset_paths.iter() {
+    preset_bank.push(Preset::from_json(std::fs::read_to_string(path)?.as_str())?);
+  }
+  if let Some(path) = save_bank_path {
+    preset_bank.save(path)?;
+  }
+
+  if let Some(preset) = preset_bank.get(0) {
+    preset.apply(&mut program);
+  }
+
+  synth_client.lock().unwrap().init_param_values(&program);
+  synth_client.lock().unwrap().set_param_ramp(param_ramp);
+
   // UI DATA
 
   let synth_client_mutex = SynthClientMutex::new(synth_client.clone());
   let synth_data = SynthData::new(&program, &module, synth_client_mutex);
 
+  // UI
+
+  // the presets selected with MIDI program changes are loaded through the UI, so it follows them
+  let ui = Ui::new(synth_data, synth_client.clone(), ui_scale);
+  let ui_events = ui.event_sink();
+
   // MIDI
 
+  // the MIDI file is played through its own handler, as another MIDI device
+  let midi_file_sequence = match midi_file_path {
+    Some(path) => {
+      let midi_file = MidiFile::from_file(path)?;
+      println!(
+        "Playing {} tracks lasting {:.2} seconds",
+        midi_file.num_tracks,
+        midi_file.duration()
+      );
+      let sequence = Sequence::new(&midi_file, midi_tracks.as_deref());
+      let handler = EventsMidiHandler::new(
+        create_midi_mapper(&program, &module),
+        preset_bank.clone(),
+        program.clone(),
+        synth_client.clone(),
+        ui_events.clone(),
+      );
+      Some((sequence, handler))
+    }
+    None => None,
+  };
+
   let midi_mapper = create_midi_mapper(&program, &module);
-  let handler = EventsMidiHandler::new(midi_mapper, synth_client.clone());
-  let _midi_driver = MidiDriver::new("kiro-synth", midi_buffer, handler)?;
+  #[cfg(not(feature = "jack"))]
+  let midi_handler = EventsMidiHandler::new(
+    midi_mapper,
+    preset_bank,
+    program.clone(),
+    synth_client.clone(),
+    ui_events,
+  )
+  .with_trace(midi_trace);
+  #[cfg(not(feature = "jack"))]
+  let _midi_driver = MidiDriver::new("kiro-synth", midi_buffer, midi_handler)?;
+
+  // with JACK the MIDI is decoded in the process callback, so the events skip the synth client
+  #[cfg(feature = "jack")]
+  let (midi_events_producer, midi_events_consumer) = RingBuffer::<Event<f32>>::new(1024).split();
+  #[cfg(feature = "jack")]
+  let (program_changes_producer, program_changes_consumer) = RingBuffer::<u8>::new(16).split();
+  #[cfg(feature = "jack")]
+  let midi_handler =
+    JackMidiHandler::new(midi_mapper, midi_events_producer, program_changes_producer);
+  #[cfg(feature = "jack")]
+  spawn_program_changes(
+    program_changes_consumer,
+    preset_bank,
+    program.clone(),
+    ui_events,
+  );
+
+  // OSC
+
+  let _osc_server = match osc_config_path {
+    Some(path) => {
+      let config = OscConfig::from_json(std::fs::read_to_string(path)?.as_str())?;
+      Some(OscServer::start(&config, &program, synth_client.clone())?)
+    }
+    None => None,
+  };
 
   // SYNTH
 
-  let synth = Synth::new(SAMPLE_RATE as f32, events_consumer, program, synth_globals);
+  let mut synth = Synth::new(sample_rate as f32, events_consumer, program, synth_globals);
+
+  // the effects live as long as the synth
+  let delay_buffer = Box::leak(vec![0.0f32; sample_rate as usize].into_boxed_slice());
+  let mut delay = Delay::new(sample_rate as f32, delay_buffer);
+  delay.set_delay_seconds(0.375);
+  delay.set_feedback(0.4);
+  synth.set_send_effect(Box::leak(Box::new(delay)));
+  // the level of the delay is shown with the output levels
+  synth.set_effect_metering(true);
 
   // AUDIO
 
   let handler = SynthAudioHandler::new(synth, feedback_producer);
-  let _audio_driver = AudioDriver::new(SAMPLE_RATE, handler)?;
+  #[cfg(feature = "jack")]
+  let handler = handler.with_midi_events(midi_events_consumer);
+  #[cfg(not(feature = "jack"))]
+  let _audio_driver = AudioDriver::new(sample_rate, handler)?;
+  #[cfg(feature = "jack")]
+  let _jack_driver = jack_client.start(midi_buffer, midi_handler, handler)?;
 
-  // UI
+  // the MIDI file starts playing once the audio is running
+  let _midi_player = midi_file_sequence
+    .map(|(sequence, handler)| MidiPlayer::start(sequence, handler, synth_client.clone()));
 
-  ui::start(synth_data, synth_client);
+  ui.start();
 
   Ok(())
 }
 
 struct EventsMidiHandler {
   midi_mapper: MidiMapper<f32>,
+  preset_bank: PresetBank,
+  /// The program as it was started, for the presets to be resolved against
+  program: Program<'static, f32>,
   synth_client: Arc<Mutex<SynthClient<f32>>>,
+  ui_events: ExtEventSink,
+  /// Print every message received, ie. to check what a MIDI device sends
+  trace: bool,
 }
 
 impl EventsMidiHandler {
-  pub fn new(midi_mapper: MidiMapper<f32>, synth_client: Arc<Mutex<SynthClient<f32>>>) -> Self {
+  pub fn new(
+    midi_mapper: MidiMapper<f32>,
+    preset_bank: PresetBank,
+    program: Program<'static, f32>,
+    synth_client: Arc<Mutex<SynthClient<f32>>>,
+    ui_events: ExtEventSink,
+  ) -> Self {
     EventsMidiHandler {
       midi_mapper,
+      preset_bank,
+      program,
       synth_client,
+      ui_events,
+      trace: false,
     }
   }
+
+  pub fn with_trace(mut self, trace: bool) -> Self {
+    self.trace = trace;
+    self
+  }
 }
 
 impl MidiHandler for EventsMidiHandler {
   fn on_message(&mut self, timestamp: u64, message: MidiMessage) {
-    println!("{:014}: {:?}", timestamp, message);
+    if self.trace {
+      println!("{:014}: {:?}", timestamp, message);
+    }
     match message {
       MidiMessage::NoteOn {
         channel: _,
@@ -126,6 +333,30 @@ impl MidiHandler for EventsMidiHandler {
           .unwrap()
           .send_note_off(key, velocity as f32 / 127.0);
       }
+      MidiMessage::AllNotesOff { channel: _ } => {
+        self.synth_client.lock().unwrap().send_all_notes_off();
+      }
+      MidiMessage::AllSoundOff { channel: _ } => {
+        self.synth_client.lock().unwrap().send_all_sound_off();
+      }
+      MidiMessage::ChannelPressure { channel: _, value } => {
+        self
+          .synth_client
+          .lock()
+          .unwrap()
+          .send_channel_pressure(value as f32 / 127.0);
+      }
+      MidiMessage::PolyphonicKeyPressure {
+        channel: _,
+        key,
+        value,
+      } => {
+        self
+          .synth_client
+          .lock()
+          .unwrap()
+          .send_poly_pressure(key, value as f32 / 127.0);
+      }
       MidiMessage::PitchBend { channel: _, value } => {
         if let Some(event) = self.midi_mapper.map_midi_pitch_bend(value) {
           self.synth_client.lock().unwrap().send_event(event);
@@ -140,16 +371,137 @@ impl MidiHandler for EventsMidiHandler {
           self.synth_client.lock().unwrap().send_event(event);
         }
       }
+      MidiMessage::ProgramChange { channel: _, value } => {
+        send_program_change(value, &self.preset_bank, &self.program, &self.ui_events);
+      }
       _ => {}
     };
   }
 
   fn on_sysex(&mut self, timestamp: u64, data: &[u8]) {
-    println!("{:014}: {:?}", timestamp, data);
+    if self.trace {
+      println!("{:014}: {:?}", timestamp, data);
+    }
     // TODO unimplemented!()
   }
 }
 
+/// Map the MIDI read from the JACK process callback into synth events,
+/// without printing, locking or allocating as it runs in the audio thread
+#[cfg(feature = "jack")]
+struct JackMidiHandler {
+  midi_mapper: MidiMapper<f32>,
+  events: Producer<Event<f32>>,
+  program_changes: Producer<u8>,
+}
+
+#[cfg(feature = "jack")]
+impl JackMidiHandler {
+  pub fn new(
+    midi_mapper: MidiMapper<f32>,
+    events: Producer<Event<f32>>,
+    program_changes: Producer<u8>,
+  ) -> Self {
+    JackMidiHandler {
+      midi_mapper,
+      events,
+      program_changes,
+    }
+  }
+
+  fn send_message(&mut self, message: SynthMessage<f32>) {
+    drop(self.events.push(Event::now(message)));
+  }
+}
+
+#[cfg(feature = "jack")]
+impl MidiHandler for JackMidiHandler {
+  fn on_message(&mut self, _timestamp: u64, message: MidiMessage) {
+    match message {
+      MidiMessage::NoteOn {
+        channel: _,
+        key,
+        velocity,
+      } => self.send_message(SynthMessage::NoteOn {
+        key,
+        velocity: velocity as f32 / 127.0,
+      }),
+      MidiMessage::NoteOff {
+        channel: _,
+        key,
+        velocity,
+      } => self.send_message(SynthMessage::NoteOff {
+        key,
+        velocity: velocity as f32 / 127.0,
+      }),
+      MidiMessage::AllNotesOff { channel: _ } => self.send_message(SynthMessage::AllNotesOff),
+      MidiMessage::AllSoundOff { channel: _ } => self.send_message(SynthMessage::AllSoundOff),
+      MidiMessage::ChannelPressure { channel: _, value } => {
+        self.send_message(SynthMessage::ChannelPressure {
+          pressure: value as f32 / 127.0,
+        })
+      }
+      MidiMessage::PolyphonicKeyPressure {
+        channel: _,
+        key,
+        value,
+      } => self.send_message(SynthMessage::PolyPressure {
+        key,
+        pressure: value as f32 / 127.0,
+      }),
+      MidiMessage::PitchBend { channel: _, value } => {
+        if let Some(event) = self.midi_mapper.map_midi_pitch_bend(value) {
+          drop(self.events.push(event));
+        }
+      }
+      MidiMessage::ControlChange {
+        channel: _,
+        controller,
+        value,
+      } => {
+        if let Some(event) = self.midi_mapper.map_midi_controller(controller, value) {
+          drop(self.events.push(event));
+        }
+      }
+      MidiMessage::ProgramChange { channel: _, value } => {
+        drop(self.program_changes.push(value));
+      }
+      _ => {}
+    };
+  }
+
+  fn on_sysex(&mut self, _timestamp: u64, _data: &[u8]) {}
+}
+
+/// Send the presets selected from the JACK MIDI input out of the audio thread
+#[cfg(feature = "jack")]
+fn spawn_program_changes(
+  mut program_changes: Consumer<u8>,
+  preset_bank: PresetBank,
+  program: Program<'static, f32>,
+  ui_events: ExtEventSink,
+) {
+  thread::spawn(move || loop {
+    while let Some(value) = program_changes.pop() {
+      send_program_change(value, &preset_bank, &program, &ui_events);
+    }
+    thread::sleep(PROGRAM_CHANGES_PERIOD);
+  });
+}
+
+/// Send the preset selected with a MIDI program change to the UI, which loads it into the synth
+fn send_program_change(
+  value: u8,
+  preset_bank: &PresetBank,
+  program: &Program<'static, f32>,
+  ui_events: &ExtEventSink,
+) {
+  if let Some(preset) = preset_bank.get(value as usize) {
+    let preset = PresetState::new(preset, program);
+    drop(ui_events.submit_command(LOAD_PRESET, preset, None));
+  }
+}
+
 fn create_midi_mapper<F: Float>(program: &Program<F>, module: &KiroModule) -> MidiMapper<F> {
   let mut midi_mapper = MidiMapper::new();
 
@@ -170,17 +522,22 @@ fn create_midi_mapper<F: Float>(program: &Program<F>, module: &KiroModule) -> Mi
   //  midi_mapper.rel_controller(32, program.get_param(module.params.osc2.semitones.reference));
   //  midi_mapper.rel_controller(33, program.get_param(module.params.osc2.cents.reference));
 
-  midi_mapper.rel_controller(29, program.get_param(module.params.eg1.attack.reference));
-  midi_mapper.rel_controller(30, program.get_param(module.params.eg1.decay.reference));
-  midi_mapper.rel_controller(31, program.get_param(module.params.eg1.sustain.reference));
-  midi_mapper.rel_controller(32, program.get_param(module.params.eg1.release.reference));
-  midi_mapper.rel_controller(33, program.get_param(module.params.eg1.mode.reference));
-  midi_mapper.rel_controller(34, program.get_param(module.params.eg1.legato.reference));
+  midi_mapper.rel_controller(29, program.get_param(module.params.amp_eg.attack.reference));
+  midi_mapper.rel_controller(30, program.get_param(module.params.amp_eg.decay.reference));
+  midi_mapper.rel_controller(
+    31,
+    program.get_param(module.params.amp_eg.sustain.reference),
+  );
+  midi_mapper.rel_controller(
+    32,
+    program.get_param(module.params.amp_eg.release.reference),
+  );
+  midi_mapper.rel_controller(33, program.get_param(module.params.amp_eg.mode.reference));
+  midi_mapper.rel_controller(34, program.get_param(module.params.amp_eg.legato.reference));
   midi_mapper.rel_controller(
     35,
-    program.get_param(module.params.eg1.reset_to_zero.reference),
+    program.get_param(module.params.amp_eg.reset_to_zero.reference),
   );
-  midi_mapper.rel_controller(36, program.get_param(module.params.eg1.dca_mod.reference));
 
   midi_mapper.rel_controller(
     41,
@@ -3,10 +3,9 @@ use thiserror::Error;
 
 use coremidi::{Client, InputPort, PacketList, Sources};
 
-use kiro_synth_midi::decoder::{CallbackResult, Decoder, DecoderCallbacks};
-use kiro_synth_midi::messages::Message;
+use kiro_synth_midi::decoder::Decoder;
 
-use crate::midi::drivers::{MidiError, MidiHandler};
+use crate::midi::drivers::{MidiDecoderCallbacks, MidiError, MidiHandler};
 
 #[derive(Error, Debug)]
 #[error("CoreMidi returned OSStatus: {0}")]
@@ -21,26 +20,6 @@ pub enum CoreMidiError {
   PortCreate(#[source] OSStatusError),
 }
 
-struct MidiDecoderCallbacks<'a, Handler: MidiHandler> {
-  timestamp: u64,
-  handler: &'a mut Handler,
-}
-
-impl<'a, Handler> DecoderCallbacks for MidiDecoderCallbacks<'a, Handler>
-where
-  Handler: MidiHandler,
-{
-  fn on_message(&mut self, message: Message) -> CallbackResult {
-    self.handler.on_message(self.timestamp, message);
-    CallbackResult::Continue
-  }
-
-  fn on_sysex(&mut self, data: &[u8]) -> CallbackResult {
-    self.handler.on_sysex(self.timestamp, data);
-    CallbackResult::Continue
-  }
-}
-
 pub struct CoreMidiDriver {
   _client: Client,
   _input_port: InputPort,
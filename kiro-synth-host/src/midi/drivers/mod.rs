@@ -1,3 +1,4 @@
+#[cfg(target_os = "macos")]
 use thiserror::Error;
 
 #[cfg(target_os = "macos")]
@@ -8,8 +9,10 @@ pub use crate::midi::drivers::coremidi::{
   CoreMidiDriver as MidiDriver, CoreMidiError as MidiErrorSource,
 };
 
+use kiro_synth_midi::decoder::{CallbackResult, DecoderCallbacks};
 use kiro_synth_midi::messages::Message;
 
+#[cfg(target_os = "macos")]
 #[derive(Error, Debug)]
 pub enum MidiError {
   #[error("Error initialising the MIDI driver")]
@@ -20,3 +23,24 @@ pub trait MidiHandler: Send {
   fn on_message(&mut self, timestamp: u64, message: Message);
   fn on_sysex(&mut self, timestamp: u64, data: &[u8]);
 }
+
+/// Forward the messages from the decoder to the handler with the timestamp of the data being decoded
+pub(crate) struct MidiDecoderCallbacks<'a, Handler: MidiHandler> {
+  pub timestamp: u64,
+  pub handler: &'a mut Handler,
+}
+
+impl<'a, Handler> DecoderCallbacks for MidiDecoderCallbacks<'a, Handler>
+where
+  Handler: MidiHandler,
+{
+  fn on_message(&mut self, message: Message) -> CallbackResult {
+    self.handler.on_message(self.timestamp, message);
+    CallbackResult::Continue
+  }
+
+  fn on_sysex(&mut self, data: &[u8]) -> CallbackResult {
+    self.handler.on_sysex(self.timestamp, data);
+    CallbackResult::Continue
+  }
+}
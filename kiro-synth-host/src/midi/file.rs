@@ -0,0 +1,379 @@
+use std::path::Path;
+
+use thiserror::Error;
+
+use kiro_synth_midi::decoder::{CallbackResult, Decoder, DecoderCallbacks};
+use kiro_synth_midi::messages::Message;
+use kiro_synth_midi::types::U7;
+
+const HEADER_CHUNK: &[u8] = b"MThd";
+const TRACK_CHUNK: &[u8] = b"MTrk";
+
+const META_EVENT: u8 = 0xff;
+const META_TEMPO: u8 = 0x51;
+const META_END_OF_TRACK: u8 = 0x2f;
+const SYSEX_EVENT: u8 = 0xf0;
+const SYSEX_ESCAPE: u8 = 0xf7;
+
+/// The tempo until the first tempo event, 120 bpm
+const DEFAULT_MICROS_PER_QUARTER: u32 = 500_000;
+
+#[derive(Error, Debug)]
+pub enum MidiFileError {
+  #[error("Not a standard MIDI file")]
+  NotMidiFile,
+
+  #[error("The MIDI file ended unexpectedly")]
+  Truncated,
+
+  #[error("Unexpected status {0:#04x} in track {1}")]
+  UnexpectedStatus(u8, usize),
+
+  #[error("Failed to read the MIDI file")]
+  Io(#[from] std::io::Error),
+}
+
+/// A MIDI message at some time from the start of the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedMessage {
+  pub seconds: f64,
+  /// The index of the track in the file, for the tracks to be selected
+  pub track: usize,
+  pub message: Message,
+}
+
+/// A change of tempo at some time from the start of the file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange {
+  pub seconds: f64,
+  pub bpm: f64,
+}
+
+/// The channel messages of a standard MIDI file, with all the tracks merged and their times in seconds
+#[derive(Debug, Clone)]
+pub struct MidiFile {
+  pub num_tracks: usize,
+  pub messages: Vec<TimedMessage>,
+  /// The tempo changes, empty when the file has no tempo events or uses SMPTE timing
+  pub tempo_changes: Vec<TempoChange>,
+}
+
+enum EventKind {
+  Tempo(u32),
+  Message(Message),
+}
+
+struct TrackEvent {
+  ticks: u64,
+  track: usize,
+  kind: EventKind,
+}
+
+impl MidiFile {
+  pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, MidiFileError> {
+    Self::parse(std::fs::read(path)?.as_slice())
+  }
+
+  pub fn parse(data: &[u8]) -> Result<Self, MidiFileError> {
+    let mut reader = Reader::new(data);
+
+    if reader.take(4)? != HEADER_CHUNK {
+      return Err(MidiFileError::NotMidiFile);
+    }
+    let header_len = reader.read_u32()? as usize;
+    let mut header = Reader::new(reader.take(header_len)?);
+    let _format = header.read_u16()?;
+    let num_tracks = header.read_u16()? as usize;
+    let division = header.read_u16()?;
+
+    let mut events = Vec::new();
+    let mut track = 0;
+    while track < num_tracks && !reader.is_empty() {
+      let chunk_type = reader.take(4)?;
+      let chunk_len = reader.read_u32()? as usize;
+      let chunk = reader.take(chunk_len)?;
+      // unknown chunks must be ignored
+      if chunk_type == TRACK_CHUNK {
+        Self::parse_track(chunk, track, &mut events)?;
+        track += 1;
+      }
+    }
+
+    // the sort is stable, so the events at the same time keep the order of the tracks
+    events.sort_by_key(|event| event.ticks);
+
+    let (messages, tempo_changes) = Self::timed_messages(events, division);
+
+    Ok(MidiFile {
+      num_tracks,
+      messages,
+      tempo_changes,
+    })
+  }
+
+  /// The time of the last message
+  pub fn duration(&self) -> f64 {
+    self
+      .messages
+      .last()
+      .map(|message| message.seconds)
+      .unwrap_or(0.0)
+  }
+
+  fn parse_track(
+    data: &[u8],
+    track: usize,
+    events: &mut Vec<TrackEvent>,
+  ) -> Result<(), MidiFileError> {
+    let mut reader = Reader::new(data);
+    // the decoder keeps the data bytes of the channel messages in the buffer
+    let mut data_buffer = [0 as U7; 2];
+    let mut decoder = Decoder::new(&mut data_buffer);
+    let mut ticks = 0u64;
+    let mut running_status = None;
+
+    while !reader.is_empty() {
+      ticks += u64::from(reader.read_var_len()?);
+
+      let status = match reader.peek()? {
+        byte if byte & 0x80 != 0 => {
+          reader.take(1)?;
+          byte
+        }
+        _ => running_status.ok_or(MidiFileError::UnexpectedStatus(0, track))?,
+      };
+
+      match status {
+        META_EVENT => {
+          let meta_type = reader.take(1)?[0];
+          let len = reader.read_var_len()? as usize;
+          let data = reader.take(len)?;
+          match meta_type {
+            META_TEMPO if len == 3 => {
+              let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+              let kind = EventKind::Tempo(tempo);
+              events.push(TrackEvent { ticks, track, kind });
+            }
+            META_END_OF_TRACK => break,
+            _ => {}
+          }
+        }
+        SYSEX_EVENT | SYSEX_ESCAPE => {
+          let len = reader.read_var_len()? as usize;
+          reader.take(len)?;
+        }
+        0x80..=0xef => {
+          running_status = Some(status);
+          let len = match status & 0xf0 {
+            0xc0 | 0xd0 => 1,
+            _ => 2,
+          };
+          let mut bytes = std::iter::once(status).chain(reader.take(len)?.iter().cloned());
+          let mut callbacks = MessageCallbacks { message: None };
+          decoder
+            .decode(&mut bytes, &mut callbacks)
+            .map_err(|_| MidiFileError::UnexpectedStatus(status, track))?;
+          if let Some(message) = callbacks.message {
+            let kind = EventKind::Message(message);
+            events.push(TrackEvent { ticks, track, kind });
+          }
+        }
+        _ => return Err(MidiFileError::UnexpectedStatus(status, track)),
+      }
+    }
+
+    Ok(())
+  }
+
+  fn timed_messages(
+    events: Vec<TrackEvent>,
+    division: u16,
+  ) -> (Vec<TimedMessage>, Vec<TempoChange>) {
+    // with SMPTE timing the ticks have a fixed duration, otherwise it depends on the tempo
+    let smpte_seconds_per_tick = if division & 0x8000 != 0 {
+      let frames_per_second = -((division >> 8) as i8) as f64;
+      let ticks_per_frame = (division & 0xff) as f64;
+      Some(1.0 / (frames_per_second * ticks_per_frame).max(1.0))
+    } else {
+      None
+    };
+    let ticks_per_quarter = f64::from(division.max(1));
+
+    let mut messages = Vec::with_capacity(events.len());
+    let mut tempo_changes = Vec::new();
+    let mut seconds_per_tick = smpte_seconds_per_tick
+      .unwrap_or(f64::from(DEFAULT_MICROS_PER_QUARTER) * 1e-6 / ticks_per_quarter);
+    let mut last_ticks = 0u64;
+    let mut seconds = 0.0;
+
+    for event in events {
+      seconds += (event.ticks - last_ticks) as f64 * seconds_per_tick;
+      last_ticks = event.ticks;
+      match event.kind {
+        EventKind::Tempo(micros_per_quarter) => {
+          if smpte_seconds_per_tick.is_none() && micros_per_quarter > 0 {
+            seconds_per_tick = f64::from(micros_per_quarter) * 1e-6 / ticks_per_quarter;
+            let bpm = 60e6 / f64::from(micros_per_quarter);
+            tempo_changes.push(TempoChange { seconds, bpm });
+          }
+        }
+        EventKind::Message(message) => messages.push(TimedMessage {
+          seconds,
+          track: event.track,
+          message,
+        }),
+      }
+    }
+
+    (messages, tempo_changes)
+  }
+}
+
+struct MessageCallbacks {
+  message: Option<Message>,
+}
+
+impl DecoderCallbacks for MessageCallbacks {
+  fn on_message(&mut self, message: Message) -> CallbackResult {
+    self.message = Some(message);
+    CallbackResult::Continue
+  }
+
+  fn on_sysex(&mut self, _data: &[U7]) -> CallbackResult {
+    CallbackResult::Continue
+  }
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+  fn new(data: &'a [u8]) -> Self {
+    Reader { data }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+
+  fn peek(&self) -> Result<u8, MidiFileError> {
+    self.data.first().cloned().ok_or(MidiFileError::Truncated)
+  }
+
+  fn take(&mut self, len: usize) -> Result<&'a [u8], MidiFileError> {
+    if len > self.data.len() {
+      return Err(MidiFileError::Truncated);
+    }
+    let (head, tail) = self.data.split_at(len);
+    self.data = tail;
+    Ok(head)
+  }
+
+  fn read_u16(&mut self) -> Result<u16, MidiFileError> {
+    let bytes = self.take(2)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+  }
+
+  fn read_u32(&mut self) -> Result<u32, MidiFileError> {
+    let bytes = self.take(4)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  /// Read a variable length quantity, 7 bits per byte with the highest bit set but in the last one
+  fn read_var_len(&mut self) -> Result<u32, MidiFileError> {
+    let mut value = 0u32;
+    for _ in 0..4 {
+      let byte = self.take(1)?[0];
+      value = (value << 7) | u32::from(byte & 0x7f);
+      if byte & 0x80 == 0 {
+        break;
+      }
+    }
+    Ok(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk = chunk_type.to_vec();
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(data);
+    chunk
+  }
+
+  #[test]
+  fn parse_tracks_with_tempo_changes() {
+    let mut data = chunk(HEADER_CHUNK, &[0, 1, 0, 2, 0, 96]);
+    // tempo track: 120 bpm, and 60 bpm after a quarter
+    data.extend(chunk(
+      TRACK_CHUNK,
+      &[
+        0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20, // 500000 us per quarter
+        0x60, 0xff, 0x51, 0x03, 0x0f, 0x42, 0x40, // 1000000 us per quarter
+        0x00, 0xff, 0x2f, 0x00,
+      ],
+    ));
+    // notes track, with running status and a note on without velocity as note off
+    data.extend(chunk(
+      TRACK_CHUNK,
+      &[
+        0x00, 0x90, 0x3c, 0x64, // note on at 0
+        0x60, 0x3c, 0x00, // note off after a quarter at 120 bpm
+        0x81, 0x40, 0xb0, 0x07, 0x50, // cc 7 after two quarters at 60 bpm
+        0x00, 0xff, 0x2f, 0x00,
+      ],
+    ));
+
+    let file = MidiFile::parse(&data).unwrap();
+
+    assert_eq!(file.num_tracks, 2);
+    let times: Vec<(f64, usize)> = file
+      .messages
+      .iter()
+      .map(|message| (message.seconds, message.track))
+      .collect();
+    assert_eq!(times, vec![(0.0, 1), (0.5, 1), (2.5, 1)]);
+    let tempos: Vec<(f64, f64)> = file
+      .tempo_changes
+      .iter()
+      .map(|change| (change.seconds, change.bpm))
+      .collect();
+    assert_eq!(tempos, vec![(0.0, 120.0), (0.5, 60.0)]);
+    assert_eq!(
+      file.messages[1].message,
+      Message::NoteOn {
+        channel: 0,
+        key: 0x3c,
+        velocity: 0
+      }
+    );
+    assert_eq!(
+      file.messages[2].message,
+      Message::ControlChange {
+        channel: 0,
+        controller: 7,
+        value: 0x50
+      }
+    );
+    assert_eq!(file.duration(), 2.5);
+  }
+
+  #[test]
+  fn parse_invalid_files() {
+    assert!(matches!(
+      MidiFile::parse(b"RIFF...."),
+      Err(MidiFileError::NotMidiFile)
+    ));
+
+    let mut data = chunk(HEADER_CHUNK, &[0, 0, 0, 1, 0, 96]);
+    data.extend(chunk(TRACK_CHUNK, &[0x00, 0x90, 0x3c]));
+    assert!(matches!(
+      MidiFile::parse(&data),
+      Err(MidiFileError::Truncated)
+    ));
+  }
+}
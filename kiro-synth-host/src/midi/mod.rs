@@ -1,2 +1,4 @@
 pub mod drivers;
+pub mod file;
 pub mod mapper;
+pub mod player;
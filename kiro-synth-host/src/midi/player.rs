@@ -0,0 +1,317 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use kiro_synth_midi::messages::Message;
+
+use crate::midi::drivers::MidiHandler;
+use crate::midi::file::{MidiFile, TempoChange, TimedMessage};
+use crate::synth::SynthClient;
+
+const NUM_KEYS: usize = 128;
+const POLL_PERIOD: Duration = Duration::from_millis(1);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SequenceEvent {
+  Message(Message),
+  Tempo(f64),
+}
+
+/// The messages of a MIDI file in the order they have to be played.
+///
+/// The notes overlapping on the same key retrigger it, and only the last note off of the key releases it,
+/// so a note is not cut by the end of an earlier one.
+pub struct Sequence {
+  messages: Vec<TimedMessage>,
+  tempo_changes: Vec<TempoChange>,
+  position: usize,
+  tempo_position: usize,
+  held_notes: [usize; NUM_KEYS],
+  pending: VecDeque<Message>,
+}
+
+impl Sequence {
+  /// Create a sequence with all the tracks merged or only the selected ones
+  pub fn new(file: &MidiFile, tracks: Option<&[usize]>) -> Self {
+    let messages = file
+      .messages
+      .iter()
+      .filter(|message| {
+        tracks
+          .map(|tracks| tracks.contains(&message.track))
+          .unwrap_or(true)
+      })
+      .cloned()
+      .collect();
+
+    Sequence {
+      messages,
+      tempo_changes: file.tempo_changes.clone(),
+      position: 0,
+      tempo_position: 0,
+      held_notes: [0; NUM_KEYS],
+      pending: VecDeque::new(),
+    }
+  }
+
+  pub fn is_finished(&self) -> bool {
+    self.position >= self.messages.len()
+      && self.tempo_position >= self.tempo_changes.len()
+      && self.pending.is_empty()
+  }
+
+  /// The next event due at the time given in seconds from the start
+  pub fn next_until(&mut self, seconds: f64) -> Option<SequenceEvent> {
+    loop {
+      if let Some(message) = self.pending.pop_front() {
+        return Some(SequenceEvent::Message(message));
+      }
+
+      let next_message = self.messages.get(self.position).cloned();
+
+      // the tempo changes before the messages at the same time
+      if let Some(change) = self.tempo_changes.get(self.tempo_position) {
+        let before_message = next_message
+          .as_ref()
+          .map(|message| change.seconds <= message.seconds)
+          .unwrap_or(true);
+        if change.seconds <= seconds && before_message {
+          self.tempo_position += 1;
+          return Some(SequenceEvent::Tempo(change.bpm));
+        }
+      }
+
+      match next_message {
+        Some(timed) if timed.seconds <= seconds => {
+          self.position += 1;
+          if let Some(message) = self.track_notes(timed.message) {
+            return Some(SequenceEvent::Message(message));
+          }
+        }
+        _ => return None,
+      }
+    }
+  }
+
+  /// Release the notes still held, ie. when the playback is stopped
+  pub fn release_notes(&mut self) -> Vec<Message> {
+    let mut messages = Vec::new();
+    for (key, count) in self.held_notes.iter_mut().enumerate() {
+      if *count > 0 {
+        *count = 0;
+        messages.push(Message::NoteOff {
+          channel: 0,
+          key: key as u8,
+          velocity: 0,
+        });
+      }
+    }
+    messages
+  }
+
+  fn track_notes(&mut self, message: Message) -> Option<Message> {
+    match message {
+      Message::NoteOn {
+        channel,
+        key,
+        velocity,
+      } if velocity > 0 => {
+        let count = &mut self.held_notes[key as usize & 0x7f];
+        *count += 1;
+        if *count > 1 {
+          // retrigger the key
+          self.pending.push_back(message);
+          Some(Message::NoteOff {
+            channel,
+            key,
+            velocity: 0,
+          })
+        } else {
+          Some(message)
+        }
+      }
+      Message::NoteOn { channel, key, .. } | Message::NoteOff { channel, key, .. } => {
+        let count = &mut self.held_notes[key as usize & 0x7f];
+        match *count {
+          0 => None,
+          1 => {
+            *count = 0;
+            match message {
+              // a note on without velocity is a note off
+              Message::NoteOn { .. } => Some(Message::NoteOff {
+                channel,
+                key,
+                velocity: 0,
+              }),
+              _ => Some(message),
+            }
+          }
+          _ => {
+            *count -= 1;
+            None
+          }
+        }
+      }
+      _ => Some(message),
+    }
+  }
+}
+
+/// Plays a sequence in a background thread, sending its messages to a MIDI handler as if they came from a device
+pub struct MidiPlayer {
+  stopped: Arc<AtomicBool>,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl MidiPlayer {
+  pub fn start<H: MidiHandler + 'static>(
+    mut sequence: Sequence,
+    mut handler: H,
+    synth_client: Arc<Mutex<SynthClient<f32>>>,
+  ) -> Self {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let thread_stopped = stopped.clone();
+
+    let thread = thread::spawn(move || {
+      let start = Instant::now();
+      while !sequence.is_finished() && !thread_stopped.load(Ordering::Relaxed) {
+        let elapsed = start.elapsed();
+        let timestamp = elapsed.as_micros() as u64;
+        while let Some(event) = sequence.next_until(elapsed.as_secs_f64()) {
+          match event {
+            SequenceEvent::Message(message) => handler.on_message(timestamp, message),
+            SequenceEvent::Tempo(bpm) => synth_client.lock().unwrap().send_tempo(bpm as f32),
+          }
+        }
+        thread::sleep(POLL_PERIOD);
+      }
+
+      let timestamp = start.elapsed().as_micros() as u64;
+      for message in sequence.release_notes() {
+        handler.on_message(timestamp, message);
+      }
+    });
+
+    MidiPlayer {
+      stopped,
+      thread: Some(thread),
+    }
+  }
+
+  /// Stop the playback releasing the notes being played
+  pub fn stop(&mut self) {
+    self.stopped.store(true, Ordering::Relaxed);
+    if let Some(thread) = self.thread.take() {
+      drop(thread.join());
+    }
+  }
+}
+
+impl Drop for MidiPlayer {
+  fn drop(&mut self) {
+    self.stop();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn note_on(key: u8) -> Message {
+    Message::NoteOn {
+      channel: 0,
+      key,
+      velocity: 100,
+    }
+  }
+
+  fn note_off(key: u8) -> Message {
+    Message::NoteOff {
+      channel: 0,
+      key,
+      velocity: 0,
+    }
+  }
+
+  fn file(messages: &[(f64, usize, Message)]) -> MidiFile {
+    MidiFile {
+      num_tracks: 2,
+      messages: messages
+        .iter()
+        .map(|(seconds, track, message)| TimedMessage {
+          seconds: *seconds,
+          track: *track,
+          message: *message,
+        })
+        .collect(),
+      tempo_changes: vec![TempoChange {
+        seconds: 0.0,
+        bpm: 90.0,
+      }],
+    }
+  }
+
+  fn events_until(sequence: &mut Sequence, seconds: f64) -> Vec<SequenceEvent> {
+    let mut events = Vec::new();
+    while let Some(event) = sequence.next_until(seconds) {
+      events.push(event);
+    }
+    events
+  }
+
+  #[test]
+  fn play_selected_tracks_in_time() {
+    let file = file(&[
+      (0.0, 0, note_on(60)),
+      (0.0, 1, note_on(48)),
+      (0.5, 0, note_off(60)),
+      (0.5, 1, note_off(48)),
+    ]);
+    let mut sequence = Sequence::new(&file, Some(&[1]));
+
+    assert_eq!(
+      events_until(&mut sequence, 0.25),
+      vec![
+        SequenceEvent::Tempo(90.0),
+        SequenceEvent::Message(note_on(48))
+      ]
+    );
+    assert!(!sequence.is_finished());
+    assert_eq!(
+      events_until(&mut sequence, 1.0),
+      vec![SequenceEvent::Message(note_off(48))]
+    );
+    assert!(sequence.is_finished());
+  }
+
+  #[test]
+  fn overlapping_notes_are_not_cut() {
+    let file = file(&[
+      (0.0, 0, note_on(60)),
+      (0.5, 0, note_on(60)),
+      (1.0, 0, note_off(60)),
+      (1.5, 0, note_off(60)),
+      (1.5, 0, note_on(62)),
+    ]);
+    let mut sequence = Sequence::new(&file, None);
+    events_until(&mut sequence, 0.0);
+
+    assert_eq!(
+      events_until(&mut sequence, 1.0),
+      vec![
+        SequenceEvent::Message(note_off(60)),
+        SequenceEvent::Message(note_on(60))
+      ]
+    );
+    assert_eq!(
+      events_until(&mut sequence, 1.5),
+      vec![
+        SequenceEvent::Message(note_off(60)),
+        SequenceEvent::Message(note_on(62))
+      ]
+    );
+    assert_eq!(sequence.release_notes(), vec![note_off(62)]);
+  }
+}
@@ -0,0 +1,255 @@
+use thiserror::Error;
+
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum OscError {
+  #[error("The OSC packet ended unexpectedly")]
+  Truncated,
+
+  #[error("Invalid OSC string")]
+  InvalidString,
+
+  #[error("Invalid OSC address {0}")]
+  InvalidAddress(String),
+
+  #[error("Missing the type tags of the OSC message")]
+  MissingTypeTags,
+
+  #[error("Unsupported OSC argument type '{0}'")]
+  UnsupportedType(char),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+  Int(i32),
+  Float(f32),
+  Double(f64),
+  String(String),
+  Bool(bool),
+}
+
+impl OscArg {
+  /// The numeric value of the argument, if it has one
+  pub fn to_f32(&self) -> Option<f32> {
+    match *self {
+      OscArg::Int(value) => Some(value as f32),
+      OscArg::Float(value) => Some(value),
+      OscArg::Double(value) => Some(value as f32),
+      OscArg::Bool(value) => Some(if value { 1.0 } else { 0.0 }),
+      OscArg::String(_) => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+  pub address: String,
+  pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+  pub fn new(address: &str, args: Vec<OscArg>) -> Self {
+    OscMessage {
+      address: address.to_string(),
+      args,
+    }
+  }
+
+  /// Decode a packet into its messages. The messages inside bundles are returned in order, ignoring their time tags.
+  pub fn decode_packet(packet: &[u8]) -> Result<Vec<OscMessage>, OscError> {
+    let mut messages = Vec::new();
+    Self::decode_into(packet, &mut messages)?;
+    Ok(messages)
+  }
+
+  fn decode_into(packet: &[u8], messages: &mut Vec<OscMessage>) -> Result<(), OscError> {
+    if packet.starts_with(BUNDLE_TAG) {
+      // the tag is followed by a time tag and the elements prefixed by their size
+      let mut reader = Reader::new(packet);
+      reader.take(BUNDLE_TAG.len() + 8)?;
+      while !reader.is_empty() {
+        let size = reader.read_i32()?.max(0) as usize;
+        Self::decode_into(reader.take(size)?, messages)?;
+      }
+      Ok(())
+    } else {
+      messages.push(Self::decode(packet)?);
+      Ok(())
+    }
+  }
+
+  fn decode(packet: &[u8]) -> Result<OscMessage, OscError> {
+    let mut reader = Reader::new(packet);
+
+    let address = reader.read_string()?;
+    if !address.starts_with('/') {
+      return Err(OscError::InvalidAddress(address));
+    }
+
+    // old implementations may send messages without arguments nor type tags
+    if reader.is_empty() {
+      return Ok(OscMessage::new(&address, Vec::new()));
+    }
+
+    let type_tags = reader.read_string()?;
+    if !type_tags.starts_with(',') {
+      return Err(OscError::MissingTypeTags);
+    }
+
+    let mut args = Vec::new();
+    for type_tag in type_tags.chars().skip(1) {
+      let arg = match type_tag {
+        'i' => OscArg::Int(reader.read_i32()?),
+        'f' => OscArg::Float(f32::from_bits(reader.read_i32()? as u32)),
+        'd' => OscArg::Double(f64::from_bits(reader.read_i64()? as u64)),
+        's' => OscArg::String(reader.read_string()?),
+        'T' => OscArg::Bool(true),
+        'F' => OscArg::Bool(false),
+        _ => return Err(OscError::UnsupportedType(type_tag)),
+      };
+      args.push(arg);
+    }
+
+    Ok(OscMessage { address, args })
+  }
+
+  pub fn encode(&self) -> Vec<u8> {
+    let mut packet = Vec::new();
+    write_string(&mut packet, &self.address);
+
+    let mut type_tags = String::from(",");
+    for arg in self.args.iter() {
+      type_tags.push(match arg {
+        OscArg::Int(_) => 'i',
+        OscArg::Float(_) => 'f',
+        OscArg::Double(_) => 'd',
+        OscArg::String(_) => 's',
+        OscArg::Bool(true) => 'T',
+        OscArg::Bool(false) => 'F',
+      });
+    }
+    write_string(&mut packet, &type_tags);
+
+    for arg in self.args.iter() {
+      match arg {
+        OscArg::Int(value) => packet.extend_from_slice(&value.to_be_bytes()),
+        OscArg::Float(value) => packet.extend_from_slice(&value.to_bits().to_be_bytes()),
+        OscArg::Double(value) => packet.extend_from_slice(&value.to_bits().to_be_bytes()),
+        OscArg::String(value) => write_string(&mut packet, value),
+        OscArg::Bool(_) => {}
+      }
+    }
+
+    packet
+  }
+}
+
+/// Write a string terminated with at least one null and padded to a multiple of 4 bytes
+fn write_string(packet: &mut Vec<u8>, value: &str) {
+  packet.extend_from_slice(value.as_bytes());
+  let padding = 4 - value.len() % 4;
+  packet.resize(packet.len() + padding, 0);
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+  fn new(data: &'a [u8]) -> Self {
+    Reader { data }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+
+  fn take(&mut self, len: usize) -> Result<&'a [u8], OscError> {
+    if len > self.data.len() {
+      return Err(OscError::Truncated);
+    }
+    let (head, tail) = self.data.split_at(len);
+    self.data = tail;
+    Ok(head)
+  }
+
+  fn read_i32(&mut self) -> Result<i32, OscError> {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(self.take(4)?);
+    Ok(i32::from_be_bytes(bytes))
+  }
+
+  fn read_i64(&mut self) -> Result<i64, OscError> {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(self.take(8)?);
+    Ok(i64::from_be_bytes(bytes))
+  }
+
+  fn read_string(&mut self) -> Result<String, OscError> {
+    let len = self
+      .data
+      .iter()
+      .position(|byte| *byte == 0)
+      .ok_or(OscError::Truncated)?;
+    let padded_len = (len / 4 + 1) * 4;
+    let bytes = self.take(padded_len.min(self.data.len()))?;
+    std::str::from_utf8(&bytes[0..len])
+      .map(|value| value.to_string())
+      .map_err(|_| OscError::InvalidString)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn encode_decode_message() {
+    let message = OscMessage::new(
+      "/param/cutoff",
+      vec![
+        OscArg::Float(0.5),
+        OscArg::Int(-3),
+        OscArg::String("kiro".to_string()),
+        OscArg::Bool(true),
+        OscArg::Double(0.25),
+      ],
+    );
+
+    let packet = message.encode();
+
+    assert_eq!(packet.len() % 4, 0);
+    assert_eq!(&packet[0..16], b"/param/cutoff\0\0\0");
+    assert_eq!(OscMessage::decode_packet(&packet), Ok(vec![message]));
+  }
+
+  #[test]
+  fn decode_bundle() {
+    let first = OscMessage::new("/param/a", vec![OscArg::Float(0.1)]);
+    let second = OscMessage::new("/param/b", vec![OscArg::Int(1)]);
+
+    let mut packet = BUNDLE_TAG.to_vec();
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    for message in [&first, &second].iter() {
+      let element = message.encode();
+      packet.extend_from_slice(&(element.len() as i32).to_be_bytes());
+      packet.extend_from_slice(&element);
+    }
+
+    assert_eq!(OscMessage::decode_packet(&packet), Ok(vec![first, second]));
+  }
+
+  #[test]
+  fn decode_invalid_packets() {
+    let mut packet = OscMessage::new("/param/a", vec![OscArg::Float(0.1)]).encode();
+    packet.truncate(packet.len() - 2);
+    assert_eq!(OscMessage::decode_packet(&packet), Err(OscError::Truncated));
+
+    let packet = OscMessage::new("param", Vec::new()).encode();
+    assert_eq!(
+      OscMessage::decode_packet(&packet),
+      Err(OscError::InvalidAddress("param".to_string()))
+    );
+  }
+}
@@ -0,0 +1,274 @@
+pub mod message;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use kiro_synth_core::float::Float;
+use kiro_synth_engine::program::{ParamRef, ParamValues, Program};
+
+use crate::synth::SynthClient;
+
+use self::message::{OscArg, OscMessage};
+
+/// Prefix of the default address of every param, followed by its id
+pub const PARAM_ADDRESS_PREFIX: &str = "/param/";
+
+const MAX_PACKET_SIZE: usize = 1536;
+
+fn default_listen() -> String {
+  "0.0.0.0:9000".to_string()
+}
+
+/// Configuration of the OSC control, loaded from a JSON file such as:
+///
+/// ```json
+/// {
+///   "listen": "0.0.0.0:9000",
+///   "send": "192.168.1.20:9001",
+///   "addresses": { "/param/cutoff": "filter1-freq" }
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct OscConfig {
+  /// The address of the socket receiving the OSC messages
+  #[serde(default = "default_listen")]
+  pub listen: String,
+
+  /// Where to send the param changes to, if any
+  #[serde(default)]
+  pub send: Option<String>,
+
+  /// Additional OSC addresses for the params, mapped to the param ids
+  #[serde(default)]
+  pub addresses: BTreeMap<String, String>,
+}
+
+impl OscConfig {
+  pub fn from_json(json: &str) -> Result<Self> {
+    Ok(serde_json::from_str(json)?)
+  }
+}
+
+struct OscMapping<F: Float> {
+  param_ref: ParamRef,
+  values: ParamValues<F>,
+}
+
+/// Maps the OSC addresses to the params and back.
+/// The values in the messages are normalized from 0.0 to 1.0, as sent by the faders of most OSC controllers.
+pub struct OscMapper<F: Float> {
+  address_to_param: HashMap<String, OscMapping<F>>,
+  param_to_address: Vec<String>,
+}
+
+impl<F: Float> OscMapper<F> {
+  /// Create a mapper with the default address for every param, `/param/<id>`
+  pub fn new(program: &Program<F>) -> Self {
+    let mut mapper = OscMapper {
+      address_to_param: HashMap::new(),
+      param_to_address: Vec::new(),
+    };
+    for param in program.get_params().iter() {
+      let address = format!("{}{}", PARAM_ADDRESS_PREFIX, param.id);
+      mapper.map(program, &address, param.id);
+    }
+    mapper
+  }
+
+  /// Map an address to a param by its id. The param changes are sent to the last address mapped.
+  /// Returns false when there is no param with that id.
+  pub fn map(&mut self, program: &Program<F>, address: &str, param_id: &str) -> bool {
+    let param = program
+      .get_param_ref(param_id)
+      .and_then(|param_ref| program.get_param(param_ref));
+
+    match param {
+      Some((param_ref, param)) => {
+        let mapping = OscMapping {
+          param_ref,
+          values: param.values.clone(),
+        };
+        self.address_to_param.insert(address.to_string(), mapping);
+
+        let index: usize = param_ref.into();
+        if index >= self.param_to_address.len() {
+          self.param_to_address.resize(index + 1, String::new());
+        }
+        self.param_to_address[index] = address.to_string();
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// The param and its value for a message with a numeric argument sent to a mapped address
+  pub fn map_message(&self, message: &OscMessage) -> Option<(ParamRef, F)> {
+    let mapping = self.address_to_param.get(&message.address)?;
+    let normalized = message.args.first().and_then(OscArg::to_f32)?;
+    let value = mapping.values.denormalize(F::val(normalized));
+    Some((mapping.param_ref, value))
+  }
+
+  /// The message to send for a param change
+  pub fn message_for(&self, param_ref: ParamRef, value: F) -> Option<OscMessage> {
+    let index: usize = param_ref.into();
+    let address = self.param_to_address.get(index)?;
+    let mapping = self.address_to_param.get(address)?;
+    let normalized = mapping.values.normalize(value).to_f32()?;
+    Some(OscMessage::new(address, vec![OscArg::Float(normalized)]))
+  }
+}
+
+/// Receives the OSC messages in a background thread and sends the param values to the synth
+pub struct OscServer {
+  _receiver: JoinHandle<()>,
+}
+
+impl OscServer {
+  pub fn start(
+    config: &OscConfig,
+    program: &Program<f32>,
+    synth_client: Arc<Mutex<SynthClient<f32>>>,
+  ) -> Result<Self> {
+    let mut mapper = OscMapper::new(program);
+    for (address, param_id) in config.addresses.iter() {
+      if !mapper.map(program, address, param_id) {
+        println!("OSC: unknown param {} for {}", param_id, address);
+      }
+    }
+    let mapper = Arc::new(mapper);
+
+    let socket = UdpSocket::bind(&config.listen)?;
+    println!("OSC: listening on {}", socket.local_addr()?);
+
+    if let Some(target) = config.send.as_ref() {
+      // a connected socket only receives from its peer, so the changes go out through their own socket
+      let target = Self::resolve(target)?;
+      let send_socket = match target {
+        SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0")?,
+        SocketAddr::V6(_) => UdpSocket::bind("[::]:0")?,
+      };
+      let send_mapper = mapper.clone();
+      synth_client
+        .lock()
+        .unwrap()
+        .set_param_observer(move |param_ref, value| {
+          if let Some(message) = send_mapper.message_for(param_ref, value) {
+            drop(send_socket.send_to(&message.encode(), target));
+          }
+        });
+    }
+
+    let receiver = thread::Builder::new()
+      .name("osc".to_string())
+      .spawn(move || Self::receive(socket, mapper, synth_client))?;
+
+    Ok(OscServer {
+      _receiver: receiver,
+    })
+  }
+
+  fn resolve(address: &str) -> Result<SocketAddr> {
+    address
+      .to_socket_addrs()?
+      .next()
+      .ok_or_else(|| anyhow!("OSC: no address for {}", address))
+  }
+
+  fn receive(
+    socket: UdpSocket,
+    mapper: Arc<OscMapper<f32>>,
+    synth_client: Arc<Mutex<SynthClient<f32>>>,
+  ) {
+    let mut buffer = [0u8; MAX_PACKET_SIZE];
+    // controllers send many messages not meant for the synth, they are reported only once
+    let mut ignored_addresses = HashSet::new();
+    loop {
+      let size = match socket.recv_from(&mut buffer) {
+        Ok((size, _)) => size,
+        Err(err) => {
+          println!("OSC: {}", err);
+          continue;
+        }
+      };
+
+      match OscMessage::decode_packet(&buffer[0..size]) {
+        Ok(messages) => {
+          for message in messages.iter() {
+            match mapper.map_message(message) {
+              Some((param_ref, value)) => synth_client
+                .lock()
+                .unwrap()
+                .send_remote_param_value(param_ref, value),
+              None => {
+                if ignored_addresses.insert(message.address.clone()) {
+                  println!("OSC: ignoring the messages to {}", message.address);
+                }
+              }
+            }
+          }
+        }
+        Err(err) => println!("OSC: {}", err),
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use kiro_synth_engine::program::ProgramBuilder;
+  use kiro_synth_program::values;
+
+  fn program() -> Program<'static, f32> {
+    let mut builder = ProgramBuilder::new();
+    builder.param("dca-pan", values::pan());
+    builder.param("filter1-freq", values::filt_freq());
+    builder.build()
+  }
+
+  #[test]
+  fn map_default_addresses() {
+    let program = program();
+    let mapper = OscMapper::new(&program);
+    let pan_ref = program.get_param_ref("dca-pan").unwrap();
+
+    let message = OscMessage::new("/param/dca-pan", vec![OscArg::Float(0.75)]);
+    assert_eq!(mapper.map_message(&message), Some((pan_ref, 0.5)));
+
+    let message = OscMessage::new("/param/dca-pan", vec![OscArg::Int(0)]);
+    assert_eq!(mapper.map_message(&message), Some((pan_ref, -1.0)));
+
+    let message = OscMessage::new("/param/unknown", vec![OscArg::Float(0.5)]);
+    assert_eq!(mapper.map_message(&message), None);
+
+    let message = OscMessage::new("/param/dca-pan", Vec::new());
+    assert_eq!(mapper.map_message(&message), None);
+  }
+
+  #[test]
+  fn map_config_addresses() {
+    let program = program();
+    let mut mapper = OscMapper::new(&program);
+    let freq_ref = program.get_param_ref("filter1-freq").unwrap();
+
+    assert!(mapper.map(&program, "/param/cutoff", "filter1-freq"));
+    assert!(!mapper.map(&program, "/param/resonance", "filter1-res"));
+
+    let message = OscMessage::new("/param/cutoff", vec![OscArg::Float(1.0)]);
+    let (param_ref, value) = mapper.map_message(&message).unwrap();
+    assert_eq!(param_ref, freq_ref);
+
+    // the changes are sent to the last address mapped
+    let message = mapper.message_for(param_ref, value).unwrap();
+    assert_eq!(message.address, "/param/cutoff");
+    let normalized = message.args[0].to_f32().unwrap();
+    assert!((normalized - 1.0).abs() < 1e-3);
+  }
+}
@@ -0,0 +1,278 @@
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use ringbuf::{Producer, RingBuffer};
+use thiserror::Error;
+
+use kiro_synth_engine::event::{Event, Message};
+use kiro_synth_engine::globals::SynthGlobals;
+use kiro_synth_engine::program::Program;
+use kiro_synth_engine::synth::{Synth, Synth32};
+use kiro_synth_midi::messages::Message as MidiMessage;
+
+use crate::midi::file::{TempoChange, TimedMessage};
+
+const EVENTS_CAPACITY: usize = 1024;
+
+#[derive(Error, Debug)]
+pub enum RenderError {
+  #[error("Invalid note at line {0}, expected: seconds key velocity duration")]
+  InvalidNote(usize),
+
+  #[error("Error writing the WAV file")]
+  Wav(#[from] hound::Error),
+}
+
+/// Parse a list of notes, one per line with the start time in seconds, the key, the velocity from 0 to 127
+/// and the duration in seconds. Empty lines and lines starting with `#` are ignored.
+pub fn parse_note_list(text: &str) -> Result<Vec<TimedMessage>, RenderError> {
+  let mut messages = Vec::new();
+  for (index, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let invalid_note = || RenderError::InvalidNote(index + 1);
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() != 4 {
+      return Err(invalid_note());
+    }
+    let start: f64 = fields[0].parse().map_err(|_| invalid_note())?;
+    let key: u8 = fields[1].parse().map_err(|_| invalid_note())?;
+    let velocity: u8 = fields[2].parse().map_err(|_| invalid_note())?;
+    let duration: f64 = fields[3].parse().map_err(|_| invalid_note())?;
+    if start < 0.0 || duration < 0.0 || key > 127 || velocity > 127 {
+      return Err(invalid_note());
+    }
+
+    messages.push(TimedMessage {
+      seconds: start,
+      track: 0,
+      message: MidiMessage::NoteOn {
+        channel: 0,
+        key,
+        velocity,
+      },
+    });
+    messages.push(TimedMessage {
+      seconds: start + duration,
+      track: 0,
+      message: MidiMessage::NoteOff {
+        channel: 0,
+        key,
+        velocity: 0,
+      },
+    });
+  }
+
+  // a note ending when another one starts on the same key must be released first
+  messages.sort_by(|a, b| {
+    let a_note_on = matches!(a.message, MidiMessage::NoteOn { .. });
+    let b_note_on = matches!(b.message, MidiMessage::NoteOn { .. });
+    a.seconds
+      .partial_cmp(&b.seconds)
+      .unwrap()
+      .then(a_note_on.cmp(&b_note_on))
+  });
+
+  Ok(messages)
+}
+
+/// Renders a synth without an audio device, as fast as possible
+pub struct OfflineRenderer<'a> {
+  synth: Synth32<'a>,
+  events: Producer<Event<f32>>,
+  sample_rate: u32,
+}
+
+impl<'a> OfflineRenderer<'a> {
+  pub fn new(sample_rate: u32, program: Program<'a, f32>, globals: SynthGlobals<f32>) -> Self {
+    let (events, events_consumer) = RingBuffer::<Event<f32>>::new(EVENTS_CAPACITY).split();
+    let synth = Synth::new(sample_rate as f32, events_consumer, program, globals);
+    OfflineRenderer {
+      synth,
+      events,
+      sample_rate,
+    }
+  }
+
+  /// Seed every random behaviour of the synth, so the renders are bit-reproducible
+  pub fn set_random_seed(&mut self, seed: u64) {
+    self.synth.set_random_seed(seed);
+  }
+
+  /// Set the master gain so a C4 note peaks at the target level, returning the gain in dB
+  pub fn auto_level(&mut self, target_dbfs: f32) -> Option<f32> {
+    self.synth.auto_level(target_dbfs)
+  }
+
+  /// Render the messages into stereo frames, until some tail time after the last message.
+  /// The tempo changes are applied to the transport of the synth.
+  pub fn render(
+    &mut self,
+    messages: &[TimedMessage],
+    tempo_changes: &[TempoChange],
+    tail_seconds: f64,
+  ) -> Vec<(f32, f32)> {
+    let sample_rate = f64::from(self.sample_rate);
+    let end_seconds = messages.last().map(|m| m.seconds).unwrap_or(0.0) + tail_seconds;
+    let num_frames = (end_seconds * sample_rate).round() as usize;
+
+    let mut frames = Vec::with_capacity(num_frames);
+    let mut pending = messages.iter().peekable();
+    let mut pending_tempo = tempo_changes.iter().peekable();
+    for frame in 0..num_frames {
+      while let Some(change) = pending_tempo.peek() {
+        if (change.seconds * sample_rate).round() as usize > frame {
+          break;
+        }
+        self.synth.set_tempo(change.bpm as f32);
+        pending_tempo.next();
+      }
+
+      let mut has_events = false;
+      while let Some(timed) = pending.peek() {
+        if (timed.seconds * sample_rate).round() as usize > frame {
+          break;
+        }
+        if let Some(message) = Self::synth_message(&timed.message) {
+          drop(self.events.push(Event::new(0u64, message)));
+          has_events = true;
+        }
+        pending.next();
+      }
+      if has_events {
+        self.synth.prepare();
+      }
+      frames.push(self.synth.process());
+    }
+
+    frames
+  }
+
+  fn synth_message(message: &MidiMessage) -> Option<Message<f32>> {
+    match *message {
+      MidiMessage::NoteOn { key, velocity, .. } if velocity > 0 => Some(Message::NoteOn {
+        key,
+        velocity: f32::from(velocity) / 127.0,
+      }),
+      MidiMessage::NoteOn { key, .. } => Some(Message::NoteOff { key, velocity: 0.0 }),
+      MidiMessage::NoteOff { key, velocity, .. } => Some(Message::NoteOff {
+        key,
+        velocity: f32::from(velocity) / 127.0,
+      }),
+      MidiMessage::ChannelPressure { value, .. } => Some(Message::ChannelPressure {
+        pressure: f32::from(value) / 127.0,
+      }),
+      MidiMessage::PolyphonicKeyPressure { key, value, .. } => Some(Message::PolyPressure {
+        key,
+        pressure: f32::from(value) / 127.0,
+      }),
+      MidiMessage::AllNotesOff { .. } => Some(Message::AllNotesOff),
+      MidiMessage::AllSoundOff { .. } => Some(Message::AllSoundOff),
+      _ => None,
+    }
+  }
+}
+
+/// Write stereo frames as a 32 bits float WAV
+pub fn write_wav<W: Write + Seek>(
+  writer: W,
+  sample_rate: u32,
+  frames: &[(f32, f32)],
+) -> Result<(), RenderError> {
+  let spec = WavSpec {
+    channels: 2,
+    sample_rate,
+    bits_per_sample: 32,
+    sample_format: SampleFormat::Float,
+  };
+  let mut writer = WavWriter::new(writer, spec)?;
+  for (left, right) in frames.iter() {
+    writer.write_sample(*left)?;
+    writer.write_sample(*right)?;
+  }
+  writer.finalize()?;
+  Ok(())
+}
+
+pub fn write_wav_file<P: AsRef<Path>>(
+  path: P,
+  sample_rate: u32,
+  frames: &[(f32, f32)],
+) -> Result<(), RenderError> {
+  let file = std::io::BufWriter::new(std::fs::File::create(path).map_err(hound::Error::from)?);
+  write_wav(file, sample_rate, frames)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::BTreeMap;
+  use std::io::Cursor;
+
+  use hound::WavReader;
+
+  use kiro_synth_program::kiro::KiroModule;
+  use kiro_synth_program::preset::{Preset, PRESET_VERSION};
+
+  const SAMPLE_RATE: u32 = 44100;
+
+  #[test]
+  fn parse_notes() {
+    let notes = parse_note_list("# two notes\n0.5 64 127 0.5\n\n0.0 60 64 0.5\n").unwrap();
+
+    let times: Vec<f64> = notes.iter().map(|note| note.seconds).collect();
+    assert_eq!(times, vec![0.0, 0.5, 0.5, 1.0]);
+    assert_eq!(
+      notes[1].message,
+      MidiMessage::NoteOff {
+        channel: 0,
+        key: 60,
+        velocity: 0
+      }
+    );
+
+    assert!(matches!(
+      parse_note_list("0.0 60 64\n"),
+      Err(RenderError::InvalidNote(1))
+    ));
+  }
+
+  #[test]
+  fn render_preset_and_notes_to_wav() {
+    let globals = SynthGlobals::new();
+    let (mut program, _) =
+      KiroModule::new_program(globals.lfo_waveforms.len(), globals.osc_waveforms.len());
+    let mut params = BTreeMap::new();
+    params.insert("amp-eg-release".to_string(), 0.1);
+    let preset = Preset {
+      version: PRESET_VERSION,
+      name: "render".to_string(),
+      params,
+      modulations: None,
+      insert_order: Vec::new(),
+    };
+    preset.apply(&mut program);
+
+    let notes = parse_note_list("0.0 60 100 0.25\n0.25 67 100 0.25\n").unwrap();
+    let mut renderer = OfflineRenderer::new(SAMPLE_RATE, program, globals);
+    let frames = renderer.render(&notes, &[], 0.5);
+
+    assert_eq!(frames.len(), SAMPLE_RATE as usize);
+
+    let mut wav = Cursor::new(Vec::new());
+    write_wav(&mut wav, SAMPLE_RATE, &frames).unwrap();
+    wav.set_position(0);
+
+    let mut reader = WavReader::new(wav).unwrap();
+    assert_eq!(reader.spec().channels, 2);
+    assert_eq!(reader.duration(), SAMPLE_RATE);
+    let samples: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    assert!(peak > 0.01, "peak {}", peak);
+  }
+}
@@ -1,16 +1,21 @@
 use generic_array::GenericArray;
-use ringbuf::Producer;
+use ringbuf::{Consumer, Producer};
 
-use kiro_synth_core::meters::PeakMeter;
+use kiro_synth_core::meters::{PeakMeter, RmsMeter, TruePeak};
+use kiro_synth_engine::event::Event;
 use kiro_synth_engine::program::MaxParams;
-use kiro_synth_engine::synth::Synth;
+use kiro_synth_engine::synth::Synth32;
 
 use crate::audio::AudioHandler;
 
+/// The integration time of the RMS levels
+const RMS_SECONDS: f32 = 0.3;
+
 #[derive(Debug, Clone)]
 pub struct SynthAudioLevels {
   pub peak: f32,
   pub level: f32,
+  pub rms: f32,
 }
 
 impl Default for SynthAudioLevels {
@@ -18,6 +23,7 @@ impl Default for SynthAudioLevels {
     SynthAudioLevels {
       peak: 0.0,
       level: 0.0,
+      rms: 0.0,
     }
   }
 }
@@ -28,39 +34,80 @@ pub struct SynthFeedback {
   pub modulations: GenericArray<f32, MaxParams>,
   pub left_levels: SynthAudioLevels,
   pub right_levels: SynthAudioLevels,
+  /// The levels of the wet signal of the send effect, which has no RMS level
+  pub send_fx_levels: SynthAudioLevels,
 }
 
 pub struct SynthAudioHandler<'a> {
-  synth: Synth<'a, f32>,
+  synth: Synth32<'a>,
   feedback: Producer<SynthFeedback>,
+  /// The events decoded in the audio thread, when the MIDI input is read from the audio callback
+  midi_events: Option<Consumer<Event<f32>>>,
   left_level: PeakMeter<f32>,
   right_level: PeakMeter<f32>,
+  /// The peaks between the samples, so the clip indicator catches the overs that the samples miss
+  left_true_peak: TruePeak<f32>,
+  right_true_peak: TruePeak<f32>,
+  left_rms: RmsMeter<f32>,
+  right_rms: RmsMeter<f32>,
 }
 
 impl<'a> SynthAudioHandler<'a> {
-  pub fn new(synth: Synth<'a, f32>, feedback: Producer<SynthFeedback>) -> Self {
+  pub fn new(synth: Synth32<'a>, feedback: Producer<SynthFeedback>) -> Self {
     let sample_rate = synth.get_sample_rate();
     SynthAudioHandler {
       synth,
       feedback,
+      midi_events: None,
       left_level: PeakMeter::new(sample_rate, 0.7, 24.0),
       right_level: PeakMeter::new(sample_rate, 0.7, 24.0),
+      left_true_peak: TruePeak::new(),
+      right_true_peak: TruePeak::new(),
+      left_rms: RmsMeter::new(sample_rate, RMS_SECONDS),
+      right_rms: RmsMeter::new(sample_rate, RMS_SECONDS),
     }
   }
+
+  pub fn with_midi_events(mut self, midi_events: Consumer<Event<f32>>) -> Self {
+    self.midi_events = Some(midi_events);
+    self
+  }
 }
 
 impl<'a> AudioHandler for SynthAudioHandler<'a> {
   fn prepare(&mut self, _len: usize) {
     self.synth.prepare();
+    if let Some(midi_events) = self.midi_events.as_mut() {
+      while let Some(event) = midi_events.pop() {
+        self.synth.handle_message(event.message);
+      }
+    }
   }
 
   fn next(&mut self) -> (f32, f32) {
     let (left, right) = self.synth.process();
-    self.left_level.process(left);
-    self.right_level.process(right);
+    self.left_level.process(self.left_true_peak.process(left));
+    self.right_level.process(self.right_true_peak.process(right));
+    self.left_rms.process(left);
+    self.right_rms.process(right);
     (left, right)
   }
 
+  fn set_sample_rate(&mut self, sample_rate: u32) {
+    let sample_rate = sample_rate as f32;
+    self.synth.set_sample_rate(sample_rate);
+    self.left_level = PeakMeter::new(sample_rate, 0.7, 24.0);
+    self.right_level = PeakMeter::new(sample_rate, 0.7, 24.0);
+    self.left_true_peak.reset();
+    self.right_true_peak.reset();
+    self.left_rms = RmsMeter::new(sample_rate, RMS_SECONDS);
+    self.right_rms = RmsMeter::new(sample_rate, RMS_SECONDS);
+  }
+
+  fn set_output_channels(&mut self, channels: usize) {
+    self.synth.set_output_channels(channels);
+  }
+
   fn finalize(&mut self) {
     let mut modulations = GenericArray::default();
     if let Some(voice) = self.synth.get_last_voice() {
@@ -74,16 +121,24 @@ impl<'a> AudioHandler for SynthAudioHandler<'a> {
       }
     }
     let num_active_voices = self.synth.get_num_active_voices();
+    let send_fx_meter = self.synth.get_send_effect_meter();
     let feedback = SynthFeedback {
       num_active_voices,
       modulations,
       left_levels: SynthAudioLevels {
         peak: self.left_level.get_peak(),
         level: self.left_level.get_level(),
+        rms: self.left_rms.get_level(),
       },
       right_levels: SynthAudioLevels {
         peak: self.right_level.get_peak(),
         level: self.right_level.get_level(),
+        rms: self.right_rms.get_level(),
+      },
+      send_fx_levels: SynthAudioLevels {
+        peak: send_fx_meter.get_peak_db(),
+        level: send_fx_meter.get_level_db(),
+        rms: f32::NEG_INFINITY,
       },
     };
     self.feedback.push(feedback).unwrap_or_default();
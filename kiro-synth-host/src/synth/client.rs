@@ -1,20 +1,89 @@
 use std::fmt::Formatter;
 use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
 
 use ringbuf::{Consumer, Producer};
 
 use kiro_synth_core::float::Float;
 use kiro_synth_engine::event::{Event, Message};
 use kiro_synth_engine::globals::SynthGlobals;
-use kiro_synth_engine::program::{ParamRef, SourceRef};
+use kiro_synth_engine::program::{ParamRef, Program, SourceRef};
 use kiro_synth_engine::waveforms::{LfoWaveforms, OscWaveforms};
 
+use crate::synth::history::{EditHistory, ParamEdit};
 use crate::synth::SynthFeedback;
 
+/// Hook called with every parameter value sent to the synth (ie. for logging or debugging)
+pub type ParamObserver<F> = Box<dyn Fn(ParamRef, F) + Send>;
+
+/// Param values received from remote controllers, in the order they arrived
+pub type RemoteParamValues<F> = Vec<(ParamRef, F)>;
+
+/// Interpolation of the param values sent from the UI, expanding every change into a short ramp of events
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamRamp {
+  /// Number of events sent for every change
+  pub steps: usize,
+  /// Duration of the ramp in milliseconds
+  pub duration_ms: u64,
+}
+
+impl Default for ParamRamp {
+  fn default() -> Self {
+    ParamRamp {
+      steps: 8,
+      duration_ms: 5,
+    }
+  }
+}
+
+/// A ramp of param values already sent, to know the value that the synth has reached while it lasts
+#[derive(Debug, Clone, Copy)]
+struct SentRamp<F: Float> {
+  start: F,
+  end: F,
+  ramp: ParamRamp,
+  sent_at: Instant,
+}
+
+impl<F: Float> SentRamp<F> {
+  fn steps(&self) -> usize {
+    self.ramp.steps.max(1)
+  }
+
+  fn step_value(&self, step: usize) -> F {
+    if step >= self.steps() {
+      self.end
+    } else {
+      let t = F::val(step) / F::val(self.steps());
+      self.start + (self.end - self.start) * t
+    }
+  }
+
+  fn step_timestamp(&self, step: usize) -> u64 {
+    self.ramp.duration_ms * 1000 * step as u64 / self.steps() as u64
+  }
+
+  /// The number of steps already applied by the synth at a given time
+  fn steps_applied(&self, now: Instant) -> usize {
+    let elapsed_us = now.saturating_duration_since(self.sent_at).as_micros() as u64;
+    (1..=self.steps())
+      .take_while(|step| self.step_timestamp(*step) <= elapsed_us)
+      .count()
+  }
+}
+
 pub struct SynthClient<F: Float> {
   globals: SynthGlobals<F>,
   events: Producer<Event<F>>,
   feedback: Consumer<SynthFeedback>,
+  param_observer: Option<ParamObserver<F>>,
+  param_ramp: Option<ParamRamp>,
+  /// The last values sent for every param, the end of the ramp for the ones sent with a ramp
+  last_param_values: Vec<Option<F>>,
+  sent_ramps: Vec<Option<SentRamp<F>>>,
+  remote_param_values: RemoteParamValues<F>,
+  edit_history: EditHistory<F>,
 }
 
 impl<F: Float> SynthClient<F> {
@@ -27,9 +96,32 @@ impl<F: Float> SynthClient<F> {
       globals,
       events,
       feedback,
+      param_observer: None,
+      param_ramp: None,
+      last_param_values: Vec::new(),
+      sent_ramps: Vec::new(),
+      remote_param_values: Vec::new(),
+      edit_history: EditHistory::new(),
     }
   }
 
+  pub fn set_param_observer<O>(&mut self, observer: O)
+  where
+    O: Fn(ParamRef, F) + Send + 'static,
+  {
+    self.param_observer = Some(Box::new(observer));
+  }
+
+  #[allow(dead_code)]
+  pub fn clear_param_observer(&mut self) {
+    self.param_observer = None;
+  }
+
+  /// Enable or disable the interpolation of the param values sent with [`send_param_value`](Self::send_param_value)
+  pub fn set_param_ramp(&mut self, param_ramp: Option<ParamRamp>) {
+    self.param_ramp = param_ramp;
+  }
+
   pub fn osc_waveforms(&self) -> &OscWaveforms<F> {
     &self.globals.osc_waveforms
   }
@@ -52,9 +144,190 @@ impl<F: Float> SynthClient<F> {
     self.send_event(Event::new(0u64, message));
   }
 
+  pub fn send_all_notes_off(&mut self) {
+    self.send_event(Event::new(0u64, Message::AllNotesOff));
+  }
+
+  pub fn send_all_sound_off(&mut self) {
+    self.send_event(Event::new(0u64, Message::AllSoundOff));
+  }
+
+  /// Set the tempo of the transport in beats per minute
+  pub fn send_tempo(&mut self, bpm: F) {
+    self.send_event(Event::new(0u64, Message::Tempo { bpm }));
+  }
+
+  pub fn send_channel_pressure(&mut self, pressure: F) {
+    let message = Message::ChannelPressure { pressure };
+    self.send_event(Event::new(0u64, message));
+  }
+
+  pub fn send_poly_pressure(&mut self, key: u8, pressure: F) {
+    let message = Message::PolyPressure { key, pressure };
+    self.send_event(Event::new(0u64, message));
+  }
+
+  /// Send a param value, as a ramp from the value reached by the synth when the param ramp is enabled
   pub fn send_param_value(&mut self, param_ref: ParamRef, value: F) {
+    self.observe_param_value(param_ref, value);
+    self.send_unobserved_param_value(param_ref, value);
+  }
+
+  /// Send a param value straight away even when the param ramp is enabled, ie. for the values of a preset,
+  /// as loading a preset changes most of the params at once and a ramp for every one would flood the synth
+  pub fn send_param_value_now(&mut self, param_ref: ParamRef, value: F) {
+    self.observe_param_value(param_ref, value);
+    self.send_value(param_ref, value, Instant::now());
+  }
+
+  /// Take the values of the program as the last ones sent, so the first edits from the UI can be undone
+  pub fn init_param_values<'a>(&mut self, program: &Program<'a, F>) {
+    for index in 0..program.get_params().len() {
+      let param_ref = ParamRef::new(index);
+      let value = program.get_param_signal(param_ref).get();
+      self.set_last_param_value(param_ref, value);
+    }
+  }
+
+  /// Send a param value edited by the user, recording the change in the edit history.
+  /// The values already sent, ie. when the UI follows an undo, are not recorded again.
+  pub fn send_param_edit(&mut self, param_ref: ParamRef, value: F) {
+    if let Some(old_value) = self.last_param_value(param_ref) {
+      if old_value != value {
+        let edit = ParamEdit {
+          param_ref,
+          old_value,
+          new_value: value,
+        };
+        self.edit_history.push(edit, Instant::now());
+      }
+    }
+    self.send_param_value(param_ref, value);
+  }
+
+  /// Revert the last edit, returning it so the UI can show the old value
+  pub fn undo_param_edit(&mut self) -> Option<ParamEdit<F>> {
+    let edit = self.edit_history.undo()?;
+    self.send_param_value(edit.param_ref, edit.old_value);
+    Some(edit)
+  }
+
+  /// Apply again the last edit undone
+  pub fn redo_param_edit(&mut self) -> Option<ParamEdit<F>> {
+    let edit = self.edit_history.redo()?;
+    self.send_param_value(edit.param_ref, edit.new_value);
+    Some(edit)
+  }
+
+  /// Send a param value coming from a remote controller, keeping it until the UI takes it with
+  /// [`take_remote_param_values`](Self::take_remote_param_values).
+  /// The observer is not told, the knob following the value already sends it back.
+  pub fn send_remote_param_value(&mut self, param_ref: ParamRef, value: F) {
+    self.send_unobserved_param_value(param_ref, value);
+    self.remote_param_values.push((param_ref, value));
+  }
+
+  /// Take the param values received from remote controllers since the last call
+  pub fn take_remote_param_values(&mut self) -> RemoteParamValues<F> {
+    std::mem::take(&mut self.remote_param_values)
+  }
+
+  /// Send a ramp of param values from start to end, using the param ramp settings or the default ones.
+  /// The events are timestamped in microseconds from now.
+  pub fn send_param_ramp(&mut self, param_ref: ParamRef, start: F, end: F) {
+    self.observe_param_value(param_ref, end);
+    let ramp = self.param_ramp.unwrap_or_default();
+    self.send_ramp(param_ref, start, end, ramp, Instant::now());
+  }
+
+  fn send_unobserved_param_value(&mut self, param_ref: ParamRef, value: F) {
+    match self.param_ramp {
+      Some(ramp) => self.send_ramped_value(param_ref, value, ramp, Instant::now()),
+      None => self.send_value(param_ref, value, Instant::now()),
+    }
+  }
+
+  fn send_ramped_value(&mut self, param_ref: ParamRef, value: F, ramp: ParamRamp, now: Instant) {
+    match self.current_param_value(param_ref, now) {
+      Some(start) => self.send_ramp(param_ref, start, value, ramp, now),
+      None => self.send_value(param_ref, value, now),
+    }
+  }
+
+  fn send_value(&mut self, param_ref: ParamRef, value: F, now: Instant) {
+    self.cancel_ramp(param_ref, now);
+    self.push_param_value(param_ref, value, 0u64);
+    self.set_last_param_value(param_ref, value);
+  }
+
+  /// Send the steps of a ramp, replacing the steps still pending of the previous one
+  fn send_ramp(&mut self, param_ref: ParamRef, start: F, end: F, ramp: ParamRamp, now: Instant) {
+    self.cancel_ramp(param_ref, now);
+    let sent_ramp = SentRamp {
+      start,
+      end,
+      ramp,
+      sent_at: now,
+    };
+    for step in 1..=sent_ramp.steps() {
+      let value = sent_ramp.step_value(step);
+      self.push_param_value(param_ref, value, sent_ramp.step_timestamp(step));
+    }
+    self.set_last_param_value(param_ref, end);
+    let index: usize = param_ref.into();
+    self.sent_ramps[index] = Some(sent_ramp);
+  }
+
+  /// Forget the ramp sent for a param, cancelling its steps in the synth when some are still pending
+  fn cancel_ramp(&mut self, param_ref: ParamRef, now: Instant) {
+    let index: usize = param_ref.into();
+    let sent_ramp = self.sent_ramps.get_mut(index).and_then(Option::take);
+    if let Some(sent_ramp) = sent_ramp {
+      if sent_ramp.steps_applied(now) < sent_ramp.steps() {
+        let message = Message::CancelParamValues { param_ref };
+        self.send_event(Event::new(0u64, message));
+      }
+    }
+  }
+
+  /// The value that the synth has for a param, as far as the values sent tell
+  fn current_param_value(&self, param_ref: ParamRef, now: Instant) -> Option<F> {
+    let index: usize = param_ref.into();
+    match self.sent_ramps.get(index).cloned().flatten() {
+      Some(sent_ramp) => Some(sent_ramp.step_value(sent_ramp.steps_applied(now))),
+      None => self.last_param_value(param_ref),
+    }
+  }
+
+  fn observe_param_value(&self, param_ref: ParamRef, value: F) {
+    if let Some(observer) = self.param_observer.as_ref() {
+      observer(param_ref, value);
+    }
+  }
+
+  fn push_param_value(&mut self, param_ref: ParamRef, value: F, timestamp: u64) {
     let message = Message::ParamValue { param_ref, value };
-    self.send_event(Event::new(0u64, message));
+    self.send_event(Event::new(timestamp, message));
+  }
+
+  fn last_param_value(&self, param_ref: ParamRef) -> Option<F> {
+    let index: usize = param_ref.into();
+    self.last_param_values.get(index).cloned().flatten()
+  }
+
+  fn set_last_param_value(&mut self, param_ref: ParamRef, value: F) {
+    let index: usize = param_ref.into();
+    if index >= self.last_param_values.len() {
+      self.last_param_values.resize(index + 1, None);
+      self.sent_ramps.resize(index + 1, None);
+    }
+    self.last_param_values[index] = Some(value);
+  }
+
+  /// Change the order of the insert effects while playing, given as their slots in processing order
+  pub fn send_insert_order(&mut self, order: &[u8]) {
+    let order = order.iter().cloned().collect();
+    self.send_event(Event::new(0u64, Message::InsertOrder { order }));
   }
 
   #[allow(dead_code)]
@@ -72,6 +345,16 @@ impl<F: Float> SynthClient<F> {
     self.send_event(Event::new(0u64, message));
   }
 
+  /// Change the phase offset in cycles at which an LFO source is sampled for a modulation
+  pub fn send_modulation_phase(&mut self, source_ref: SourceRef, param_ref: ParamRef, phase: F) {
+    let message = Message::ModulationPhase {
+      source_ref,
+      param_ref,
+      phase,
+    };
+    self.send_event(Event::new(0u64, message));
+  }
+
   pub fn send_modulation_delete(&mut self, source_ref: SourceRef, param_ref: ParamRef) {
     let message = Message::ModulationDelete {
       source_ref,
@@ -100,6 +383,40 @@ impl<F: Float> SynthClientMutex<F> {
       .map(|mut client| client.send_param_value(param_ref, value))
   }
 
+  pub fn send_param_value_now(
+    &self,
+    param_ref: ParamRef,
+    value: F,
+  ) -> Result<(), PoisonError<MutexGuard<'_, SynthClient<F>>>> {
+    self
+      .0
+      .lock()
+      .map(|mut client| client.send_param_value_now(param_ref, value))
+  }
+
+  pub fn send_param_edit(
+    &self,
+    param_ref: ParamRef,
+    value: F,
+  ) -> Result<(), PoisonError<MutexGuard<'_, SynthClient<F>>>> {
+    self
+      .0
+      .lock()
+      .map(|mut client| client.send_param_edit(param_ref, value))
+  }
+
+  pub fn undo_param_edit(
+    &self,
+  ) -> Result<Option<ParamEdit<F>>, PoisonError<MutexGuard<'_, SynthClient<F>>>> {
+    self.0.lock().map(|mut client| client.undo_param_edit())
+  }
+
+  pub fn redo_param_edit(
+    &self,
+  ) -> Result<Option<ParamEdit<F>>, PoisonError<MutexGuard<'_, SynthClient<F>>>> {
+    self.0.lock().map(|mut client| client.redo_param_edit())
+  }
+
   pub fn send_modulation_update(
     &self,
     source_ref: SourceRef,
@@ -112,6 +429,18 @@ impl<F: Float> SynthClientMutex<F> {
       .map(|mut client| client.send_modulation_update(source_ref, param_ref, amount))
   }
 
+  pub fn send_modulation_phase(
+    &self,
+    source_ref: SourceRef,
+    param_ref: ParamRef,
+    phase: F,
+  ) -> Result<(), PoisonError<MutexGuard<'_, SynthClient<F>>>> {
+    self
+      .0
+      .lock()
+      .map(|mut client| client.send_modulation_phase(source_ref, param_ref, phase))
+  }
+
   pub fn send_modulation_delete(
     &self,
     source_ref: SourceRef,
@@ -123,6 +452,29 @@ impl<F: Float> SynthClientMutex<F> {
       .map(|mut client| client.send_modulation_delete(source_ref, param_ref))
   }
 
+  pub fn send_insert_order(
+    &self,
+    order: &[u8],
+  ) -> Result<(), PoisonError<MutexGuard<'_, SynthClient<F>>>> {
+    self
+      .0
+      .lock()
+      .map(|mut client| client.send_insert_order(order))
+  }
+
+  pub fn send_all_sound_off(&self) -> Result<(), PoisonError<MutexGuard<'_, SynthClient<F>>>> {
+    self.0.lock().map(|mut client| client.send_all_sound_off())
+  }
+
+  pub fn take_remote_param_values(
+    &self,
+  ) -> Result<RemoteParamValues<F>, PoisonError<MutexGuard<'_, SynthClient<F>>>> {
+    self
+      .0
+      .lock()
+      .map(|mut client| client.take_remote_param_values())
+  }
+
   pub fn get_feedback(
     &mut self,
   ) -> Result<Option<SynthFeedback>, PoisonError<MutexGuard<'_, SynthClient<F>>>> {
@@ -135,3 +487,240 @@ impl<F: Float> std::fmt::Debug for SynthClientMutex<F> {
     f.write_str("SynthClient")
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::time::Duration;
+
+  use ringbuf::RingBuffer;
+
+  use kiro_synth_engine::program::ProgramBuilder;
+  use kiro_synth_program::values;
+
+  fn client() -> (
+    SynthClient<f32>,
+    Consumer<Event<f32>>,
+    Producer<SynthFeedback>,
+  ) {
+    let (events_producer, events_consumer) = RingBuffer::new(16).split();
+    let (feedback_producer, feedback_consumer) = RingBuffer::new(1).split();
+    let client = SynthClient::new(SynthGlobals::new(), events_producer, feedback_consumer);
+    (client, events_consumer, feedback_producer)
+  }
+
+  #[test]
+  fn send_param_value_without_observer() {
+    let (mut client, mut events, _) = client();
+    assert!(client.param_observer.is_none());
+
+    client.send_param_value(ParamRef::new(3), 0.5);
+
+    match events.pop().map(|event| event.message) {
+      Some(Message::ParamValue { param_ref, value }) => {
+        assert_eq!(param_ref, ParamRef::new(3));
+        assert_eq!(value, 0.5);
+      }
+      other => panic!("unexpected message {:?}", other),
+    }
+  }
+
+  #[test]
+  fn send_param_value_with_observer() {
+    let (mut client, mut events, _) = client();
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let observed_clone = observed.clone();
+    client.set_param_observer(move |param_ref, value| {
+      observed_clone.lock().unwrap().push((param_ref, value))
+    });
+
+    client.send_param_value(ParamRef::new(1), 0.25);
+    client.clear_param_observer();
+    client.send_param_value(ParamRef::new(2), 0.75);
+
+    assert_eq!(*observed.lock().unwrap(), vec![(ParamRef::new(1), 0.25)]);
+    assert!(events.pop().is_some());
+    assert!(events.pop().is_some());
+  }
+
+  #[test]
+  fn send_param_value_with_ramp() {
+    let (mut client, mut events, _) = client();
+    client.set_param_ramp(Some(ParamRamp {
+      steps: 4,
+      duration_ms: 2,
+    }));
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let observed_clone = observed.clone();
+    client.set_param_observer(move |_, value| observed_clone.lock().unwrap().push(value));
+
+    // the first value has nothing to ramp from
+    client.send_param_value(ParamRef::new(0), 0.0);
+    assert!(events.pop().is_some());
+    assert!(events.pop().is_none());
+
+    client.send_param_value(ParamRef::new(0), 1.0);
+    let mut ramp = Vec::new();
+    while let Some(Event { timestamp, message }) = events.pop() {
+      match message {
+        Message::ParamValue { param_ref, value } => {
+          assert_eq!(param_ref, ParamRef::new(0));
+          ramp.push((timestamp, value));
+        }
+        other => panic!("unexpected message {:?}", other),
+      }
+    }
+
+    assert_eq!(
+      ramp,
+      vec![(500, 0.25), (1000, 0.5), (1500, 0.75), (2000, 1.0)]
+    );
+    // the observer sees the changes, not the steps of the ramps
+    assert_eq!(*observed.lock().unwrap(), vec![0.0, 1.0]);
+  }
+
+  #[test]
+  fn send_param_value_in_the_middle_of_a_ramp() {
+    let (mut client, mut events, _) = client();
+    let ramp = ParamRamp {
+      steps: 4,
+      duration_ms: 4,
+    };
+    let param_ref = ParamRef::new(0);
+    let start = Instant::now();
+    client.send_value(param_ref, 0.0, start);
+    client.send_ramped_value(param_ref, 1.0, ramp, start);
+    while events.pop().is_some() {}
+
+    let take_events = |events: &mut Consumer<Event<f32>>| {
+      let mut taken = Vec::new();
+      while let Some(Event { timestamp, message }) = events.pop() {
+        taken.push((timestamp, message));
+      }
+      taken
+    };
+
+    // half of the ramp has been applied when the value changes again
+    client.send_ramped_value(param_ref, 0.0, ramp, start + Duration::from_millis(2));
+    let mut sent = take_events(&mut events).into_iter();
+    let (timestamp, message) = sent.next().unwrap();
+    assert_eq!(timestamp, 0);
+    match message {
+      Message::CancelParamValues { param_ref: other } => assert_eq!(other, param_ref),
+      other => panic!("unexpected message {:?}", other),
+    }
+    let values: Vec<(u64, f32)> = sent
+      .map(|(timestamp, message)| match message {
+        Message::ParamValue { value, .. } => (timestamp, value),
+        other => panic!("unexpected message {:?}", other),
+      })
+      .collect();
+    assert_eq!(
+      values,
+      vec![(1000, 0.375), (2000, 0.25), (3000, 0.125), (4000, 0.0)]
+    );
+    assert_eq!(client.last_param_value(param_ref), Some(0.0));
+
+    // a ramp already finished has nothing to cancel
+    client.send_ramped_value(param_ref, 1.0, ramp, start + Duration::from_millis(10));
+    match take_events(&mut events).first() {
+      Some((1000, Message::ParamValue { value, .. })) => assert_eq!(*value, 0.25),
+      other => panic!("unexpected message {:?}", other),
+    }
+  }
+
+  #[test]
+  fn send_param_value_now_with_ramp() {
+    let (mut client, mut events, _) = client();
+    client.set_param_ramp(Some(ParamRamp::default()));
+    client.send_param_value(ParamRef::new(0), 0.0);
+    client.send_param_value(ParamRef::new(0), 1.0);
+    while events.pop().is_some() {}
+
+    // the ramp in progress is cancelled before the value is sent
+    client.send_param_value_now(ParamRef::new(0), 0.5);
+    let messages: Vec<Message<f32>> = std::iter::from_fn(|| events.pop())
+      .map(|event| {
+        assert_eq!(event.timestamp, 0);
+        event.message
+      })
+      .collect();
+    match messages.as_slice() {
+      [Message::CancelParamValues { .. }, Message::ParamValue { value, .. }] => {
+        assert_eq!(*value, 0.5)
+      }
+      other => panic!("unexpected messages {:?}", other),
+    }
+  }
+
+  #[test]
+  fn send_remote_param_value() {
+    let (mut client, mut events, _) = client();
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let observed_clone = observed.clone();
+    client.set_param_observer(move |_, value| observed_clone.lock().unwrap().push(value));
+    client.send_remote_param_value(ParamRef::new(4), 0.5);
+
+    assert!(events.pop().is_some());
+    // the value is not echoed back to the controller that sent it
+    assert!(observed.lock().unwrap().is_empty());
+    assert_eq!(
+      client.take_remote_param_values(),
+      vec![(ParamRef::new(4), 0.5)]
+    );
+    assert!(client.take_remote_param_values().is_empty());
+  }
+
+  #[test]
+  fn undo_redo_param_edits() {
+    let mut builder = ProgramBuilder::new();
+    let pan = builder.param("dca-pan", values::pan());
+    let program: Program<f32> = builder.build();
+    let param_ref = pan.reference;
+
+    let (mut client, mut events, _) = client();
+    client.init_param_values(&program);
+
+    // a drag of the knob is undone in a single step
+    for value in [0.1, 0.2, 0.3].iter() {
+      client.send_param_edit(param_ref, *value);
+    }
+    while events.pop().is_some() {}
+
+    let sent_value = |events: &mut Consumer<Event<f32>>| match events.pop().map(|e| e.message) {
+      Some(Message::ParamValue { value, .. }) => value,
+      other => panic!("unexpected message {:?}", other),
+    };
+
+    let edit = client.undo_param_edit().unwrap();
+    assert_eq!((edit.old_value, edit.new_value), (0.0, 0.3));
+    assert_eq!(sent_value(&mut events), 0.0);
+    assert!(client.undo_param_edit().is_none());
+
+    // the UI following the undo does not record another edit
+    client.send_param_edit(param_ref, 0.0);
+    assert_eq!(sent_value(&mut events), 0.0);
+
+    assert_eq!(client.redo_param_edit(), Some(edit));
+    assert_eq!(sent_value(&mut events), 0.3);
+    assert!(client.redo_param_edit().is_none());
+  }
+
+  #[test]
+  fn send_param_ramp_is_monotonic() {
+    let (mut client, mut events, _) = client();
+    client.send_param_ramp(ParamRef::new(2), 0.9, 0.1);
+
+    let mut values = Vec::new();
+    while let Some(event) = events.pop() {
+      if let Message::ParamValue { value, .. } = event.message {
+        values.push(value);
+      }
+    }
+
+    assert_eq!(values.len(), ParamRamp::default().steps);
+    assert!(values.windows(2).all(|pair| pair[1] < pair[0]));
+    assert_eq!(values.last(), Some(&0.1));
+  }
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use kiro_synth_engine::program::ParamRef;
+
+/// Changes of the same param closer in time are undone in a single step, ie. when dragging a knob
+const COALESCE_PERIOD: Duration = Duration::from_millis(500);
+
+/// The oldest edits are forgotten beyond this
+const MAX_EDITS: usize = 256;
+
+/// A change of a param value that can be reverted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamEdit<F> {
+  pub param_ref: ParamRef,
+  pub old_value: F,
+  pub new_value: F,
+}
+
+/// The undo and redo stacks of the param edits made by the user
+pub struct EditHistory<F> {
+  undo: VecDeque<ParamEdit<F>>,
+  redo: Vec<ParamEdit<F>>,
+  last_edit_time: Option<Instant>,
+}
+
+impl<F: Copy> Default for EditHistory<F> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<F: Copy> EditHistory<F> {
+  pub fn new() -> Self {
+    EditHistory {
+      undo: VecDeque::new(),
+      redo: Vec::new(),
+      last_edit_time: None,
+    }
+  }
+
+  /// Record an edit made at some time, merging it with the last one when they are part of the same gesture.
+  /// The edits undone until now can not be redone anymore.
+  pub fn push(&mut self, edit: ParamEdit<F>, time: Instant) {
+    self.redo.clear();
+
+    let coalesce = self
+      .last_edit_time
+      .map(|last_time| time.duration_since(last_time) < COALESCE_PERIOD)
+      .unwrap_or(false);
+    self.last_edit_time = Some(time);
+
+    match self.undo.back_mut() {
+      Some(last_edit) if coalesce && last_edit.param_ref == edit.param_ref => {
+        last_edit.new_value = edit.new_value;
+      }
+      _ => {
+        if self.undo.len() == MAX_EDITS {
+          self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+      }
+    }
+  }
+
+  pub fn undo(&mut self) -> Option<ParamEdit<F>> {
+    let edit = self.undo.pop_back()?;
+    self.redo.push(edit);
+    self.last_edit_time = None;
+    Some(edit)
+  }
+
+  pub fn redo(&mut self) -> Option<ParamEdit<F>> {
+    let edit = self.redo.pop()?;
+    self.undo.push_back(edit);
+    self.last_edit_time = None;
+    Some(edit)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn edit(index: usize, old_value: f32, new_value: f32) -> ParamEdit<f32> {
+    ParamEdit {
+      param_ref: ParamRef::new(index),
+      old_value,
+      new_value,
+    }
+  }
+
+  #[test]
+  fn coalesce_changes_of_the_same_param() {
+    let start = Instant::now();
+    let ms = |millis| start + Duration::from_millis(millis);
+    let mut history = EditHistory::new();
+
+    // a drag, another param, and the first param again after a while
+    history.push(edit(0, 0.0, 0.1), ms(0));
+    history.push(edit(0, 0.1, 0.2), ms(100));
+    history.push(edit(0, 0.2, 0.3), ms(200));
+    history.push(edit(1, 0.5, 0.6), ms(300));
+    history.push(edit(0, 0.3, 0.4), ms(2000));
+
+    assert_eq!(history.undo(), Some(edit(0, 0.3, 0.4)));
+    assert_eq!(history.undo(), Some(edit(1, 0.5, 0.6)));
+    assert_eq!(history.undo(), Some(edit(0, 0.0, 0.3)));
+    assert_eq!(history.undo(), None);
+
+    assert_eq!(history.redo(), Some(edit(0, 0.0, 0.3)));
+    history.push(edit(1, 0.5, 0.7), ms(2100));
+    assert_eq!(history.redo(), None);
+  }
+}
@@ -1,6 +1,8 @@
 mod audio_handler;
 mod client;
-pub mod program;
+pub mod history;
+pub mod tuning;
+pub mod wav;
 
 pub use audio_handler::{SynthAudioHandler, SynthAudioLevels, SynthFeedback};
-pub use client::{SynthClient, SynthClientMutex};
+pub use client::{ParamRamp, SynthClient, SynthClientMutex};
@@ -1,3 +0,0 @@
-pub mod kiro;
-pub mod params;
-pub mod values;
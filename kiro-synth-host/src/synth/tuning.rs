@@ -0,0 +1,69 @@
+//! The detune of the oscillators, split in the octaves, semitones and cents params.
+
+pub const CENTS_PER_SEMITONE: i32 = 100;
+pub const CENTS_PER_OCTAVE: i32 = 1200;
+
+pub const MAX_OCTAVES: i32 = 8;
+
+/// The largest detune that the octaves, semitones and cents params can hold together
+pub const MAX_TOTAL_CENTS: i32 = MAX_OCTAVES * CENTS_PER_OCTAVE + 12 * CENTS_PER_SEMITONE + 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detune {
+  pub octaves: i32,
+  pub semitones: i32,
+  pub cents: i32,
+}
+
+impl Detune {
+  pub fn new(octaves: i32, semitones: i32, cents: i32) -> Self {
+    Detune {
+      octaves,
+      semitones,
+      cents,
+    }
+  }
+
+  /// Distribute a total detune across the params, all of them with the same sign,
+  /// so the octaves take as much as possible, then the semitones and the rest goes to the cents
+  pub fn from_cents(total_cents: f64) -> Self {
+    let total = limit(total_cents.round() as i32, MAX_TOTAL_CENTS);
+    let octaves = limit(total / CENTS_PER_OCTAVE, MAX_OCTAVES);
+    let remaining = total - octaves * CENTS_PER_OCTAVE;
+    let semitones = limit(remaining / CENTS_PER_SEMITONE, 12);
+    let cents = remaining - semitones * CENTS_PER_SEMITONE;
+
+    Detune::new(octaves, semitones, cents)
+  }
+
+  pub fn total_cents(&self) -> i32 {
+    self.octaves * CENTS_PER_OCTAVE + self.semitones * CENTS_PER_SEMITONE + self.cents
+  }
+}
+
+/// Keep a value within -max and max
+fn limit(value: i32, max: i32) -> i32 {
+  if value > max {
+    max
+  } else if value < -max {
+    -max
+  } else {
+    value
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decompose_total_cents() {
+    assert_eq!(Detune::from_cents(1300.0), Detune::new(1, 1, 0));
+    assert_eq!(Detune::from_cents(-1350.0), Detune::new(-1, -1, -50));
+    assert_eq!(Detune::from_cents(99.6), Detune::new(0, 1, 0));
+
+    let max = Detune::from_cents(20000.0);
+    assert_eq!(max, Detune::new(8, 12, 100));
+    assert_eq!(max.total_cents(), MAX_TOTAL_CENTS);
+  }
+}
@@ -0,0 +1,181 @@
+use std::path::Path;
+
+use hound::{SampleFormat, WavReader};
+use thiserror::Error;
+
+use kiro_synth_core::float::Float;
+use kiro_synth_core::oscillators::osc_waveform::OscWaveform;
+use kiro_synth_core::resampler::Resampler;
+use kiro_synth_core::waveforms::wavetable::{Interpolation, Wavetable, TABLE_SIZE};
+use kiro_synth_engine::waveforms::OscWaveforms;
+
+#[derive(Error, Debug)]
+pub enum WavError {
+  #[error("Error reading the WAV file")]
+  Read(#[from] hound::Error),
+
+  #[error("The WAV file has no samples")]
+  Empty,
+
+  #[error("There is no room for more oscillator waveforms")]
+  TooManyWaveforms,
+
+  #[error("Unknown wavetable interpolation {0}, expected nearest, linear or cubic")]
+  UnknownInterpolation(String),
+}
+
+/// Parse the interpolation of the wavetables given in the command line
+pub fn parse_interpolation(name: &str) -> Result<Interpolation, WavError> {
+  match name {
+    "nearest" => Ok(Interpolation::Nearest),
+    "linear" => Ok(Interpolation::Linear),
+    "cubic" => Ok(Interpolation::Cubic),
+    _ => Err(WavError::UnknownInterpolation(name.to_string())),
+  }
+}
+
+/// A single cycle waveform resampled to the size of the wavetables
+#[derive(Debug, Clone)]
+pub struct SingleCycle {
+  pub table: Vec<f32>,
+  /// The number of cycles detected in the source, it should be 1 for single cycle waveforms
+  pub cycles: usize,
+}
+
+impl SingleCycle {
+  /// Take the first channel from the interleaved samples and resample it to [`TABLE_SIZE`]
+  pub fn from_samples(samples: &[f32], channels: usize) -> Result<Self, WavError> {
+    let channels = channels.max(1);
+    let source: Vec<f32> = samples.iter().step_by(channels).cloned().collect();
+    if source.is_empty() {
+      return Err(WavError::Empty);
+    }
+
+    let mut resampler = Resampler::new(source.as_slice());
+    resampler.set_looping(true);
+    let ratio = source.len() as f32 / TABLE_SIZE as f32;
+    let table = (0..TABLE_SIZE).map(|_| resampler.process(ratio)).collect();
+
+    Ok(SingleCycle {
+      table,
+      cycles: Self::count_cycles(source.as_slice()),
+    })
+  }
+
+  pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, WavError> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+      SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<f32>, _>>()?,
+      SampleFormat::Int => {
+        let scale = (1u32 << (spec.bits_per_sample - 1).min(31)) as f32;
+        reader
+          .samples::<i32>()
+          .map(|sample| sample.map(|value| value as f32 / scale))
+          .collect::<Result<Vec<f32>, _>>()?
+      }
+    };
+    Self::from_samples(samples.as_slice(), spec.channels as usize)
+  }
+
+  /// Count the cycles as the number of times the signal crosses zero going up
+  fn count_cycles(samples: &[f32]) -> usize {
+    let crossings = samples
+      .iter()
+      .zip(samples.iter().cycle().skip(1))
+      .filter(|(current, next)| **current < 0.0 && **next >= 0.0)
+      .count();
+    crossings.max(1)
+  }
+}
+
+/// Load a single cycle WAV file and add it to the oscillator waveforms named as the file.
+/// Returns the index of the new waveform.
+pub fn load_osc_waveform<F: Float, P: AsRef<Path>>(
+  waveforms: &mut OscWaveforms<F>,
+  path: P,
+) -> Result<usize, WavError> {
+  let path = path.as_ref();
+  let single_cycle = SingleCycle::from_file(path)?;
+  if single_cycle.cycles > 1 {
+    println!(
+      "WARNING: {} looks like it has {} cycles instead of a single one",
+      path.display(),
+      single_cycle.cycles
+    );
+  }
+
+  let name = path
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().to_string())
+    .unwrap_or_else(|| "wav".to_string());
+
+  // waveforms are kept for the whole life of the synth
+  let name: &'static str = Box::leak(name.into_boxed_str());
+  let table: Vec<F> = single_cycle
+    .table
+    .iter()
+    .map(|sample| F::val(*sample))
+    .collect();
+  let table: &'static [F] = Box::leak(table.into_boxed_slice());
+
+  waveforms
+    .add(name, OscWaveform::Wavetable(Wavetable::new(table)))
+    .ok_or(WavError::TooManyWaveforms)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sine(period: usize, len: usize, channels: usize) -> Vec<f32> {
+    let mut samples = Vec::with_capacity(len * channels);
+    for i in 0..len {
+      let value = (2.0 * std::f32::consts::PI * i as f32 / period as f32).sin();
+      samples.resize(samples.len() + channels, value);
+    }
+    samples
+  }
+
+  #[test]
+  fn from_samples_resamples_to_table_size() {
+    let single_cycle = SingleCycle::from_samples(&sine(600, 600, 1), 1).unwrap();
+    assert_eq!(single_cycle.table.len(), TABLE_SIZE);
+    assert_eq!(single_cycle.cycles, 1);
+    let quarter = single_cycle.table[TABLE_SIZE / 4];
+    assert!((quarter - 1.0).abs() < 1e-3);
+  }
+
+  #[test]
+  fn from_samples_takes_first_channel() {
+    let mut samples = sine(256, 256, 2);
+    samples
+      .iter_mut()
+      .skip(1)
+      .step_by(2)
+      .for_each(|sample| *sample = 0.0);
+    let single_cycle = SingleCycle::from_samples(&samples, 2).unwrap();
+    assert!((single_cycle.table[TABLE_SIZE / 4] - 1.0).abs() < 1e-3);
+  }
+
+  #[test]
+  fn from_samples_detects_multiple_cycles() {
+    let single_cycle = SingleCycle::from_samples(&sine(100, 300, 1), 1).unwrap();
+    assert_eq!(single_cycle.cycles, 3);
+  }
+
+  #[test]
+  fn parse_interpolation_names() {
+    assert_eq!(parse_interpolation("cubic").unwrap(), Interpolation::Cubic);
+    assert_eq!(
+      parse_interpolation("nearest").unwrap(),
+      Interpolation::Nearest
+    );
+    assert!(parse_interpolation("sinc").is_err());
+  }
+
+  #[test]
+  fn from_samples_empty() {
+    assert!(SingleCycle::from_samples(&[], 1).is_err());
+  }
+}
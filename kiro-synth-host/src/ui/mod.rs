@@ -1,33 +1,61 @@
 pub mod controllers;
 mod icons;
 mod model;
+mod scale;
+mod theme;
 mod view;
 pub mod widgets;
 
 use std::sync::{Arc, Mutex};
 
-use druid::{theme, AppLauncher, Color, Data, Env, WindowDesc};
+use druid::{AppLauncher, Color, Data, Env, ExtEventSink, WindowDesc};
 
 use kiro_synth_core::float::Float;
 
 use crate::synth::SynthClient;
 
-pub use model::Synth;
+pub use model::{PresetState, Synth};
+use scale::scaled;
+use theme::Theme;
+pub use view::LOAD_PRESET;
 use widgets::knob;
 
-pub fn start<F: Float + 'static>(synth_model: Synth, synth_client: Arc<Mutex<SynthClient<F>>>) {
-  let data = synth_model.clone();
+/// The UI ready to be started, which other threads can already send commands to
+pub struct Ui {
+  launcher: AppLauncher<Synth>,
+  data: Synth,
+}
+
+impl Ui {
+  /// Build the UI with its sizes and fonts multiplied by some scale
+  pub fn new<F: Float + 'static>(
+    synth_model: Synth,
+    synth_client: Arc<Mutex<SynthClient<F>>>,
+    ui_scale: f64,
+  ) -> Self {
+    scale::set_ui_scale(ui_scale);
+    let data = synth_model.clone();
+
+    let window = WindowDesc::new(move || view::build(&synth_model, synth_client.clone()))
+      .title("Kiro Synth")
+      .window_size((scaled(550.0), scaled(514.0)))
+      .resizable(false);
+
+    let launcher = AppLauncher::with_window(window)
+      .configure_env(setup_theme)
+      .use_simple_logger();
+
+    Ui { launcher, data }
+  }
 
-  let window = WindowDesc::new(move || view::build(&synth_model, synth_client.clone()))
-    .title("Kiro Synth")
-    .window_size((550.0, 514.0))
-    .resizable(false);
+  /// The sink to send commands to the UI from other threads, ie. [`LOAD_PRESET`]
+  pub fn event_sink(&self) -> ExtEventSink {
+    self.launcher.get_external_handle()
+  }
 
-  AppLauncher::with_window(window)
-    .configure_env(setup_theme)
-    .use_simple_logger()
-    .launch(data)
-    .expect("UI launch failed");
+  pub fn start(self) {
+    self.launcher.launch(self.data).expect("UI launch failed");
+  }
 }
 
 pub const KNOB_VALUE: Color = Color::rgb8(236, 138, 56);
@@ -45,15 +73,13 @@ pub const GREY_214: Color = Color::grey8(214);
 fn setup_theme<T: Data>(env: &mut Env, _data: &T) {
   widgets::knob::theme::init(env);
 
-  env.set(theme::WINDOW_BACKGROUND_COLOR, GREY_46);
+  env.set(druid::theme::TEXT_SIZE_NORMAL, scaled(11.0));
 
-  env.set(theme::TEXT_SIZE_NORMAL, 11.0);
-  env.set(theme::LABEL_COLOR, GREY_214);
+  // the colors of the dark theme until the views apply the one selected
+  Theme::dark().apply(env);
 
   env.set(knob::theme::KNOB_VALUE_FG_COLOR, KNOB_VALUE);
-  env.set(knob::theme::KNOB_VALUE_BG_COLOR, GREY_83);
   env.set(knob::theme::KNOB_MODULATION_VALUE_FG_COLOR, KNOB_MODULATION);
-  env.set(knob::theme::KNOB_MODULATION_VALUE_BG_COLOR, GREY_54);
   env.set(knob::theme::KNOB_MODULATION_TOTAL_AMOUNT_COLOR, KNOB_CONFIG);
   env.set(
     knob::theme::KNOB_MODULATION_CONFIG_AMOUNT_COLOR,
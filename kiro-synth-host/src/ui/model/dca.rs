@@ -2,14 +2,16 @@ use druid::{Data, Lens};
 
 use kiro_synth_core::float::Float;
 use kiro_synth_engine::program::Program;
+use kiro_synth_program::params::DcaParams;
 
-use crate::synth::program::params::DcaParams;
 use crate::synth::SynthClientMutex;
-use crate::ui::model::Param;
+use crate::ui::model::{Param, UnitFormat};
 
 #[derive(Debug, Clone, Data, Lens)]
 pub struct Dca {
   pub amplitude: Param,
+  pub velocity_floor: Param,
+  pub saturation: Param,
   pub pan: Param,
 }
 
@@ -20,13 +22,25 @@ impl Dca {
     synth_client: SynthClientMutex<f32>,
   ) -> Self {
     Dca {
-      amplitude: Param::new(program, &params.amplitude, synth_client.clone()),
+      amplitude: Param::new(program, &params.amplitude, synth_client.clone())
+        .with_unit(UnitFormat::Decibels),
+      velocity_floor: Param::new(program, &params.velocity_floor, synth_client.clone()),
+      saturation: Param::new(program, &params.saturation, synth_client.clone()),
       pan: Param::new(program, &params.pan, synth_client).with_origin(0.0),
     }
   }
 
   pub fn for_each_modulated_param(&mut self, apply: &impl Fn(&mut Param)) {
     apply(&mut self.amplitude);
+    apply(&mut self.velocity_floor);
+    apply(&mut self.saturation);
+    apply(&mut self.pan);
+  }
+
+  pub fn for_each_param(&mut self, apply: &mut impl FnMut(&mut Param)) {
+    apply(&mut self.amplitude);
+    apply(&mut self.velocity_floor);
+    apply(&mut self.saturation);
     apply(&mut self.pan);
   }
 }
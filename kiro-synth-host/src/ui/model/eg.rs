@@ -2,10 +2,10 @@ use druid::{Data, Lens};
 
 use kiro_synth_core::float::Float;
 use kiro_synth_engine::program::Program;
+use kiro_synth_program::params::{EnvGenParams, PitchEgParams};
 
-use crate::synth::program::params::EnvGenParams;
 use crate::synth::SynthClientMutex;
-use crate::ui::model::{Param, Synth};
+use crate::ui::model::{Param, Synth, UnitFormat};
 
 pub struct EgFromSynth;
 
@@ -28,7 +28,8 @@ pub struct EnvGen {
   pub mode: Param,
   pub legato: Param,
   pub reset_to_zero: Param,
-  pub dca_intensity: Param,
+  pub velocity_to_attack: Param,
+  pub velocity_to_decay: Param,
 }
 
 impl EnvGen {
@@ -38,14 +39,16 @@ impl EnvGen {
     synth_client: SynthClientMutex<f32>,
   ) -> Self {
     EnvGen {
-      attack: Param::new(program, &params.attack, synth_client.clone()),
-      decay: Param::new(program, &params.decay, synth_client.clone()),
+      attack: Param::new(program, &params.attack, synth_client.clone()).with_unit(UnitFormat::Time),
+      decay: Param::new(program, &params.decay, synth_client.clone()).with_unit(UnitFormat::Time),
       sustain: Param::new(program, &params.sustain, synth_client.clone()),
-      release: Param::new(program, &params.release, synth_client.clone()),
+      release: Param::new(program, &params.release, synth_client.clone())
+        .with_unit(UnitFormat::Time),
       mode: Param::new(program, &params.mode, synth_client.clone()),
       legato: Param::new(program, &params.legato, synth_client.clone()),
       reset_to_zero: Param::new(program, &params.reset_to_zero, synth_client.clone()),
-      dca_intensity: Param::new(program, &params.dca_mod, synth_client),
+      velocity_to_attack: Param::new(program, &params.velocity_to_attack, synth_client.clone()),
+      velocity_to_decay: Param::new(program, &params.velocity_to_decay, synth_client),
     }
   }
 
@@ -54,6 +57,58 @@ impl EnvGen {
     apply(&mut self.decay);
     apply(&mut self.sustain);
     apply(&mut self.release);
-    apply(&mut self.dca_intensity);
+  }
+
+  pub fn for_each_param(&mut self, apply: &mut impl FnMut(&mut Param)) {
+    apply(&mut self.attack);
+    apply(&mut self.decay);
+    apply(&mut self.sustain);
+    apply(&mut self.release);
+    apply(&mut self.mode);
+    apply(&mut self.legato);
+    apply(&mut self.reset_to_zero);
+    apply(&mut self.velocity_to_attack);
+    apply(&mut self.velocity_to_decay);
+  }
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct PitchEg {
+  pub attack: Param,
+  pub decay: Param,
+  pub sustain: Param,
+  pub release: Param,
+  pub amount: Param,
+}
+
+impl PitchEg {
+  pub fn new<'a, F: Float + 'static>(
+    program: &Program<'a, F>,
+    params: &PitchEgParams,
+    synth_client: SynthClientMutex<f32>,
+  ) -> Self {
+    PitchEg {
+      attack: Param::new(program, &params.attack, synth_client.clone()).with_unit(UnitFormat::Time),
+      decay: Param::new(program, &params.decay, synth_client.clone()).with_unit(UnitFormat::Time),
+      sustain: Param::new(program, &params.sustain, synth_client.clone()),
+      release: Param::new(program, &params.release, synth_client.clone())
+        .with_unit(UnitFormat::Time),
+      amount: Param::new(program, &params.amount, synth_client).with_unit(UnitFormat::Semitones),
+    }
+  }
+
+  pub fn for_each_modulated_param(&mut self, apply: &impl Fn(&mut Param)) {
+    apply(&mut self.attack);
+    apply(&mut self.decay);
+    apply(&mut self.sustain);
+    apply(&mut self.release);
+  }
+
+  pub fn for_each_param(&mut self, apply: &mut impl FnMut(&mut Param)) {
+    apply(&mut self.attack);
+    apply(&mut self.decay);
+    apply(&mut self.sustain);
+    apply(&mut self.release);
+    apply(&mut self.amount);
   }
 }
@@ -2,10 +2,10 @@ use druid::{Data, Lens};
 
 use kiro_synth_core::float::Float;
 use kiro_synth_engine::program::Program;
+use kiro_synth_program::params::FilterParams;
 
-use crate::synth::program::params::FilterParams;
 use crate::synth::SynthClientMutex;
-use crate::ui::model::{Param, Synth};
+use crate::ui::model::{Param, Synth, UnitFormat};
 
 pub struct FilterFromSynth;
 
@@ -23,7 +23,12 @@ impl Lens<Synth, Filter> for FilterFromSynth {
 pub struct Filter {
   pub mode: Param,
   pub freq: Param,
+  pub key_track: Param,
+  pub key_track_center: Param,
   pub q: Param,
+  pub velocity_to_q: Param,
+  pub q_compensation: Param,
+  pub self_osc: Param,
 }
 
 impl Filter {
@@ -34,8 +39,15 @@ impl Filter {
   ) -> Self {
     Filter {
       mode: Param::new(program, &params.mode, synth_client.clone()),
-      freq: Param::new(program, &params.freq, synth_client.clone()),
-      q: Param::new(program, &params.q, synth_client),
+      freq: Param::new(program, &params.freq, synth_client.clone())
+        .with_unit(UnitFormat::Frequency),
+      key_track: Param::new(program, &params.key_track, synth_client.clone()).with_origin(0.0),
+      key_track_center: Param::new(program, &params.key_track_center, synth_client.clone()),
+      q: Param::new(program, &params.q, synth_client.clone()),
+      velocity_to_q: Param::new(program, &params.velocity_to_q, synth_client.clone())
+        .with_origin(0.0),
+      q_compensation: Param::new(program, &params.q_compensation, synth_client.clone()),
+      self_osc: Param::new(program, &params.self_osc, synth_client),
     }
   }
 
@@ -43,4 +55,15 @@ impl Filter {
     apply(&mut self.freq);
     apply(&mut self.q);
   }
+
+  pub fn for_each_param(&mut self, apply: &mut impl FnMut(&mut Param)) {
+    apply(&mut self.mode);
+    apply(&mut self.freq);
+    apply(&mut self.key_track);
+    apply(&mut self.key_track_center);
+    apply(&mut self.q);
+    apply(&mut self.velocity_to_q);
+    apply(&mut self.q_compensation);
+    apply(&mut self.self_osc);
+  }
 }
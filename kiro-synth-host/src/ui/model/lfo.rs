@@ -2,10 +2,10 @@ use druid::{Data, Lens};
 
 use kiro_synth_core::float::Float;
 use kiro_synth_engine::program::Program;
+use kiro_synth_program::params::LfoParams;
 
-use crate::synth::program::params::LfoParams;
 use crate::synth::SynthClientMutex;
-use crate::ui::model::{Param, Synth};
+use crate::ui::model::{Param, Synth, UnitFormat};
 
 pub struct LfoFromSynth;
 
@@ -25,6 +25,10 @@ pub struct Lfo {
   pub rate: Param,
   pub phase: Param,
   pub depth: Param,
+  pub slew: Param,
+  pub slew_sync: Param,
+  pub polarity: Param,
+  pub invert: Param,
 }
 
 impl Lfo {
@@ -35,9 +39,14 @@ impl Lfo {
   ) -> Self {
     Lfo {
       shape: Param::new(program, &params.shape, synth_client.clone()),
-      rate: Param::new(program, &params.rate, synth_client.clone()),
+      rate: Param::new(program, &params.rate, synth_client.clone())
+        .with_unit(UnitFormat::Frequency),
       phase: Param::new(program, &params.phase, synth_client.clone()),
-      depth: Param::new(program, &params.depth, synth_client),
+      depth: Param::new(program, &params.depth, synth_client.clone()),
+      slew: Param::new(program, &params.slew, synth_client.clone()),
+      slew_sync: Param::new(program, &params.slew_sync, synth_client.clone()),
+      polarity: Param::new(program, &params.polarity, synth_client.clone()),
+      invert: Param::new(program, &params.invert, synth_client),
     }
   }
 
@@ -45,5 +54,17 @@ impl Lfo {
     apply(&mut self.rate);
     apply(&mut self.phase);
     apply(&mut self.depth);
+    apply(&mut self.slew);
+  }
+
+  pub fn for_each_param(&mut self, apply: &mut impl FnMut(&mut Param)) {
+    apply(&mut self.shape);
+    apply(&mut self.rate);
+    apply(&mut self.phase);
+    apply(&mut self.depth);
+    apply(&mut self.slew);
+    apply(&mut self.slew_sync);
+    apply(&mut self.polarity);
+    apply(&mut self.invert);
   }
 }
@@ -0,0 +1,38 @@
+use druid::{Data, Lens};
+
+use kiro_synth_core::float::Float;
+use kiro_synth_engine::program::Program;
+use kiro_synth_program::params::MacroParams;
+
+use crate::synth::SynthClientMutex;
+use crate::ui::model::Param;
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct Macros {
+  pub macro1: Param,
+  pub macro2: Param,
+  pub macro3: Param,
+  pub macro4: Param,
+}
+
+impl Macros {
+  pub fn new<'a, F: Float + 'static>(
+    program: &Program<'a, F>,
+    params: &MacroParams,
+    synth_client: SynthClientMutex<f32>,
+  ) -> Self {
+    Macros {
+      macro1: Param::new(program, &params.macro1, synth_client.clone()),
+      macro2: Param::new(program, &params.macro2, synth_client.clone()),
+      macro3: Param::new(program, &params.macro3, synth_client.clone()),
+      macro4: Param::new(program, &params.macro4, synth_client),
+    }
+  }
+
+  pub fn for_each_param(&mut self, apply: &mut impl FnMut(&mut Param)) {
+    apply(&mut self.macro1);
+    apply(&mut self.macro2);
+    apply(&mut self.macro3);
+    apply(&mut self.macro4);
+  }
+}
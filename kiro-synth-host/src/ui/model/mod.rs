@@ -2,21 +2,25 @@ mod dca;
 mod eg;
 mod filter;
 mod lfo;
+mod macros;
 pub mod modulations;
 mod oscillators;
 mod param;
+mod snapshots;
 mod synth;
 
 use druid::Lens;
 
 pub use dca::Dca;
-pub use eg::{EgFromSynth, EnvGen};
+pub use eg::{EgFromSynth, EnvGen, PitchEg};
 pub use filter::{Filter, FilterFromSynth};
 pub use lfo::{Lfo, LfoFromSynth};
+pub use macros::Macros;
 pub use modulations::Modulations;
 pub use oscillators::{Osc, OscFromSynth};
-pub use param::{KnobDataFromParam, Param};
-pub use synth::{AudioLevel, Synth};
+pub use param::{KnobDataFromParam, Param, UnitFormat};
+pub use snapshots::SnapshotSlot;
+pub use synth::{AudioLevel, PresetState, Synth};
 
 pub struct ZeroIndex;
 
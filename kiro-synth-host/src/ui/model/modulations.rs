@@ -8,9 +8,10 @@ use druid::widget::ListIter;
 use druid::{Data, Lens};
 
 use kiro_synth_core::float::Float;
+use kiro_synth_engine::program::modulations::Modulation as ProgramModulation;
 use kiro_synth_engine::program::{ParamRef, Program, SourceRef};
+use kiro_synth_program::kiro::KiroModule;
 
-use crate::synth::program::kiro::KiroModule;
 use crate::synth::SynthClientMutex;
 use crate::ui::model::Param;
 
@@ -79,6 +80,11 @@ pub struct Modulation {
 
   pub amount: f64,
 
+  /// Whether the source is an LFO, the only sources that can be sampled with a phase offset
+  pub lfo: bool,
+  /// The phase offset in cycles at which the LFO is sampled
+  pub phase: f64,
+
   #[data(ignore)]
   #[derivative(PartialEq = "ignore")]
   pub synth_client: SynthClientMutex<f32>,
@@ -99,9 +105,36 @@ pub struct InternalModulation {
   pub max: f64,
   pub step: f64,
   pub amount: f64,
+
+  pub lfo: bool,
+  pub phase: f64,
 }
 
 impl InternalModulation {
+  /// A modulation of the program, taking the names and the range of values from its source and param
+  pub fn from_program<'a, F: Float>(
+    program: &Program<'a, F>,
+    param_ref: ParamRef,
+    modulation: &ProgramModulation<F>,
+  ) -> Option<Self> {
+    let source_ref = modulation.source_ref;
+    let source = program.get_source(source_ref)?;
+    let (_, param) = program.get_param(param_ref)?;
+    Some(InternalModulation {
+      source_ref,
+      source_name: source.id.to_string(),
+      param_ref,
+      param_name: param.id.to_string(),
+      origin: param.values.origin.to_f64().unwrap(),
+      min: param.values.min.to_f64().unwrap(),
+      max: param.values.max.to_f64().unwrap(),
+      step: param.values.resolution.to_f64().unwrap(),
+      amount: modulation.amount.to_f64().unwrap(),
+      lfo: source.lfo.is_some(),
+      phase: modulation.phase.to_f64().unwrap(),
+    })
+  }
+
   pub fn as_modulation(
     &self,
     index: usize,
@@ -118,6 +151,8 @@ impl InternalModulation {
       max: self.max,
       step: self.step,
       amount: self.amount,
+      lfo: self.lfo,
+      phase: self.phase,
       synth_client,
     }
   }
@@ -143,6 +178,7 @@ pub struct Source {
   pub name: String,
   #[data(same_fn = "PartialEq::eq")]
   pub reference: SourceRef,
+  pub lfo: bool,
 }
 
 impl NamedReference for Source {
@@ -191,25 +227,17 @@ impl Modulations {
       .map(|(index, source)| Source {
         name: source.id.to_string(),
         reference: SourceRef::new(index),
+        lfo: source.lfo.is_some(),
       })
       .collect();
 
-    for (index, param) in program.get_params().iter().enumerate() {
+    for index in 0..program.get_params().len() {
       let param_ref = ParamRef::new(index); // TODO param_ref should come from the program.get_params() call
       params.push_back(Param::new(program, param_ref, synth_client.clone()));
       for param_modulation in program.get_param_modulations(param_ref) {
-        if let Some(source) = program.get_source(param_modulation.source_ref) {
-          let modulation = InternalModulation {
-            source_ref: param_modulation.source_ref,
-            source_name: source.id.to_string(),
-            param_ref,
-            param_name: param.id.to_string(),
-            origin: param.values.origin.to_f64().unwrap(),
-            min: param.values.min.to_f64().unwrap(),
-            max: param.values.max.to_f64().unwrap(),
-            step: param.values.resolution.to_f64().unwrap(),
-            amount: param_modulation.amount.to_f64().unwrap(),
-          };
+        if let Some(modulation) =
+          InternalModulation::from_program(program, param_ref, param_modulation)
+        {
           modulations.push_back(modulation)
         }
       }
@@ -244,6 +272,19 @@ impl Modulations {
       .collect()
   }
 
+  pub fn get_sources_by_param(&self) -> HashMap<usize, Vector<usize>> {
+    let mut sources = HashMap::<usize, Vector<usize>>::new();
+    for modulation in self.modulations.iter() {
+      let param_index: usize = modulation.param_ref.into();
+      let source_index: usize = modulation.source_ref.into();
+      let param_sources = sources.entry(param_index).or_default();
+      if !param_sources.contains(&source_index) {
+        param_sources.push_back(source_index);
+      }
+    }
+    sources
+  }
+
   pub fn get_total_amounts_for_param(&self, param_ref: ParamRef) -> f64 {
     self
       .modulations
@@ -388,10 +429,8 @@ impl Modulations {
         modulation.amount = config_amount;
       }
       None => {
-        let source_name = self
-          .get_source(source_ref)
-          .map(|source| source.name.clone())
-          .unwrap();
+        let source = self.get_source(source_ref).unwrap();
+        let (source_name, lfo) = (source.name.clone(), source.lfo);
 
         let param = self.get_param(param_ref).unwrap();
 
@@ -405,6 +444,8 @@ impl Modulations {
           min: param.min,
           max: param.max,
           step: param.step,
+          lfo,
+          phase: 0.0,
         };
 
         self.add_modulation(modulation);
@@ -427,6 +468,44 @@ impl Modulations {
       .send_modulation_delete(source_ref, param_ref)
       .unwrap();
   }
+
+  /// Replace all the modulations, only deleting from the synth the ones that are not kept
+  pub fn replace_modulations(&mut self, modulations: Vector<InternalModulation>) {
+    for modulation in self.modulations.iter() {
+      let kept = modulations
+        .iter()
+        .any(|m| m.source_ref == modulation.source_ref && m.param_ref == modulation.param_ref);
+      if !kept {
+        self
+          .synth_client
+          .send_modulation_delete(modulation.source_ref, modulation.param_ref)
+          .unwrap();
+      }
+    }
+
+    for modulation in modulations.iter() {
+      self
+        .synth_client
+        .send_modulation_update(
+          modulation.source_ref,
+          modulation.param_ref,
+          modulation.amount as f32,
+        )
+        .unwrap();
+      if modulation.lfo {
+        self
+          .synth_client
+          .send_modulation_phase(
+            modulation.source_ref,
+            modulation.param_ref,
+            modulation.phase as f32,
+          )
+          .unwrap();
+      }
+    }
+
+    self.modulations = modulations;
+  }
 }
 
 impl ListIter<Group> for Modulations {
@@ -469,7 +548,9 @@ impl ListIter<Group> for Modulations {
     groups.iter_mut().enumerate().for_each(|(i, group)| {
       cb(group, i);
       for modulation in group.modulations.iter() {
-        self.modulations[modulation.index].amount = modulation.amount;
+        let internal_modulation = &mut self.modulations[modulation.index];
+        internal_modulation.amount = modulation.amount;
+        internal_modulation.phase = modulation.phase;
       }
     });
   }
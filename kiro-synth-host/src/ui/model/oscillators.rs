@@ -2,10 +2,11 @@ use druid::{Data, Lens};
 
 use kiro_synth_core::float::Float;
 use kiro_synth_engine::program::Program;
+use kiro_synth_program::params::OscParams;
 
-use crate::synth::program::params::OscParams;
+use crate::synth::tuning::Detune;
 use crate::synth::SynthClientMutex;
-use crate::ui::model::{Param, Synth};
+use crate::ui::model::{Param, Synth, UnitFormat};
 
 pub struct OscFromSynth;
 
@@ -26,6 +27,18 @@ pub struct Osc {
   pub semitones: Param,
   pub cents: Param,
   pub amplitude: Param,
+  pub amplitude_db: Param,
+  pub level_db: Param,
+  pub fm_mode: Param,
+  pub sync_mode: Param,
+  pub lfo_sync: Param,
+  pub key_low: Param,
+  pub key_high: Param,
+  pub fixed_freq: Param,
+  pub freq_hz: Param,
+
+  /// The total detune in cents being entered, to distribute across the octaves, semitones and cents
+  pub detune_entry: String,
 }
 
 impl Osc {
@@ -39,8 +52,42 @@ impl Osc {
       octaves: Param::new(program, &params.octaves, synth_client.clone()).with_origin(0.0),
       semitones: Param::new(program, &params.semitones, synth_client.clone()).with_origin(0.0),
       cents: Param::new(program, &params.cents, synth_client.clone()).with_origin(0.0),
-      amplitude: Param::new(program, &params.amplitude, synth_client),
+      amplitude: Param::new(program, &params.amplitude, synth_client.clone()),
+      amplitude_db: Param::new(program, &params.amplitude_db, synth_client.clone())
+        .with_unit(UnitFormat::Decibels),
+      level_db: Param::new(program, &params.level_db, synth_client.clone()),
+      fm_mode: Param::new(program, &params.fm_mode, synth_client.clone()),
+      sync_mode: Param::new(program, &params.sync_mode, synth_client.clone()),
+      lfo_sync: Param::new(program, &params.lfo_sync, synth_client.clone()),
+      key_low: Param::new(program, &params.key_low, synth_client.clone()),
+      key_high: Param::new(program, &params.key_high, synth_client.clone()),
+      fixed_freq: Param::new(program, &params.fixed_freq, synth_client.clone()),
+      freq_hz: Param::new(program, &params.freq_hz, synth_client).with_unit(UnitFormat::Frequency),
+      detune_entry: String::new(),
+    }
+  }
+
+  pub fn total_detune(&self) -> Detune {
+    Detune::new(
+      self.octaves.value.round() as i32,
+      self.semitones.value.round() as i32,
+      self.cents.value.round() as i32,
+    )
+  }
+
+  pub fn set_total_detune(&mut self, total_cents: f64) {
+    let detune = Detune::from_cents(total_cents);
+    self.octaves.value = detune.octaves as f64;
+    self.semitones.value = detune.semitones as f64;
+    self.cents.value = detune.cents as f64;
+  }
+
+  /// Apply the detune entered if it is a valid number of cents, and leave the entry empty
+  pub fn apply_detune_entry(&mut self) {
+    if let Ok(total_cents) = self.detune_entry.trim().parse::<f64>() {
+      self.set_total_detune(total_cents);
     }
+    self.detune_entry.clear();
   }
 
   pub fn for_each_modulated_param(&mut self, apply: &impl Fn(&mut Param)) {
@@ -49,5 +96,23 @@ impl Osc {
     apply(&mut self.semitones);
     apply(&mut self.cents);
     apply(&mut self.amplitude);
+    apply(&mut self.amplitude_db);
+  }
+
+  pub fn for_each_param(&mut self, apply: &mut impl FnMut(&mut Param)) {
+    apply(&mut self.shape);
+    apply(&mut self.octaves);
+    apply(&mut self.semitones);
+    apply(&mut self.cents);
+    apply(&mut self.amplitude);
+    apply(&mut self.amplitude_db);
+    apply(&mut self.level_db);
+    apply(&mut self.fm_mode);
+    apply(&mut self.sync_mode);
+    apply(&mut self.lfo_sync);
+    apply(&mut self.key_low);
+    apply(&mut self.key_high);
+    apply(&mut self.fixed_freq);
+    apply(&mut self.freq_hz);
   }
 }
@@ -1,7 +1,8 @@
+use druid::im::Vector;
 use druid::{Data, Lens};
 
 use kiro_synth_core::float::Float;
-use kiro_synth_engine::program::{Param as ProgParam, ParamRef, Program, SourceRef};
+use kiro_synth_engine::program::{Param as ProgParam, ParamRef, ParamScale, Program, SourceRef};
 
 use crate::synth::SynthClientMutex;
 use crate::ui::widgets::knob::{KnobData, KnobModulationData};
@@ -28,6 +29,7 @@ impl KnobDataFromParam {
       data.clone(),
     )
     .with_modulation(knob_modulation)
+    .with_scale(data.scale)
   }
 }
 
@@ -61,6 +63,9 @@ pub struct ParamModulation {
 
   /// Total amount of modulation applied to the parameter from all the connected sources
   pub total_amount: f64,
+
+  /// The indices of the sources connected to the parameter, to show them next to the knob
+  pub sources: Vector<usize>,
 }
 
 impl Default for ParamModulation {
@@ -70,6 +75,35 @@ impl Default for ParamModulation {
       config_source: None,
       config_amount: 0.0,
       total_amount: 0.0,
+      sources: Vector::new(),
+    }
+  }
+}
+
+/// How the value of a param is displayed, scaling the unit to keep it short
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitFormat {
+  /// Only the number
+  None,
+  /// In Hz, or kHz from 1000 Hz
+  Frequency,
+  /// In seconds, or milliseconds below 1 s
+  Time,
+  Decibels,
+  Semitones,
+}
+
+impl UnitFormat {
+  /// Format a value with the given number of decimals for the base unit
+  pub fn format(self, value: f64, precision: usize) -> String {
+    match self {
+      UnitFormat::None => format!("{:.*}", precision, value),
+      UnitFormat::Frequency if value.abs() >= 1000.0 => format!("{:.2} kHz", value / 1000.0),
+      UnitFormat::Frequency => format!("{:.*} Hz", precision, value),
+      UnitFormat::Time if value.abs() < 1.0 => format!("{:.0} ms", value * 1000.0),
+      UnitFormat::Time => format!("{:.*} s", precision, value),
+      UnitFormat::Decibels => format!("{:.*} dB", precision, value),
+      UnitFormat::Semitones => format!("{:.*} st", precision, value),
     }
   }
 }
@@ -87,6 +121,12 @@ pub struct Param {
   pub step: f64,
   pub value: f64,
 
+  #[data(same_fn = "PartialEq::eq")]
+  pub scale: ParamScale,
+
+  #[data(same_fn = "PartialEq::eq")]
+  pub unit: UnitFormat,
+
   pub modulation: ParamModulation,
 
   #[data(ignore)]
@@ -115,7 +155,9 @@ impl Param {
       min: param.values.min.to_f64().unwrap(),
       max: param.values.max.to_f64().unwrap(),
       step: param.values.resolution.to_f64().unwrap(),
-      value: param.values.initial_value.to_f64().unwrap(),
+      value: param.value.get().to_f64().unwrap(),
+      scale: param.values.scale,
+      unit: UnitFormat::None,
       modulation: ParamModulation::default(),
       synth_client,
     }
@@ -125,4 +167,24 @@ impl Param {
     self.origin = origin;
     self
   }
+
+  pub fn with_unit(mut self, unit: UnitFormat) -> Self {
+    self.unit = unit;
+    self
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unit_format_with_engineering_units() {
+    assert_eq!(UnitFormat::Frequency.format(1500.0, 0), "1.50 kHz");
+    assert_eq!(UnitFormat::Frequency.format(440.0, 0), "440 Hz");
+    assert_eq!(UnitFormat::Time.format(0.25, 3), "250 ms");
+    assert_eq!(UnitFormat::Time.format(1.5, 2), "1.50 s");
+    assert_eq!(UnitFormat::Decibels.format(-6.0, 1), "-6.0 dB");
+    assert_eq!(UnitFormat::None.format(0.5, 2), "0.50");
+  }
 }
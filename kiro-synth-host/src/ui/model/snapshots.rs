@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use druid::im::Vector;
+use druid::{Data, Lens};
+
+use kiro_synth_core::float::Float;
+use kiro_synth_engine::program::{ParamRef, Program};
+
+use crate::ui::model::modulations::InternalModulation;
+
+/// The values of the params and the modulations of the patch at some point
+#[derive(Debug, Clone)]
+pub struct SynthState {
+  pub params: Vec<(ParamRef, f64)>,
+  pub modulations: Vector<InternalModulation>,
+}
+
+impl SynthState {
+  /// The values of all the params of a program and its modulations
+  pub fn from_program<'a, F: Float>(program: &Program<'a, F>) -> Self {
+    let mut params = Vec::new();
+    let mut modulations = Vector::new();
+    for (index, param) in program.get_params().iter().enumerate() {
+      let param_ref = ParamRef::new(index);
+      params.push((param_ref, param.value.get().to_f64().unwrap()));
+      for modulation in program.get_param_modulations(param_ref) {
+        if let Some(modulation) = InternalModulation::from_program(program, param_ref, modulation) {
+          modulations.push_back(modulation);
+        }
+      }
+    }
+    SynthState {
+      params,
+      modulations,
+    }
+  }
+
+  pub fn param_value(&self, param_ref: ParamRef) -> Option<f64> {
+    self
+      .params
+      .iter()
+      .find(|(state_param_ref, _)| *state_param_ref == param_ref)
+      .map(|(_, value)| *value)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub enum SnapshotSlot {
+  A,
+  B,
+}
+
+impl SnapshotSlot {
+  pub fn other(self) -> Self {
+    match self {
+      SnapshotSlot::A => SnapshotSlot::B,
+      SnapshotSlot::B => SnapshotSlot::A,
+    }
+  }
+}
+
+/// Two snapshots of the patch to compare them
+#[derive(Debug, Clone, Default, Data, Lens)]
+pub struct Snapshots {
+  pub a: Option<Arc<SynthState>>,
+  pub b: Option<Arc<SynthState>>,
+
+  /// The slot stored or recalled the last time, which the changes of the patch belong to
+  pub active: Option<SnapshotSlot>,
+}
+
+impl Snapshots {
+  pub fn get(&self, slot: SnapshotSlot) -> Option<Arc<SynthState>> {
+    match slot {
+      SnapshotSlot::A => self.a.clone(),
+      SnapshotSlot::B => self.b.clone(),
+    }
+  }
+
+  pub fn store(&mut self, slot: SnapshotSlot, state: SynthState) {
+    let state = Some(Arc::new(state));
+    match slot {
+      SnapshotSlot::A => self.a = state,
+      SnapshotSlot::B => self.b = state,
+    }
+    self.active = Some(slot);
+  }
+
+  /// The slot to switch to when toggling, if it has been stored
+  pub fn toggle_slot(&self) -> Option<SnapshotSlot> {
+    let slot = self
+      .active
+      .map(SnapshotSlot::other)
+      .unwrap_or(SnapshotSlot::A);
+    self.get(slot).map(|_| slot)
+  }
+}
@@ -3,16 +3,23 @@ use druid::{Data, Lens};
 
 use kiro_synth_core::float::Float;
 use kiro_synth_engine::program::{ParamRef, Program, SourceRef};
+use kiro_synth_program::kiro::KiroModule;
+use kiro_synth_program::preset::Preset;
 
-use crate::synth::program::kiro::KiroModule;
 use crate::synth::{SynthAudioLevels, SynthClientMutex};
 
-use crate::ui::model::{Dca, EnvGen, Filter, Lfo, Modulations, Osc, Param};
+use crate::ui::model::snapshots::{SnapshotSlot, Snapshots, SynthState};
+use crate::ui::model::{Dca, EnvGen, Filter, Lfo, Macros, Modulations, Osc, Param, PitchEg};
+use crate::ui::theme::ThemeKind;
 
 #[derive(Debug, Clone, Data)]
 pub struct AudioLevel {
+  /// The highest recent peak in dB, held for a while before decaying
   pub peak: f64,
+  /// The peak level in dB with a fast attack and a slow release
   pub level: f64,
+  /// The RMS level in dB
+  pub rms: f64,
 }
 
 impl Default for AudioLevel {
@@ -20,6 +27,7 @@ impl Default for AudioLevel {
     AudioLevel {
       peak: f64::NEG_INFINITY,
       level: f64::NEG_INFINITY,
+      rms: f64::NEG_INFINITY,
     }
   }
 }
@@ -29,6 +37,27 @@ impl AudioLevel {
     AudioLevel {
       peak: level.peak as f64,
       level: level.level as f64,
+      rms: level.rms as f64,
+    }
+  }
+}
+
+/// A preset resolved against the program, for the UI to load it when it is selected from another thread
+#[derive(Debug, Clone)]
+pub struct PresetState {
+  pub state: SynthState,
+  /// The slots of the insert effects in the order they process the output
+  pub insert_order: Vec<u8>,
+}
+
+impl PresetState {
+  /// Apply the preset to a copy of the program as it was started, the current values being the ones of the UI
+  pub fn new<'a, F: Float>(preset: &Preset, program: &Program<'a, F>) -> Self {
+    let mut program = program.clone();
+    preset.apply(&mut program);
+    PresetState {
+      state: SynthState::from_program(&program),
+      insert_order: program.get_insert_order().to_vec(),
     }
   }
 }
@@ -39,6 +68,8 @@ pub struct Synth {
 
   pub left_level: AudioLevel,
   pub right_level: AudioLevel,
+  /// The level of the wet signal of the send effect
+  pub send_fx_level: AudioLevel,
 
   pub osc: Vector<Osc>,
   pub osc_index: usize,
@@ -46,6 +77,8 @@ pub struct Synth {
   pub mod_index: usize,
 
   pub eg: Vector<EnvGen>,
+  /// The pitch envelope, in the modulators tab after the LFOs
+  pub pitch_eg: PitchEg,
 
   pub lfo: Vector<Lfo>,
 
@@ -54,8 +87,14 @@ pub struct Synth {
 
   pub dca: Dca,
 
+  pub macros: Macros,
+
   pub modulations: Modulations,
 
+  pub snapshots: Snapshots,
+
+  pub theme: ThemeKind,
+
   #[data(ignore)]
   pub synth_client: SynthClientMutex<f32>,
 }
@@ -73,6 +112,7 @@ impl Synth {
 
       left_level: AudioLevel::default(),
       right_level: AudioLevel::default(),
+      send_fx_level: AudioLevel::default(),
 
       osc: vector![
         Osc::new(program, &params.osc1, synth_client.clone()),
@@ -84,7 +124,11 @@ impl Synth {
 
       mod_index: 0,
 
-      eg: vector![EnvGen::new(program, &params.eg1, synth_client.clone()),],
+      eg: vector![
+        EnvGen::new(program, &params.amp_eg, synth_client.clone()),
+        EnvGen::new(program, &params.eg1, synth_client.clone()),
+      ],
+      pitch_eg: PitchEg::new(program, &params.pitch_eg, synth_client.clone()),
 
       lfo: vector![
         Lfo::new(program, &params.lfo1, synth_client.clone()),
@@ -96,8 +140,14 @@ impl Synth {
 
       dca: Dca::new(program, &params.dca, synth_client.clone()),
 
+      macros: Macros::new(program, &params.macros, synth_client.clone()),
+
       modulations: Modulations::new(program, module, synth_client.clone()),
 
+      snapshots: Snapshots::default(),
+
+      theme: ThemeKind::Dark,
+
       synth_client,
     }
     .with_init_modulations_config()
@@ -106,6 +156,21 @@ impl Synth {
 
 impl<'a> Synth {
   pub fn with_init_modulations_config(mut self) -> Self {
+    self.update_total_amounts();
+    self.update_modulation_sources();
+    self
+  }
+
+  fn update_modulation_sources(&mut self) {
+    let sources = self.modulations.get_sources_by_param();
+
+    self.for_each_modulated_param(move |param| {
+      let key: usize = param.param_ref.into();
+      param.modulation.sources = sources.get(&key).cloned().unwrap_or_default();
+    });
+  }
+
+  fn update_total_amounts(&mut self) {
     let total_amounts = self.modulations.get_total_amounts_by_param();
 
     self.for_each_modulated_param(move |param| {
@@ -114,8 +179,6 @@ impl<'a> Synth {
         .cloned()
         .unwrap_or(0.0);
     });
-
-    self
   }
 
   pub fn start_modulations_config(&mut self, source_ref: SourceRef) {
@@ -156,6 +219,7 @@ impl<'a> Synth {
         param.modulation.total_amount = total_amount;
       }
     });
+    self.update_modulation_sources();
   }
 
   pub fn stop_modulations_config(&mut self, source_ref: SourceRef) {
@@ -174,6 +238,140 @@ impl<'a> Synth {
         param.modulation.total_amount = total_amount;
       }
     });
+    self.update_modulation_sources();
+  }
+
+  pub fn toggle_theme(&mut self) {
+    self.theme = self.theme.toggled();
+  }
+
+  pub fn select_osc(&mut self, index: usize) {
+    if index < self.osc.len() {
+      self.osc_index = index;
+    }
+  }
+
+  /// Select the next or the previous modulator tab, wrapping around
+  pub fn cycle_mod_index(&mut self, forward: bool) {
+    let num_modulators = self.eg.len() + self.lfo.len() + 1;
+    self.mod_index = if forward {
+      (self.mod_index + 1) % num_modulators
+    } else {
+      (self.mod_index + num_modulators - 1) % num_modulators
+    };
+  }
+
+  pub fn undo_param_edit(&mut self) {
+    if let Some(edit) = self.synth_client.undo_param_edit().unwrap() {
+      self.set_param_value(edit.param_ref, edit.old_value as f64);
+    }
+  }
+
+  pub fn redo_param_edit(&mut self) {
+    if let Some(edit) = self.synth_client.redo_param_edit().unwrap() {
+      self.set_param_value(edit.param_ref, edit.new_value as f64);
+    }
+  }
+
+  fn set_param_value(&mut self, param_ref: ParamRef, value: f64) {
+    self.for_each_param(|param| {
+      if param.param_ref == param_ref {
+        param.value = value;
+      }
+    });
+  }
+
+  pub fn store_snapshot(&mut self, slot: SnapshotSlot) {
+    let state = self.capture_state();
+    self.snapshots.store(slot, state);
+  }
+
+  pub fn recall_snapshot(&mut self, slot: SnapshotSlot) {
+    if let Some(state) = self.snapshots.get(slot) {
+      self.apply_state(&state);
+      self.snapshots.active = Some(slot);
+    }
+  }
+
+  /// Switch to the other snapshot, keeping the changes made since the last switch in the current one
+  pub fn toggle_snapshot(&mut self) {
+    if let Some(slot) = self.snapshots.toggle_slot() {
+      if let Some(active) = self.snapshots.active {
+        self.store_snapshot(active);
+      }
+      self.recall_snapshot(slot);
+    }
+  }
+
+  pub fn copy_snapshot_a_to_b(&mut self) {
+    if let Some(state) = self.snapshots.a.clone() {
+      self.snapshots.b = Some(state);
+      if self.snapshots.active == Some(SnapshotSlot::B) {
+        self.recall_snapshot(SnapshotSlot::B);
+      }
+    }
+  }
+
+  fn capture_state(&mut self) -> SynthState {
+    let mut params = Vec::new();
+    self.for_each_param(|param| params.push((param.param_ref, param.value)));
+    SynthState {
+      params,
+      modulations: self.modulations.modulations.clone(),
+    }
+  }
+
+  /// Only the values that changed are sent to the synth, and the voices are not released,
+  /// so the notes being played continue with the recalled patch
+  fn apply_state(&mut self, state: &SynthState) {
+    let synth_client = self.synth_client.clone();
+    self.for_each_param(|param| {
+      if let Some(value) = state.param_value(param.param_ref) {
+        if value != param.value {
+          param.value = value;
+          synth_client
+            .send_param_value(param.param_ref, value as f32)
+            .unwrap();
+        }
+      }
+    });
+
+    self.apply_modulations(state);
+  }
+
+  /// Load a preset selected out of the UI, ie. with a MIDI program change. As with the snapshots
+  /// the voices are not released, but all the params are sent, including the ones without knobs.
+  /// The values are sent without ramps, as there are too many of them to ramp at once.
+  pub fn load_preset(&mut self, preset: &PresetState) {
+    let state = &preset.state;
+    for (param_ref, value) in state.params.iter() {
+      self
+        .synth_client
+        .send_param_value_now(*param_ref, *value as f32)
+        .unwrap();
+    }
+    self.for_each_param(|param| {
+      if let Some(value) = state.param_value(param.param_ref) {
+        param.value = value;
+      }
+    });
+
+    self.apply_modulations(state);
+    self
+      .synth_client
+      .send_insert_order(&preset.insert_order)
+      .unwrap();
+  }
+
+  fn apply_modulations(&mut self, state: &SynthState) {
+    self
+      .modulations
+      .replace_modulations(state.modulations.clone());
+    match self.modulations.config_source {
+      Some(source_ref) => self.start_modulations_config(source_ref),
+      None => self.update_total_amounts(),
+    }
+    self.update_modulation_sources();
   }
 
   fn for_each_modulated_param(&mut self, apply: impl Fn(&mut Param)) {
@@ -183,6 +381,7 @@ impl<'a> Synth {
     for eg in self.eg.iter_mut() {
       eg.for_each_modulated_param(&apply);
     }
+    self.pitch_eg.for_each_modulated_param(&apply);
     for lfo in self.lfo.iter_mut() {
       lfo.for_each_modulated_param(&apply);
     }
@@ -192,11 +391,47 @@ impl<'a> Synth {
     self.dca.for_each_modulated_param(&apply);
   }
 
+  fn for_each_param(&mut self, mut apply: impl FnMut(&mut Param)) {
+    for osc in self.osc.iter_mut() {
+      osc.for_each_param(&mut apply);
+    }
+    for eg in self.eg.iter_mut() {
+      eg.for_each_param(&mut apply);
+    }
+    self.pitch_eg.for_each_param(&mut apply);
+    for lfo in self.lfo.iter_mut() {
+      lfo.for_each_param(&mut apply);
+    }
+    for filter in self.filter.iter_mut() {
+      filter.for_each_param(&mut apply);
+    }
+    self.dca.for_each_param(&mut apply);
+    self.macros.for_each_param(&mut apply);
+  }
+
   pub fn update_feedback(&mut self) {
+    // the knobs follow the values from remote controllers, such as OSC
+    let remote_values = self
+      .synth_client
+      .take_remote_param_values()
+      .unwrap_or_default();
+    if !remote_values.is_empty() {
+      self.for_each_param(|param| {
+        let last_value = remote_values
+          .iter()
+          .rev()
+          .find(|(param_ref, _)| *param_ref == param.param_ref);
+        if let Some((_, value)) = last_value {
+          param.value = *value as f64;
+        }
+      });
+    }
+
     if let Some(feedback) = self.synth_client.get_feedback().unwrap_or(None) {
       self.active_voices = feedback.num_active_voices;
       self.left_level = AudioLevel::new(&feedback.left_levels);
       self.right_level = AudioLevel::new(&feedback.right_levels);
+      self.send_fx_level = AudioLevel::new(&feedback.send_fx_levels);
       self.for_each_modulated_param(|param| {
         let param_index: usize = param.param_ref.into();
         let modulation = feedback.modulations[param_index];
@@ -205,3 +440,108 @@ impl<'a> Synth {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::{Arc, Mutex};
+
+  use ringbuf::RingBuffer;
+
+  use kiro_synth_engine::event::Message;
+  use kiro_synth_engine::globals::SynthGlobals;
+  use kiro_synth_engine::program::ProgramBuilder;
+  use kiro_synth_program::preset::{PresetBank, PresetModulation, PRESET_VERSION};
+  use kiro_synth_program::values;
+
+  use crate::synth::{ParamRamp, SynthClient};
+
+  #[test]
+  fn preset_state_from_the_program() {
+    let mut builder = ProgramBuilder::new();
+    let source = builder.signal();
+    let lfo = builder.source("lfo1", source);
+    let pan = builder.param("dca-pan", values::pan());
+    let rate = builder.param("lfo1-rate", values::lfo_rate());
+    builder.modulation(&rate, lfo, 0.25);
+    let program: Program<f32> = builder.build();
+
+    let preset = Preset {
+      version: PRESET_VERSION,
+      name: String::new(),
+      params: vec![("dca-pan".to_string(), -0.5)].into_iter().collect(),
+      modulations: Some(vec![PresetModulation {
+        source: "lfo1".to_string(),
+        param: "dca-pan".to_string(),
+        amount: 0.5,
+        phase: 0.0,
+      }]),
+      insert_order: vec![1, 0],
+    };
+    let preset_state = PresetState::new(&preset, &program);
+
+    let state = &preset_state.state;
+    assert_eq!(state.param_value(pan.reference), Some(-0.5));
+    assert_eq!(state.param_value(rate.reference), Some(1.0));
+    assert_eq!(state.modulations.len(), 1);
+    let modulation = &state.modulations[0];
+    assert_eq!(modulation.source_ref, lfo);
+    assert_eq!(modulation.param_ref, pan.reference);
+    assert_eq!(modulation.amount, 0.5);
+    assert_eq!(preset_state.insert_order, vec![1, 0]);
+
+    // the program given is only a template
+    assert_eq!(program.get_param_signal(pan.reference).get(), 0.0);
+  }
+
+  #[test]
+  fn program_change_loads_the_preset_without_ramps() {
+    let (program, module) = KiroModule::new_program::<f32>(4, 4);
+    let (events_producer, mut events) = RingBuffer::new(1024).split();
+    let (_, feedback) = RingBuffer::new(1).split();
+    let mut synth_client = SynthClient::new(SynthGlobals::new(), events_producer, feedback);
+    synth_client.set_param_ramp(Some(ParamRamp::default()));
+    synth_client.init_param_values(&program);
+    let synth_client = SynthClientMutex::new(Arc::new(Mutex::new(synth_client)));
+    let mut synth = Synth::new(&program, &module, synth_client);
+
+    let mut bank = PresetBank::new();
+    for freq in [500.0, 2000.0].iter() {
+      bank.push(Preset {
+        version: PRESET_VERSION,
+        name: String::new(),
+        params: vec![("filt1-freq".to_string(), *freq)]
+          .into_iter()
+          .collect(),
+        modulations: None,
+        insert_order: vec![1, 0],
+      });
+    }
+
+    // as the MIDI handler resolves the preset of a program change for the UI to load it
+    let program_change = 1;
+    let preset = PresetState::new(bank.get(program_change).unwrap(), &program);
+    synth.load_preset(&preset);
+
+    let mut param_values = 0;
+    let mut insert_order = None;
+    let freq_ref = module.params.filter1.freq.reference;
+    while let Some(event) = events.pop() {
+      assert_eq!(event.timestamp, 0);
+      match event.message {
+        Message::ParamValue { param_ref, value } => {
+          param_values += 1;
+          if param_ref == freq_ref {
+            assert_eq!(value, 2000.0);
+          }
+        }
+        Message::InsertOrder { order } => insert_order = Some(order.to_vec()),
+        _ => {}
+      }
+    }
+    assert_eq!(param_values, program.get_params().len());
+    assert_eq!(insert_order, Some(vec![1, 0]));
+    assert_eq!(synth.filter[0].freq.value, 2000.0);
+  }
+}
@@ -0,0 +1,37 @@
+//! The scale factor of the UI, applied to the sizes, paddings and fonts of the views on top of
+//! the scale of the display that druid already takes into account.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use druid::Insets;
+
+/// The bits of the scale, 1.0 by default
+static UI_SCALE: AtomicU64 = AtomicU64::new(0x3ff0_0000_0000_0000);
+
+pub const MIN_UI_SCALE: f64 = 0.5;
+pub const MAX_UI_SCALE: f64 = 4.0;
+
+/// Set the scale before building the views, as their sizes are fixed when they are built
+pub fn set_ui_scale(scale: f64) {
+  let scale = scale.max(MIN_UI_SCALE).min(MAX_UI_SCALE);
+  UI_SCALE.store(scale.to_bits(), Ordering::Relaxed);
+}
+
+pub fn ui_scale() -> f64 {
+  f64::from_bits(UI_SCALE.load(Ordering::Relaxed))
+}
+
+/// A size designed for the default scale
+pub fn scaled(size: f64) -> f64 {
+  size * ui_scale()
+}
+
+pub fn scaled_insets(insets: impl Into<Insets>) -> Insets {
+  let insets = insets.into();
+  Insets::new(
+    scaled(insets.x0),
+    scaled(insets.y0),
+    scaled(insets.x1),
+    scaled(insets.y1),
+  )
+}
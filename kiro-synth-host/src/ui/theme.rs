@@ -0,0 +1,146 @@
+use druid::widget::Painter;
+use druid::{theme, Color, Data, Env, Key, PaintCtx, RenderContext};
+
+use crate::ui::widgets::knob::theme as knob_theme;
+use crate::ui::widgets::tab::theme as tab_theme;
+use crate::ui::{GREY_214, GREY_46, GREY_54, GREY_65, GREY_74, GREY_83};
+
+pub const PANEL_BACKGROUND_COLOR: Key<Color> = Key::new("kiro.panel-background-color");
+pub const PANEL_BORDER_COLOR: Key<Color> = Key::new("kiro.panel-border-color");
+pub const BUTTON_BACKGROUND_COLOR: Key<Color> = Key::new("kiro.button-background-color");
+
+/// The colors identifying the modulation sources, repeated when there are more sources
+const MODULATION_SOURCE_COLORS: [Color; 8] = [
+  Color::rgb8(236, 138, 56),
+  Color::rgb8(86, 180, 233),
+  Color::rgb8(0, 158, 115),
+  Color::rgb8(240, 228, 66),
+  Color::rgb8(204, 121, 167),
+  Color::rgb8(213, 94, 0),
+  Color::rgb8(0, 114, 178),
+  Color::rgb8(170, 170, 170),
+];
+
+pub fn modulation_source_color(source_index: usize) -> Color {
+  MODULATION_SOURCE_COLORS[source_index % MODULATION_SOURCE_COLORS.len()].clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Data)]
+pub enum ThemeKind {
+  Dark,
+  Light,
+}
+
+impl ThemeKind {
+  pub fn toggled(self) -> Self {
+    match self {
+      ThemeKind::Dark => ThemeKind::Light,
+      ThemeKind::Light => ThemeKind::Dark,
+    }
+  }
+}
+
+/// The colors of the panels, tabs and knobs, which the views read from the environment
+#[derive(Debug, Clone)]
+pub struct Theme {
+  pub window_background: Color,
+  pub label: Color,
+  pub panel_background: Color,
+  pub panel_border: Color,
+  pub button_background: Color,
+  pub knob_value_background: Color,
+  pub knob_modulation_background: Color,
+  pub tab_selected: Color,
+  pub tab_unselected: Color,
+  pub tab_hover: Color,
+}
+
+impl Theme {
+  pub fn dark() -> Self {
+    Theme {
+      window_background: GREY_46,
+      label: GREY_214,
+      panel_background: GREY_65,
+      panel_border: GREY_83,
+      button_background: GREY_46,
+      knob_value_background: GREY_83,
+      knob_modulation_background: GREY_54,
+      tab_selected: GREY_83,
+      tab_unselected: GREY_65,
+      tab_hover: GREY_74,
+    }
+  }
+
+  pub fn light() -> Self {
+    Theme {
+      window_background: Color::grey8(214),
+      label: Color::grey8(32),
+      panel_background: Color::grey8(232),
+      panel_border: Color::grey8(176),
+      button_background: Color::grey8(200),
+      knob_value_background: Color::grey8(176),
+      knob_modulation_background: Color::grey8(200),
+      tab_selected: Color::grey8(176),
+      tab_unselected: Color::grey8(232),
+      tab_hover: Color::grey8(200),
+    }
+  }
+
+  pub fn from_kind(kind: ThemeKind) -> Self {
+    match kind {
+      ThemeKind::Dark => Self::dark(),
+      ThemeKind::Light => Self::light(),
+    }
+  }
+
+  pub fn apply(&self, env: &mut Env) {
+    env.set(
+      theme::WINDOW_BACKGROUND_COLOR,
+      self.window_background.clone(),
+    );
+    env.set(theme::LABEL_COLOR, self.label.clone());
+
+    env.set(PANEL_BACKGROUND_COLOR, self.panel_background.clone());
+    env.set(PANEL_BORDER_COLOR, self.panel_border.clone());
+    env.set(BUTTON_BACKGROUND_COLOR, self.button_background.clone());
+
+    env.set(
+      knob_theme::KNOB_VALUE_BG_COLOR,
+      self.knob_value_background.clone(),
+    );
+    env.set(
+      knob_theme::KNOB_MODULATION_VALUE_BG_COLOR,
+      self.knob_modulation_background.clone(),
+    );
+
+    env.set(
+      tab_theme::TAB_SELECTED_BORDER_COLOR,
+      self.tab_selected.clone(),
+    );
+    env.set(
+      tab_theme::TAB_UNSELECTED_BORDER_COLOR,
+      self.tab_unselected.clone(),
+    );
+    env.set(tab_theme::TAB_HOVER_BORDER_COLOR, self.tab_hover.clone());
+    env.set(
+      tab_theme::TAB_SELECTED_BACKGROUND_COLOR,
+      self.tab_selected.clone(),
+    );
+    env.set(
+      tab_theme::TAB_UNSELECTED_BACKGROUND_COLOR,
+      self.tab_unselected.clone(),
+    );
+    env.set(
+      tab_theme::TAB_HOVER_BACKGROUND_COLOR,
+      self.tab_hover.clone(),
+    );
+  }
+}
+
+/// A background with a color from the environment, so it follows the changes of theme
+pub fn themed_background<T: Data>(key: Key<Color>) -> Painter<T> {
+  Painter::new(move |ctx: &mut PaintCtx, _: &T, env: &Env| {
+    let bounds = ctx.size().to_rect();
+    ctx.fill(bounds, &env.get(&key));
+  })
+}
@@ -21,6 +21,8 @@ impl DcaView {
 
 fn build_dca_view() -> impl Widget<Dca> {
   Flex::row()
-    .with_child(build_knob_value("Amplitude", " dB").lens(Dca::amplitude))
-    .with_child(build_knob_value("Pan", "").lens(Dca::pan))
+    .with_child(build_knob_value("Amplitude").lens(Dca::amplitude))
+    .with_child(build_knob_value("Vel Floor").lens(Dca::velocity_floor))
+    .with_child(build_knob_value("Saturation").lens(Dca::saturation))
+    .with_child(build_knob_value("Pan").lens(Dca::pan))
 }
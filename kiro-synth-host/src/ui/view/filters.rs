@@ -2,7 +2,7 @@ use druid::widget::{Flex, WidgetExt};
 use druid::{Env, Widget};
 
 use crate::ui::model::{Filter, FilterFromSynth, Synth};
-use crate::ui::view::{build_knob_value, build_switcher, build_tabs};
+use crate::ui::view::{build_knob_enum, build_knob_value, build_switcher, build_tabs};
 
 pub struct FiltersView;
 
@@ -24,8 +24,21 @@ impl FiltersView {
 
 fn build_filter_view() -> impl Widget<Filter> {
   Flex::row()
-    .with_child(build_knob_value("Mode", "").lens(Filter::mode))
-    .with_child(build_knob_value("Cutoff", " Hz").lens(Filter::freq))
-    .with_child(build_knob_value("Res", "").lens(Filter::q))
+    .with_child(build_knob_value("Mode").lens(Filter::mode))
+    .with_child(build_knob_value("Cutoff").lens(Filter::freq))
+    .with_child(build_knob_value("Key Trk").lens(Filter::key_track))
+    .with_child(build_knob_value("Center").lens(Filter::key_track_center))
+    .with_child(build_knob_value("Res").lens(Filter::q))
+    .with_child(build_knob_value("Vel Res").lens(Filter::velocity_to_q))
+    .with_child(build_knob_enum("Res Comp", on_off_name).lens(Filter::q_compensation))
+    .with_child(build_knob_enum("Osc", on_off_name).lens(Filter::self_osc))
     .with_flex_spacer(1.0)
 }
+
+fn on_off_name(index: usize) -> String {
+  match index {
+    1 => "on",
+    _ => "off",
+  }
+  .to_string()
+}
@@ -1,19 +1,24 @@
 use typenum::marker_traits::Unsigned;
 
 use druid::kurbo::{BezPath, Rect, Size};
-use druid::widget::{Container, FillStrat, Flex, Label, Painter, SizedBox, WidgetExt};
-use druid::{Color, Env, PaintCtx, RenderContext, Widget};
+use druid::widget::{Container, FillStrat, Flex, Label, LabelText, Painter, SizedBox, WidgetExt};
+use druid::{Color, Env, EventCtx, PaintCtx, RenderContext, Widget};
 
 use kiro_synth_engine::synth::MaxVoices;
 
 use druid_icon::Icon;
 
-use crate::ui::model::{AudioLevel, Synth};
+use crate::ui::icons;
+use crate::ui::model::{AudioLevel, SnapshotSlot, Synth};
+use crate::ui::scale::{scaled, scaled_insets};
+use crate::ui::theme::{
+  themed_background, ThemeKind, BUTTON_BACKGROUND_COLOR, PANEL_BACKGROUND_COLOR,
+};
 use crate::ui::widgets::knob::theme::KNOB_MODULATION_VALUE_FG_COLOR;
-use crate::ui::{icons, GREY_46, GREY_65};
 
 const LEVEL_GREEN_BG: Color = Color::rgb8(23, 40, 11);
 const LEVEL_RED_BG: Color = Color::rgb8(40, 11, 11);
+const LEVEL_GREEN_PEAK: Color = Color::rgb8(33, 110, 64);
 const LEVEL_GREEN_FG: Color = Color::rgb8(55, 200, 113);
 const LEVEL_RED_FG: Color = Color::rgb8(200, 55, 55);
 
@@ -23,22 +28,93 @@ impl HeaderView {
   pub fn build() -> impl Widget<Synth> {
     let icon = Icon::new(&icons::LOGO_KIRO_SYNTH)
       .fill_strategy(FillStrat::ScaleDown)
-      .fix_width(108.0)
-      .fix_height(48.0)
-      .padding((4.0, 0.0));
+      .fix_width(scaled(108.0))
+      .fix_height(scaled(48.0))
+      .padding(scaled_insets((4.0, 0.0)));
 
     Container::new(
       Flex::row()
         .with_child(icon)
         .with_flex_spacer(1.0)
+        .with_child(Self::theme())
+        .with_spacer(scaled(12.0))
+        .with_child(Self::snapshots())
+        .with_spacer(scaled(12.0))
+        .with_child(Self::panic())
+        .with_spacer(scaled(12.0))
         .with_child(Self::voices())
-        .with_spacer(12.0)
+        .with_spacer(scaled(12.0))
         .with_child(Self::audio_levels())
-        .with_spacer(8.0),
+        .with_spacer(scaled(8.0)),
     )
-    .rounded(4.0)
-    .background(GREY_65)
-    .padding(4.0)
+    .rounded(scaled(4.0))
+    .background(themed_background(PANEL_BACKGROUND_COLOR))
+    .padding(scaled(4.0))
+  }
+
+  fn panic() -> impl Widget<Synth> {
+    Label::new("PANIC")
+      .center()
+      .fix_size(scaled(44.0), scaled(29.0))
+      .background(themed_background(BUTTON_BACKGROUND_COLOR))
+      .rounded(scaled(2.0))
+      .on_click(|_ctx: &mut EventCtx, data: &mut Synth, _env: &Env| {
+        data.synth_client.send_all_sound_off().unwrap()
+      })
+  }
+
+  fn theme() -> impl Widget<Synth> {
+    let text_fn = |data: &Synth, _: &Env| {
+      match data.theme {
+        ThemeKind::Dark => "LIGHT",
+        ThemeKind::Light => "DARK",
+      }
+      .to_string()
+    };
+
+    Label::new(text_fn)
+      .center()
+      .fix_size(scaled(44.0), scaled(29.0))
+      .background(themed_background(BUTTON_BACKGROUND_COLOR))
+      .rounded(scaled(2.0))
+      .on_click(|_ctx: &mut EventCtx, data: &mut Synth, _env: &Env| data.toggle_theme())
+  }
+
+  /// Store the patch in the A and B slots, switch between them to compare, or copy A into B
+  fn snapshots() -> impl Widget<Synth> {
+    let toggle_fn = |data: &Synth, _: &Env| {
+      match data.snapshots.active {
+        Some(SnapshotSlot::A) => "A▸B",
+        Some(SnapshotSlot::B) => "B▸A",
+        None => "A/B",
+      }
+      .to_string()
+    };
+
+    Flex::row()
+      .with_child(Self::snapshot_button("SET A", |data| {
+        data.store_snapshot(SnapshotSlot::A)
+      }))
+      .with_spacer(scaled(2.0))
+      .with_child(Self::snapshot_button("SET B", |data| {
+        data.store_snapshot(SnapshotSlot::B)
+      }))
+      .with_spacer(scaled(2.0))
+      .with_child(Self::snapshot_button(toggle_fn, Synth::toggle_snapshot))
+      .with_spacer(scaled(2.0))
+      .with_child(Self::snapshot_button("A→B", Synth::copy_snapshot_a_to_b))
+  }
+
+  fn snapshot_button(
+    text: impl Into<LabelText<Synth>>,
+    action: impl Fn(&mut Synth) + 'static,
+  ) -> impl Widget<Synth> {
+    Label::new(text)
+      .center()
+      .fix_size(scaled(36.0), scaled(29.0))
+      .background(themed_background(BUTTON_BACKGROUND_COLOR))
+      .rounded(scaled(2.0))
+      .on_click(move |_ctx: &mut EventCtx, data: &mut Synth, _env: &Env| action(data))
   }
 
   fn voices() -> impl Widget<Synth> {
@@ -46,13 +122,13 @@ impl HeaderView {
 
     let num_voices = Label::new(value_fn)
       .center()
-      .fix_size(44.0, 14.0)
+      .fix_size(scaled(44.0), scaled(14.0))
       .background(Painter::new(Self::paint_voices))
       .lens(Synth::active_voices);
 
     Flex::column()
-      .with_child(Label::new("VOICES").fix_height(14.0))
-      .with_spacer(1.0)
+      .with_child(Label::new("VOICES").fix_height(scaled(14.0)))
+      .with_spacer(scaled(1.0))
       .with_child(num_voices)
   }
 
@@ -63,24 +139,26 @@ impl HeaderView {
     let width = (size.width - 2.0 * margin) * *num_voices as f64 / max_voices as f64;
     let color = env.get(KNOB_MODULATION_VALUE_FG_COLOR);
     let rect = Rect::new(margin, margin, margin + width, size.height - margin);
-    ctx.fill(size.to_rect(), &GREY_46);
+    ctx.fill(size.to_rect(), &env.get(BUTTON_BACKGROUND_COLOR));
     ctx.fill(rect, &color);
   }
 
   fn audio_levels() -> impl Widget<Synth> {
     let scale = Icon::new(&icons::LEVEL_METER_SCALE)
       .fill_strategy(FillStrat::ScaleDown)
-      .fix_size(64.0, 12.0);
+      .fix_size(scaled(64.0), scaled(12.0));
 
     Flex::column()
       .with_child(scale)
       .with_child(Self::audio_level().lens(Synth::left_level))
       .with_child(Self::audio_level().lens(Synth::right_level))
+      .with_spacer(scaled(2.0))
+      .with_child(Self::audio_level().lens(Synth::send_fx_level))
   }
 
   fn audio_level() -> impl Widget<AudioLevel> {
     SizedBox::empty()
-      .fix_size(64.0, 9.0)
+      .fix_size(scaled(64.0), scaled(9.0))
       .background(Painter::new(Self::paint_level))
   }
 
@@ -91,8 +169,13 @@ impl HeaderView {
     ctx.fill(shape.green_bar(0.0), &LEVEL_GREEN_BG);
     ctx.fill(shape.max_bar(), &LEVEL_RED_BG);
 
+    // the peak level behind the RMS level, so both are visible
     if level.level >= -60.0 {
-      ctx.fill(shape.green_bar(level.level), &LEVEL_GREEN_FG);
+      ctx.fill(shape.green_bar(level.level), &LEVEL_GREEN_PEAK);
+    }
+
+    if level.rms >= -60.0 {
+      ctx.fill(shape.green_bar(level.rms), &LEVEL_GREEN_FG);
     }
 
     if level.level > 0.0 {
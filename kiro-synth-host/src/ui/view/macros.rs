@@ -0,0 +1,29 @@
+use druid::widget::{Flex, WidgetExt};
+use druid::{Env, Widget};
+
+use crate::ui::model::{Macros, Synth, ZeroIndex};
+use crate::ui::view::{build_knob_value, build_switcher, build_tabs};
+
+pub struct MacrosView;
+
+impl MacrosView {
+  pub fn build() -> impl Widget<Synth> {
+    let tabs = build_tabs(1, |_index| "Macros".to_string()).lens(ZeroIndex);
+
+    build_switcher(
+      tabs,
+      |_data: &Synth, _env: &Env| 0usize,
+      move |_index: &usize, _data: &Synth, _env: &Env| {
+        Box::new(build_macros_view().lens(Synth::macros))
+      },
+    )
+  }
+}
+
+fn build_macros_view() -> impl Widget<Macros> {
+  Flex::row()
+    .with_child(build_knob_value("M1").lens(Macros::macro1))
+    .with_child(build_knob_value("M2").lens(Macros::macro2))
+    .with_child(build_knob_value("M3").lens(Macros::macro3))
+    .with_child(build_knob_value("M4").lens(Macros::macro4))
+}
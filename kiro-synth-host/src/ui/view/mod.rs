@@ -1,6 +1,7 @@
 mod dca;
 mod filters;
 mod header;
+mod macros;
 mod modulations;
 mod modulators;
 mod oscillators;
@@ -9,23 +10,27 @@ use std::sync::{Arc, Mutex};
 
 use kiro_synth_core::float::Float;
 
+use druid::kurbo::Circle;
 use druid::widget::{
-  Container, Controller, CrossAxisAlignment, Flex, Label, ViewSwitcher, WidgetExt,
+  Container, Controller, CrossAxisAlignment, Flex, Label, Painter, ViewSwitcher, WidgetExt,
 };
 use druid::{
-  Command, Data, Env, Event, EventCtx, LifeCycle, LifeCycleCtx, TimerToken, UpdateCtx, Widget,
+  theme, Command, Data, Env, Event, EventCtx, HotKey, KeyCode, KeyEvent, LifeCycle, LifeCycleCtx,
+  PaintCtx, RenderContext, Selector, SysMods, TimerToken, UpdateCtx, Widget,
 };
 
 use crate::synth::SynthClient;
-use crate::ui::model::{KnobDataFromParam, Param, Synth};
+use crate::ui::model::{KnobDataFromParam, Param, PresetState, Synth};
+use crate::ui::scale::{scaled, scaled_insets};
+use crate::ui::theme::{modulation_source_color, themed_background, Theme, PANEL_BORDER_COLOR};
 use crate::ui::view::header::HeaderView;
 use crate::ui::view::modulations::UPDATE_MODULATIONS_CONFIG;
 use crate::ui::widgets::knob::{Knob, KnobData};
 use crate::ui::widgets::tab::Tab;
-use crate::ui::{GREY_65, GREY_74, GREY_83};
 
 use dca::DcaView;
 use filters::FiltersView;
+use macros::MacrosView;
 use modulations::ModulationsView;
 use modulators::ModulatorsView;
 use oscillators::OscillatorsView;
@@ -113,39 +118,125 @@ impl<W: Widget<Synth>> Controller<Synth, W> for TimerFeedbackController {
   }
 }
 
+/// Load a preset selected out of the UI, such as with a MIDI program change
+pub const LOAD_PRESET: Selector<PresetState> = Selector::new("synth.load-preset");
+
+/// Loads the presets sent to the UI, so the knobs and the modulations follow them
+pub struct PresetsController;
+
+impl<W: Widget<Synth>> Controller<Synth, W> for PresetsController {
+  fn event(
+    &mut self,
+    child: &mut W,
+    ctx: &mut EventCtx,
+    event: &Event,
+    data: &mut Synth,
+    env: &Env,
+  ) {
+    match event {
+      Event::Command(command) if command.is(LOAD_PRESET) => {
+        if let Some(preset) = command.get(LOAD_PRESET) {
+          data.load_preset(preset);
+        }
+        ctx.set_handled();
+      }
+      _ => child.event(ctx, event, data, env),
+    }
+  }
+}
+
+/// Handles the keyboard shortcuts of the whole window, taking the focus when the window is connected:
+/// undo and redo, the number keys to select the oscillator, and Tab/Shift+Tab to go through the modulators
+pub struct KeyboardShortcutsController;
+
+impl KeyboardShortcutsController {
+  const NUMBER_KEYS: [&'static str; 9] = ["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+  /// The index selected with a number key
+  fn number_index(key_event: &KeyEvent) -> Option<usize> {
+    Self::NUMBER_KEYS
+      .iter()
+      .position(|key| HotKey::new(None, *key).matches(key_event))
+  }
+}
+
+impl<W: Widget<Synth>> Controller<Synth, W> for KeyboardShortcutsController {
+  fn event(
+    &mut self,
+    child: &mut W,
+    ctx: &mut EventCtx,
+    event: &Event,
+    data: &mut Synth,
+    env: &Env,
+  ) {
+    match event {
+      Event::WindowConnected => {
+        ctx.request_focus();
+        child.event(ctx, event, data, env);
+      }
+      Event::KeyDown(key_event) if HotKey::new(SysMods::Cmd, "z").matches(key_event) => {
+        data.undo_param_edit();
+        ctx.set_handled();
+      }
+      Event::KeyDown(key_event) if HotKey::new(SysMods::CmdShift, "Z").matches(key_event) => {
+        data.redo_param_edit();
+        ctx.set_handled();
+      }
+      Event::KeyDown(key_event) if HotKey::new(None, KeyCode::Tab).matches(key_event) => {
+        data.cycle_mod_index(true);
+        ctx.set_handled();
+      }
+      Event::KeyDown(key_event) if HotKey::new(SysMods::Shift, KeyCode::Tab).matches(key_event) => {
+        data.cycle_mod_index(false);
+        ctx.set_handled();
+      }
+      Event::KeyDown(key_event) if Self::number_index(key_event).is_some() => {
+        data.select_osc(Self::number_index(key_event).unwrap());
+        ctx.set_handled();
+      }
+      _ => child.event(ctx, event, data, env),
+    }
+  }
+}
+
 pub fn build<F: Float + 'static>(
   synth_model: &Synth,
   synth_client: Arc<Mutex<SynthClient<F>>>,
 ) -> impl Widget<Synth> {
-  let height = 114.0;
+  let height = scaled(114.0);
   let devices = Flex::column()
     .with_child(
       OscillatorsView::build(synth_model, synth_client.clone())
         .fix_height(height)
-        .padding(4.0),
+        .padding(scaled(4.0)),
     )
     .with_child(
       Flex::row()
         .with_flex_child(
           FiltersView::build(synth_model)
             .fix_height(height)
-            .padding(4.0),
+            .padding(scaled(4.0)),
           1.0,
         )
-        .with_child(DcaView::build(synth_model).fix_height(height).padding(4.0))
+        .with_child(
+          DcaView::build(synth_model)
+            .fix_height(height)
+            .padding(scaled(4.0)),
+        )
         .must_fill_main_axis(true),
     )
+    .with_child(MacrosView::build().fix_height(height).padding(scaled(4.0)))
     .with_child(
       ModulatorsView::build(synth_model, synth_client)
         .fix_height(height * 2.0)
-        .padding(4.0),
+        .padding(scaled(4.0)),
     )
     .cross_axis_alignment(CrossAxisAlignment::Start);
 
   let modulations = ModulationsView::build();
 
   let main_panel = Flex::row()
-    .with_child(devices.fix_width(330.0))
+    .with_child(devices.fix_width(scaled(330.0)))
     .with_flex_child(modulations, 1.0)
     .cross_axis_alignment(CrossAxisAlignment::Start);
 
@@ -153,9 +244,14 @@ pub fn build<F: Float + 'static>(
 
   Flex::column()
     .with_child(header)
-    .with_spacer(4.0)
+    .with_spacer(scaled(4.0))
     .with_flex_child(main_panel, 1.0)
     .controller(TimerFeedbackController::new())
+    .controller(PresetsController)
+    .controller(KeyboardShortcutsController)
+    // the window background is painted here for it to follow the changes of theme
+    .background(themed_background(theme::WINDOW_BACKGROUND_COLOR))
+    .env_scope(|env: &mut Env, data: &Synth| Theme::from_kind(data.theme).apply(env))
   // .debug_widget_id()
   // .debug_paint_layout()
 }
@@ -167,7 +263,7 @@ where
   F: Fn(usize, &T) -> W,
 {
   let mut tabs_row = Flex::row();
-  tabs_row.add_spacer(2.0);
+  tabs_row.add_spacer(scaled(2.0));
   for (index, tab_data) in tabs_data.iter().enumerate() {
     let moved_tab_data = tab_data.clone();
     let on_click = move |data: &mut T, _: &Env| *data = moved_tab_data.clone();
@@ -177,17 +273,11 @@ where
     let child = child_builder(index, tab_data);
 
     let tab = Tab::new(child, on_click, is_selected)
-      .border_width(2.0)
-      .selected_border_color(GREY_83)
-      .unselected_border_color(GREY_65)
-      .hover_border_color(GREY_74)
-      .selected_background(GREY_83)
-      .unselected_background(GREY_65)
-      .hover_background(GREY_74)
-      .rounded(2.0);
+      .border_width(scaled(2.0))
+      .rounded(scaled(2.0));
 
     tabs_row.add_child(tab);
-    tabs_row.add_spacer(4.0);
+    tabs_row.add_spacer(scaled(4.0));
   }
   tabs_row
 }
@@ -195,7 +285,7 @@ where
 pub fn build_tabs(n: usize, title: impl Fn(usize) -> String + 'static) -> impl Widget<usize> {
   let tabs_data = (0..n).collect::<Vec<usize>>();
   build_static_tabs(tabs_data, move |_index: usize, data: &usize| {
-    Label::<usize>::new(title(*data)).padding((6.0, 4.0, 4.0, 2.0))
+    Label::<usize>::new(title(*data)).padding(scaled_insets((6.0, 4.0, 4.0, 2.0)))
   })
 }
 
@@ -209,9 +299,11 @@ where
   U: PartialEq + 'static,
   W: Widget<T> + 'static,
 {
-  let switcher = ViewSwitcher::new(child_picker, child_builder).padding(6.0);
+  let switcher = ViewSwitcher::new(child_picker, child_builder).padding(scaled(6.0));
 
-  let body = Container::new(switcher).rounded(2.0).border(GREY_83, 2.0);
+  let body = Container::new(switcher)
+    .rounded(scaled(2.0))
+    .border(PANEL_BORDER_COLOR, scaled(2.0));
 
   Flex::column()
     .with_child(tabs)
@@ -219,12 +311,13 @@ where
     .cross_axis_alignment(CrossAxisAlignment::Start)
 }
 
-pub fn build_knob_value(title: &'static str, unit: &'static str) -> impl Widget<Param> {
+/// A knob showing the value of the param in its unit
+pub fn build_knob_value(title: &'static str) -> impl Widget<Param> {
   let value_fn = move |data: &KnobData<Param>| {
     let step = data.step.max(0.001);
     let precision = (-step.log10().ceil()).max(0.0).min(3.0) as usize;
     let value = (data.value / step).round() * step;
-    format!("{:.*}{}", precision, value, unit)
+    data.context.unit.format(value, precision)
   };
 
   build_knob(title, value_fn)
@@ -239,6 +332,19 @@ pub fn build_knob_enum(
   })
 }
 
+/// Paint a dot with the color of every source modulating the param, in the gap at the bottom of the knob
+fn paint_modulation_sources(ctx: &mut PaintCtx, data: &KnobData<Param>, _env: &Env) {
+  let sources = &data.context.modulation.sources;
+  let size = ctx.size();
+  let spacing = scaled(4.0);
+  let start = size.width / 2.0 - spacing * (sources.len() as f64 - 1.0) / 2.0;
+  let y = size.height - scaled(5.0);
+  for (index, source_index) in sources.iter().enumerate() {
+    let circle = Circle::new((start + spacing * index as f64, y), scaled(1.5));
+    ctx.fill(circle, &modulation_source_color(*source_index));
+  }
+}
+
 pub fn build_knob(
   title: &'static str,
   value_fn: impl Fn(&KnobData<Param>) -> String + 'static,
@@ -256,24 +362,26 @@ pub fn build_knob(
       None => data
         .context
         .synth_client
-        .send_param_value(data.context.param_ref, data.value as f32)
+        .send_param_edit(data.context.param_ref, data.value as f32)
         .unwrap(),
     }
   };
 
   let knob = Knob::new(callback)
-    .modulation_width(4.0)
-    .padding(2.0)
+    .value_width(scaled(2.0))
+    .modulation_width(scaled(4.0))
+    .padding(scaled(2.0))
     .center()
-    .fix_size(48.0, 48.0);
+    .fix_size(scaled(48.0), scaled(48.0))
+    .background(Painter::new(paint_modulation_sources));
 
   Flex::column()
-    .with_child(Label::new(title).center().fix_width(48.0))
+    .with_child(Label::new(title).center().fix_width(scaled(48.0)))
     .with_child(knob)
     .with_child(
       Label::new(move |data: &KnobData<Param>, _env: &Env| value_fn(data))
         .center()
-        .fix_width(48.0),
+        .fix_width(scaled(48.0)),
     )
     .lens(KnobDataFromParam)
 }
@@ -3,12 +3,14 @@ use std::marker::PhantomData;
 use druid::im::Vector;
 use druid::theme::LABEL_COLOR;
 use druid::widget::{
-  Container, Controller, CrossAxisAlignment, Either, FillStrat, Flex, Label, List, Scroll,
+  Container, Controller, CrossAxisAlignment, Either, FillStrat, Flex, Label, List, Painter, Scroll,
   SizedBox, ViewSwitcher,
 };
 use druid::{
+  kurbo::Circle,
   lens::{self, LensExt},
-  Color, Command, Data, Env, Event, EventCtx, Selector, UnitPoint, UpdateCtx, Widget, WidgetExt,
+  Color, Command, Data, Env, Event, EventCtx, PaintCtx, RenderContext, Selector, UnitPoint,
+  UpdateCtx, Widget, WidgetExt,
 };
 
 use druid_icon::Icon;
@@ -19,6 +21,8 @@ use crate::ui::controllers::{HotChangedController, IconColorController};
 use crate::ui::icons;
 use crate::ui::model::modulations::{ConfigMode, Group, Modulation, Modulations, Reference, View};
 use crate::ui::model::Synth;
+use crate::ui::scale::{scaled, scaled_insets};
+use crate::ui::theme::{modulation_source_color, PANEL_BORDER_COLOR};
 use crate::ui::view::build_static_tabs;
 use crate::ui::widgets::knob::{Knob, KnobData};
 use crate::ui::{GREY_74, GREY_83, KNOB_CONFIG};
@@ -101,13 +105,13 @@ impl ModulationsView {
     );
 
     let body = Container::new(body)
-      .rounded(2.0)
-      .border(GREY_83, 2.0)
-      .padding((4.0, 0.0, 4.0, 4.0))
+      .rounded(scaled(2.0))
+      .border(PANEL_BORDER_COLOR, scaled(2.0))
+      .padding(scaled_insets((4.0, 0.0, 4.0, 4.0)))
       .expand_height();
 
     Flex::column()
-      .with_child(tabs.padding((4.0, 4.0, 0.0, 0.0)))
+      .with_child(tabs.padding(scaled_insets((4.0, 4.0, 0.0, 0.0))))
       .with_flex_child(body, 1.0)
       .cross_axis_alignment(CrossAxisAlignment::Start)
       .lens(Synth::modulations)
@@ -121,9 +125,9 @@ impl ModulationsView {
     };
 
     Icon::new(icon)
-      .fix_height(12.0)
+      .fix_height(scaled(12.0))
       .center()
-      .padding((6.0, 4.0, 4.0, 2.0))
+      .padding(scaled_insets((6.0, 4.0, 4.0, 2.0)))
   }
 
   fn build_modulations_list() -> impl Widget<Modulations> {
@@ -133,7 +137,7 @@ impl ModulationsView {
         .with_child(Self::build_modulation_knobs())
     });
 
-    Scroll::new(list.padding((4.0, 0.0))).vertical()
+    Scroll::new(list.padding(scaled_insets((4.0, 0.0)))).vertical()
   }
 
   fn build_group() -> impl Widget<Group> {
@@ -144,14 +148,14 @@ impl ModulationsView {
       },
       Icon::new(&icons::MODULATION_SOURCE)
         .fill_strategy(FillStrat::ScaleDown)
-        .fix_height(9.0),
+        .fix_height(scaled(9.0)),
       Icon::new(&icons::MODULATION_PARAM)
         .fill_strategy(FillStrat::ScaleDown)
-        .fix_height(9.0),
+        .fix_height(scaled(9.0)),
     );
 
     let name = Label::new(|data: &Group, _env: &_| data.name.clone())
-      .padding((3.0, 3.0))
+      .padding(scaled_insets((3.0, 3.0)))
       .expand_width()
       .height(20.0);
 
@@ -162,7 +166,7 @@ impl ModulationsView {
           match config_mode {
             ConfigMode::Ready => Icon::new(&icons::MODULATION_ARROW)
               .fill_strategy(FillStrat::ScaleDown)
-              .fix_height(10.0)
+              .fix_height(scaled(10.0))
               .on_click(move |ctx: &mut EventCtx, _data: &mut Group, _: &Env| {
                 let command = Command::new(START_MODULATIONS_CONFIG, source_ref);
                 ctx.submit_command(command, None);
@@ -171,7 +175,7 @@ impl ModulationsView {
             ConfigMode::Ongoing => Icon::new(&icons::MODULATION_ARROW)
               .color(KNOB_CONFIG)
               .fill_strategy(FillStrat::ScaleDown)
-              .fix_height(10.0)
+              .fix_height(scaled(10.0))
               .on_click(move |ctx: &mut EventCtx, _data: &mut Group, _: &Env| {
                 let command = Command::new(STOP_MODULATIONS_CONFIG, source_ref);
                 ctx.submit_command(command, None);
@@ -180,18 +184,34 @@ impl ModulationsView {
             ConfigMode::Disabled => Icon::new(&icons::MODULATION_ARROW)
               .color(GREY_74)
               .fill_strategy(FillStrat::ScaleDown)
-              .fix_height(10.0)
+              .fix_height(scaled(10.0))
               .boxed(),
           }
         } else {
-          SizedBox::empty().fix_height(10.0).boxed()
+          SizedBox::empty().fix_height(scaled(10.0)).boxed()
         }
       },
     )
-    .padding((0.0, 0.0, 8.0, 0.0));
+    .padding(scaled_insets((0.0, 0.0, 8.0, 0.0)));
+
+    // the color of the source, as shown next to the knobs it modulates
+    let source_color = SizedBox::empty()
+      .fix_size(scaled(6.0), scaled(6.0))
+      .background(Painter::new(
+        |ctx: &mut PaintCtx, data: &Group, _env: &Env| {
+          if let Reference::Source(source_ref) = data.reference {
+            let size = ctx.size();
+            let center = (size.width / 2.0, size.height / 2.0);
+            let circle = Circle::new(center, size.width.min(size.height) / 2.0);
+            ctx.fill(circle, &modulation_source_color(source_ref.into()));
+          }
+        },
+      ))
+      .padding(scaled_insets((3.0, 0.0, 0.0, 0.0)));
 
     Flex::row()
       .with_child(group_icon)
+      .with_child(source_color)
       .with_flex_child(name, 1.0)
       .with_child(config_mode)
   }
@@ -219,9 +239,10 @@ impl ModulationsView {
     };
 
     let knob = Knob::new(callback)
-      .padding(4.0)
+      .value_width(scaled(2.0))
+      .padding(scaled(4.0))
       .center()
-      .fix_size(38.0, 38.0)
+      .fix_size(scaled(38.0), scaled(38.0))
       .lens(lens::Id.map(
         |data: &Modulation| {
           KnobData::new(
@@ -236,9 +257,16 @@ impl ModulationsView {
         |data: &mut Modulation, knob_data: KnobData<Modulation>| data.amount = knob_data.value,
       ));
 
+    // only the LFOs can be sampled with a phase offset for a modulation
+    let phase = Either::new(
+      |data: &Modulation, _: &Env| data.lfo,
+      Self::build_modulation_phase_knob(),
+      SizedBox::empty(),
+    );
+
     let name = Label::new(|data: &Modulation, _env: &_| data.name.clone())
       .align_vertical(UnitPoint::new(0.0, 0.5))
-      .fix_height(19.0);
+      .fix_height(scaled(19.0));
 
     let value_fn = move |data: &Modulation, _env: &_| {
       let step = data.step.max(0.001);
@@ -249,7 +277,7 @@ impl ModulationsView {
 
     let value = Label::new(value_fn)
       .align_vertical(UnitPoint::new(0.0, 0.5))
-      .fix_height(19.0);
+      .fix_height(scaled(19.0));
 
     let name_and_value = Flex::column()
       .cross_axis_alignment(CrossAxisAlignment::Start)
@@ -274,8 +302,8 @@ impl ModulationsView {
         DELETE_MODULATION_IS_HOT,
         modulation_id,
       ))
-      .fix_height(10.0)
-      .padding((0.0, 0.0, 8.0, 0.0))
+      .fix_height(scaled(10.0))
+      .padding(scaled_insets((0.0, 0.0, 8.0, 0.0)))
       .on_click(move |ctx: &mut EventCtx, data: &mut Modulation, _: &Env| {
         let payload = (data.source_ref, data.param_ref);
         let command = Command::new(DELETE_MODULATION, payload);
@@ -285,6 +313,7 @@ impl ModulationsView {
     Flex::row()
       .with_child(knob)
       .with_flex_child(name_and_value, 1.0)
+      .with_child(phase)
       .with_child(remove)
       .controller(HotChangedController::new(
         DELETE_MODULATION_IS_HOT,
@@ -292,4 +321,32 @@ impl ModulationsView {
         hot_color,
       ))
   }
+  /// The phase offset in cycles at which the LFO is sampled for the modulation
+  fn build_modulation_phase_knob() -> impl Widget<Modulation> {
+    let callback = move |_ctx: &mut UpdateCtx, data: &KnobData<Modulation>| {
+      let source_ref = data.context.source_ref;
+      let param_ref = data.context.param_ref;
+      data
+        .context
+        .synth_client
+        .send_modulation_phase(source_ref, param_ref, data.value as f32)
+        .unwrap();
+    };
+
+    let knob = Knob::new(callback)
+      .value_width(scaled(2.0))
+      .padding(scaled(2.0))
+      .center()
+      .fix_size(scaled(26.0), scaled(26.0))
+      .lens(lens::Id.map(
+        |data: &Modulation| KnobData::new(0.0, 0.0, 1.0, 0.01, data.phase, data.clone()),
+        |data: &mut Modulation, knob_data: KnobData<Modulation>| data.phase = knob_data.value,
+      ));
+
+    let degrees = Label::new(|data: &Modulation, _env: &_| format!("{:.0}°", data.phase * 360.0))
+      .center()
+      .fix_height(scaled(14.0));
+
+    Flex::column().with_child(knob).with_child(degrees)
+  }
 }
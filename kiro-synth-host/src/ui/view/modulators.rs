@@ -1,12 +1,12 @@
 use std::sync::{Arc, Mutex};
 
-use druid::widget::{Flex, SizedBox, WidgetExt};
+use druid::widget::{Flex, WidgetExt};
 use druid::{Env, Widget};
 
 use kiro_synth_core::float::Float;
 
 use crate::synth::SynthClient;
-use crate::ui::model::{EgFromSynth, EnvGen, Lfo, LfoFromSynth, Synth};
+use crate::ui::model::{EgFromSynth, EnvGen, Lfo, LfoFromSynth, PitchEg, Synth};
 use crate::ui::view::{build_knob_enum, build_knob_value, build_switcher, build_tabs};
 
 pub struct ModulatorsView;
@@ -17,10 +17,16 @@ impl ModulatorsView {
     synth_client: Arc<Mutex<SynthClient<F>>>,
   ) -> impl Widget<Synth> {
     let eg_len = synth_model.eg.len();
-    let tabs_len = eg_len + synth_model.lfo.len();
+    let pitch_eg_index = eg_len + synth_model.lfo.len();
+    let tabs_len = pitch_eg_index + 1;
+    // The first envelope is the amp one, and the pitch envelope goes after the LFOs
     let tab_title = move |index| {
-      if index < eg_len {
-        format!("EG{}", index + 1)
+      if index == 0 {
+        "AMP".to_string()
+      } else if index < eg_len {
+        format!("EG{}", index)
+      } else if index == pitch_eg_index {
+        "PITCH".to_string()
       } else {
         format!("LFO{}", index - eg_len + 1)
       }
@@ -32,7 +38,9 @@ impl ModulatorsView {
       tabs,
       |data: &Synth, _env: &Env| data.mod_index,
       move |index: &usize, _data: &Synth, _env: &Env| {
-        if *index < eg_len {
+        if *index == pitch_eg_index {
+          Box::new(build_pitch_eg_view().lens(Synth::pitch_eg))
+        } else if *index < eg_len {
           Box::new(build_eg_view().lens(EgFromSynth))
         } else {
           Box::new(build_lfo_view(synth_client.clone()).lens(LfoFromSynth))
@@ -44,20 +52,39 @@ impl ModulatorsView {
 
 fn build_eg_view() -> impl Widget<EnvGen> {
   let row1 = Flex::row()
-    .with_child(build_knob_value("Attack", " s").lens(EnvGen::attack))
-    .with_child(build_knob_value("Decay", " s").lens(EnvGen::decay))
-    .with_child(build_knob_value("Sustain", "").lens(EnvGen::sustain))
-    .with_child(build_knob_value("Release", " s").lens(EnvGen::release))
+    .with_child(build_knob_value("Attack").lens(EnvGen::attack))
+    .with_child(build_knob_value("Decay").lens(EnvGen::decay))
+    .with_child(build_knob_value("Sustain").lens(EnvGen::sustain))
+    .with_child(build_knob_value("Release").lens(EnvGen::release))
     .with_flex_spacer(1.0);
 
   let row2 = Flex::row()
-    .with_child(build_knob_value("Mode", "").lens(EnvGen::mode))
-    .with_child(build_knob_value("Intensity", "").lens(EnvGen::dca_intensity))
+    .with_child(build_knob_value("Mode").lens(EnvGen::mode))
+    .with_child(build_knob_value("Vel>Att").lens(EnvGen::velocity_to_attack))
+    .with_child(build_knob_value("Vel>Dec").lens(EnvGen::velocity_to_decay))
     .with_flex_spacer(1.0);
 
   Flex::column()
     .with_child(row1)
-    .with_spacer(10.0)
+    .with_spacer(scaled(10.0))
+    .with_child(row2)
+}
+
+fn build_pitch_eg_view() -> impl Widget<PitchEg> {
+  let row1 = Flex::row()
+    .with_child(build_knob_value("Attack").lens(PitchEg::attack))
+    .with_child(build_knob_value("Decay").lens(PitchEg::decay))
+    .with_child(build_knob_value("Sustain").lens(PitchEg::sustain))
+    .with_child(build_knob_value("Release").lens(PitchEg::release))
+    .with_flex_spacer(1.0);
+
+  let row2 = Flex::row()
+    .with_child(build_knob_value("Amount").lens(PitchEg::amount))
+    .with_flex_spacer(1.0);
+
+  Flex::column()
+    .with_child(row1)
+    .with_spacer(scaled(10.0))
     .with_child(row2)
 }
 
@@ -76,12 +103,44 @@ fn build_lfo_view<F: Float + 'static>(
 
   let row1 = Flex::row()
     .with_child(build_knob_enum("Shape", shape_fn).lens(Lfo::shape))
-    .with_child(build_knob_value("Rate", " Hz").lens(Lfo::rate))
-    .with_child(build_knob_value("Phase", "").lens(Lfo::phase))
-    .with_child(build_knob_value("Depth", "").lens(Lfo::depth))
+    .with_child(build_knob_value("Rate").lens(Lfo::rate))
+    .with_child(build_knob_value("Phase").lens(Lfo::phase))
+    .with_child(build_knob_value("Depth").lens(Lfo::depth))
     .with_flex_spacer(1.0);
 
-  let row2 = SizedBox::<Lfo>::empty().height(86.0);
+  let row2 = Flex::row()
+    .with_child(build_knob_value("Slew").lens(Lfo::slew))
+    .with_child(build_knob_enum("Slew Sync", slew_sync_name).lens(Lfo::slew_sync))
+    .with_child(build_knob_enum("Polarity", polarity_name).lens(Lfo::polarity))
+    .with_child(build_knob_enum("Invert", invert_name).lens(Lfo::invert))
+    .with_flex_spacer(1.0);
 
-  Flex::column().with_child(row1).with_child(row2)
+  Flex::column()
+    .with_child(row1)
+    .with_spacer(scaled(10.0))
+    .with_child(row2)
+}
+
+fn polarity_name(index: usize) -> String {
+  match index {
+    1 => "uni",
+    _ => "bi",
+  }
+  .to_string()
+}
+
+fn invert_name(index: usize) -> String {
+  match index {
+    1 => "on",
+    _ => "off",
+  }
+  .to_string()
+}
+
+fn slew_sync_name(index: usize) -> String {
+  match index {
+    1 => "beats",
+    _ => "secs",
+  }
+  .to_string()
 }
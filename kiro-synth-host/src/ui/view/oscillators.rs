@@ -1,12 +1,14 @@
 use std::sync::{Arc, Mutex};
 
-use druid::widget::{Flex, WidgetExt};
-use druid::{Env, Widget};
+use druid::widget::{CrossAxisAlignment, Flex, Label, TextBox, WidgetExt};
+use druid::{Env, EventCtx, Widget};
 
 use kiro_synth_core::float::Float;
 
 use crate::synth::SynthClient;
 use crate::ui::model::{Osc, OscFromSynth, Synth};
+use crate::ui::scale::scaled;
+use crate::ui::theme::{themed_background, BUTTON_BACKGROUND_COLOR};
 use crate::ui::view::{build_knob_enum, build_knob_value, build_switcher, build_tabs};
 
 pub struct OscillatorsView;
@@ -44,9 +46,89 @@ fn build_osc_view<F: Float + 'static>(
 
   Flex::row()
     .with_child(build_knob_enum("Shape", shape_fn).lens(Osc::shape))
-    .with_child(build_knob_value("Octaves", "").lens(Osc::octaves))
-    .with_child(build_knob_value("Semitones", "").lens(Osc::semitones))
-    .with_child(build_knob_value("Cents", "").lens(Osc::cents))
-    .with_child(build_knob_value("Amplitude", "").lens(Osc::amplitude))
+    .with_child(build_knob_value("Octaves").lens(Osc::octaves))
+    .with_child(build_knob_value("Semitones").lens(Osc::semitones))
+    .with_child(build_knob_value("Cents").lens(Osc::cents))
+    .with_child(build_detune())
+    .with_child(build_knob_enum("Fixed", fixed_freq_name).lens(Osc::fixed_freq))
+    .with_child(build_knob_value("Freq").lens(Osc::freq_hz))
+    .with_child(build_knob_enum("Level", level_db_name).lens(Osc::level_db))
+    .with_child(build_knob_value("Amplitude").lens(Osc::amplitude))
+    .with_child(build_knob_value("Amp dB").lens(Osc::amplitude_db))
+    .with_child(build_knob_enum("FM", fm_mode_name).lens(Osc::fm_mode))
+    .with_child(build_knob_enum("Sync", sync_mode_name).lens(Osc::sync_mode))
+    .with_child(build_knob_enum("LFO Sync", lfo_sync_name).lens(Osc::lfo_sync))
+    .with_child(build_knob_value("Low Key").lens(Osc::key_low))
+    .with_child(build_knob_value("High Key").lens(Osc::key_high))
     .with_flex_spacer(1.0)
 }
+
+/// The total detune of the octaves, semitones and cents, and an entry to set an exact one
+fn build_detune() -> impl Widget<Osc> {
+  let total =
+    Label::new(|data: &Osc, _env: &Env| format!("{:+}ct", data.total_detune().total_cents()))
+      .fix_height(scaled(14.0));
+
+  let entry = TextBox::new()
+    .fix_width(scaled(44.0))
+    .lens(Osc::detune_entry);
+
+  let apply = Label::new("SET")
+    .center()
+    .fix_size(scaled(44.0), scaled(16.0))
+    .background(themed_background(BUTTON_BACKGROUND_COLOR))
+    .rounded(scaled(2.0))
+    .on_click(|_ctx: &mut EventCtx, data: &mut Osc, _env: &Env| data.apply_detune_entry());
+
+  Flex::column()
+    .cross_axis_alignment(CrossAxisAlignment::Center)
+    .with_child(Label::new("Detune").fix_height(scaled(14.0)))
+    .with_child(total)
+    .with_spacer(scaled(2.0))
+    .with_child(entry)
+    .with_spacer(scaled(2.0))
+    .with_child(apply)
+    .padding(scaled(4.0))
+}
+
+fn fixed_freq_name(index: usize) -> String {
+  match index {
+    1 => "on",
+    _ => "off",
+  }
+  .to_string()
+}
+
+fn level_db_name(index: usize) -> String {
+  match index {
+    1 => "dB",
+    _ => "lin",
+  }
+  .to_string()
+}
+
+fn fm_mode_name(index: usize) -> String {
+  match index {
+    1 => "lin",
+    _ => "exp",
+  }
+  .to_string()
+}
+
+fn sync_mode_name(index: usize) -> String {
+  match index {
+    1 => "hard",
+    2 => "soft",
+    _ => "off",
+  }
+  .to_string()
+}
+
+fn lfo_sync_name(index: usize) -> String {
+  match index {
+    1 => "lfo1",
+    2 => "lfo2",
+    _ => "off",
+  }
+  .to_string()
+}
@@ -14,6 +14,8 @@ use druid::{
 };
 use std::marker::PhantomData;
 
+use kiro_synth_engine::program::{ParamScale, ParamValues};
+
 pub mod theme {
   pub use druid::theme::*;
   use druid::{Color, Env, Key};
@@ -96,6 +98,10 @@ pub struct KnobData<T> {
   pub value: f64,
   pub modulation: KnobModulationData,
 
+  /// How the knob rotation maps into the values
+  #[data(same_fn = "PartialEq::eq")]
+  pub scale: ParamScale,
+
   #[data(ignore)]
   pub context: T,
 }
@@ -109,6 +115,7 @@ impl<T: Data> KnobData<T> {
       step,
       value,
       modulation: KnobModulationData::default(),
+      scale: ParamScale::Linear,
       context,
     }
   }
@@ -117,6 +124,33 @@ impl<T: Data> KnobData<T> {
     self.modulation = modulation_data;
     self
   }
+
+  pub fn with_scale(mut self, scale: ParamScale) -> Self {
+    self.scale = scale;
+    self
+  }
+
+  fn param_values(&self) -> ParamValues<f64> {
+    ParamValues {
+      initial_value: self.value,
+      origin: self.origin,
+      min: self.min,
+      max: self.max,
+      resolution: self.step,
+      scale: self.scale,
+      discrete: false,
+    }
+  }
+
+  /// The position of a value in the knob rotation, from 0.0 to 1.0
+  pub fn value_to_normalized(&self, value: f64) -> f64 {
+    self.param_values().normalize(value)
+  }
+
+  /// The value for a position in the knob rotation, from 0.0 to 1.0
+  pub fn normalized_to_value(&self, normalized: f64) -> f64 {
+    self.param_values().denormalize(normalized)
+  }
 }
 
 struct MouseMove {
@@ -190,9 +224,8 @@ where
     self
   }
 
-  fn value_to_angle(&self, value: f64, min: f64, max: f64) -> f64 {
-    let range = max - min;
-    Self::START_ANGLE + (Self::END_ANGLE - Self::START_ANGLE) * (value - min) / range
+  fn value_to_angle(&self, data: &KnobData<Context>, value: f64) -> f64 {
+    Self::START_ANGLE + (Self::END_ANGLE - Self::START_ANGLE) * data.value_to_normalized(value)
   }
 
   #[allow(clippy::too_many_arguments)]
@@ -253,8 +286,8 @@ where
     center: Point,
     radius: f64,
   ) {
-    let start_angle = self.value_to_angle(data.origin, data.min, data.max);
-    let end_angle = self.value_to_angle(data.value, data.min, data.max);
+    let start_angle = self.value_to_angle(data, data.origin);
+    let end_angle = self.value_to_angle(data, data.value);
     let color = self.value_fg_color.resolve(env);
     Self::paint_arc(
       ctx,
@@ -299,8 +332,8 @@ where
     let value = (data.value + data.modulation.value)
       .max(data.min)
       .min(data.max);
-    let start_angle = self.value_to_angle(data.value, data.min, data.max);
-    let end_angle = self.value_to_angle(value, data.min, data.max);
+    let start_angle = self.value_to_angle(data, data.value);
+    let end_angle = self.value_to_angle(data, value);
     let color = self.modulation_value_fg_color.resolve(env);
     Self::paint_arc(
       ctx,
@@ -327,11 +360,11 @@ where
     let start_value = (data.value - data.modulation.total_amount)
       .max(data.min)
       .min(data.max);
-    let start_angle = self.value_to_angle(start_value, data.min, data.max);
+    let start_angle = self.value_to_angle(data, start_value);
     let end_value = (data.value + data.modulation.total_amount)
       .max(data.min)
       .min(data.max);
-    let end_angle = self.value_to_angle(end_value, data.min, data.max);
+    let end_angle = self.value_to_angle(data, end_value);
     let color = self.modulation_total_amount_color.resolve(env);
     Self::paint_arc(
       ctx,
@@ -357,8 +390,8 @@ where
     let width = self.value_width;
     let radius = value_radius;
     let value = data.value + config_amount;
-    let start_angle = self.value_to_angle(data.value, data.min, data.max);
-    let end_angle = self.value_to_angle(value, data.min, data.max);
+    let start_angle = self.value_to_angle(data, data.value);
+    let end_angle = self.value_to_angle(data, value);
     let color = self.modulation_config_amount_color.resolve(env);
     Self::paint_arc(
       ctx,
@@ -404,14 +437,17 @@ where
         if ctx.is_active() {
           let height = ctx.size().height;
           let offset = self.mouse_move.orig_pos - mouse.pos.y;
-          let value_inc = (data.max - data.min) * (self.sensitivity * offset / height);
-          let value = (self.mouse_move.orig_value + value_inc)
-            .max(data.min)
-            .min(data.max);
-          match data.modulation.config_source.as_mut() {
-            Some(_) => data.modulation.config_amount = (value / data.step).round() * data.step,
-            None => data.value = (value / data.step).round() * data.step,
-          };
+          let normalized_inc = self.sensitivity * offset / height;
+          if data.modulation.config_source.is_some() {
+            let value_inc = (data.max - data.min) * normalized_inc;
+            let value = (self.mouse_move.orig_value + value_inc)
+              .max(data.min)
+              .min(data.max);
+            data.modulation.config_amount = (value / data.step).round() * data.step;
+          } else {
+            let normalized = data.value_to_normalized(self.mouse_move.orig_value) + normalized_inc;
+            data.value = data.normalized_to_value(normalized);
+          }
           ctx.request_paint();
         }
       }
@@ -498,3 +534,24 @@ where
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn knob_data_linear_scale() {
+    let data = KnobData::new(0.0, -1.0, 1.0, 0.01, 0.0, ());
+    assert!(data.normalized_to_value(0.5).abs() < 1e-9);
+    assert!((data.value_to_normalized(1.0) - 1.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn knob_data_log_scale() {
+    let data =
+      KnobData::new(80.0, 80.0, 18000.0, 10.0, 1000.0, ()).with_scale(ParamScale::Logarithmic);
+    let geometric_mean = (80.0f64 * 18000.0).sqrt();
+    assert!((data.normalized_to_value(0.5) - geometric_mean).abs() <= 10.0);
+    assert!((data.value_to_normalized(geometric_mean) - 0.5).abs() < 1e-9);
+  }
+}
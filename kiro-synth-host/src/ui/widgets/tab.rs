@@ -16,7 +16,7 @@ use crate::ui::widgets::tab::theme::{
 
 const ARC_TOLERANCE: f64 = 1e-12;
 
-mod theme {
+pub mod theme {
   pub use druid::theme;
   use druid::{Color, Key};
 
@@ -0,0 +1,803 @@
+use kiro_synth_core::float::Float;
+use kiro_synth_core::oscillators::pitched_oscillator::{FmMode, SyncMode};
+use kiro_synth_engine::program::blocks::osc::LfoSync;
+use kiro_synth_engine::program::blocks::{dca, envgen, filter, lfo, osc, sequencer};
+use kiro_synth_engine::program::macros::{MacroCurve, MacroTarget};
+use kiro_synth_engine::program::routing::OscRouting;
+use kiro_synth_engine::program::{
+  Block, MasterBlock, ParamBlock, Program, ProgramBuilder, SignalRef, SourceRef,
+};
+
+use crate::params::{
+  DcaParams, EnvGenParams, FilterParams, LfoParams, MacroParams, MasterParams, OscParams,
+  PitchEgParams, SequencerParams,
+};
+use crate::values;
+
+pub struct KiroParams {
+  pub pitch_bend: ParamBlock,
+
+  pub lfo1: LfoParams,
+  pub lfo2: LfoParams,
+
+  pub seq1: SequencerParams,
+
+  pub amp_eg: EnvGenParams,
+  pub eg1: EnvGenParams,
+  pub pitch_eg: PitchEgParams,
+
+  pub osc1: OscParams,
+  pub osc2: OscParams,
+  pub osc3: OscParams,
+  pub osc4: OscParams,
+
+  pub osc_mix_level: ParamBlock,
+
+  pub filter1: FilterParams,
+
+  pub dca: DcaParams,
+
+  pub master: MasterParams,
+
+  pub macros: MacroParams,
+}
+
+pub struct KiroSignals {
+  pub lfo1: SignalRef,
+  pub lfo2: SignalRef,
+  pub lfo1_sync: SignalRef,
+  pub lfo2_sync: SignalRef,
+  pub lfo1_phase: SignalRef,
+  pub lfo2_phase: SignalRef,
+  pub seq1: SignalRef,
+  pub seq1_gate: SignalRef,
+  pub eg1_normal: SignalRef,
+  pub eg1_biased: SignalRef,
+  pub pitch_eg_normal: SignalRef,
+  pub pitch_eg_biased: SignalRef,
+  pub osc1: SignalRef,
+  pub osc2: SignalRef,
+  pub osc3: SignalRef,
+  pub osc4: SignalRef,
+  pub osc1_sync: SignalRef,
+  pub osc2_sync: SignalRef,
+  pub osc3_sync: SignalRef,
+  pub osc4_sync: SignalRef,
+  pub filter1: SignalRef,
+  pub dca_left: SignalRef,
+  pub dca_right: SignalRef,
+}
+
+pub struct KiroSources {
+  pub amp_eg: SourceRef,
+  pub lfo1: SourceRef,
+  pub lfo2: SourceRef,
+  pub seq1: SourceRef,
+  pub eg1_normal: SourceRef,
+  pub eg1_biased: SourceRef,
+  pub osc1: SourceRef,
+  pub osc2: SourceRef,
+  pub osc3: SourceRef,
+  pub osc4: SourceRef,
+  pub pressure: SourceRef,
+}
+
+pub struct KiroModule {
+  pub signals: KiroSignals,
+  pub sources: KiroSources,
+  pub params: KiroParams,
+}
+
+impl KiroModule {
+  pub fn new_program<'a, F: Float>(
+    num_lfo_shapes: usize,
+    num_osc_shapes: usize,
+  ) -> (Program<'a, F>, KiroModule) {
+    let mut program_builder = ProgramBuilder::new();
+
+    let module = Self::new(&mut program_builder, num_lfo_shapes, num_osc_shapes);
+
+    program_builder.out(module.signals.dca_left, module.signals.dca_right);
+
+    (program_builder.build(), module)
+  }
+
+  pub fn new<F: Float>(
+    program: &mut ProgramBuilder<F>,
+    num_lfo_shapes: usize,
+    num_osc_shapes: usize,
+  ) -> KiroModule {
+    let voice = program.voice().clone();
+
+    let zero = program.const_zero();
+    let one = program.const_one();
+
+    let num_filters = filter::Mode::count();
+
+    let params = KiroParams {
+      pitch_bend: program.param("pitch-bend", values::pitch_bend()),
+
+      lfo1: LfoParams {
+        shape: program.param("lfo1-shape", values::enumeration(num_lfo_shapes)),
+        rate: program.param("lfo1-rate", values::lfo_rate()),
+        phase: program.param("lfo1-phase", values::lfo_phase()),
+        depth: program.param("lfo1-depth", values::amplitude()),
+        slew: program.param("lfo1-slew", values::lfo_slew()),
+        slew_sync: program.param("lfo1-slew-sync", values::boolean(false)),
+        polarity: program.param("lfo1-polarity", values::enumeration(2)),
+        invert: program.param("lfo1-invert", values::boolean(false)),
+      },
+
+      lfo2: LfoParams {
+        shape: program.param("lfo2-shape", values::enumeration(num_lfo_shapes)),
+        rate: program.param("lfo2-rate", values::lfo_rate()),
+        phase: program.param("lfo2-phase", values::lfo_phase()),
+        depth: program.param("lfo2-depth", values::amplitude()),
+        slew: program.param("lfo2-slew", values::lfo_slew()),
+        slew_sync: program.param("lfo2-slew-sync", values::boolean(false)),
+        polarity: program.param("lfo2-polarity", values::enumeration(2)),
+        invert: program.param("lfo2-invert", values::boolean(false)),
+      },
+
+      seq1: SequencerParams {
+        division: program.param("seq1-division", values::seq_division()),
+        length: program.param("seq1-length", values::seq_length()),
+      },
+
+      amp_eg: EnvGenParams {
+        attack: program.param("amp-eg-attack", values::adsr(0.02)),
+        decay: program.param("amp-eg-decay", values::adsr(0.1)),
+        sustain: program.param("amp-eg-sustain", values::adsr(1.0)),
+        release: program.param("amp-eg-release", values::adsr(1.5)),
+        mode: program.param("amp-eg-mode", values::eg_mode()),
+        legato: program.param("amp-eg-legato", values::boolean(false)),
+        reset_to_zero: program.param("amp-eg-reset-to-zero", values::boolean(false)),
+        velocity_to_attack: program.param("amp-eg-velocity-to-attack", values::bipolar()),
+        velocity_to_decay: program.param("amp-eg-velocity-to-decay", values::bipolar()),
+      },
+
+      eg1: EnvGenParams {
+        attack: program.param("eg1-attack", values::adsr(0.02)),
+        decay: program.param("eg1-decay", values::adsr(0.1)),
+        sustain: program.param("eg1-sustain", values::adsr(1.0)),
+        release: program.param("eg1-release", values::adsr(1.5)),
+        mode: program.param("eg1-mode", values::eg_mode()),
+        legato: program.param("eg1-legato", values::boolean(false)),
+        reset_to_zero: program.param("eg1-reset-to-zero", values::boolean(false)),
+        velocity_to_attack: program.param("eg1-velocity-to-attack", values::bipolar()),
+        velocity_to_decay: program.param("eg1-velocity-to-decay", values::bipolar()),
+      },
+
+      pitch_eg: PitchEgParams {
+        attack: program.param("pitch-eg-attack", values::adsr(0.0)),
+        decay: program.param("pitch-eg-decay", values::adsr(0.2)),
+        sustain: program.param("pitch-eg-sustain", values::adsr(0.0)),
+        release: program.param("pitch-eg-release", values::adsr(0.2)),
+        amount: program.param("pitch-eg-amount", values::pitch_eg_amount()),
+      },
+
+      osc1: OscParams {
+        shape: program.param(
+          "osc1-shape",
+          values::enumeration(num_osc_shapes).with_initial_value(F::val(2)),
+        ),
+        amplitude: program.param("osc1-amplitude", values::amplitude()),
+        amplitude_db: program.param("osc1-amplitude-db", values::osc_amplitude_db()),
+        level_db: program.param("osc1-level-db", values::boolean(false)),
+        octaves: program.param("osc1-octaves", values::octave()),
+        semitones: program.param("osc1-semitones", values::semitones()),
+        cents: program.param("osc1-cents", values::cents()),
+        fm_mode: program.param("osc1-fm-mode", values::enumeration(FmMode::count())),
+        sync_mode: program.param("osc1-sync-mode", values::enumeration(SyncMode::count())),
+        lfo_sync: program.param("osc1-lfo-sync", values::enumeration(LfoSync::count())),
+        key_low: program.param("osc1-key-low", values::key()),
+        key_high: program.param(
+          "osc1-key-high",
+          values::key().with_initial_value(F::val(127)),
+        ),
+        fixed_freq: program.param("osc1-fixed-freq", values::boolean(false)),
+        freq_hz: program.param("osc1-freq-hz", values::osc_freq()),
+      },
+
+      osc2: OscParams {
+        shape: program.param("osc2-shape", values::enumeration(num_osc_shapes)),
+        amplitude: program.param(
+          "osc2-amplitude",
+          values::amplitude().with_initial_value(F::val(0.25)),
+        ),
+        amplitude_db: program.param(
+          "osc2-amplitude-db",
+          values::osc_amplitude_db().with_initial_value(F::val(-12.0)),
+        ),
+        level_db: program.param("osc2-level-db", values::boolean(false)),
+        octaves: program.param(
+          "osc2-octaves",
+          values::octave().with_initial_value(F::val(-1)),
+        ),
+        semitones: program.param("osc2-semitones", values::semitones()),
+        cents: program.param("osc2-cents", values::cents()),
+        fm_mode: program.param("osc2-fm-mode", values::enumeration(FmMode::count())),
+        sync_mode: program.param("osc2-sync-mode", values::enumeration(SyncMode::count())),
+        lfo_sync: program.param("osc2-lfo-sync", values::enumeration(LfoSync::count())),
+        key_low: program.param("osc2-key-low", values::key()),
+        key_high: program.param(
+          "osc2-key-high",
+          values::key().with_initial_value(F::val(127)),
+        ),
+        fixed_freq: program.param("osc2-fixed-freq", values::boolean(false)),
+        freq_hz: program.param("osc2-freq-hz", values::osc_freq()),
+      },
+
+      osc3: OscParams {
+        shape: program.param("osc3-shape", values::enumeration(num_osc_shapes)),
+        amplitude: program.param(
+          "osc3-amplitude",
+          values::amplitude().with_initial_value(F::zero()),
+        ),
+        amplitude_db: program.param(
+          "osc3-amplitude-db",
+          values::osc_amplitude_db().with_initial_value(F::val(-96.0)),
+        ),
+        level_db: program.param("osc3-level-db", values::boolean(false)),
+        octaves: program.param("osc3-octaves", values::octave()),
+        semitones: program.param("osc3-semitones", values::semitones()),
+        cents: program.param("osc3-cents", values::cents()),
+        fm_mode: program.param("osc3-fm-mode", values::enumeration(FmMode::count())),
+        sync_mode: program.param("osc3-sync-mode", values::enumeration(SyncMode::count())),
+        lfo_sync: program.param("osc3-lfo-sync", values::enumeration(LfoSync::count())),
+        key_low: program.param("osc3-key-low", values::key()),
+        key_high: program.param(
+          "osc3-key-high",
+          values::key().with_initial_value(F::val(127)),
+        ),
+        fixed_freq: program.param("osc3-fixed-freq", values::boolean(false)),
+        freq_hz: program.param("osc3-freq-hz", values::osc_freq()),
+      },
+
+      osc4: OscParams {
+        shape: program.param("osc4-shape", values::enumeration(num_osc_shapes)),
+        amplitude: program.param(
+          "osc4-amplitude",
+          values::amplitude().with_initial_value(F::zero()),
+        ),
+        amplitude_db: program.param(
+          "osc4-amplitude-db",
+          values::osc_amplitude_db().with_initial_value(F::val(-96.0)),
+        ),
+        level_db: program.param("osc4-level-db", values::boolean(false)),
+        octaves: program.param("osc4-octaves", values::octave()),
+        semitones: program.param("osc4-semitones", values::semitones()),
+        cents: program.param("osc4-cents", values::cents()),
+        fm_mode: program.param("osc4-fm-mode", values::enumeration(FmMode::count())),
+        sync_mode: program.param("osc4-sync-mode", values::enumeration(SyncMode::count())),
+        lfo_sync: program.param("osc4-lfo-sync", values::enumeration(LfoSync::count())),
+        key_low: program.param("osc4-key-low", values::key()),
+        key_high: program.param(
+          "osc4-key-high",
+          values::key().with_initial_value(F::val(127)),
+        ),
+        fixed_freq: program.param("osc4-fixed-freq", values::boolean(false)),
+        freq_hz: program.param("osc4-freq-hz", values::osc_freq()),
+      },
+
+      osc_mix_level: program.param("osc-mix-level", values::level()),
+
+      filter1: FilterParams {
+        mode: program.param(
+          "filt1-mode",
+          values::enumeration(num_filters).with_initial_value(F::val(3)),
+        ),
+        freq: program.param("filt1-freq", values::filt_freq()),
+        key_track: program.param("filt1-key-track", values::bipolar()),
+        key_track_center: program.param(
+          "filt1-key-track-center",
+          values::key().with_initial_value(F::val(60)),
+        ),
+        q: program.param("filt1-q", values::filt_q()),
+        velocity_to_q: program.param("filt1-velocity-to-q", values::bipolar()),
+        q_compensation: program.param("filt1-q-compensation", values::boolean(false)),
+        gain: program.param("filt1-gain", values::gain_db()),
+        self_osc: program.param("filt1-self-osc", values::boolean(false)),
+      },
+
+      dca: DcaParams {
+        amplitude: program.param(
+          "dca-amplitude-db",
+          values::amplitude_db().with_initial_value(F::val(-3.0)),
+        ),
+        velocity_floor: program.param("dca-velocity-floor", values::amount()),
+        saturation: program.param("dca-saturation", values::amount()),
+        pan: program.param("dca-pan", values::pan()),
+      },
+
+      master: MasterParams {
+        pre_fx_gain: program.param("master-pre-fx-gain-db", values::gain_db()),
+        gain: program.param("master-gain-db", values::gain_db()),
+        fx_send: program.param(
+          "master-fx-send",
+          values::amplitude().with_initial_value(F::val(0.2)),
+        ),
+        fx_return: program.param("master-fx-return", values::amplitude()),
+        high_pass: program.param("master-high-pass", values::boolean(false)),
+        high_pass_freq: program.param("master-high-pass-freq", values::master_cutoff(30)),
+        low_pass: program.param("master-low-pass", values::boolean(false)),
+        low_pass_freq: program.param("master-low-pass-freq", values::master_cutoff(16000)),
+        pan: program.param("master-pan", values::pan()),
+        lfo_shape: program.param("master-lfo-shape", values::enumeration(num_lfo_shapes)),
+        lfo_rate: program.param("master-lfo-rate", values::lfo_rate()),
+        lfo_to_pan: program.param("master-lfo-pan", values::amount()),
+      },
+
+      macros: MacroParams {
+        macro1: program.param("macro1", values::amount()),
+        macro2: program.param("macro2", values::amount()),
+        macro3: program.param("macro3", values::amount()),
+        macro4: program.param("macro4", values::amount()),
+      },
+    };
+
+    let signals = KiroSignals {
+      lfo1: program.signal(),
+      lfo2: program.signal(),
+      lfo1_sync: program.signal(),
+      lfo2_sync: program.signal(),
+      lfo1_phase: program.signal(),
+      lfo2_phase: program.signal(),
+      seq1: program.signal(),
+      seq1_gate: program.signal(),
+      eg1_normal: program.signal(),
+      eg1_biased: program.signal(),
+      pitch_eg_normal: program.signal(),
+      pitch_eg_biased: program.signal(),
+      osc1: program.signal(),
+      osc2: program.signal(),
+      osc3: program.signal(),
+      osc4: program.signal(),
+      osc1_sync: program.signal(),
+      osc2_sync: program.signal(),
+      osc3_sync: program.signal(),
+      osc4_sync: program.signal(),
+      filter1: program.signal(),
+      dca_left: program.signal(),
+      dca_right: program.signal(),
+    };
+
+    let amp_eg = program.amp_envelope(params.amp_eg.inputs());
+
+    let lfo1 = lfo::Block {
+      inputs: lfo::Inputs {
+        shape: params.lfo1.shape.out_signal_ref,
+        rate: params.lfo1.rate.out_signal_ref,
+        phase: params.lfo1.phase.out_signal_ref,
+        depth: params.lfo1.depth.out_signal_ref,
+        slew: params.lfo1.slew.out_signal_ref,
+        slew_sync: params.lfo1.slew_sync.out_signal_ref,
+        polarity: params.lfo1.polarity.out_signal_ref,
+        invert: params.lfo1.invert.out_signal_ref,
+      },
+      output: signals.lfo1,
+      sync_out: signals.lfo1_sync,
+      phase_out: signals.lfo1_phase,
+    };
+
+    let lfo2 = lfo::Block {
+      inputs: lfo::Inputs {
+        shape: params.lfo2.shape.out_signal_ref,
+        rate: params.lfo2.rate.out_signal_ref,
+        phase: params.lfo2.phase.out_signal_ref,
+        depth: params.lfo2.depth.out_signal_ref,
+        slew: params.lfo2.slew.out_signal_ref,
+        slew_sync: params.lfo2.slew_sync.out_signal_ref,
+        polarity: params.lfo2.polarity.out_signal_ref,
+        invert: params.lfo2.invert.out_signal_ref,
+      },
+      output: signals.lfo2,
+      sync_out: signals.lfo2_sync,
+      phase_out: signals.lfo2_phase,
+    };
+
+    let sources = KiroSources {
+      amp_eg: program.source("amp-eg", amp_eg.normal),
+      lfo1: program.lfo_source("lfo1", &lfo1),
+      lfo2: program.lfo_source("lfo2", &lfo2),
+      seq1: program.source("seq1", signals.seq1),
+      eg1_normal: program.source("eg1", signals.eg1_normal),
+      eg1_biased: program.source("eg1-biased", signals.eg1_biased),
+      osc1: program.source("osc1", signals.osc1),
+      osc2: program.source("osc2", signals.osc2),
+      osc3: program.source("osc3", signals.osc3),
+      osc4: program.source("osc4", signals.osc4),
+      pressure: program.source("pressure", voice.pressure),
+    };
+
+    let seq1 = sequencer::Block::with_values(
+      &[
+        F::val(1.0),
+        F::val(0.25),
+        F::val(0.5),
+        F::val(0.0),
+        F::val(0.75),
+        F::val(0.25),
+        F::val(1.0),
+        F::val(0.5),
+      ],
+      sequencer::Inputs {
+        division: params.seq1.division.out_signal_ref,
+        length: params.seq1.length.out_signal_ref,
+      },
+      sequencer::Outputs {
+        value: signals.seq1,
+        gate: signals.seq1_gate,
+      },
+    );
+
+    program.modulation(&params.filter1.freq, sources.lfo1, F::val(800));
+    program.modulation(&params.filter1.freq, sources.eg1_normal, F::val(700));
+    program.modulation(&params.filter1.q, sources.lfo2, F::val(0.09));
+    program.modulation(&params.osc1.amplitude, sources.lfo2, F::val(0.1));
+    program.modulation(&params.dca.pan, sources.lfo1, F::val(0.1));
+
+    let eg1 = envgen::Block {
+      inputs: params.eg1.inputs(),
+      outputs: envgen::Outputs {
+        normal: signals.eg1_normal,
+        biased: signals.eg1_biased,
+      },
+    };
+
+    let pitch_eg = envgen::Block {
+      inputs: params.pitch_eg.inputs(zero),
+      outputs: envgen::Outputs {
+        normal: signals.pitch_eg_normal,
+        biased: signals.pitch_eg_biased,
+      },
+    };
+
+    // the pitch of all the oscillators follows the pitch envelope, scaled by its amount in semitones
+    let pitch_mod = program.expr(|expr| {
+      expr.mul_signal_param(signals.pitch_eg_normal, params.pitch_eg.amount.reference)
+    });
+
+    let osc1 = osc::Block {
+      inputs: osc::Inputs {
+        shape: params.osc1.shape.out_signal_ref,
+        amplitude: params.osc1.amplitude.out_signal_ref,
+        amplitude_db: params.osc1.amplitude_db.out_signal_ref,
+        level_db: params.osc1.level_db.out_signal_ref,
+        amp_mod: zero,
+        octaves: params.osc1.octaves.out_signal_ref,
+        semitones: params.osc1.semitones.out_signal_ref,
+        cents: params.osc1.cents.out_signal_ref,
+        note_pitch: voice.note_pitch,
+        pitch_bend: params.pitch_bend.out_signal_ref,
+        pitch_mod: pitch_mod.output,
+        freq_mod: zero,
+        fm_mode: params.osc1.fm_mode.out_signal_ref,
+        sync_mode: params.osc1.sync_mode.out_signal_ref,
+        sync_in: zero,
+        lfo_sync: params.osc1.lfo_sync.out_signal_ref,
+        lfo1_sync_in: signals.lfo1_sync,
+        lfo2_sync_in: signals.lfo2_sync,
+        key_low: params.osc1.key_low.out_signal_ref,
+        key_high: params.osc1.key_high.out_signal_ref,
+        fixed_freq: params.osc1.fixed_freq.out_signal_ref,
+        freq_hz: params.osc1.freq_hz.out_signal_ref,
+      },
+      output: signals.osc1,
+      sync_out: signals.osc1_sync,
+    };
+
+    let osc2 = osc::Block {
+      inputs: osc::Inputs {
+        shape: params.osc2.shape.out_signal_ref,
+        amplitude: params.osc2.amplitude.out_signal_ref,
+        amplitude_db: params.osc2.amplitude_db.out_signal_ref,
+        level_db: params.osc2.level_db.out_signal_ref,
+        amp_mod: zero,
+        octaves: params.osc2.octaves.out_signal_ref,
+        semitones: params.osc2.semitones.out_signal_ref,
+        cents: params.osc2.cents.out_signal_ref,
+        note_pitch: voice.note_pitch,
+        pitch_bend: params.pitch_bend.out_signal_ref,
+        pitch_mod: pitch_mod.output,
+        freq_mod: zero,
+        fm_mode: params.osc2.fm_mode.out_signal_ref,
+        sync_mode: params.osc2.sync_mode.out_signal_ref,
+        sync_in: zero,
+        lfo_sync: params.osc2.lfo_sync.out_signal_ref,
+        lfo1_sync_in: signals.lfo1_sync,
+        lfo2_sync_in: signals.lfo2_sync,
+        key_low: params.osc2.key_low.out_signal_ref,
+        key_high: params.osc2.key_high.out_signal_ref,
+        fixed_freq: params.osc2.fixed_freq.out_signal_ref,
+        freq_hz: params.osc2.freq_hz.out_signal_ref,
+      },
+      output: signals.osc2,
+      sync_out: signals.osc2_sync,
+    };
+
+    let osc3 = osc::Block {
+      inputs: osc::Inputs {
+        shape: params.osc3.shape.out_signal_ref,
+        amplitude: params.osc3.amplitude.out_signal_ref,
+        amplitude_db: params.osc3.amplitude_db.out_signal_ref,
+        level_db: params.osc3.level_db.out_signal_ref,
+        amp_mod: zero,
+        octaves: params.osc3.octaves.out_signal_ref,
+        semitones: params.osc3.semitones.out_signal_ref,
+        cents: params.osc3.cents.out_signal_ref,
+        note_pitch: voice.note_pitch,
+        pitch_bend: params.pitch_bend.out_signal_ref,
+        pitch_mod: pitch_mod.output,
+        freq_mod: zero,
+        fm_mode: params.osc3.fm_mode.out_signal_ref,
+        sync_mode: params.osc3.sync_mode.out_signal_ref,
+        sync_in: zero,
+        lfo_sync: params.osc3.lfo_sync.out_signal_ref,
+        lfo1_sync_in: signals.lfo1_sync,
+        lfo2_sync_in: signals.lfo2_sync,
+        key_low: params.osc3.key_low.out_signal_ref,
+        key_high: params.osc3.key_high.out_signal_ref,
+        fixed_freq: params.osc3.fixed_freq.out_signal_ref,
+        freq_hz: params.osc3.freq_hz.out_signal_ref,
+      },
+      output: signals.osc3,
+      sync_out: signals.osc3_sync,
+    };
+
+    let osc4 = osc::Block {
+      inputs: osc::Inputs {
+        shape: params.osc4.shape.out_signal_ref,
+        amplitude: params.osc4.amplitude.out_signal_ref,
+        amplitude_db: params.osc4.amplitude_db.out_signal_ref,
+        level_db: params.osc4.level_db.out_signal_ref,
+        amp_mod: zero,
+        octaves: params.osc4.octaves.out_signal_ref,
+        semitones: params.osc4.semitones.out_signal_ref,
+        cents: params.osc4.cents.out_signal_ref,
+        note_pitch: voice.note_pitch,
+        pitch_bend: params.pitch_bend.out_signal_ref,
+        pitch_mod: pitch_mod.output,
+        freq_mod: zero,
+        fm_mode: params.osc4.fm_mode.out_signal_ref,
+        sync_mode: params.osc4.sync_mode.out_signal_ref,
+        sync_in: zero,
+        lfo_sync: params.osc4.lfo_sync.out_signal_ref,
+        lfo1_sync_in: signals.lfo1_sync,
+        lfo2_sync_in: signals.lfo2_sync,
+        key_low: params.osc4.key_low.out_signal_ref,
+        key_high: params.osc4.key_high.out_signal_ref,
+        fixed_freq: params.osc4.fixed_freq.out_signal_ref,
+        freq_hz: params.osc4.freq_hz.out_signal_ref,
+      },
+      output: signals.osc4,
+      sync_out: signals.osc4_sync,
+    };
+
+    let osc_mix = program.expr(|expr| {
+      let sum1 = expr.add_signals(signals.osc1, signals.osc2);
+      let sum2 = expr.add_signals(signals.osc3, signals.osc4);
+      let sum = expr.add(sum1, sum2);
+      expr.mul_param(sum, params.osc_mix_level.reference)
+    });
+
+    let filter1 = filter::Block {
+      input: osc_mix.output,
+      params: filter::Params {
+        mode: params.filter1.mode.out_signal_ref,
+        freq: params.filter1.freq.out_signal_ref,
+        freq_mod: zero,
+        key_track: params.filter1.key_track.out_signal_ref,
+        key_track_center: params.filter1.key_track_center.out_signal_ref,
+        q: params.filter1.q.out_signal_ref,
+        velocity_to_q: params.filter1.velocity_to_q.out_signal_ref,
+        q_compensation: params.filter1.q_compensation.out_signal_ref,
+        gain: params.filter1.gain.out_signal_ref,
+        self_osc: params.filter1.self_osc.out_signal_ref,
+      },
+      output: signals.filter1,
+    };
+
+    let dca = dca::Block {
+      inputs: dca::Inputs {
+        left: filter1.output,
+        right: filter1.output,
+        velocity: voice.velocity,
+        velocity_floor: params.dca.velocity_floor.out_signal_ref,
+        amplitude: params.dca.amplitude.out_signal_ref,
+        amp_mod: zero,
+        eg_mod: one,
+        saturation: params.dca.saturation.out_signal_ref,
+        pan: params.dca.pan.out_signal_ref,
+        pan_mod: zero,
+      },
+      outputs: dca::Outputs {
+        left: signals.dca_left,
+        right: signals.dca_right,
+      },
+    };
+
+    params.lfo1.add_param_blocks(program);
+    program.block(Block::Lfo(lfo1));
+
+    params.lfo2.add_param_blocks(program);
+    program.block(Block::Lfo(lfo2));
+
+    params.seq1.add_param_blocks(program);
+    program.block(Block::Sequencer(seq1));
+
+    params.amp_eg.add_param_blocks(program);
+
+    params.eg1.add_param_blocks(program);
+    program.block(Block::EG(eg1));
+
+    params.pitch_eg.add_param_blocks(program);
+    program.block(Block::EG(pitch_eg));
+    program.block(Block::Expr(pitch_mod));
+
+    params.osc1.add_param_blocks(program);
+    params.osc2.add_param_blocks(program);
+    params.osc3.add_param_blocks(program);
+    params.osc4.add_param_blocks(program);
+    program.oscillators(&[osc1, osc2, osc3, osc4], &Self::osc_routing());
+
+    program.block(Block::Expr(osc_mix));
+
+    params.filter1.add_param_blocks(program);
+    program.block(Block::Filter(filter1));
+
+    params.dca.add_param_blocks(program);
+    program.block(Block::DCA(dca));
+
+    program.master(MasterBlock {
+      pre_fx_gain: Some(params.master.pre_fx_gain.reference),
+      gain: Some(params.master.gain.reference),
+      fx_send: Some(params.master.fx_send.reference),
+      fx_return: Some(params.master.fx_return.reference),
+      high_pass: Some(params.master.high_pass.reference),
+      high_pass_freq: Some(params.master.high_pass_freq.reference),
+      low_pass: Some(params.master.low_pass.reference),
+      low_pass_freq: Some(params.master.low_pass_freq.reference),
+      pan: Some(params.master.pan.reference),
+      lfo_shape: Some(params.master.lfo_shape.reference),
+      lfo_rate: Some(params.master.lfo_rate.reference),
+      lfo_to_pan: Some(params.master.lfo_to_pan.reference),
+    });
+
+    Self::add_macros(program, &params);
+
+    KiroModule {
+      signals,
+      sources,
+      params,
+    }
+  }
+
+  /// Default targets for the macros: brightness, envelope times, detune and drive
+  fn add_macros<F: Float>(program: &mut ProgramBuilder<F>, params: &KiroParams) {
+    program.macro_control(
+      params.macros.macro1.reference,
+      &[
+        MacroTarget::new(params.filter1.freq.reference, F::val(0.5))
+          .with_curve(MacroCurve::Exponential),
+        MacroTarget::new(params.filter1.q.reference, F::val(0.3)),
+      ],
+    );
+    program.macro_control(
+      params.macros.macro2.reference,
+      &[
+        MacroTarget::new(params.amp_eg.attack.reference, F::val(0.2))
+          .with_curve(MacroCurve::Exponential),
+        MacroTarget::new(params.amp_eg.release.reference, F::val(0.3))
+          .with_curve(MacroCurve::Exponential),
+        MacroTarget::new(params.eg1.attack.reference, F::val(0.2))
+          .with_curve(MacroCurve::Exponential),
+      ],
+    );
+    program.macro_control(
+      params.macros.macro3.reference,
+      &[
+        MacroTarget::new(params.osc2.cents.reference, F::val(0.1)),
+        MacroTarget::new(params.osc3.cents.reference, F::val(-0.1)),
+      ],
+    );
+    program.macro_control(
+      params.macros.macro4.reference,
+      &[
+        MacroTarget::new(params.dca.saturation.reference, F::one()),
+        MacroTarget::new(params.filter1.gain.reference, F::val(0.1)),
+      ],
+    );
+  }
+
+  /// Every oscillator can be synced to the previous one
+  fn osc_routing() -> OscRouting {
+    let mut routing = OscRouting::new(4);
+    for osc in 1..4 {
+      routing.set_sync_source(osc, Some(osc - 1)).unwrap();
+    }
+    routing
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use ringbuf::RingBuffer;
+
+  use kiro_synth_engine::globals::SynthGlobals;
+  use kiro_synth_engine::synth::Synth;
+
+  fn kiro_synth<'a>() -> Synth<'a, f32> {
+    let (_, consumer) = RingBuffer::new(1).split();
+    let (program, _) = KiroModule::new_program(4, 4);
+    Synth::new(44100.0, consumer, program, SynthGlobals::new())
+  }
+
+  fn param_value(synth: &Synth<f32>, id: &str) -> Option<f64> {
+    let param_ref = synth.get_program().get_param_ref(id)?;
+    synth.get_param_f64(param_ref)
+  }
+
+  #[test]
+  fn randomize_changes_the_continuous_params() {
+    let mut synth = kiro_synth();
+    let continuous = [
+      "filt1-freq",
+      "master-low-pass-freq",
+      "osc1-cents",
+      "osc1-semitones",
+    ];
+    let discrete = ["osc1-shape", "filt1-mode", "filt1-self-osc"];
+    let initial = |synth: &Synth<f32>, ids: &[&str]| -> Vec<Option<f64>> {
+      ids.iter().map(|id| param_value(synth, id)).collect()
+    };
+    let initial_continuous = initial(&synth, &continuous);
+    let initial_discrete = initial(&synth, &discrete);
+
+    synth.randomize(0.5, 7);
+
+    for (id, value) in continuous.iter().zip(initial_continuous) {
+      assert_ne!(param_value(&synth, id), value, "{}", id);
+    }
+    for (id, value) in discrete.iter().zip(initial_discrete) {
+      assert_eq!(param_value(&synth, id), value, "{}", id);
+    }
+  }
+
+  fn kiro_program<'a>(values: &[(&str, f32)]) -> Program<'a, f32> {
+    let (mut program, _) = KiroModule::new_program(4, 4);
+    for (id, value) in values.iter() {
+      let param_ref = program.get_param_ref(id).unwrap();
+      let (_, param) = program.get_param_mut(param_ref).unwrap();
+      param.value.set(*value);
+    }
+    program
+  }
+
+  #[test]
+  fn morph_blends_the_cutoff() {
+    let mut synth = kiro_synth();
+    let a = kiro_program(&[
+      ("filt1-freq", 100.0),
+      ("osc1-cents", -20.0),
+      ("filt1-mode", 0.0),
+    ]);
+    let b = kiro_program(&[
+      ("filt1-freq", 1000.0),
+      ("osc1-cents", 20.0),
+      ("filt1-mode", 2.0),
+    ]);
+
+    synth.morph(&a, &b, 0.25);
+    assert_eq!(param_value(&synth, "filt1-freq"), Some(325.0));
+    assert_eq!(param_value(&synth, "osc1-cents"), Some(-10.0));
+    assert_eq!(param_value(&synth, "filt1-mode"), Some(0.0));
+
+    synth.morph(&a, &b, 0.5);
+    assert_eq!(param_value(&synth, "filt1-freq"), Some(550.0));
+    assert_eq!(param_value(&synth, "osc1-cents"), Some(0.0));
+    assert_eq!(param_value(&synth, "filt1-mode"), Some(2.0));
+  }
+}
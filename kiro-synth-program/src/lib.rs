@@ -0,0 +1,6 @@
+//! The Kiro program and its presets, shared by the host application and the plugins
+
+pub mod kiro;
+pub mod params;
+pub mod preset;
+pub mod values;
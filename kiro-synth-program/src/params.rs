@@ -0,0 +1,201 @@
+use kiro_synth_core::float::Float;
+use kiro_synth_engine::program::blocks::envgen;
+use kiro_synth_engine::program::{Block, ParamBlock, ProgramBuilder, SignalRef};
+
+macro_rules! param_blocks {
+  ( $name:ident, $( $param_block:ident ),* $(, $( [$nested:ident] ),* )? ) => {
+    impl $name {
+      pub fn add_param_blocks<F: Float>(&self, program: &mut ProgramBuilder<F>) {
+        $(
+          program.block(Block::Param(self.$param_block.clone()));
+        )*
+
+        $(
+          $(
+            self.$nested.add_param_blocks(program)
+          )*
+        )?
+      }
+    }
+  };
+}
+
+pub struct EnvGenParams {
+  pub attack: ParamBlock,
+  pub decay: ParamBlock,
+  pub sustain: ParamBlock,
+  pub release: ParamBlock,
+  pub mode: ParamBlock,
+  pub legato: ParamBlock,
+  pub reset_to_zero: ParamBlock,
+  pub velocity_to_attack: ParamBlock,
+  pub velocity_to_decay: ParamBlock,
+}
+
+param_blocks!(
+  EnvGenParams,
+  attack,
+  decay,
+  sustain,
+  release,
+  mode,
+  legato,
+  reset_to_zero,
+  velocity_to_attack,
+  velocity_to_decay
+);
+
+impl EnvGenParams {
+  pub fn inputs(&self) -> envgen::Inputs {
+    envgen::Inputs {
+      attack: self.attack.out_signal_ref,
+      decay: self.decay.out_signal_ref,
+      sustain: self.sustain.out_signal_ref,
+      release: self.release.out_signal_ref,
+      mode: self.mode.out_signal_ref,
+      legato: self.legato.out_signal_ref,
+      reset_to_zero: self.reset_to_zero.out_signal_ref,
+      velocity_to_attack: self.velocity_to_attack.out_signal_ref,
+      velocity_to_decay: self.velocity_to_decay.out_signal_ref,
+    }
+  }
+}
+
+/// The pitch envelope only has the stages and its depth, the other inputs of the envelope are fixed.
+/// The depth is read by the expression scaling the envelope, so it has no block of its own.
+pub struct PitchEgParams {
+  pub attack: ParamBlock,
+  pub decay: ParamBlock,
+  pub sustain: ParamBlock,
+  pub release: ParamBlock,
+  pub amount: ParamBlock,
+}
+
+param_blocks!(PitchEgParams, attack, decay, sustain, release);
+
+impl PitchEgParams {
+  pub fn inputs(&self, zero: SignalRef) -> envgen::Inputs {
+    envgen::Inputs {
+      attack: self.attack.out_signal_ref,
+      decay: self.decay.out_signal_ref,
+      sustain: self.sustain.out_signal_ref,
+      release: self.release.out_signal_ref,
+      mode: zero,
+      legato: zero,
+      reset_to_zero: zero,
+      velocity_to_attack: zero,
+      velocity_to_decay: zero,
+    }
+  }
+}
+
+pub struct LfoParams {
+  pub shape: ParamBlock,
+  pub rate: ParamBlock,
+  pub phase: ParamBlock,
+  pub depth: ParamBlock,
+  pub slew: ParamBlock,
+  pub slew_sync: ParamBlock,
+  pub polarity: ParamBlock,
+  pub invert: ParamBlock,
+}
+
+param_blocks!(LfoParams, shape, rate, phase, depth, slew, slew_sync, polarity, invert);
+
+pub struct SequencerParams {
+  pub division: ParamBlock,
+  pub length: ParamBlock,
+}
+
+param_blocks!(SequencerParams, division, length);
+
+pub struct OscParams {
+  pub shape: ParamBlock,
+  pub amplitude: ParamBlock,
+  pub amplitude_db: ParamBlock,
+  pub level_db: ParamBlock,
+  pub octaves: ParamBlock,
+  pub semitones: ParamBlock,
+  pub cents: ParamBlock,
+  pub fm_mode: ParamBlock,
+  pub sync_mode: ParamBlock,
+  pub lfo_sync: ParamBlock,
+  pub key_low: ParamBlock,
+  pub key_high: ParamBlock,
+  pub fixed_freq: ParamBlock,
+  pub freq_hz: ParamBlock,
+}
+
+param_blocks!(
+  OscParams,
+  shape,
+  amplitude,
+  amplitude_db,
+  level_db,
+  octaves,
+  semitones,
+  cents,
+  fm_mode,
+  sync_mode,
+  lfo_sync,
+  key_low,
+  key_high,
+  fixed_freq,
+  freq_hz
+);
+
+pub struct FilterParams {
+  pub mode: ParamBlock,
+  pub freq: ParamBlock,
+  pub key_track: ParamBlock,
+  pub key_track_center: ParamBlock,
+  pub q: ParamBlock,
+  pub velocity_to_q: ParamBlock,
+  pub q_compensation: ParamBlock,
+  pub gain: ParamBlock,
+  pub self_osc: ParamBlock,
+}
+
+param_blocks!(
+  FilterParams,
+  mode,
+  freq,
+  key_track,
+  key_track_center,
+  q,
+  velocity_to_q,
+  q_compensation,
+  gain,
+  self_osc
+);
+
+pub struct DcaParams {
+  pub amplitude: ParamBlock,
+  pub velocity_floor: ParamBlock,
+  pub saturation: ParamBlock,
+  pub pan: ParamBlock,
+}
+
+param_blocks!(DcaParams, amplitude, velocity_floor, saturation, pan);
+
+pub struct MacroParams {
+  pub macro1: ParamBlock,
+  pub macro2: ParamBlock,
+  pub macro3: ParamBlock,
+  pub macro4: ParamBlock,
+}
+
+pub struct MasterParams {
+  pub pre_fx_gain: ParamBlock,
+  pub gain: ParamBlock,
+  pub fx_send: ParamBlock,
+  pub fx_return: ParamBlock,
+  pub high_pass: ParamBlock,
+  pub high_pass_freq: ParamBlock,
+  pub low_pass: ParamBlock,
+  pub low_pass_freq: ParamBlock,
+  pub pan: ParamBlock,
+  pub lfo_shape: ParamBlock,
+  pub lfo_rate: ParamBlock,
+  pub lfo_to_pan: ParamBlock,
+}
@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use kiro_synth_core::float::Float;
+use kiro_synth_engine::program::{ParamRef, Program};
+
+/// The current version of the presets format:
+///
+/// - 1: only the values of the params.
+/// - 2: adds the name of the preset and the modulations.
+///   Presets without modulations keep the ones from the program.
+/// - 3: the voices get a dedicated amp envelope, initialised from the `eg1` params in older versions.
+/// - 4: adds the order of the insert effects, which process in the order they were added when missing.
+pub const PRESET_VERSION: u64 = 4;
+
+#[derive(Error, Debug)]
+pub enum PresetError {
+  #[error("Invalid preset format")]
+  Format(#[from] serde_json::Error),
+
+  #[error("Unsupported preset version {0}")]
+  UnsupportedVersion(u64),
+
+  #[error("Invalid preset bank format, expected an array of presets")]
+  BankFormat,
+
+  #[error("Failed to access the preset file")]
+  Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresetModulation {
+  pub source: String,
+  pub param: String,
+  pub amount: f64,
+
+  /// Offset in cycles at which an LFO source is sampled, ignored for the rest of the sources
+  #[serde(default)]
+  pub phase: f64,
+}
+
+/// The values of the params and the modulations of a program, referenced by their ids
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+  pub version: u64,
+
+  #[serde(default)]
+  pub name: String,
+
+  #[serde(default)]
+  pub params: BTreeMap<String, f64>,
+
+  #[serde(default)]
+  pub modulations: Option<Vec<PresetModulation>>,
+
+  /// The slots of the insert effects in the order they process the output
+  #[serde(default)]
+  pub insert_order: Vec<u8>,
+}
+
+impl Preset {
+  #[allow(dead_code)]
+  pub fn from_program<F: Float>(name: &str, program: &Program<F>) -> Self {
+    let mut params = BTreeMap::new();
+    let mut modulations = Vec::new();
+
+    for (index, param) in program.get_params().iter().enumerate() {
+      params.insert(param.id.to_string(), param.value.get().to_f64().unwrap());
+
+      for modulation in program.get_param_modulations(ParamRef::new(index)) {
+        if let Some(source) = program.get_source(modulation.source_ref) {
+          modulations.push(PresetModulation {
+            source: source.id.to_string(),
+            param: param.id.to_string(),
+            amount: modulation.amount.to_f64().unwrap(),
+            phase: modulation.phase.to_f64().unwrap(),
+          });
+        }
+      }
+    }
+
+    Preset {
+      version: PRESET_VERSION,
+      name: name.to_string(),
+      params,
+      modulations: Some(modulations),
+      insert_order: program.get_insert_order().to_vec(),
+    }
+  }
+
+  /// Load a preset from JSON, migrating it from older versions when needed
+  pub fn from_json(json: &str) -> Result<Self, PresetError> {
+    Self::from_value(serde_json::from_str(json)?)
+  }
+
+  fn from_value(preset: Value) -> Result<Self, PresetError> {
+    Ok(serde_json::from_value(migrate(preset)?)?)
+  }
+
+  #[allow(dead_code)]
+  pub fn to_json(&self) -> Result<String, PresetError> {
+    Ok(serde_json::to_string_pretty(self)?)
+  }
+
+  /// Set the preset values into the program. Params missing in the preset go back to their initial values
+  /// and the ids not found in the program are ignored.
+  pub fn apply<F: Float>(&self, program: &mut Program<F>) {
+    program.set_insert_order(&self.insert_order);
+
+    for param in program.get_params_mut().iter_mut() {
+      let values = &param.values;
+      let value = self
+        .params
+        .get(param.id)
+        .map(|value| F::val(*value).max(values.min).min(values.max))
+        .unwrap_or(values.initial_value);
+      param.value.set(value);
+    }
+
+    if let Some(modulations) = self.modulations.as_ref() {
+      program.clear_modulations();
+      for modulation in modulations.iter() {
+        let source_ref = program.get_source_ref(modulation.source.as_str());
+        let param_ref = program.get_param_ref(modulation.param.as_str());
+        if let (Some(source_ref), Some(param_ref)) = (source_ref, param_ref) {
+          drop(program.update_modulation(param_ref, source_ref, F::val(modulation.amount)));
+          program.update_modulation_phase(param_ref, source_ref, F::val(modulation.phase));
+        }
+      }
+    }
+  }
+}
+
+/// Collection of presets selected by their index, ie. with MIDI program changes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PresetBank {
+  presets: Vec<Preset>,
+}
+
+impl PresetBank {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add a preset at the end of the bank and return its index
+  pub fn push(&mut self, preset: Preset) -> usize {
+    self.presets.push(preset);
+    self.presets.len() - 1
+  }
+
+  pub fn get(&self, index: usize) -> Option<&Preset> {
+    self.presets.get(index)
+  }
+
+  pub fn len(&self) -> usize {
+    self.presets.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.presets.is_empty()
+  }
+
+  /// Load a bank from a JSON array of presets. The invalid presets are skipped and returned
+  /// with their index and error, so the rest of the bank can still be used.
+  pub fn from_json(json: &str) -> Result<(Self, Vec<(usize, PresetError)>), PresetError> {
+    let entries = match serde_json::from_str(json)? {
+      Value::Array(entries) => entries,
+      _ => return Err(PresetError::BankFormat),
+    };
+
+    let mut bank = PresetBank::new();
+    let mut skipped = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+      match Preset::from_value(entry) {
+        Ok(preset) => {
+          bank.push(preset);
+        }
+        Err(err) => skipped.push((index, err)),
+      }
+    }
+    Ok((bank, skipped))
+  }
+
+  pub fn to_json(&self) -> Result<String, PresetError> {
+    Ok(serde_json::to_string_pretty(&self.presets)?)
+  }
+
+  /// Load a bank from a file, warning about the presets that could not be loaded
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PresetError> {
+    let (bank, skipped) = Self::from_json(std::fs::read_to_string(path)?.as_str())?;
+    for (index, err) in skipped.iter() {
+      eprintln!("Skipping the preset {} of the bank: {}", index, err);
+    }
+    Ok(bank)
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PresetError> {
+    std::fs::write(path, self.to_json()?)?;
+    Ok(())
+  }
+}
+
+/// Upgrade a preset from an older version to the current one
+pub fn migrate(mut preset: Value) -> Result<Value, PresetError> {
+  let version = preset.get("version").and_then(Value::as_u64).unwrap_or(1);
+
+  if version > PRESET_VERSION {
+    return Err(PresetError::UnsupportedVersion(version));
+  }
+
+  if let Some(fields) = preset.as_object_mut() {
+    if version < 2 && !fields.contains_key("name") {
+      fields.insert("name".to_string(), Value::from(""));
+    }
+
+    if version < 3 {
+      if let Some(params) = fields.get_mut("params").and_then(Value::as_object_mut) {
+        let eg1_params: Vec<(String, Value)> = params
+          .iter()
+          .filter(|(id, _)| id.starts_with("eg1-"))
+          .map(|(id, value)| (id.replacen("eg1-", "amp-eg-", 1), value.clone()))
+          .collect();
+        for (id, value) in eg1_params {
+          params.entry(id).or_insert(value);
+        }
+      }
+    }
+
+    fields.insert("version".to_string(), Value::from(PRESET_VERSION));
+  }
+
+  Ok(preset)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use kiro_synth_engine::program::{ProgramBuilder, SourceRef};
+
+  use crate::values;
+
+  #[test]
+  fn from_json_v1() {
+    let json = r#"{
+      "version": 1,
+      "params": {
+        "filt1-freq": 2000.0,
+        "dca-pan": -0.5
+      }
+    }"#;
+
+    let preset = Preset::from_json(json).unwrap();
+
+    assert_eq!(preset.version, PRESET_VERSION);
+    assert_eq!(preset.name, "");
+    assert_eq!(preset.params.len(), 2);
+    assert_eq!(preset.params["filt1-freq"], 2000.0);
+    assert_eq!(preset.params["dca-pan"], -0.5);
+    assert_eq!(preset.modulations, None);
+  }
+
+  #[test]
+  fn from_json_v2() {
+    let json = r#"{
+      "version": 2,
+      "name": "Lead",
+      "params": {
+        "eg1-attack": 0.5,
+        "eg1-release": 2.0
+      }
+    }"#;
+
+    let preset = Preset::from_json(json).unwrap();
+
+    assert_eq!(preset.version, PRESET_VERSION);
+    assert_eq!(preset.params.len(), 4);
+    assert_eq!(preset.params["eg1-attack"], 0.5);
+    assert_eq!(preset.params["amp-eg-attack"], 0.5);
+    assert_eq!(preset.params["amp-eg-release"], 2.0);
+  }
+
+  #[test]
+  fn from_json_modulations_without_phase() {
+    let json = r#"{
+      "version": 4,
+      "modulations": [{ "source": "lfo1", "param": "dca-pan", "amount": 0.5 }]
+    }"#;
+
+    let preset = Preset::from_json(json).unwrap();
+
+    let modulations = preset.modulations.unwrap();
+    assert_eq!(modulations.len(), 1);
+    assert_eq!(modulations[0].phase, 0.0);
+  }
+
+  #[test]
+  fn from_json_current() {
+    let preset = Preset {
+      version: PRESET_VERSION,
+      name: "Pad".to_string(),
+      params: vec![("dca-pan".to_string(), 0.25)].into_iter().collect(),
+      modulations: Some(vec![PresetModulation {
+        source: "lfo1".to_string(),
+        param: "dca-pan".to_string(),
+        amount: 0.5,
+        phase: 0.25,
+      }]),
+      insert_order: vec![1, 0],
+    };
+
+    let json = preset.to_json().unwrap();
+
+    assert_eq!(Preset::from_json(json.as_str()).unwrap(), preset);
+  }
+
+  #[test]
+  fn apply() {
+    let mut builder = ProgramBuilder::new();
+    let source = builder.signal();
+    let lfo = builder.source("lfo1", source);
+    let pan = builder.param("dca-pan", values::pan());
+    let rate = builder.param("lfo1-rate", values::lfo_rate());
+    builder.modulation(&rate, lfo, 0.25);
+    let mut program: Program<f32> = builder.build();
+    program
+      .get_param_mut(rate.reference)
+      .unwrap()
+      .1
+      .value
+      .set(5.0);
+
+    let preset = Preset {
+      version: PRESET_VERSION,
+      name: String::new(),
+      params: vec![("dca-pan".to_string(), -4.0), ("unknown".to_string(), 1.0)]
+        .into_iter()
+        .collect(),
+      modulations: Some(vec![PresetModulation {
+        source: "lfo1".to_string(),
+        param: "dca-pan".to_string(),
+        amount: 0.5,
+        phase: 0.25,
+      }]),
+      insert_order: vec![1, 0],
+    };
+
+    preset.apply(&mut program);
+
+    assert_eq!(program.get_param_signal(pan.reference).get(), -1.0);
+    assert_eq!(program.get_param_signal(rate.reference).get(), 1.0);
+    assert_eq!(program.get_param_modulations(rate.reference).count(), 0);
+    let modulations: Vec<(SourceRef, f32, f32)> = program
+      .get_param_modulations(pan.reference)
+      .map(|modulation| (modulation.source_ref, modulation.amount, modulation.phase))
+      .collect();
+    assert_eq!(modulations, vec![(lfo, 0.5, 0.25)]);
+    assert_eq!(program.get_insert_order(), [1, 0]);
+  }
+
+  fn bank_preset(name: &str, pan: f64) -> Preset {
+    Preset {
+      version: PRESET_VERSION,
+      name: name.to_string(),
+      params: vec![("dca-pan".to_string(), pan)].into_iter().collect(),
+      modulations: Some(Vec::new()),
+      insert_order: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn bank_save_and_load() {
+    let mut bank = PresetBank::new();
+    bank.push(bank_preset("Lead", -0.5));
+    bank.push(bank_preset("Pad", 0.0));
+    bank.push(bank_preset("Bass", 0.5));
+
+    // the tests of several builds could run at the same time
+    let file_name = format!("kiro-synth-bank-{}.json", std::process::id());
+    let path = std::env::temp_dir().join(file_name);
+    bank.save(&path).unwrap();
+    let loaded = PresetBank::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded, bank);
+  }
+
+  #[test]
+  fn bank_skips_corrupt_presets() {
+    let json = r#"[
+      { "version": 3, "name": "Lead", "params": { "dca-pan": -0.5 } },
+      { "version": 99, "name": "Future" },
+      { "version": 3, "name": 42 },
+      { "version": 3, "name": "Bass", "params": { "dca-pan": 0.5 } }
+    ]"#;
+
+    let (bank, skipped) = PresetBank::from_json(json).unwrap();
+
+    assert_eq!(bank.len(), 2);
+    assert_eq!(bank.get(0).unwrap().name, "Lead");
+    assert_eq!(bank.get(1).unwrap().name, "Bass");
+    let skipped: Vec<usize> = skipped.iter().map(|(index, _)| *index).collect();
+    assert_eq!(skipped, vec![1, 2]);
+  }
+
+  #[test]
+  fn bank_invalid_format() {
+    let result = PresetBank::from_json(r#"{ "version": 3 }"#);
+    assert!(matches!(result, Err(PresetError::BankFormat)));
+  }
+
+  #[test]
+  fn from_json_unsupported_version() {
+    let result = Preset::from_json(r#"{ "version": 99 }"#);
+    assert!(matches!(result, Err(PresetError::UnsupportedVersion(99))));
+  }
+}
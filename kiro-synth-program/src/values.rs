@@ -0,0 +1,332 @@
+use kiro_synth_core::filters::freq_control::FreqControl;
+use kiro_synth_core::float::Float;
+use kiro_synth_engine::program::{ParamScale, ParamValues};
+use num_traits::ToPrimitive;
+
+pub fn pitch_bend<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::one().neg(),
+    max: F::one(),
+    resolution: F::epsilon(),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn adsr<F: Float, T: ToPrimitive>(value: T) -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::val(value),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::val(10.0),
+    resolution: F::val(0.01),
+    scale: ParamScale::Exponential,
+    discrete: false,
+  }
+}
+
+pub fn eg_mode<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::one(),
+    resolution: F::one(),
+    scale: ParamScale::Linear,
+    discrete: true,
+  }
+}
+
+pub fn boolean<F: Float>(initial: bool) -> ParamValues<F> {
+  ParamValues {
+    initial_value: if initial { F::one() } else { F::zero() },
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::one(),
+    resolution: F::one(),
+    scale: ParamScale::Linear,
+    discrete: true,
+  }
+}
+
+pub fn enumeration<F: Float>(count: usize) -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::val(0.0),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::val(count - 1),
+    resolution: F::one(),
+    scale: ParamScale::Linear,
+    discrete: true,
+  }
+}
+
+pub fn amplitude<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::one(),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::one(),
+    resolution: F::val(0.01),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn amplitude_db<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::val(0.0),
+    origin: F::zero(),
+    min: F::val(-96.0),
+    max: F::val(24.0),
+    resolution: F::val(0.1),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn osc_amplitude_db<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::val(-96.0),
+    max: F::zero(),
+    resolution: F::val(0.1),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn gain_db<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::val(-24.0),
+    max: F::val(24.0),
+    resolution: F::val(0.1),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn level<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::one(),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::val(2.0),
+    resolution: F::val(0.01),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn amount<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::one(),
+    resolution: F::val(0.01),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn key<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::val(127),
+    resolution: F::one(),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn octave<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::val(-8.0),
+    max: F::val(8.0),
+    resolution: F::one(),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn semitones<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::val(-12.0),
+    max: F::val(12.0),
+    resolution: F::one(),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+/// The depth of the pitch envelope in semitones, going down for negative amounts
+pub fn pitch_eg_amount<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::val(-48.0),
+    max: F::val(48.0),
+    resolution: F::val(0.1),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn cents<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::val(-100.0),
+    max: F::val(100.0),
+    resolution: F::one(),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+/// The frequency of an oscillator that doesn't follow the notes
+pub fn osc_freq<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::val(440.0),
+    origin: F::one(),
+    min: F::one(),
+    max: F::val(20000.0),
+    resolution: F::val(0.1),
+    scale: ParamScale::Logarithmic,
+    discrete: false,
+  }
+}
+
+pub fn lfo_rate<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::one(),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::val(20.0),
+    resolution: F::val(0.01),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn lfo_phase<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::one(),
+    resolution: F::val(1.0 / 8.0),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+/// In seconds, or in beats when synced with the tempo
+pub fn lfo_slew<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::val(4.0),
+    resolution: F::val(0.01),
+    scale: ParamScale::Exponential,
+    discrete: false,
+  }
+}
+
+pub fn filt_freq<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: FreqControl::default_frequency(),
+    origin: FreqControl::min_frequency(),
+    min: FreqControl::min_frequency(),
+    max: FreqControl::max_frequency(),
+    resolution: F::val(10.0),
+    scale: ParamScale::Logarithmic,
+    discrete: false,
+  }
+}
+
+/// The cutoff of the filters of the master output
+pub fn master_cutoff<F: Float, T: ToPrimitive>(value: T) -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::val(value),
+    origin: F::val(20.0),
+    min: F::val(20.0),
+    max: F::val(20000.0),
+    resolution: F::one(),
+    scale: ParamScale::Logarithmic,
+    discrete: false,
+  }
+}
+
+pub fn filt_q<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::val(0.5),
+    origin: F::zero(),
+    min: F::zero(),
+    max: F::one(),
+    resolution: F::val(0.01),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn pan<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::one().neg(),
+    max: F::one(),
+    resolution: F::val(0.01),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn bipolar<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::zero(),
+    origin: F::zero(),
+    min: F::one().neg(),
+    max: F::one(),
+    resolution: F::val(0.01),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn seq_division<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::val(4.0),
+    origin: F::one(),
+    min: F::one(),
+    max: F::val(16.0),
+    resolution: F::one(),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}
+
+pub fn seq_length<F: Float>() -> ParamValues<F> {
+  ParamValues {
+    initial_value: F::val(8.0),
+    origin: F::one(),
+    min: F::one(),
+    max: F::val(16.0),
+    resolution: F::one(),
+    scale: ParamScale::Linear,
+    discrete: false,
+  }
+}